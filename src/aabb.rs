@@ -0,0 +1,112 @@
+use crate::ray::Ray;
+use crate::transform::Transform;
+use crate::vector::Vector;
+
+/// Axis-aligned bounding box.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct Aabb {
+    pub min: Vector,
+    pub max: Vector,
+}
+
+impl Aabb {
+    pub fn new(min: Vector, max: Vector) -> Aabb {
+        Aabb { min, max }
+    }
+
+    // the smallest box containing both
+    pub fn surrounding(&self, other: &Aabb) -> Aabb {
+        Aabb {
+            min: Vector::new(
+                self.min.x().min(other.min.x()),
+                self.min.y().min(other.min.y()),
+                self.min.z().min(other.min.z()),
+            ),
+            max: Vector::new(
+                self.max.x().max(other.max.x()),
+                self.max.y().max(other.max.y()),
+                self.max.z().max(other.max.z()),
+            ),
+        }
+    }
+
+    // the part inside both, which is inverted (and hit by no ray) if they don't touch
+    pub fn overlap(&self, other: &Aabb) -> Aabb {
+        Aabb {
+            min: Vector::new(
+                self.min.x().max(other.min.x()),
+                self.min.y().max(other.min.y()),
+                self.min.z().max(other.min.z()),
+            ),
+            max: Vector::new(
+                self.max.x().min(other.max.x()),
+                self.max.y().min(other.max.y()),
+                self.max.z().min(other.max.z()),
+            ),
+        }
+    }
+
+    // grown by `margin` on every side
+    pub fn padded(&self, margin: f64) -> Aabb {
+        let margin = Vector::new(margin, margin, margin);
+        Aabb {
+            min: self.min - margin,
+            max: self.max + margin,
+        }
+    }
+
+    pub fn corners(&self) -> [Vector; 8] {
+        let (a, b) = (self.min, self.max);
+        [
+            Vector::new(a.x(), a.y(), a.z()),
+            Vector::new(b.x(), a.y(), a.z()),
+            Vector::new(a.x(), b.y(), a.z()),
+            Vector::new(b.x(), b.y(), a.z()),
+            Vector::new(a.x(), a.y(), b.z()),
+            Vector::new(b.x(), a.y(), b.z()),
+            Vector::new(a.x(), b.y(), b.z()),
+            Vector::new(b.x(), b.y(), b.z()),
+        ]
+    }
+
+    // the box around this one once transformed
+    pub fn transformed(&self, transform: &Transform) -> Aabb {
+        let corners = self.corners();
+        let first = transform.point(corners[0]);
+        corners[1..]
+            .iter()
+            .fold(Aabb::new(first, first), |aabb, corner| {
+                let p = transform.point(*corner);
+                aabb.surrounding(&Aabb::new(p, p))
+            })
+    }
+
+    // slab test, whether the ray goes through the box between t_min and t_max
+    pub fn hit(&self, ray: &Ray, t_min: f64, t_max: f64) -> bool {
+        self.range(ray, t_min, t_max).is_some()
+    }
+
+    // the part of t_min..t_max the ray spends in the box, if any
+    pub fn range(&self, ray: &Ray, mut t_min: f64, mut t_max: f64) -> Option<(f64, f64)> {
+        let (origin, direction) = (ray.origin(), ray.direction());
+        let axes = [
+            (origin.x(), direction.x(), self.min.x(), self.max.x()),
+            (origin.y(), direction.y(), self.min.y(), self.max.y()),
+            (origin.z(), direction.z(), self.min.z(), self.max.z()),
+        ];
+        for (o, d, min, max) in axes {
+            let inv = 1.0 / d;
+            let (t0, t1) = if inv < 0.0 {
+                ((max - o) * inv, (min - o) * inv)
+            } else {
+                ((min - o) * inv, (max - o) * inv)
+            };
+            t_min = t_min.max(t0);
+            t_max = t_max.min(t1);
+            if t_max <= t_min {
+                return None;
+            }
+        }
+        Some((t_min, t_max))
+    }
+}
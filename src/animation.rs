@@ -0,0 +1,530 @@
+use crate::camera::{Camera, CameraModel};
+use crate::ray::Ray;
+use crate::transform::{Quaternion, Transform};
+use crate::vector::Vector;
+use std::str::FromStr;
+
+/// Values that can be keyframed.
+pub trait Animatable: Copy {
+    fn add(self, other: Self) -> Self;
+    fn scale(self, s: f64) -> Self;
+
+    fn sub(self, other: Self) -> Self {
+        self.add(other.scale(-1.0))
+    }
+
+    // the value a fraction `u` of the way to `other`, for linear keys
+    fn interpolate(self, other: Self, u: f64) -> Self {
+        self.add(other.sub(self).scale(u))
+    }
+
+    // -1 for values that are the same negated, like quaternions, when the
+    // negation is nearer `other`, so that curves between them go the short
+    // way; 1 otherwise
+    fn sign_towards(self, _other: Self) -> f64 {
+        1.0
+    }
+}
+
+impl Animatable for f64 {
+    fn add(self, other: f64) -> f64 {
+        self + other
+    }
+
+    fn scale(self, s: f64) -> f64 {
+        self * s
+    }
+}
+
+impl Animatable for Vector {
+    fn add(self, other: Vector) -> Vector {
+        self + other
+    }
+
+    fn scale(self, s: f64) -> Vector {
+        s * self
+    }
+}
+
+// Linear keys are slerped. The curves blend the components, with the keys
+// turned to the same side as their neighbours, and get normalised when used.
+impl Animatable for Quaternion {
+    fn add(self, other: Quaternion) -> Quaternion {
+        Quaternion::new(
+            self.w + other.w,
+            self.x + other.x,
+            self.y + other.y,
+            self.z + other.z,
+        )
+    }
+
+    fn scale(self, s: f64) -> Quaternion {
+        Quaternion::new(s * self.w, s * self.x, s * self.y, s * self.z)
+    }
+
+    fn interpolate(self, other: Quaternion, u: f64) -> Quaternion {
+        self.slerp(&other, u)
+    }
+
+    fn sign_towards(self, other: Quaternion) -> f64 {
+        if self.dot(&other) < 0.0 {
+            -1.0
+        } else {
+            1.0
+        }
+    }
+}
+
+/// How a track goes from a key to the next one.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum Interpolation {
+    // holds the value until the next key
+    Step,
+    Linear,
+    // cubic Bezier through the handles of both keys
+    Bezier,
+    // smooth curve through the keys, whose tangents point from the previous key to the next
+    CatmullRom,
+}
+
+#[derive(Copy, Clone, Debug)]
+pub struct Keyframe<T> {
+    pub time: f64,
+    pub value: T,
+    // for the segment that starts at this key
+    pub interpolation: Interpolation,
+    // Bezier control points, relative to `value`, on the side of the
+    // previous and of the next key
+    pub in_handle: T,
+    pub out_handle: T,
+}
+
+impl<T: Animatable> Keyframe<T> {
+    pub fn new(time: f64, value: T, interpolation: Interpolation) -> Keyframe<T> {
+        Keyframe {
+            time,
+            value,
+            interpolation,
+            in_handle: value.scale(0.0),
+            out_handle: value.scale(0.0),
+        }
+    }
+
+    pub fn linear(time: f64, value: T) -> Keyframe<T> {
+        Keyframe::new(time, value, Interpolation::Linear)
+    }
+
+    pub fn catmull_rom(time: f64, value: T) -> Keyframe<T> {
+        Keyframe::new(time, value, Interpolation::CatmullRom)
+    }
+
+    // handles of zero ease in and out of the key
+    pub fn bezier(time: f64, value: T, in_handle: T, out_handle: T) -> Keyframe<T> {
+        Keyframe {
+            in_handle,
+            out_handle,
+            ..Keyframe::new(time, value, Interpolation::Bezier)
+        }
+    }
+}
+
+/// A value changing over time, given by keys. It stays at the first key's
+/// value before it and at the last one's after it.
+#[derive(Clone, Debug)]
+pub struct Track<T> {
+    keys: Vec<Keyframe<T>>,
+}
+
+impl<T: Animatable> Track<T> {
+    /// Fails if there are no keys, or two at the same time.
+    pub fn new(mut keys: Vec<Keyframe<T>>) -> Result<Track<T>, String> {
+        if keys.is_empty() {
+            return Err(String::from("a track needs at least one key"));
+        }
+        keys.sort_by(|a, b| a.time.total_cmp(&b.time));
+        if let Some(pair) = keys.windows(2).find(|pair| pair[0].time == pair[1].time) {
+            return Err(format!("a track has two keys at time {}", pair[0].time));
+        }
+        Ok(Track { keys })
+    }
+
+    pub fn constant(value: T) -> Track<T> {
+        Track {
+            keys: vec![Keyframe::linear(0.0, value)],
+        }
+    }
+
+    pub fn keys(&self) -> &[Keyframe<T>] {
+        &self.keys
+    }
+
+    pub fn sample(&self, time: f64) -> T {
+        let keys = &self.keys;
+        let last = keys.len() - 1;
+        if time <= keys[0].time {
+            return keys[0].value;
+        }
+        if time >= keys[last].time {
+            return keys[last].value;
+        }
+        // the segment from keys[i] to keys[i + 1] contains `time`
+        let i = keys.partition_point(|k| k.time <= time) - 1;
+        let (k0, k1) = (&keys[i], &keys[i + 1]);
+        let duration = k1.time - k0.time;
+        let u = (time - k0.time) / duration;
+        // the next key, and its handle, on the side of this one
+        let sign = k1.value.sign_towards(k0.value);
+        let v1 = k1.value.scale(sign);
+        match k0.interpolation {
+            Interpolation::Step => k0.value,
+            Interpolation::Linear => k0.value.interpolate(k1.value, u),
+            Interpolation::Bezier => {
+                let p1 = k0.value.add(k0.out_handle);
+                let p2 = v1.add(k1.in_handle.scale(sign));
+                let v = 1.0 - u;
+                k0.value
+                    .scale(v * v * v)
+                    .add(p1.scale(3.0 * v * v * u))
+                    .add(p2.scale(3.0 * v * u * u))
+                    .add(v1.scale(u * u * u))
+            }
+            Interpolation::CatmullRom => {
+                // the keys either side, one-sided at the ends, on the side
+                // of their neighbours in the segment
+                let (before, after) = (&keys[i.saturating_sub(1)], &keys[(i + 2).min(last)]);
+                let v_before = before.value.scale(before.value.sign_towards(k0.value));
+                let v_after = after.value.scale(after.value.sign_towards(v1));
+                // tangents per unit of time, scaled to the segment for the Hermite basis
+                let m0 = v1.sub(v_before).scale(duration / (k1.time - before.time));
+                let m1 = v_after
+                    .sub(k0.value)
+                    .scale(duration / (after.time - k0.time));
+                let (u2, u3) = (u * u, u * u * u);
+                k0.value
+                    .scale(2.0 * u3 - 3.0 * u2 + 1.0)
+                    .add(m0.scale(u3 - 2.0 * u2 + u))
+                    .add(v1.scale(-2.0 * u3 + 3.0 * u2))
+                    .add(m1.scale(u3 - u2))
+            }
+        }
+    }
+}
+
+/// Keyframed position, orientation and lens of a camera.
+#[derive(Clone, Debug)]
+pub struct CameraAnimation {
+    pub lookfrom: Track<Vector>,
+    pub lookat: Track<Vector>,
+    pub vup: Vector,
+    // vertical, in degrees
+    pub vertical_field_of_view: Track<f64>,
+    pub focus_dist: Track<f64>,
+    pub aperture: f64,
+    pub aspect_ratio: f64,
+}
+
+impl CameraAnimation {
+    /// The camera at the middle of the shutter interval, which is given to
+    /// its rays so that moving objects get blurred. The camera's own motion
+    /// is blurred by `AnimatedCamera`.
+    pub fn camera(&self, time0: f64, time1: f64) -> Camera {
+        let time = (time0 + time1) / 2.0;
+        Camera::new(
+            self.lookfrom.sample(time),
+            self.lookat.sample(time),
+            self.vup,
+            self.vertical_field_of_view.sample(time),
+            self.aspect_ratio,
+            self.aperture,
+            self.focus_dist.sample(time),
+            time0,
+            time1,
+        )
+    }
+
+    // where the camera is and the way it faces at `time`: its position and
+    // the unit vectors to the right, up and back
+    fn pose(&self, time: f64) -> [Vector; 4] {
+        let lookfrom = self.lookfrom.sample(time);
+        let w = (lookfrom - self.lookat.sample(time)).unit();
+        let u = self.vup.cross(w).unit();
+        [lookfrom, u, w.cross(u), w]
+    }
+}
+
+/// A camera model built at the middle of the shutter interval, whose rays
+/// are moved along with the camera to where it is at their time, so that
+/// its motion gets blurred. The field of view and focus stay those of the
+/// middle of the interval.
+pub struct AnimatedCamera {
+    model: Box<dyn CameraModel>,
+    animation: CameraAnimation,
+    // the pose `model` was built at
+    reference: [Vector; 4],
+}
+
+impl AnimatedCamera {
+    /// `model` is the camera the animation gives for the shutter interval
+    /// from time0 to time1, maybe seen through another projection or lens.
+    pub fn new(
+        model: Box<dyn CameraModel>,
+        animation: CameraAnimation,
+        time0: f64,
+        time1: f64,
+    ) -> AnimatedCamera {
+        AnimatedCamera {
+            model,
+            reference: animation.pose((time0 + time1) / 2.0),
+            animation,
+        }
+    }
+}
+
+impl CameraModel for AnimatedCamera {
+    fn get_ray(&self, s: f64, t: f64) -> Option<Ray> {
+        self.get_weighted_ray(s, t).map(|(ray, _)| ray)
+    }
+
+    fn get_weighted_ray(&self, s: f64, t: f64) -> Option<(Ray, Vector)> {
+        let (ray, weight) = self.model.get_weighted_ray(s, t)?;
+        let [origin0, u0, v0, w0] = self.reference;
+        let [origin, u, v, w] = self.animation.pose(ray.time());
+        // from the reference camera's frame to the one at the ray's time
+        let turn = |d: Vector| d.dot(u0) * u + d.dot(v0) * v + d.dot(w0) * w;
+        let ray = Ray::new(
+            origin + turn(ray.origin() - origin0),
+            turn(ray.direction()),
+            ray.time(),
+        );
+        Some((ray, weight))
+    }
+}
+
+/// The camera a scene is rendered through.
+pub enum CameraRig {
+    Still(Camera),
+    Animated(CameraAnimation),
+}
+
+impl CameraRig {
+    /// The camera for a shutter interval, or for the scene's own one if None.
+    /// Animated cameras default to an instantaneous shutter at time 0.
+    pub fn camera(&self, shutter: Option<(f64, f64)>) -> Camera {
+        match (self, shutter) {
+            (CameraRig::Still(camera), Some((time0, time1))) => {
+                camera.clone().with_shutter(time0, time1)
+            }
+            (CameraRig::Still(camera), None) => camera.clone(),
+            (CameraRig::Animated(animation), Some((time0, time1))) => {
+                animation.camera(time0, time1)
+            }
+            (CameraRig::Animated(animation), None) => animation.camera(0.0, 0.0),
+        }
+    }
+
+    /// The camera model rays are made with for a shutter interval, or for
+    /// the scene's own one if None, from the one `build` makes out of the
+    /// camera. Animated cameras move during the interval.
+    pub fn model(
+        &self,
+        shutter: Option<(f64, f64)>,
+        build: impl FnOnce(Camera) -> Box<dyn CameraModel>,
+    ) -> Box<dyn CameraModel> {
+        let model = build(self.camera(shutter));
+        match (self, shutter) {
+            (CameraRig::Animated(animation), Some((time0, time1))) if time0 < time1 => {
+                Box::new(AnimatedCamera::new(model, animation.clone(), time0, time1))
+            }
+            _ => model,
+        }
+    }
+}
+
+/// Keyframed scale, rotation and translation, applied in that order. Linear
+/// rotation keys turn along the shortest arc at a constant speed, so keys more
+/// than half a turn apart need keys in between.
+#[derive(Clone, Debug)]
+pub struct TransformAnimation {
+    pub translation: Track<Vector>,
+    pub rotation: Track<Quaternion>,
+    pub scale: Track<Vector>,
+}
+
+impl TransformAnimation {
+    pub fn new(translation: Track<Vector>) -> TransformAnimation {
+        TransformAnimation {
+            translation,
+            rotation: Track::constant(Quaternion::identity()),
+            scale: Track::constant(Vector::new(1.0, 1.0, 1.0)),
+        }
+    }
+
+    pub fn at(&self, time: f64) -> Transform {
+        Transform::scale(self.scale.sample(time))
+            .then(&Transform::from_quaternion(self.rotation.sample(time)))
+            .then(&Transform::translate(self.translation.sample(time)))
+    }
+}
+
+/// Inclusive range of frames to render, "A-B" or a single "A".
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub struct FrameRange {
+    pub first: u32,
+    pub last: u32,
+}
+
+impl FromStr for FrameRange {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let (first, last) = s.split_once('-').unwrap_or((s, s));
+        let parse = |v: &str| {
+            v.trim()
+                .parse::<u32>()
+                .map_err(|_| format!("invalid frame range {:?}", s))
+        };
+        let (first, last) = (parse(first)?, parse(last)?);
+        if first > last {
+            return Err(format!(
+                "invalid frame range {:?}, {} is after {}",
+                s, first, last
+            ));
+        }
+        Ok(FrameRange { first, last })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn assert_close(a: f64, b: f64) {
+        assert!((a - b).abs() < 1e-12, "{} vs {}", a, b);
+    }
+
+    // the same rotation, whatever the sign and length of the quaternions
+    fn assert_same_rotation(a: Quaternion, b: Quaternion, tolerance: f64) {
+        let cos = a.normalised().dot(&b.normalised()).abs();
+        assert!(1.0 - cos < tolerance, "{:?} vs {:?}", a, b);
+    }
+
+    fn around_y(degrees: f64) -> Quaternion {
+        Quaternion::from_axis_angle(Vector::new(0.0, 1.0, 0.0), degrees)
+    }
+
+    fn negated(q: Quaternion) -> Quaternion {
+        q.scale(-1.0)
+    }
+
+    #[test]
+    fn rejects_empty_tracks_and_repeated_times() {
+        assert!(Track::<f64>::new(Vec::new()).is_err());
+        let keys = vec![
+            Keyframe::linear(1.0, 0.0),
+            Keyframe::linear(0.0, 1.0),
+            Keyframe::linear(1.0, 2.0),
+        ];
+        assert_eq!(
+            Track::new(keys).err().unwrap(),
+            "a track has two keys at time 1"
+        );
+    }
+
+    #[test]
+    fn steps_and_interpolates_linearly() {
+        let track = Track::new(vec![
+            Keyframe::new(0.0, 1.0, Interpolation::Step),
+            Keyframe::linear(1.0, 3.0),
+            Keyframe::linear(3.0, 7.0),
+        ])
+        .unwrap();
+        assert_close(track.sample(-1.0), 1.0);
+        assert_close(track.sample(0.5), 1.0);
+        assert_close(track.sample(1.0), 3.0);
+        assert_close(track.sample(2.5), 6.0);
+        assert_close(track.sample(5.0), 7.0);
+
+        // keys may come in any order
+        let track = Track::new(vec![Keyframe::linear(2.0, 4.0), Keyframe::linear(0.0, 0.0)]);
+        assert_close(track.unwrap().sample(0.5), 1.0);
+    }
+
+    #[test]
+    fn follows_bezier_handles() {
+        // handles a third of the way along make a straight line
+        let track = Track::new(vec![
+            Keyframe::bezier(0.0, 0.0, 0.0, 1.0),
+            Keyframe::bezier(1.0, 3.0, -1.0, 0.0),
+        ])
+        .unwrap();
+        for &t in &[0.0, 0.25, 0.5, 0.9, 1.0] {
+            assert_close(track.sample(t), 3.0 * t);
+        }
+        // and zero handles ease in and out
+        let track = Track::new(vec![
+            Keyframe::bezier(0.0, 0.0, 0.0, 0.0),
+            Keyframe::bezier(1.0, 1.0, 0.0, 0.0),
+        ])
+        .unwrap();
+        assert_close(track.sample(0.5), 0.5);
+        assert_close(track.sample(0.25), 0.15625);
+    }
+
+    #[test]
+    fn passes_catmull_rom_curves_through_the_keys() {
+        // keys on a line, at uneven times, give a curve through every one
+        let track = Track::new(vec![
+            Keyframe::catmull_rom(0.0, Vector::new(0.0, 0.0, 0.0)),
+            Keyframe::catmull_rom(1.0, Vector::new(1.0, 2.0, 0.0)),
+            Keyframe::catmull_rom(3.0, Vector::new(0.0, 1.0, 5.0)),
+            Keyframe::catmull_rom(4.0, Vector::new(2.0, 2.0, 2.0)),
+        ])
+        .unwrap();
+        for key in track.keys() {
+            assert!((track.sample(key.time) - key.value).length() < 1e-12);
+        }
+
+        // and evenly spaced keys on a line are followed at a steady pace
+        let track = Track::new(
+            (0..4)
+                .map(|i| Keyframe::catmull_rom(i as f64, 2.0 * i as f64))
+                .collect(),
+        )
+        .unwrap();
+        for &t in &[0.3, 1.5, 2.75] {
+            assert_close(track.sample(t), 2.0 * t);
+        }
+    }
+
+    #[test]
+    fn blends_quaternions_the_short_way() {
+        // the second key is the quarter turn negated, which is the same
+        // rotation, so the halfway point is an eighth of a turn either way
+        let halfway = around_y(45.0);
+        let slerped = Track::new(vec![
+            Keyframe::linear(0.0, Quaternion::identity()),
+            Keyframe::linear(1.0, negated(around_y(90.0))),
+        ])
+        .unwrap();
+        assert_same_rotation(slerped.sample(0.5), halfway, 1e-12);
+
+        let zero = Quaternion::new(0.0, 0.0, 0.0, 0.0);
+        let bezier = Track::new(vec![
+            Keyframe::bezier(0.0, Quaternion::identity(), zero, zero),
+            Keyframe::bezier(1.0, negated(around_y(90.0)), zero, zero),
+        ])
+        .unwrap();
+        assert_same_rotation(bezier.sample(0.5), halfway, 1e-12);
+
+        let curve = Track::new(vec![
+            Keyframe::catmull_rom(0.0, Quaternion::identity()),
+            Keyframe::catmull_rom(1.0, negated(around_y(90.0))),
+            Keyframe::catmull_rom(2.0, around_y(180.0)),
+            Keyframe::catmull_rom(3.0, negated(around_y(270.0))),
+        ])
+        .unwrap();
+        for (t, degrees) in [(0.5, 45.0), (1.5, 135.0), (2.5, 225.0)] {
+            assert_same_rotation(curve.sample(t), around_y(degrees), 1e-3);
+        }
+    }
+}
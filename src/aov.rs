@@ -0,0 +1,154 @@
+use crate::vector::Vector;
+use std::str::FromStr;
+
+/// Arbitrary output variables, extra images rendered alongside the beauty pass.
+///
+/// The lighting passes add up to the beauty pass: `emission` is light seen
+/// directly by the camera (emitters and the background), `direct_*` is light
+/// reaching the first surface straight from an emitter or the background, and
+/// `indirect_*` is everything that bounced more than once. Diffuse and specular
+/// are split by the lobe the first surface scattered into.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum Aov {
+    Albedo,
+    Normal,
+    Position,
+    Depth,
+    ObjectId,
+    MaterialId,
+    DirectDiffuse,
+    IndirectDiffuse,
+    DirectSpecular,
+    IndirectSpecular,
+    Emission,
+}
+
+/// How samples of an AOV are combined into a pixel.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum AovFilter {
+    // weighted by the reconstruction filter, like the beauty pass
+    Reconstruction,
+    // averaged over the samples taken inside the pixel
+    Average,
+    // the value of the first sample taken inside the pixel, for ids that can't be averaged
+    First,
+}
+
+impl Aov {
+    pub const ALL: [Aov; 11] = [
+        Aov::Albedo,
+        Aov::Normal,
+        Aov::Position,
+        Aov::Depth,
+        Aov::ObjectId,
+        Aov::MaterialId,
+        Aov::DirectDiffuse,
+        Aov::IndirectDiffuse,
+        Aov::DirectSpecular,
+        Aov::IndirectSpecular,
+        Aov::Emission,
+    ];
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Aov::Albedo => "albedo",
+            Aov::Normal => "normal",
+            Aov::Position => "position",
+            Aov::Depth => "depth",
+            Aov::ObjectId => "object_id",
+            Aov::MaterialId => "material_id",
+            Aov::DirectDiffuse => "direct_diffuse",
+            Aov::IndirectDiffuse => "indirect_diffuse",
+            Aov::DirectSpecular => "direct_specular",
+            Aov::IndirectSpecular => "indirect_specular",
+            Aov::Emission => "emission",
+        }
+    }
+
+    // channel names when written as image layers
+    pub fn channels(self) -> &'static [&'static str] {
+        match self {
+            Aov::Normal | Aov::Position => &["X", "Y", "Z"],
+            Aov::Depth => &["Z"],
+            Aov::ObjectId | Aov::MaterialId => &["id"],
+            _ => &["R", "G", "B"],
+        }
+    }
+
+    pub fn filter(self) -> AovFilter {
+        match self {
+            Aov::Albedo | Aov::Normal | Aov::Position | Aov::Depth => AovFilter::Average,
+            Aov::ObjectId | Aov::MaterialId => AovFilter::First,
+            _ => AovFilter::Reconstruction,
+        }
+    }
+
+    // stable identifier, used by checkpoints
+    pub fn index(self) -> u32 {
+        Aov::ALL.iter().position(|a| *a == self).unwrap() as u32
+    }
+}
+
+impl FromStr for Aov {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Aov::ALL
+            .iter()
+            .copied()
+            .find(|aov| aov.name() == s)
+            .ok_or_else(|| {
+                let names: Vec<&str> = Aov::ALL.iter().map(|aov| aov.name()).collect();
+                format!("unknown AOV {:?}, expected one of {}", s, names.join(", "))
+            })
+    }
+}
+
+/// Everything a single camera sample records besides its color.
+#[derive(Copy, Clone, Default)]
+pub struct AovSample {
+    pub albedo: Vector,
+    pub normal: Vector,
+    pub position: Vector,
+    // distance from the camera to the first hit, 0 when nothing was hit
+    pub depth: f64,
+    // 0 when nothing was hit
+    pub object_id: u32,
+    pub material_id: u32,
+    pub direct_diffuse: Vector,
+    pub indirect_diffuse: Vector,
+    pub direct_specular: Vector,
+    pub indirect_specular: Vector,
+    pub emission: Vector,
+}
+
+impl AovSample {
+    // the lighting passes multiplied by `weight`, like the color of the sample
+    pub fn weighted(self, weight: Vector) -> AovSample {
+        AovSample {
+            direct_diffuse: self.direct_diffuse.hadamard_product(weight),
+            indirect_diffuse: self.indirect_diffuse.hadamard_product(weight),
+            direct_specular: self.direct_specular.hadamard_product(weight),
+            indirect_specular: self.indirect_specular.hadamard_product(weight),
+            emission: self.emission.hadamard_product(weight),
+            ..self
+        }
+    }
+
+    // scalar values are returned in the x component
+    pub fn value(&self, aov: Aov) -> Vector {
+        match aov {
+            Aov::Albedo => self.albedo,
+            Aov::Normal => self.normal,
+            Aov::Position => self.position,
+            Aov::Depth => Vector::new(self.depth, 0.0, 0.0),
+            Aov::ObjectId => Vector::new(self.object_id as f64, 0.0, 0.0),
+            Aov::MaterialId => Vector::new(self.material_id as f64, 0.0, 0.0),
+            Aov::DirectDiffuse => self.direct_diffuse,
+            Aov::IndirectDiffuse => self.indirect_diffuse,
+            Aov::DirectSpecular => self.direct_specular,
+            Aov::IndirectSpecular => self.indirect_specular,
+            Aov::Emission => self.emission,
+        }
+    }
+}
@@ -0,0 +1,112 @@
+use crate::tracer::{fnv1a, random_float};
+use crate::vector::{degrees_to_radians, random_in_unit_disk, Vector};
+use std::f64::consts::PI;
+use std::fmt;
+use std::path::Path;
+use std::sync::Arc;
+
+/// Shape of the lens opening, which is the shape of out of focus highlights.
+#[derive(Clone, Debug, PartialEq)]
+pub enum ApertureShape {
+    Circle,
+    // a diaphragm of `blades` straight blades, rotated by `rotation` degrees.
+    // Curvature bends the blades from straight (0) to a circle (1).
+    Polygon {
+        blades: u32,
+        rotation: f64,
+        curvature: f64,
+    },
+    Image(Arc<ApertureImage>),
+}
+
+impl ApertureShape {
+    /// Point of the aperture, distributed like the light going through it,
+    /// within [-1, 1] x [-1, 1]. z is always 0.
+    pub fn sample(&self) -> Vector {
+        match self {
+            ApertureShape::Circle => random_in_unit_disk(),
+            ApertureShape::Polygon {
+                blades,
+                rotation,
+                curvature,
+            } => {
+                let sector = 2.0 * PI / *blades as f64;
+                let rotation = degrees_to_radians(*rotation);
+                loop {
+                    let p = random_in_unit_disk();
+                    // distance from the center to the edge of the polygon in
+                    // the direction of p, whose corners are on the unit circle
+                    let angle = (p.y().atan2(p.x()) - rotation).rem_euclid(sector);
+                    let edge = (sector / 2.0).cos() / (angle - sector / 2.0).cos();
+                    let edge = edge + curvature * (1.0 - edge);
+                    if p.length() <= edge {
+                        return p;
+                    }
+                }
+            }
+            ApertureShape::Image(image) => image.sample(),
+        }
+    }
+}
+
+/// Aperture given by a greyscale image, the brighter a pixel the more light
+/// goes through it. The image is centered on the lens and its longest side
+/// spans the lens diameter.
+#[derive(PartialEq)]
+pub struct ApertureImage {
+    name: String,
+    width: usize,
+    height: usize,
+    // running sum of the pixel values, row by row from the top
+    cdf: Vec<f64>,
+    hash: u64,
+}
+
+impl ApertureImage {
+    pub fn load(path: &Path) -> Result<ApertureImage, String> {
+        let image = image::open(path)
+            .map_err(|e| format!("can't read aperture image {}: {}", path.display(), e))?
+            .into_luma8();
+        let (width, height) = (image.width() as usize, image.height() as usize);
+        let mut cdf = Vec::with_capacity(width * height);
+        let mut total = 0.0;
+        for pixel in image.pixels() {
+            total += pixel.0[0] as f64 / 255.0;
+            cdf.push(total);
+        }
+        if total <= 0.0 {
+            return Err(format!("aperture image {} is black", path.display()));
+        }
+        Ok(ApertureImage {
+            name: path.display().to_string(),
+            width,
+            height,
+            cdf,
+            hash: fnv1a(image.as_raw()),
+        })
+    }
+
+    fn sample(&self) -> Vector {
+        let total = self.cdf[self.cdf.len() - 1];
+        let target = random_float() * total;
+        let i = self
+            .cdf
+            .partition_point(|c| *c <= target)
+            .min(self.cdf.len() - 1);
+        let (x, y) = (i % self.width, i / self.width);
+        let size = self.width.max(self.height) as f64;
+        let px = (x as f64 + random_float() - self.width as f64 / 2.0) / size * 2.0;
+        let py = (self.height as f64 / 2.0 - y as f64 - random_float()) / size * 2.0;
+        Vector::new(px, py, 0.0)
+    }
+}
+
+impl fmt::Debug for ApertureImage {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "ApertureImage({} {}x{} {:016x})",
+            self.name, self.width, self.height, self.hash
+        )
+    }
+}
@@ -0,0 +1,344 @@
+use crate::aabb::Aabb;
+use crate::hitable::{number_hit, HitRecord, Hitable};
+use crate::ray::Ray;
+use crate::vector::Vector;
+
+// items per leaf, below which splitting doesn't pay off
+const LEAF_SIZE: usize = 4;
+
+enum Node {
+    // the node's first child is the next one, the second is at `second`,
+    // split along `axis`
+    Interior {
+        bounds: Aabb,
+        second: usize,
+        axis: usize,
+    },
+    Leaf {
+        bounds: Aabb,
+        first: usize,
+        count: usize,
+    },
+}
+
+impl Node {
+    fn bounds(&self) -> &Aabb {
+        match self {
+            Node::Interior { bounds, .. } | Node::Leaf { bounds, .. } => bounds,
+        }
+    }
+}
+
+/// Bounding volume hierarchy over the boxes of a list of items, which it
+/// refers to by their index. Built by splitting at the middle item along the
+/// axis the items' centers spread the most on.
+pub struct Bvh {
+    nodes: Vec<Node>,
+    // item indices, in the order the leaves refer to them
+    items: Vec<usize>,
+}
+
+impl Bvh {
+    pub fn new(boxes: &[Aabb]) -> Bvh {
+        let mut bvh = Bvh {
+            nodes: Vec::with_capacity(2 * boxes.len() / LEAF_SIZE + 1),
+            items: (0..boxes.len()).collect(),
+        };
+        if !boxes.is_empty() {
+            bvh.build(boxes, 0, boxes.len());
+        }
+        bvh
+    }
+
+    fn build(&mut self, boxes: &[Aabb], first: usize, end: usize) {
+        let items = &mut self.items[first..end];
+        let bounds = items
+            .iter()
+            .skip(1)
+            .fold(boxes[items[0]], |b, &i| b.surrounding(&boxes[i]));
+        if items.len() <= LEAF_SIZE {
+            self.nodes.push(Node::Leaf {
+                bounds,
+                first,
+                count: items.len(),
+            });
+            return;
+        }
+
+        let center = |i: usize| 0.5 * (boxes[i].min + boxes[i].max);
+        let c = center(items[0]);
+        let centers = items.iter().fold(Aabb::new(c, c), |b, &i| {
+            b.surrounding(&Aabb::new(center(i), center(i)))
+        });
+        let extent = centers.max - centers.min;
+        let axis = if extent.x() >= extent.y() && extent.x() >= extent.z() {
+            0
+        } else if extent.y() >= extent.z() {
+            1
+        } else {
+            2
+        };
+        let middle = items.len() / 2;
+        items.select_nth_unstable_by(middle, |&a, &b| {
+            component(center(a), axis).total_cmp(&component(center(b), axis))
+        });
+
+        let index = self.nodes.len();
+        self.nodes.push(Node::Interior {
+            bounds,
+            second: 0,
+            axis,
+        });
+        self.build(boxes, first, first + middle);
+        let second = self.nodes.len();
+        self.build(boxes, first + middle, end);
+        self.nodes[index] = Node::Interior {
+            bounds,
+            second,
+            axis,
+        };
+    }
+
+    pub fn bounds(&self) -> Option<Aabb> {
+        self.nodes.first().map(|node| *node.bounds())
+    }
+
+    /// Calls `hit` with the index of every item whose box the ray goes
+    /// through before t_max, along with the current t_max. `hit` returns the
+    /// distance to the item if the ray hits it, which then becomes t_max.
+    /// Returns the index of the nearest item hit.
+    pub fn traverse(
+        &self,
+        ray: &Ray,
+        t_min: f64,
+        mut t_max: f64,
+        mut hit: impl FnMut(usize, f64) -> Option<f64>,
+    ) -> Option<usize> {
+        let mut nearest = None;
+        let mut stack = Vec::with_capacity(64);
+        if !self.nodes.is_empty() {
+            stack.push(0);
+        }
+        while let Some(index) = stack.pop() {
+            let node = &self.nodes[index];
+            if !node.bounds().hit(ray, t_min, t_max) {
+                continue;
+            }
+            match *node {
+                Node::Leaf { first, count, .. } => {
+                    for &item in &self.items[first..first + count] {
+                        if let Some(t) = hit(item, t_max) {
+                            t_max = t;
+                            nearest = Some(item);
+                        }
+                    }
+                }
+                Node::Interior { second, axis, .. } => {
+                    // the nearer child first, so that it can cut the other short
+                    if component(ray.direction(), axis) < 0.0 {
+                        stack.push(index + 1);
+                        stack.push(second);
+                    } else {
+                        stack.push(second);
+                        stack.push(index + 1);
+                    }
+                }
+            }
+        }
+        nearest
+    }
+
+    /// Like `traverse`, but `hit` also returns what it found at the item,
+    /// and the nearest item is returned along with what was found there.
+    pub fn nearest_hit<T>(
+        &self,
+        ray: &Ray,
+        t_min: f64,
+        t_max: f64,
+        mut hit: impl FnMut(usize, f64) -> Option<(f64, T)>,
+    ) -> Option<(usize, T)> {
+        // every hit is nearer than the one before
+        let mut nearest = None;
+        let index = self.traverse(ray, t_min, t_max, |i, t_max| {
+            let (t, found) = hit(i, t_max)?;
+            nearest = Some(found);
+            Some(t)
+        })?;
+        nearest.map(|found| (index, found))
+    }
+}
+
+/// Objects found through a bounding volume hierarchy over their boxes,
+/// rather than tried one after the other as in a `HitableList`. Unbounded
+/// ones, which can't be put in the hierarchy, are still tried every time.
+pub struct BvhList {
+    // each object with the ids taken by the ones before it
+    bounded: Vec<(u32, Box<dyn Hitable>)>,
+    unbounded: Vec<(u32, Box<dyn Hitable>)>,
+    object_count: u32,
+    bvh: Bvh,
+}
+
+impl BvhList {
+    /// The hierarchy is built around where the objects are between times 0
+    /// and 1.
+    pub fn new(objects: Vec<Box<dyn Hitable>>) -> BvhList {
+        let mut boxes = Vec::new();
+        let (mut bounded, mut unbounded) = (Vec::new(), Vec::new());
+        let mut object_count = 0u32;
+        for object in objects {
+            let ids_before = object_count;
+            object_count = object_count.saturating_add(object.object_count());
+            match object.bounding_box(0.0, 1.0) {
+                Some(aabb) => {
+                    boxes.push(aabb);
+                    bounded.push((ids_before, object));
+                }
+                None => unbounded.push((ids_before, object)),
+            }
+        }
+        BvhList {
+            bvh: Bvh::new(&boxes),
+            bounded,
+            unbounded,
+            object_count,
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        self.bounded.len() + self.unbounded.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+}
+
+impl Hitable for BvhList {
+    fn hit(&self, ray: &Ray, t_min: f64, t_max: f64) -> Option<HitRecord<'_>> {
+        let mut nearest = self
+            .bvh
+            .nearest_hit(ray, t_min, t_max, |i, t_max| {
+                let (ids_before, object) = &self.bounded[i];
+                let mut hit = object.hit(ray, t_min, t_max)?;
+                number_hit(&mut hit, *ids_before);
+                Some((hit.t, hit))
+            })
+            .map(|(_, hit)| hit);
+        let mut closest_so_far = nearest.as_ref().map_or(t_max, |hit| hit.t);
+        for (ids_before, object) in &self.unbounded {
+            if let Some(mut hit) = object.hit(ray, t_min, closest_so_far) {
+                number_hit(&mut hit, *ids_before);
+                closest_so_far = hit.t;
+                nearest = Some(hit);
+            }
+        }
+        nearest
+    }
+
+    fn bounding_box(&self, _time0: f64, _time1: f64) -> Option<Aabb> {
+        if self.unbounded.is_empty() {
+            self.bvh.bounds()
+        } else {
+            None
+        }
+    }
+
+    fn object_count(&self) -> u32 {
+        self.object_count
+    }
+}
+
+fn component(v: Vector, axis: usize) -> f64 {
+    match axis {
+        0 => v.x(),
+        1 => v.y(),
+        _ => v.z(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::hitable::HitableList;
+    use crate::material::Lambertian;
+    use crate::sphere::Sphere;
+    use rand::rngs::SmallRng;
+    use rand::{Rng, SeedableRng};
+
+    fn sphere_at(center: Vector, radius: f64) -> Sphere<Lambertian> {
+        Sphere::new(center, radius, Lambertian::new(Vector::new(0.5, 0.5, 0.5)))
+    }
+
+    fn sphere(x: f64) -> Sphere<Lambertian> {
+        sphere_at(Vector::new(x, 0.0, 0.0), 1.0)
+    }
+
+    fn random_point(rng: &mut SmallRng, size: f64) -> Vector {
+        Vector::new(
+            rng.gen_range(-size..size),
+            rng.gen_range(-size..size),
+            rng.gen_range(-size..size),
+        )
+    }
+
+    #[test]
+    fn traversal_finds_the_nearest_hit() {
+        let mut rng = SmallRng::seed_from_u64(1);
+        let spheres: Vec<_> = (0..200)
+            .map(|_| sphere_at(random_point(&mut rng, 10.0), rng.gen_range(0.1..1.0)))
+            .collect();
+        let boxes: Vec<Aabb> = spheres
+            .iter()
+            .map(|s| s.bounding_box(0.0, 1.0).unwrap())
+            .collect();
+        let bvh = Bvh::new(&boxes);
+        let mut hits = 0;
+        for _ in 0..1000 {
+            let origin = random_point(&mut rng, 15.0);
+            let ray = Ray::new(origin, random_point(&mut rng, 1.0) - 0.05 * origin, 0.0);
+            let brute_force = spheres
+                .iter()
+                .enumerate()
+                .filter_map(|(i, s)| Some((i, s.hit(&ray, 0.001, f64::MAX)?.t)))
+                .min_by(|a, b| a.1.total_cmp(&b.1));
+            let found = bvh.nearest_hit(&ray, 0.001, f64::MAX, |i, t_max| {
+                let t = spheres[i].hit(&ray, 0.001, t_max)?.t;
+                Some((t, t))
+            });
+            assert_eq!(found, brute_force);
+            hits += found.is_some() as u32;
+        }
+        assert!(hits > 100, "only {} rays hit anything", hits);
+    }
+
+    // the id of what a ray straight down the z axis at `x` hits first
+    fn id_at(scene: &dyn Hitable, x: f64) -> Option<u32> {
+        let ray = Ray::new(Vector::new(x, 0.0, -10.0), Vector::new(0.0, 0.0, 1.0), 0.0);
+        scene.hit(&ray, 0.001, f64::MAX).map(|hit| hit.object_id)
+    }
+
+    #[test]
+    fn numbers_the_objects_under_a_bvh() {
+        let bvh = BvhList::new(vec![
+            Box::new(sphere(3.0)),
+            Box::new(sphere(6.0)),
+            Box::new(sphere(9.0)),
+        ]);
+        assert_eq!(bvh.object_count(), 3);
+        assert_eq!(id_at(&bvh, 3.0), Some(1));
+        assert_eq!(id_at(&bvh, 6.0), Some(2));
+        assert_eq!(id_at(&bvh, 9.0), Some(3));
+
+        // and a list holding it numbers them after what comes before
+        let mut scene = HitableList::default();
+        scene.push(sphere(0.0));
+        scene.push(bvh);
+        scene.push(sphere(12.0));
+        let ids: Vec<_> = [0.0, 3.0, 6.0, 9.0, 12.0]
+            .iter()
+            .map(|&x| id_at(&scene, x))
+            .collect();
+        assert_eq!(ids, [Some(1), Some(2), Some(3), Some(4), Some(5)]);
+    }
+}
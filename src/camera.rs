@@ -1,8 +1,204 @@
+use crate::aperture::ApertureShape;
 use crate::ray::Ray;
-use crate::tracer::random_float_between;
-use crate::vector::{degrees_to_radians, random_in_unit_disk, Vector};
+use crate::realistic::{Realistic, RealisticCamera};
+use crate::tracer::{random_float, random_float_between};
+use crate::vector::{degrees_to_radians, Vector};
+use std::f64::consts::PI;
+use std::str::FromStr;
 
-#[derive(Copy, Clone)]
+/// Anything that turns a point of the image into a camera ray.
+///
+/// (s, t) are normalised image coordinates, (0, 0) being the bottom-left
+/// corner and (1, 1) the top-right one. Models that don't cover the whole
+/// frame, like a circular fisheye, return None for the points they don't see.
+pub trait CameraModel: Sync {
+    fn get_ray(&self, s: f64, t: f64) -> Option<Ray>;
+
+    /// A ray along with the weight the radiance it brings back is multiplied
+    /// by, for models whose rays don't carry all colors equally.
+    fn get_weighted_ray(&self, s: f64, t: f64) -> Option<(Ray, Vector)> {
+        self.get_ray(s, t)
+            .map(|ray| (ray, Vector::new(1.0, 1.0, 1.0)))
+    }
+}
+
+/// Imperfections and styling of the thin lens, see `Camera::with_lens`.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Lens {
+    // lens diameter, the one the camera was created with if None
+    pub aperture: Option<f64>,
+    pub shape: ApertureShape,
+    // how far the lens barrel clips the aperture toward the corners of the
+    // frame, from none (0) to half of it (1), giving cat's eye shaped bokeh
+    pub cats_eye: f64,
+    // difference in magnification between the red and blue images
+    pub lateral_ca: f64,
+    // difference in focus distance between the red and blue images, relative
+    pub longitudinal_ca: f64,
+}
+
+impl Default for Lens {
+    fn default() -> Self {
+        Lens {
+            aperture: None,
+            shape: ApertureShape::Circle,
+            cats_eye: 0.0,
+            lateral_ca: 0.0,
+            longitudinal_ca: 0.0,
+        }
+    }
+}
+
+/// How far open the shutter is over the time it's open for. Real shutters
+/// take a while to open and close, which makes motion trails fade in and out.
+#[derive(Clone, Debug, PartialEq, Default)]
+pub enum ShutterCurve {
+    // fully open the whole time
+    #[default]
+    Box,
+    // opens during the first `opening` part of the time and closes during the
+    // last `closing` one, linearly
+    Trapezoid {
+        opening: f64,
+        closing: f64,
+    },
+    // openness at evenly spaced times, from open to close, linearly interpolated
+    Curve(Vec<f64>),
+}
+
+impl ShutterCurve {
+    // (time, openness) points with times from 0 to 1, None for the box
+    fn points(&self) -> Option<Vec<(f64, f64)>> {
+        match self {
+            ShutterCurve::Box => None,
+            ShutterCurve::Trapezoid { opening, closing } => Some(vec![
+                (0.0, 0.0),
+                (*opening, 1.0),
+                (1.0 - closing, 1.0),
+                (1.0, 0.0),
+            ]),
+            ShutterCurve::Curve(values) => {
+                let last = (values.len() - 1) as f64;
+                Some(
+                    values
+                        .iter()
+                        .enumerate()
+                        .map(|(i, v)| (i as f64 / last, *v))
+                        .collect(),
+                )
+            }
+        }
+    }
+}
+
+impl FromStr for ShutterCurve {
+    type Err = String;
+
+    // "box", "triangle", "trapezoid=OPENING,CLOSING" or "curve=V,V,..."
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let (name, value) = match s.split_once('=') {
+            Some((name, value)) => (name, Some(value)),
+            None => (s, None),
+        };
+        let numbers = |value: &str| -> Result<Vec<f64>, String> {
+            value
+                .split(',')
+                .map(|v| match v.trim().parse::<f64>() {
+                    Ok(v) if v >= 0.0 && v.is_finite() => Ok(v),
+                    _ => Err(format!("invalid shutter curve {:?}", s)),
+                })
+                .collect()
+        };
+        match (name, value) {
+            ("box", None) => Ok(ShutterCurve::Box),
+            ("triangle", None) => Ok(ShutterCurve::Trapezoid {
+                opening: 0.5,
+                closing: 0.5,
+            }),
+            ("trapezoid", Some(value)) => match numbers(value)?[..] {
+                [opening, closing] if opening + closing <= 1.0 => {
+                    Ok(ShutterCurve::Trapezoid { opening, closing })
+                }
+                _ => Err(format!(
+                    "invalid shutter curve {:?}, expected two fractions of the shutter time adding up to at most 1",
+                    s
+                )),
+            },
+            ("curve", Some(value)) => {
+                let values = numbers(value)?;
+                if values.len() < 2 || values.iter().all(|v| *v == 0.0) {
+                    return Err(format!(
+                        "invalid shutter curve {:?}, expected at least two values, not all 0",
+                        s
+                    ));
+                }
+                Ok(ShutterCurve::Curve(values))
+            }
+            _ => Err(format!(
+                "unknown shutter curve {:?}, expected one of box, triangle, trapezoid=OPENING,CLOSING, curve=V,V,...",
+                s
+            )),
+        }
+    }
+}
+
+/// When the shutter is open, and how far.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Shutter {
+    pub time0: f64,
+    pub time1: f64,
+    pub curve: ShutterCurve,
+}
+
+impl Shutter {
+    pub fn new(time0: f64, time1: f64) -> Shutter {
+        Shutter {
+            time0,
+            time1,
+            curve: ShutterCurve::Box,
+        }
+    }
+
+    /// Time for a ray, distributed like the light the shutter lets through.
+    pub fn sample(&self) -> f64 {
+        if self.time1 <= self.time0 {
+            return self.time0;
+        }
+        let points = match self.curve.points() {
+            Some(points) => points,
+            None => return random_float_between(self.time0, self.time1),
+        };
+        // pick a segment by the light going through during it, then a time
+        // in it by inverting the integral of its linear openness
+        let areas: Vec<f64> = points
+            .windows(2)
+            .map(|p| (p[1].0 - p[0].0) * (p[0].1 + p[1].1) / 2.0)
+            .collect();
+        let mut target = random_float() * areas.iter().sum::<f64>();
+        let mut i = 0;
+        while i + 1 < areas.len() && target >= areas[i] {
+            target -= areas[i];
+            i += 1;
+        }
+        let ((x0, y0), (x1, y1)) = (points[i], points[i + 1]);
+        let (width, slope) = (x1 - x0, y1 - y0);
+        if width <= 0.0 || areas[i] <= 0.0 {
+            return self.time0 + (self.time1 - self.time0) * x0;
+        }
+        // solve y0 u + slope u^2 / 2 = target / width for u in 0..1
+        let r = target / width;
+        let u = if slope.abs() < 1e-9 {
+            r / y0
+        } else {
+            ((y0 * y0 + 2.0 * slope * r).max(0.0).sqrt() - y0) / slope
+        };
+        let x = x0 + width * u.clamp(0.0, 1.0);
+        self.time0 + (self.time1 - self.time0) * x
+    }
+}
+
+/// Thin lens perspective camera.
+#[derive(Clone)]
 pub struct Camera {
     origin: Vector,
     lower_left_corner: Vector,
@@ -10,12 +206,14 @@ pub struct Camera {
     vertical: Vector,
     u: Vector,
     v: Vector,
+    w: Vector,
     lens_radius: f64,
-    time0: f64,
-    time1: f64,
+    lens: Lens,
+    shutter: Shutter,
 }
 
 impl Camera {
+    #[allow(clippy::too_many_arguments)]
     pub fn new(
         lookfrom: Vector, // point camera is looking from
         lookat: Vector,   // point camera is looking at
@@ -42,23 +240,464 @@ impl Camera {
 
         Camera {
             origin: lookfrom,
-            horizontal: horizontal,
-            vertical: vertical,
-            lower_left_corner: lower_left_corner,
-            u: u,
-            v: v,
+            horizontal,
+            vertical,
+            lower_left_corner,
+            u,
+            v,
+            w,
             lens_radius: aperture / 2.0,
-            time0: time0,
-            time1: time1,
+            lens: Lens::default(),
+            shutter: Shutter::new(time0, time1),
+        }
+    }
+
+    pub fn with_lens(self, lens: Lens) -> Camera {
+        Camera {
+            lens_radius: lens.aperture.map_or(self.lens_radius, |a| a / 2.0),
+            lens,
+            ..self
         }
     }
-    pub fn get_ray(self, s: f64, t: f64) -> Ray {
-        let rd = self.lens_radius * random_in_unit_disk();
+
+    // the same camera with its shutter open from time0 to time1
+    pub fn with_shutter(self, time0: f64, time1: f64) -> Camera {
+        Camera {
+            shutter: Shutter {
+                time0,
+                time1,
+                ..self.shutter
+            },
+            ..self
+        }
+    }
+
+    pub fn with_shutter_curve(self, curve: ShutterCurve) -> Camera {
+        Camera {
+            shutter: Shutter {
+                curve,
+                ..self.shutter
+            },
+            ..self
+        }
+    }
+
+    /// The same view seen through another projection. Only the position,
+    /// orientation and shutter interval are kept, the other models are pinholes.
+    pub fn with_projection(&self, projection: Projection) -> Box<dyn CameraModel> {
+        let aspect_ratio = self.horizontal.length() / self.vertical.length();
+        match projection {
+            Projection::Perspective => Box::new(self.clone()),
+            Projection::Orthographic { height } => {
+                // by default, what is in focus keeps its size
+                let height = height.unwrap_or_else(|| self.vertical.length());
+                Box::new(OrthographicCamera {
+                    origin: self.origin,
+                    u: self.u,
+                    v: self.v,
+                    w: self.w,
+                    width: height * aspect_ratio,
+                    height,
+                    shutter: self.shutter.clone(),
+                })
+            }
+            Projection::Fisheye { mapping, fov } => Box::new(FisheyeCamera {
+                origin: self.origin,
+                u: self.u,
+                v: self.v,
+                w: self.w,
+                mapping,
+                half_fov: degrees_to_radians(fov) / 2.0,
+                aspect_ratio,
+                shutter: self.shutter.clone(),
+            }),
+            Projection::Equirectangular => Box::new(self.equirectangular(0.0, None)),
+        }
+    }
+
+    /// Left and right eye views of `projection` packed into one frame. The
+    /// perspective camera gives an off-axis pair, both eyes looking straight
+    /// ahead through a window at the convergence distance, which is the focus
+    /// distance unless set. The equirectangular one gives an omni-directional
+    /// stereo panorama, whose eyes look at infinity unless a convergence is set.
+    pub fn stereo_pair(
+        &self,
+        projection: Projection,
+        stereo: Stereo,
+    ) -> Result<Box<dyn CameraModel>, String> {
+        let eye = stereo.ipd / 2.0;
+        let (left, right): (Box<dyn CameraModel>, Box<dyn CameraModel>) = match projection {
+            Projection::Perspective => (
+                Box::new(self.off_axis_eye(-eye, stereo.convergence)),
+                Box::new(self.off_axis_eye(eye, stereo.convergence)),
+            ),
+            Projection::Equirectangular => (
+                Box::new(self.equirectangular(-eye, stereo.convergence)),
+                Box::new(self.equirectangular(eye, stereo.convergence)),
+            ),
+            _ => {
+                return Err(String::from(
+                    "stereo needs the perspective or the equirectangular camera",
+                ))
+            }
+        };
+        Ok(Box::new(StereoCamera {
+            left,
+            right,
+            layout: stereo.layout,
+        }))
+    }
+
+    /// The same view through a lens system, focused at the camera's focus
+    /// distance unless the settings say otherwise.
+    pub fn realistic(&self, settings: &Realistic) -> Result<Box<dyn CameraModel>, String> {
+        let focus_dist = settings.focus_distance.unwrap_or_else(|| self.focus_dist());
+        let lens = settings.lens.focused(focus_dist / settings.scale)?;
+        let camera = RealisticCamera::new(
+            self.origin,
+            self.u,
+            self.v,
+            self.w,
+            self.horizontal.length() / self.vertical.length(),
+            settings,
+            lens,
+            self.shutter.clone(),
+        )?;
+        Ok(Box::new(camera))
+    }
+
+    fn focus_dist(&self) -> f64 {
+        let center = self.lower_left_corner + self.horizontal / 2.0 + self.vertical / 2.0;
+        (center - self.origin).length()
+    }
+
+    // the camera moved `offset` along u, with its window shifted back so the
+    // view axis of the original camera is in the same place at `convergence`
+    fn off_axis_eye(&self, offset: f64, convergence: Option<f64>) -> Camera {
+        let focus_dist = self.focus_dist();
+        let convergence = convergence.unwrap_or(focus_dist);
+        Camera {
+            origin: self.origin + offset * self.u,
+            lower_left_corner: self.lower_left_corner
+                + (offset * (1.0 - focus_dist / convergence)) * self.u,
+            ..self.clone()
+        }
+    }
+
+    fn equirectangular(&self, eye: f64, convergence: Option<f64>) -> EquirectangularCamera {
+        EquirectangularCamera {
+            origin: self.origin,
+            u: self.u,
+            v: self.v,
+            w: self.w,
+            eye,
+            convergence,
+            shutter: self.shutter.clone(),
+        }
+    }
+}
+
+impl CameraModel for Camera {
+    // with chromatic aberration, the ray of a random color channel
+    fn get_ray(&self, s: f64, t: f64) -> Option<Ray> {
+        self.get_weighted_ray(s, t).map(|(ray, _)| ray)
+    }
+
+    /// With chromatic aberration every ray only carries one color channel,
+    /// picked at random, and is weighted to make up for the other two.
+    fn get_weighted_ray(&self, s: f64, t: f64) -> Option<(Ray, Vector)> {
+        let lens = &self.lens;
+        let (channel, weight) = if lens.lateral_ca != 0.0 || lens.longitudinal_ca != 0.0 {
+            let channel = ((random_float() * 3.0) as usize).min(2);
+            let mut weight = [0.0; 3];
+            weight[channel] = 3.0;
+            // -1 for red, 0 for green and 1 for blue
+            (
+                channel as f64 - 1.0,
+                Vector::new(weight[0], weight[1], weight[2]),
+            )
+        } else {
+            (0.0, Vector::new(1.0, 1.0, 1.0))
+        };
+
+        let magnification = 1.0 + lens.lateral_ca * channel / 2.0;
+        let (s, t) = (
+            0.5 + (s - 0.5) * magnification,
+            0.5 + (t - 0.5) * magnification,
+        );
+        let target = self.lower_left_corner + s * self.horizontal + t * self.vertical;
+        let target =
+            self.origin + (1.0 + lens.longitudinal_ca * channel / 2.0) * (target - self.origin);
+
+        let p = lens.shape.sample();
+        if lens.cats_eye > 0.0 {
+            // the barrel is a circle as large as the lens, seen further off
+            // center the further the point is from the center of the frame
+            let (width, height) = (self.horizontal.length(), self.vertical.length());
+            let half_diagonal = (width * width + height * height).sqrt() / 2.0;
+            let x = (s - 0.5) * width / half_diagonal;
+            let y = (t - 0.5) * height / half_diagonal;
+            let barrel = Vector::new(x, y, 0.0).scale(lens.cats_eye);
+            if (p - barrel).squared_length() > 1.0 {
+                return None;
+            }
+        }
+
+        let rd = self.lens_radius * p;
         let offset = rd.x() * self.u + rd.y() * self.v;
-        Ray::new(
+        let ray = Ray::new(
             self.origin + offset,
-            self.lower_left_corner + s * self.horizontal + t * self.vertical - self.origin - offset,
-            random_float_between(self.time0, self.time1),
-        )
+            target - self.origin - offset,
+            self.shutter.sample(),
+        );
+        Some((ray, weight))
+    }
+}
+
+/// Parallel rays, all looking down the view direction from a `width` x `height`
+/// rectangle centered on the camera position.
+#[derive(Clone)]
+pub struct OrthographicCamera {
+    origin: Vector,
+    u: Vector,
+    v: Vector,
+    w: Vector,
+    width: f64,
+    height: f64,
+    shutter: Shutter,
+}
+
+impl CameraModel for OrthographicCamera {
+    fn get_ray(&self, s: f64, t: f64) -> Option<Ray> {
+        let origin =
+            self.origin + ((s - 0.5) * self.width) * self.u + ((t - 0.5) * self.height) * self.v;
+        Some(Ray::new(origin, self.w.reverse(), self.shutter.sample()))
+    }
+}
+
+/// How a fisheye lens maps the angle from the view direction to the distance
+/// from the image center.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum FisheyeMapping {
+    // distance proportional to the angle
+    Equidistant,
+    // preserves areas, r = 2 sin(theta / 2)
+    Equisolid,
+}
+
+/// Circular fisheye, the image circle touches the top and bottom of the frame
+/// and spans `fov` degrees across.
+#[derive(Clone)]
+pub struct FisheyeCamera {
+    origin: Vector,
+    u: Vector,
+    v: Vector,
+    w: Vector,
+    mapping: FisheyeMapping,
+    half_fov: f64,
+    aspect_ratio: f64,
+    shutter: Shutter,
+}
+
+impl CameraModel for FisheyeCamera {
+    fn get_ray(&self, s: f64, t: f64) -> Option<Ray> {
+        // position in the image circle, which has radius 1
+        let x = 2.0 * (s - 0.5) * self.aspect_ratio;
+        let y = 2.0 * (t - 0.5);
+        let r = (x * x + y * y).sqrt();
+        if r > 1.0 {
+            return None;
+        }
+        let theta = match self.mapping {
+            FisheyeMapping::Equidistant => r * self.half_fov,
+            FisheyeMapping::Equisolid => 2.0 * (r * (self.half_fov / 2.0).sin()).asin(),
+        };
+        let phi = y.atan2(x);
+        let direction =
+            theta.sin() * (phi.cos() * self.u + phi.sin() * self.v) - theta.cos() * self.w;
+        Some(Ray::new(self.origin, direction, self.shutter.sample()))
+    }
+}
+
+/// Full 360 x 180 degree panorama in the latitude-longitude layout used for
+/// environment maps, with the view direction in the middle of the frame. The
+/// image should be twice as wide as it is high.
+///
+/// For omni-directional stereo the rays start from a circle of radius |eye|
+/// around the origin instead, tangent to it, which is where an eye is when the
+/// viewer turns to look that way. Positive is the right eye.
+#[derive(Clone)]
+pub struct EquirectangularCamera {
+    origin: Vector,
+    u: Vector,
+    v: Vector,
+    w: Vector,
+    eye: f64,
+    // distance at which both eyes look at the same point, infinity if None
+    convergence: Option<f64>,
+    shutter: Shutter,
+}
+
+impl CameraModel for EquirectangularCamera {
+    fn get_ray(&self, s: f64, t: f64) -> Option<Ray> {
+        let longitude = (s - 0.5) * 2.0 * PI;
+        let latitude = (t - 0.5) * PI;
+        let direction = latitude.cos() * (longitude.sin() * self.u - longitude.cos() * self.w)
+            + latitude.sin() * self.v;
+        // the circle shrinks toward the poles, so they don't swirl
+        let offset =
+            (self.eye * latitude.cos()) * (longitude.cos() * self.u + longitude.sin() * self.w);
+        let direction = match self.convergence {
+            Some(convergence) => convergence * direction - offset,
+            None => direction,
+        };
+        Some(Ray::new(
+            self.origin + offset,
+            direction,
+            self.shutter.sample(),
+        ))
+    }
+}
+
+/// Camera model to render a scene with, see `Camera::with_projection`.
+#[derive(Copy, Clone, Debug, PartialEq, Default)]
+pub enum Projection {
+    // the scene's own camera
+    #[default]
+    Perspective,
+    // height of the view in scene units, the height of the focus plane if None
+    Orthographic {
+        height: Option<f64>,
+    },
+    // fov in degrees
+    Fisheye {
+        mapping: FisheyeMapping,
+        fov: f64,
+    },
+    Equirectangular,
+}
+
+impl FromStr for Projection {
+    type Err = String;
+
+    // "perspective", "orthographic[=HEIGHT]", "fisheye[=FOV]",
+    // "fisheye-equisolid[=FOV]" or "equirectangular"
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let (name, value) = match s.split_once('=') {
+            Some((name, value)) => (name, Some(value)),
+            None => (s, None),
+        };
+        let number = |what: &str| -> Result<Option<f64>, String> {
+            match value {
+                Some(value) => match value.parse::<f64>() {
+                    Ok(v) if v > 0.0 => Ok(Some(v)),
+                    _ => Err(format!("invalid {} {:?}", what, value)),
+                },
+                None => Ok(None),
+            }
+        };
+        let fisheye = |mapping| -> Result<Projection, String> {
+            let fov = number("field of view")?.unwrap_or(180.0);
+            if fov > 360.0 {
+                return Err(format!("fisheye field of view {} is over 360 degrees", fov));
+            }
+            Ok(Projection::Fisheye { mapping, fov })
+        };
+        match name {
+            "perspective" if value.is_none() => Ok(Projection::Perspective),
+            "orthographic" => Ok(Projection::Orthographic {
+                height: number("height")?,
+            }),
+            "fisheye" => fisheye(FisheyeMapping::Equidistant),
+            "fisheye-equisolid" => fisheye(FisheyeMapping::Equisolid),
+            "equirectangular" if value.is_none() => Ok(Projection::Equirectangular),
+            _ => Err(format!(
+                "unknown camera {:?}, expected one of perspective, orthographic[=HEIGHT], fisheye[=FOV], fisheye-equisolid[=FOV], equirectangular",
+                s
+            )),
+        }
+    }
+}
+
+/// How the two eyes of a stereo pair share the frame, the left eye always
+/// comes first: on top, or on the left.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum StereoLayout {
+    TopBottom,
+    SideBySide,
+}
+
+impl StereoLayout {
+    // size of the frame holding two eyes of `width` x `height`
+    pub fn frame_size(self, width: usize, height: usize) -> (usize, usize) {
+        match self {
+            StereoLayout::TopBottom => (width, 2 * height),
+            StereoLayout::SideBySide => (2 * width, height),
+        }
+    }
+}
+
+impl FromStr for StereoLayout {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "top-bottom" => Ok(StereoLayout::TopBottom),
+            "side-by-side" => Ok(StereoLayout::SideBySide),
+            _ => Err(format!(
+                "unknown stereo layout {:?}, expected top-bottom or side-by-side",
+                s
+            )),
+        }
+    }
+}
+
+/// Stereo rendering settings, see `Camera::stereo_pair`.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct Stereo {
+    pub layout: StereoLayout,
+    // interpupillary distance, in scene units
+    pub ipd: f64,
+    // distance at which the eyes converge, the camera's default if None
+    pub convergence: Option<f64>,
+}
+
+impl Stereo {
+    pub fn new(layout: StereoLayout) -> Stereo {
+        Stereo {
+            layout,
+            ipd: 0.064,
+            convergence: None,
+        }
+    }
+}
+
+struct StereoCamera {
+    left: Box<dyn CameraModel>,
+    right: Box<dyn CameraModel>,
+    layout: StereoLayout,
+}
+
+impl StereoCamera {
+    // the eye seeing (s, t) and the position in its own image
+    fn eye(&self, s: f64, t: f64) -> (&dyn CameraModel, f64, f64) {
+        match self.layout {
+            StereoLayout::TopBottom if t >= 0.5 => (self.left.as_ref(), s, 2.0 * t - 1.0),
+            StereoLayout::TopBottom => (self.right.as_ref(), s, 2.0 * t),
+            StereoLayout::SideBySide if s < 0.5 => (self.left.as_ref(), 2.0 * s, t),
+            StereoLayout::SideBySide => (self.right.as_ref(), 2.0 * s - 1.0, t),
+        }
+    }
+}
+
+impl CameraModel for StereoCamera {
+    fn get_ray(&self, s: f64, t: f64) -> Option<Ray> {
+        let (eye, s, t) = self.eye(s, t);
+        eye.get_ray(s, t)
+    }
+
+    fn get_weighted_ray(&self, s: f64, t: f64) -> Option<(Ray, Vector)> {
+        let (eye, s, t) = self.eye(s, t);
+        eye.get_weighted_ray(s, t)
     }
 }
@@ -0,0 +1,341 @@
+use crate::aov::Aov;
+use crate::film::{AovBuffer, Film};
+use crate::render::{PixelBounds, RenderSettings};
+use crate::vector::Vector;
+use std::convert::TryFrom;
+use std::fmt;
+use std::fs::{self, File};
+use std::io::{self, BufReader, BufWriter, Read, Write};
+use std::path::Path;
+
+const MAGIC: &[u8; 8] = b"TRACERCK";
+const VERSION: u32 = 3;
+// the magic number, version, settings hash, seed, passes and bounds
+const HEADER_SIZE: u64 = 8 + 4 + 8 + 8 + 4 + 4 * 8;
+
+/// Snapshot of a progressive render that can be resumed later.
+///
+/// The sample generators are reseeded for every pixel of every pass from the
+/// render seed (see `render::render_pass`), so the seed together with the
+/// number of completed passes is the whole RNG state.
+pub struct Checkpoint {
+    pub settings_hash: u64,
+    pub seed: u64,
+    pub passes: u32,
+    pub film: Film,
+}
+
+#[derive(Debug)]
+pub enum CheckpointError {
+    Io(io::Error),
+    Format(String),
+    SettingsMismatch { expected: u64, found: u64 },
+}
+
+impl fmt::Display for CheckpointError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            CheckpointError::Io(e) => write!(f, "checkpoint i/o error: {}", e),
+            CheckpointError::Format(msg) => write!(f, "invalid checkpoint: {}", msg),
+            CheckpointError::SettingsMismatch { expected, found } => write!(
+                f,
+                "checkpoint was rendered with different settings (hash {:016x}, expected {:016x})",
+                found, expected
+            ),
+        }
+    }
+}
+
+impl std::error::Error for CheckpointError {}
+
+impl From<io::Error> for CheckpointError {
+    fn from(e: io::Error) -> Self {
+        CheckpointError::Io(e)
+    }
+}
+
+impl Checkpoint {
+    pub fn new(settings: &RenderSettings, passes: u32, film: Film) -> Checkpoint {
+        Checkpoint {
+            settings_hash: settings.hash(),
+            seed: settings.seed,
+            passes,
+            film,
+        }
+    }
+
+    /// Writes the checkpoint next to `path` first and then renames it into place,
+    /// so an interrupted save never clobbers the previous checkpoint.
+    pub fn save(&self, path: &Path) -> Result<(), CheckpointError> {
+        let tmp = path.with_extension("tmp");
+        {
+            let mut out = BufWriter::new(File::create(&tmp)?);
+            out.write_all(MAGIC)?;
+            out.write_all(&VERSION.to_le_bytes())?;
+            out.write_all(&self.settings_hash.to_le_bytes())?;
+            out.write_all(&self.seed.to_le_bytes())?;
+            out.write_all(&self.passes.to_le_bytes())?;
+            let bounds = self.film.bounds();
+            for v in [bounds.x0, bounds.y0, bounds.x1, bounds.y1] {
+                out.write_all(&(v as u64).to_le_bytes())?;
+            }
+            let film = &self.film;
+            for ((sum, weight), count) in film.sums().iter().zip(film.weights()).zip(film.counts())
+            {
+                for c in [sum.x(), sum.y(), sum.z(), *weight] {
+                    out.write_all(&c.to_le_bytes())?;
+                }
+                out.write_all(&count.to_le_bytes())?;
+            }
+            out.write_all(&(film.aov_buffers().len() as u32).to_le_bytes())?;
+            for buffer in film.aov_buffers() {
+                out.write_all(&buffer.aov.index().to_le_bytes())?;
+                for sum in buffer.sums.iter() {
+                    for c in [sum.x(), sum.y(), sum.z()] {
+                        out.write_all(&c.to_le_bytes())?;
+                    }
+                }
+            }
+            out.flush()?;
+        }
+        fs::rename(&tmp, path)?;
+        Ok(())
+    }
+
+    /// Reads the checkpoint at `path`, failing unless it can be continued
+    /// with `settings`. Its bounds are checked against the image's, and
+    /// against the length of the file, before any pixels are read.
+    pub fn load(path: &Path, settings: &RenderSettings) -> Result<Checkpoint, CheckpointError> {
+        let file = File::open(path)?;
+        let length = file.metadata()?.len();
+        let mut input = BufReader::new(file);
+        let mut magic = [0u8; 8];
+        input.read_exact(&mut magic)?;
+        if &magic != MAGIC {
+            return Err(CheckpointError::Format(String::from("bad magic number")));
+        }
+        let version = read_u32(&mut input)?;
+        if version != VERSION {
+            return Err(CheckpointError::Format(format!(
+                "unsupported version {}",
+                version
+            )));
+        }
+        let settings_hash = read_u64(&mut input)?;
+        let seed = read_u64(&mut input)?;
+        let passes = read_u32(&mut input)?;
+        let mut read_usize = || -> Result<usize, CheckpointError> {
+            let n = read_u64(&mut input)?;
+            usize::try_from(n).map_err(|_| CheckpointError::Format(format!("invalid bound {}", n)))
+        };
+        let bounds = PixelBounds {
+            x0: read_usize()?,
+            y0: read_usize()?,
+            x1: read_usize()?,
+            y1: read_usize()?,
+        };
+        if bounds.x0 > bounds.x1 || bounds.y0 > bounds.y1 {
+            return Err(CheckpointError::Format(format!(
+                "invalid bounds {:?}",
+                bounds
+            )));
+        }
+        let expected = settings.hash();
+        if settings_hash != expected {
+            return Err(CheckpointError::SettingsMismatch {
+                expected,
+                found: settings_hash,
+            });
+        }
+        if bounds != settings.render_bounds() {
+            return Err(CheckpointError::Format(format!(
+                "bounds {:?} don't match the image's {:?}",
+                bounds,
+                settings.render_bounds()
+            )));
+        }
+
+        // the header, then each pixel's sum, weight and count, and the
+        // number of AOVs; each AOV then takes an index and a sum per pixel
+        let area = bounds.area() as u64;
+        let mut remaining = length.saturating_sub(HEADER_SIZE);
+        let mut take = |bytes: Option<u64>| match bytes {
+            Some(bytes) if bytes <= remaining => {
+                remaining -= bytes;
+                Ok(())
+            }
+            _ => Err(CheckpointError::Format(String::from(
+                "the file is truncated",
+            ))),
+        };
+        take(area.checked_mul(4 * 8 + 4).and_then(|n| n.checked_add(4)))?;
+
+        let mut sums = Vec::with_capacity(bounds.area());
+        let mut weights = Vec::with_capacity(bounds.area());
+        let mut counts = Vec::with_capacity(bounds.area());
+        for _ in 0..bounds.area() {
+            let x = read_f64(&mut input)?;
+            let y = read_f64(&mut input)?;
+            let z = read_f64(&mut input)?;
+            sums.push(Vector::new(x, y, z));
+            weights.push(read_f64(&mut input)?);
+            counts.push(read_u32(&mut input)?);
+        }
+
+        let aov_count = read_u32(&mut input)?;
+        let mut aovs = Vec::new();
+        for _ in 0..aov_count {
+            take(area.checked_mul(3 * 8).and_then(|n| n.checked_add(4)))?;
+            let index = read_u32(&mut input)?;
+            let aov = *Aov::ALL
+                .get(index as usize)
+                .ok_or_else(|| CheckpointError::Format(format!("unknown AOV {}", index)))?;
+            let mut sums = Vec::with_capacity(bounds.area());
+            for _ in 0..bounds.area() {
+                let x = read_f64(&mut input)?;
+                let y = read_f64(&mut input)?;
+                let z = read_f64(&mut input)?;
+                sums.push(Vector::new(x, y, z));
+            }
+            aovs.push(AovBuffer { aov, sums });
+        }
+
+        Ok(Checkpoint {
+            settings_hash,
+            seed,
+            passes,
+            film: Film::from_parts(bounds, sums, weights, counts, aovs),
+        })
+    }
+}
+
+fn read_u32(input: &mut impl Read) -> io::Result<u32> {
+    let mut buf = [0u8; 4];
+    input.read_exact(&mut buf)?;
+    Ok(u32::from_le_bytes(buf))
+}
+
+fn read_u64(input: &mut impl Read) -> io::Result<u64> {
+    let mut buf = [0u8; 8];
+    input.read_exact(&mut buf)?;
+    Ok(u64::from_le_bytes(buf))
+}
+
+fn read_f64(input: &mut impl Read) -> io::Result<f64> {
+    let mut buf = [0u8; 8];
+    input.read_exact(&mut buf)?;
+    Ok(f64::from_le_bytes(buf))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::path::PathBuf;
+
+    // a directory of its own for each test, removed again when it's dropped
+    struct TempDir(PathBuf);
+
+    impl TempDir {
+        fn new(name: &str) -> TempDir {
+            let path = std::env::temp_dir().join(format!(
+                "tracer-checkpoint-{}-{}",
+                name,
+                std::process::id()
+            ));
+            fs::create_dir_all(&path).unwrap();
+            TempDir(path)
+        }
+    }
+
+    impl Drop for TempDir {
+        fn drop(&mut self) {
+            let _ = fs::remove_dir_all(&self.0);
+        }
+    }
+
+    fn test_settings() -> RenderSettings {
+        let mut settings = RenderSettings::new(8, 2.0);
+        settings.aovs = vec![Aov::Normal];
+        settings.seed = 7;
+        settings
+    }
+
+    fn film(settings: &RenderSettings) -> Film {
+        let bounds = settings.render_bounds();
+        let sums = (0..bounds.area())
+            .map(|i| Vector::new(i as f64, 0.5, -(i as f64)))
+            .collect();
+        let weights = (0..bounds.area()).map(|i| 1.0 + i as f64 * 0.25).collect();
+        let counts = (0..bounds.area()).map(|i| i as u32).collect();
+        let normals = AovBuffer {
+            aov: Aov::Normal,
+            sums: (0..bounds.area())
+                .map(|i| Vector::new(0.0, 1.0, i as f64))
+                .collect(),
+        };
+        Film::from_parts(bounds, sums, weights, counts, vec![normals])
+    }
+
+    #[test]
+    fn round_trips_through_a_file() {
+        let dir = TempDir::new("round-trip");
+        let path = dir.0.join("render.ckpt");
+        let settings = test_settings();
+        let saved = Checkpoint::new(&settings, 3, film(&settings));
+        saved.save(&path).unwrap();
+        assert!(!path.with_extension("tmp").exists());
+
+        let loaded = Checkpoint::load(&path, &settings).unwrap();
+        assert_eq!(loaded.settings_hash, settings.hash());
+        assert_eq!(loaded.seed, 7);
+        assert_eq!(loaded.passes, 3);
+        assert_eq!(loaded.film.bounds(), saved.film.bounds());
+        assert_eq!(loaded.film.sums(), saved.film.sums());
+        assert_eq!(loaded.film.weights(), saved.film.weights());
+        assert_eq!(loaded.film.counts(), saved.film.counts());
+        let aovs = loaded.film.aov_buffers();
+        assert_eq!(aovs.len(), 1);
+        assert_eq!(aovs[0].aov, Aov::Normal);
+        assert_eq!(aovs[0].sums, saved.film.aov_buffers()[0].sums);
+    }
+
+    #[test]
+    fn rejects_other_settings() {
+        let dir = TempDir::new("mismatch");
+        let path = dir.0.join("render.ckpt");
+        let settings = test_settings();
+        Checkpoint::new(&settings, 1, film(&settings))
+            .save(&path)
+            .unwrap();
+
+        let mut reseeded = test_settings();
+        reseeded.seed = 8;
+        match Checkpoint::load(&path, &reseeded) {
+            Err(CheckpointError::SettingsMismatch { expected, found }) => {
+                assert_eq!(expected, reseeded.hash());
+                assert_eq!(found, settings.hash());
+            }
+            _ => panic!("expected a settings mismatch"),
+        }
+    }
+
+    #[test]
+    fn rejects_truncated_files() {
+        let dir = TempDir::new("truncated");
+        let path = dir.0.join("render.ckpt");
+        let settings = test_settings();
+        Checkpoint::new(&settings, 1, film(&settings))
+            .save(&path)
+            .unwrap();
+
+        let bytes = fs::read(&path).unwrap();
+        // cut into the pixels, and into the AOVs
+        for cut in [HEADER_SIZE as usize + 10, bytes.len() - 1] {
+            fs::write(&path, &bytes[..cut]).unwrap();
+            match Checkpoint::load(&path, &settings) {
+                Err(CheckpointError::Format(msg)) => assert_eq!(msg, "the file is truncated"),
+                _ => panic!("expected {} bytes to be rejected", cut),
+            }
+        }
+    }
+}
@@ -0,0 +1,260 @@
+use crate::aabb::Aabb;
+use crate::hitable::{HitRecord, Hitable};
+use crate::ray::Ray;
+
+/// Part of a ray inside a solid, from where it enters to where it leaves.
+#[derive(Copy, Clone)]
+pub struct Interval<'a> {
+    pub enter: HitRecord<'a>,
+    pub exit: HitRecord<'a>,
+}
+
+/// A closed object, with an inside and an outside.
+pub trait Solid: Hitable {
+    /// Every part of the line the ray lies on that is inside the solid,
+    /// including behind the ray's origin, in order and not overlapping.
+    /// Normals point out of the solid on both ends.
+    fn intervals(&self, ray: &Ray) -> Vec<Interval<'_>>;
+}
+
+// the first boundary between t_min and t_max
+pub fn first_boundary<'a>(
+    intervals: &[Interval<'a>],
+    t_min: f64,
+    t_max: f64,
+) -> Option<HitRecord<'a>> {
+    intervals
+        .iter()
+        .flat_map(|interval| [interval.enter, interval.exit])
+        .find(|hit| hit.t > t_min && hit.t < t_max)
+}
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum CsgOperation {
+    // inside either
+    Union,
+    // inside both
+    Intersection,
+    // inside the first but not the second
+    Difference,
+}
+
+impl CsgOperation {
+    fn inside(self, a: bool, b: bool) -> bool {
+        match self {
+            CsgOperation::Union => a || b,
+            CsgOperation::Intersection => a && b,
+            CsgOperation::Difference => a && !b,
+        }
+    }
+}
+
+/// Two solids combined into one. Every surface keeps the material of the
+/// solid it comes from, so the hollow a difference carves out has the
+/// material of the second solid.
+pub struct Csg<A: Solid, B: Solid> {
+    a: A,
+    b: B,
+    operation: CsgOperation,
+}
+
+impl<A: Solid, B: Solid> Csg<A, B> {
+    pub fn new(a: A, b: B, operation: CsgOperation) -> Self {
+        Csg { a, b, operation }
+    }
+
+    pub fn union(a: A, b: B) -> Self {
+        Csg::new(a, b, CsgOperation::Union)
+    }
+
+    pub fn intersection(a: A, b: B) -> Self {
+        Csg::new(a, b, CsgOperation::Intersection)
+    }
+
+    pub fn difference(a: A, b: B) -> Self {
+        Csg::new(a, b, CsgOperation::Difference)
+    }
+}
+
+impl<A: Solid, B: Solid> Hitable for Csg<A, B> {
+    fn hit(&self, ray: &Ray, t_min: f64, t_max: f64) -> Option<HitRecord<'_>> {
+        first_boundary(&self.intervals(ray), t_min, t_max)
+    }
+
+    fn bounding_box(&self, time0: f64, time1: f64) -> Option<Aabb> {
+        let a = self.a.bounding_box(time0, time1);
+        match self.operation {
+            CsgOperation::Union => Some(a?.surrounding(&self.b.bounding_box(time0, time1)?)),
+            CsgOperation::Intersection => match (a, self.b.bounding_box(time0, time1)) {
+                (Some(a), Some(b)) => Some(a.overlap(&b)),
+                (a, b) => a.or(b),
+            },
+            CsgOperation::Difference => a,
+        }
+    }
+}
+
+impl<A: Solid, B: Solid> Solid for Csg<A, B> {
+    // Walks through the boundaries of both solids in order, keeping track of
+    // which ones the ray is in, and records where that makes it go in and out
+    // of the combination.
+    fn intervals(&self, ray: &Ray) -> Vec<Interval<'_>> {
+        // (hit, from b, entering)
+        let mut events: Vec<(HitRecord, bool, bool)> = Vec::new();
+        for (intervals, from_b) in [
+            (self.a.intervals(ray), false),
+            (self.b.intervals(ray), true),
+        ] {
+            for interval in intervals {
+                events.push((interval.enter, from_b, true));
+                events.push((interval.exit, from_b, false));
+            }
+        }
+        events.sort_by(|x, y| x.0.t.total_cmp(&y.0.t));
+
+        let (mut in_a, mut in_b) = (false, false);
+        let mut enter: Option<HitRecord> = None;
+        let mut intervals = Vec::new();
+        for (mut hit, from_b, entering) in events {
+            let was_inside = self.operation.inside(in_a, in_b);
+            if from_b {
+                in_b = entering;
+            } else {
+                in_a = entering;
+            }
+            let inside = self.operation.inside(in_a, in_b);
+            if inside == was_inside {
+                continue;
+            }
+            // the surface of a hollow faces into it
+            if from_b && self.operation == CsgOperation::Difference {
+                hit.normal = hit.normal.reverse();
+            }
+            match enter.take() {
+                None => enter = Some(hit),
+                Some(enter) => intervals.push(Interval { enter, exit: hit }),
+            }
+        }
+        intervals
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::material::Lambertian;
+    use crate::sphere::Sphere;
+    use crate::vector::Vector;
+
+    fn ball(x: f64, radius: f64) -> Sphere<Lambertian> {
+        Sphere::new(
+            Vector::new(x, 0.0, 0.0),
+            radius,
+            Lambertian::new(Vector::new(0.5, 0.5, 0.5)),
+        )
+    }
+
+    // along the x axis from `x`, so that x is t plus where it starts
+    fn ray_from(x: f64, direction: f64) -> Ray {
+        Ray::new(
+            Vector::new(x, 0.0, 0.0),
+            Vector::new(direction, 0.0, 0.0),
+            0.0,
+        )
+    }
+
+    // the x at both ends of each interval, and the x of the ends' normals
+    fn spans(solid: &impl Solid) -> Vec<[f64; 4]> {
+        let ray = ray_from(-10.0, 1.0);
+        solid
+            .intervals(&ray)
+            .iter()
+            .map(|i| {
+                [
+                    i.enter.t - 10.0,
+                    i.exit.t - 10.0,
+                    i.enter.normal.x(),
+                    i.exit.normal.x(),
+                ]
+            })
+            .collect()
+    }
+
+    fn assert_spans(found: Vec<[f64; 4]>, expected: &[[f64; 4]]) {
+        assert_eq!(found.len(), expected.len(), "{:?}", found);
+        for (a, b) in found.iter().zip(expected) {
+            for (x, y) in a.iter().zip(b) {
+                assert!((x - y).abs() < 1e-9, "{:?} vs {:?}", found, expected);
+            }
+        }
+    }
+
+    #[test]
+    fn combines_overlapping_solids() {
+        // from -1 to 1, and from 0 to 2
+        let union = Csg::union(ball(0.0, 1.0), ball(1.0, 1.0));
+        assert_spans(spans(&union), &[[-1.0, 2.0, -1.0, 1.0]]);
+        let intersection = Csg::intersection(ball(0.0, 1.0), ball(1.0, 1.0));
+        assert_spans(spans(&intersection), &[[0.0, 1.0, -1.0, 1.0]]);
+        // the hollow faces out of what's left
+        let difference = Csg::difference(ball(0.0, 1.0), ball(1.0, 1.0));
+        assert_spans(spans(&difference), &[[-1.0, 0.0, -1.0, 1.0]]);
+
+        // apart, they only have a union
+        let union = Csg::union(ball(0.0, 1.0), ball(3.0, 1.0));
+        assert_spans(
+            spans(&union),
+            &[[-1.0, 1.0, -1.0, 1.0], [2.0, 4.0, -1.0, 1.0]],
+        );
+        let intersection = Csg::intersection(ball(0.0, 1.0), ball(3.0, 1.0));
+        assert!(spans(&intersection).is_empty());
+        let difference = Csg::difference(ball(0.0, 1.0), ball(3.0, 1.0));
+        assert_spans(spans(&difference), &[[-1.0, 1.0, -1.0, 1.0]]);
+    }
+
+    #[test]
+    fn hits_the_nearest_boundary_ahead() {
+        let union = Csg::union(ball(0.0, 1.0), ball(1.0, 1.0));
+        let hit = union.hit(&ray_from(-10.0, 1.0), 0.001, f64::MAX).unwrap();
+        assert!((hit.t - 9.0).abs() < 1e-9);
+        // the surface inside the union isn't there
+        let hit = union.hit(&ray_from(0.5, 1.0), 0.001, f64::MAX).unwrap();
+        assert!((hit.t - 1.5).abs() < 1e-9);
+        assert!((hit.normal.x() - 1.0).abs() < 1e-9);
+        assert!(union.hit(&ray_from(0.5, 1.0), 0.001, 1.0).is_none());
+
+        // from inside the hollow, back onto its wall
+        let difference = Csg::difference(ball(0.0, 1.0), ball(1.0, 1.0));
+        assert!(difference
+            .hit(&ray_from(0.5, 1.0), 0.001, f64::MAX)
+            .is_none());
+        let hit = difference
+            .hit(&ray_from(0.5, -1.0), 0.001, f64::MAX)
+            .unwrap();
+        assert!((hit.t - 0.5).abs() < 1e-9);
+        assert!((hit.normal.x() - 1.0).abs() < 1e-9);
+    }
+
+    #[test]
+    fn nests_differences() {
+        // from -1 to 1, less 0 to 2, less -1.5 to -0.5
+        let nested = Csg::difference(
+            Csg::difference(ball(0.0, 1.0), ball(1.0, 1.0)),
+            ball(-1.0, 0.5),
+        );
+        assert_spans(spans(&nested), &[[-0.5, 0.0, -1.0, 1.0]]);
+
+        // from -1 to 1, less what's left of 0 to 2 less 0.5 to 1.5
+        let nested = Csg::difference(
+            ball(0.0, 1.0),
+            Csg::difference(ball(1.0, 1.0), ball(1.0, 0.5)),
+        );
+        assert_spans(
+            spans(&nested),
+            &[[-1.0, 0.0, -1.0, 1.0], [0.5, 1.0, -1.0, 1.0]],
+        );
+        let hit = nested.hit(&ray_from(0.25, 1.0), 0.001, f64::MAX).unwrap();
+        assert!((hit.t - 0.25).abs() < 1e-9);
+        assert!((hit.normal.x() + 1.0).abs() < 1e-9);
+    }
+}
@@ -0,0 +1,86 @@
+use crate::aabb::Aabb;
+use crate::csg::{first_boundary, Interval, Solid};
+use crate::hitable::{HitRecord, Hitable};
+use crate::material::Material;
+use crate::ray::Ray;
+use crate::vector::Vector;
+
+/// Axis-aligned box between two opposite corners, which can be turned with
+/// `Transformed`.
+pub struct Cuboid<M: Material> {
+    min: Vector,
+    max: Vector,
+    material: M,
+}
+
+impl<M: Material> Cuboid<M> {
+    pub fn new(a: Vector, b: Vector, material: M) -> Self {
+        let bounds = Aabb::new(a, a).surrounding(&Aabb::new(b, b));
+        Cuboid {
+            min: bounds.min,
+            max: bounds.max,
+            material,
+        }
+    }
+}
+
+impl<M: Material + Sync> Hitable for Cuboid<M> {
+    fn hit(&self, ray: &Ray, t_min: f64, t_max: f64) -> Option<HitRecord<'_>> {
+        first_boundary(&self.intervals(ray), t_min, t_max)
+    }
+
+    fn bounding_box(&self, _time0: f64, _time1: f64) -> Option<Aabb> {
+        Some(Aabb::new(self.min, self.max))
+    }
+}
+
+impl<M: Material + Sync> Solid for Cuboid<M> {
+    // The ray's line is inside the slab between the two faces of every axis
+    // for a while, and inside the box while it's in all three. It enters
+    // through the face it reaches last and leaves through the first it leaves.
+    fn intervals(&self, ray: &Ray) -> Vec<Interval<'_>> {
+        let (origin, direction) = (ray.origin(), ray.direction());
+        let axes = [
+            (origin.x(), direction.x(), self.min.x(), self.max.x()),
+            (origin.y(), direction.y(), self.min.y(), self.max.y()),
+            (origin.z(), direction.z(), self.min.z(), self.max.z()),
+        ];
+        let (mut t_enter, mut t_exit) = (f64::NEG_INFINITY, f64::INFINITY);
+        let (mut enter_axis, mut exit_axis) = ((0, -1.0), (0, 1.0));
+        for (axis, &(o, d, min, max)) in axes.iter().enumerate() {
+            if d == 0.0 {
+                if o < min || o > max {
+                    return Vec::new();
+                }
+                continue;
+            }
+            // the near and far faces, with the sign of their normals
+            let (near, far) = if d > 0.0 {
+                (((min - o) / d, -1.0), ((max - o) / d, 1.0))
+            } else {
+                (((max - o) / d, 1.0), ((min - o) / d, -1.0))
+            };
+            if near.0 > t_enter {
+                t_enter = near.0;
+                enter_axis = (axis, near.1);
+            }
+            if far.0 < t_exit {
+                t_exit = far.0;
+                exit_axis = (axis, far.1);
+            }
+        }
+        if t_enter >= t_exit {
+            return Vec::new();
+        }
+        let record = |t: f64, (axis, sign): (usize, f64)| {
+            let mut normal = [0.0; 3];
+            normal[axis] = sign;
+            let normal = Vector::new(normal[0], normal[1], normal[2]);
+            HitRecord::new(t, ray.at(t), normal, &self.material as &dyn Material)
+        };
+        vec![Interval {
+            enter: record(t_enter, enter_axis),
+            exit: record(t_exit, exit_axis),
+        }]
+    }
+}
@@ -0,0 +1,417 @@
+//! Thin curves for hair, fur and grass: cubic Béziers with a width that
+//! varies along them, intersected by recursive subdivision like PBRT's
+//! `Curve`. The ray is moved to a space where it runs down the z axis from
+//! the origin, and the curve is split until each piece is close enough to a
+//! straight segment to be tested as one.
+//!
+//! Hits have u running along the whole strand and v across its width, from
+//! 0 to 1 in the direction of `normal × tangent`, with the tangent set to the
+//! direction of the curve.
+
+use crate::aabb::Aabb;
+use crate::bvh::Bvh;
+use crate::hitable::{HitRecord, Hitable};
+use crate::material::Material;
+use crate::ray::Ray;
+use crate::vector::Vector;
+use std::f64::consts::SQRT_2;
+use std::fs;
+use std::path::Path;
+
+/// The shape of a curve across its width.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum CurveKind {
+    /// Always faces the ray and is shaded like a tube, for hair and fur.
+    Cylinder,
+    /// A flat strip facing the normals given at its start and end, which
+    /// thins out when seen edge on, for grass and leaves.
+    Ribbon(Vector, Vector),
+}
+
+/// A cubic Bézier segment, part of a strand that runs from u0 to u1.
+#[derive(Copy, Clone, Debug)]
+pub struct Curve {
+    points: [Vector; 4],
+    widths: (f64, f64),
+    kind: CurveKind,
+    // the part of the strand this segment covers, for the reported u
+    u_range: (f64, f64),
+}
+
+impl Curve {
+    pub fn cylinder(points: [Vector; 4], width0: f64, width1: f64) -> Curve {
+        Curve {
+            points,
+            widths: (width0, width1),
+            kind: CurveKind::Cylinder,
+            u_range: (0.0, 1.0),
+        }
+    }
+
+    pub fn ribbon(
+        points: [Vector; 4],
+        width0: f64,
+        width1: f64,
+        normal0: Vector,
+        normal1: Vector,
+    ) -> Curve {
+        Curve {
+            points,
+            widths: (width0, width1),
+            kind: CurveKind::Ribbon(normal0.unit(), normal1.unit()),
+            u_range: (0.0, 1.0),
+        }
+    }
+
+    /// Splits a strand of 3n + 1 control points into n segments sharing
+    /// their ends, the width and ribbon normals changing along the whole
+    /// strand.
+    pub fn strand(points: &[Vector], width0: f64, width1: f64, kind: CurveKind) -> Vec<Curve> {
+        let segments = points.len().saturating_sub(1) / 3;
+        (0..segments)
+            .map(|i| {
+                let u0 = i as f64 / segments as f64;
+                let u1 = (i + 1) as f64 / segments as f64;
+                let kind = match kind {
+                    CurveKind::Cylinder => CurveKind::Cylinder,
+                    CurveKind::Ribbon(n0, n1) => CurveKind::Ribbon(
+                        slerp(n0.unit(), n1.unit(), u0),
+                        slerp(n0.unit(), n1.unit(), u1),
+                    ),
+                };
+                Curve {
+                    points: [
+                        points[3 * i],
+                        points[3 * i + 1],
+                        points[3 * i + 2],
+                        points[3 * i + 3],
+                    ],
+                    widths: (lerp(u0, width0, width1), lerp(u1, width0, width1)),
+                    kind,
+                    u_range: (u0, u1),
+                }
+            })
+            .collect()
+    }
+
+    pub fn bounding_box(&self) -> Aabb {
+        let [p0, p1, p2, p3] = self.points;
+        let min = |a: f64, b: f64, c: f64, d: f64| a.min(b).min(c).min(d);
+        let max = |a: f64, b: f64, c: f64, d: f64| a.max(b).max(c).max(d);
+        Aabb::new(
+            Vector::new(
+                min(p0.x(), p1.x(), p2.x(), p3.x()),
+                min(p0.y(), p1.y(), p2.y(), p3.y()),
+                min(p0.z(), p1.z(), p2.z(), p3.z()),
+            ),
+            Vector::new(
+                max(p0.x(), p1.x(), p2.x(), p3.x()),
+                max(p0.y(), p1.y(), p2.y(), p3.y()),
+                max(p0.z(), p1.z(), p2.z(), p3.z()),
+            ),
+        )
+        .padded(0.5 * self.widths.0.max(self.widths.1))
+    }
+
+    // the nearest hit before t_max and the segment's own u there
+    fn intersect(&self, ray: &Ray, t_min: f64, t_max: f64) -> Option<(f64, f64)> {
+        let length = ray.direction().length();
+        let z = ray.direction() / length;
+        let helper = if z.x().abs() > 0.9 {
+            Vector::new(0.0, 1.0, 0.0)
+        } else {
+            Vector::new(1.0, 0.0, 0.0)
+        };
+        let x = helper.cross(z).unit();
+        let y = z.cross(x);
+        let to_ray = |p: Vector| {
+            let p = p - ray.origin();
+            Vector::new(p.dot(x), p.dot(y), p.dot(z))
+        };
+        let points = [
+            to_ray(self.points[0]),
+            to_ray(self.points[1]),
+            to_ray(self.points[2]),
+            to_ray(self.points[3]),
+        ];
+
+        // split until the pieces are within 5% of the width of straight
+        let mut flatness = 0.0f64;
+        for i in 0..2 {
+            let d = points[i] - 2.0 * points[i + 1] + points[i + 2];
+            flatness = flatness.max(d.x().abs()).max(d.y().abs()).max(d.z().abs());
+        }
+        let epsilon = 0.05 * self.widths.0.max(self.widths.1);
+        let depth =
+            ((SQRT_2 * 6.0 * flatness / (8.0 * epsilon)).log2() / 2.0).clamp(0.0, 10.0) as u32;
+
+        let mut search = Search {
+            curve: self,
+            ray_direction: z,
+            z_range: (t_min * length, t_max * length),
+            hit: None,
+        };
+        search.recurse(&points, (0.0, 1.0), depth);
+        search.hit.map(|(distance, u)| (distance / length, u))
+    }
+
+    fn width(&self, u: f64) -> f64 {
+        lerp(u, self.widths.0, self.widths.1)
+    }
+
+    fn normal(&self, u: f64) -> Option<Vector> {
+        match self.kind {
+            CurveKind::Cylinder => None,
+            CurveKind::Ribbon(n0, n1) => Some(slerp(n0, n1, u)),
+        }
+    }
+}
+
+// the state of one ray's subdivision search
+struct Search<'a> {
+    curve: &'a Curve,
+    ray_direction: Vector,
+    z_range: (f64, f64),
+    hit: Option<(f64, f64)>,
+}
+
+impl Search<'_> {
+    fn recurse(&mut self, points: &[Vector; 4], (u0, u1): (f64, f64), depth: u32) {
+        let half_width = 0.5 * self.curve.width(u0).max(self.curve.width(u1));
+        let (low, high) = points.iter().fold(
+            (
+                Vector::new(f64::MAX, f64::MAX, f64::MAX),
+                Vector::new(f64::MIN, f64::MIN, f64::MIN),
+            ),
+            |(low, high), p| {
+                (
+                    Vector::new(low.x().min(p.x()), low.y().min(p.y()), low.z().min(p.z())),
+                    Vector::new(
+                        high.x().max(p.x()),
+                        high.y().max(p.y()),
+                        high.z().max(p.z()),
+                    ),
+                )
+            },
+        );
+        let z_max = self.hit.map_or(self.z_range.1, |(z, _)| z);
+        if low.x() - half_width > 0.0
+            || high.x() + half_width < 0.0
+            || low.y() - half_width > 0.0
+            || high.y() + half_width < 0.0
+            || low.z() - half_width > z_max
+            || high.z() + half_width < self.z_range.0
+        {
+            return;
+        }
+
+        if depth > 0 {
+            let [p0, p1, p2, p3] = *points;
+            let split = [
+                p0,
+                0.5 * (p0 + p1),
+                0.25 * (p0 + 2.0 * p1 + p2),
+                0.125 * (p0 + 3.0 * p1 + 3.0 * p2 + p3),
+                0.25 * (p1 + 2.0 * p2 + p3),
+                0.5 * (p2 + p3),
+                p3,
+            ];
+            let middle = 0.5 * (u0 + u1);
+            self.recurse(
+                &[split[0], split[1], split[2], split[3]],
+                (u0, middle),
+                depth - 1,
+            );
+            self.recurse(
+                &[split[3], split[4], split[5], split[6]],
+                (middle, u1),
+                depth - 1,
+            );
+            return;
+        }
+
+        // the origin has to be between the perpendiculars at both ends
+        let [p0, p1, p2, p3] = *points;
+        if (p1.y() - p0.y()) * -p0.y() + p0.x() * (p0.x() - p1.x()) < 0.0 {
+            return;
+        }
+        if (p2.y() - p3.y()) * -p3.y() + p3.x() * (p3.x() - p2.x()) < 0.0 {
+            return;
+        }
+
+        // closest point to the origin along the straightened piece
+        let (dx, dy) = (p3.x() - p0.x(), p3.y() - p0.y());
+        let denominator = dx * dx + dy * dy;
+        if denominator == 0.0 {
+            return;
+        }
+        let w = (-p0.x() * dx - p0.y() * dy) / denominator;
+        let u = lerp(w, u0, u1).clamp(u0, u1);
+        let mut width = self.curve.width(u);
+        if let Some(normal) = self.curve.normal(u) {
+            width *= normal.dot(self.ray_direction).abs();
+        }
+        let (pc, _) = bezier(points, w.clamp(0.0, 1.0));
+        let distance2 = pc.x() * pc.x() + pc.y() * pc.y();
+        if distance2 > 0.25 * width * width || pc.z() <= self.z_range.0 || pc.z() >= z_max {
+            return;
+        }
+        self.hit = Some((pc.z(), u));
+    }
+}
+
+/// Curves sharing one material, found through a bounding volume hierarchy.
+pub struct Curves<M: Material> {
+    curves: Vec<Curve>,
+    bvh: Bvh,
+    material: M,
+}
+
+impl<M: Material> Curves<M> {
+    pub fn new(curves: Vec<Curve>, material: M) -> Self {
+        let boxes: Vec<Aabb> = curves.iter().map(Curve::bounding_box).collect();
+        Curves {
+            bvh: Bvh::new(&boxes),
+            curves,
+            material,
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        self.curves.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.curves.is_empty()
+    }
+
+    /// Parses strands, one per line: `cylinder` or `ribbon`, the widths at
+    /// the start and the end, for ribbons the normals at the start and the
+    /// end, then 3n + 1 control points, all separated by whitespace.
+    /// Everything after a '#' is a comment.
+    pub fn parse(text: &str, material: M) -> Result<Self, String> {
+        let mut curves = Vec::new();
+        for (number, line) in text.lines().enumerate() {
+            let line = line.split('#').next().unwrap_or("").trim();
+            if line.is_empty() {
+                continue;
+            }
+            let invalid = || format!("line {}: invalid curve {:?}", number + 1, line);
+            let mut words = line.split_whitespace();
+            let kind = words.next().unwrap_or("");
+            let values: Vec<f64> = words
+                .map(str::parse)
+                .collect::<Result<_, _>>()
+                .map_err(|_| invalid())?;
+            let vector = |i: usize| Vector::new(values[i], values[i + 1], values[i + 2]);
+            let (kind, first_point) = match kind {
+                "cylinder" if values.len() >= 2 => (CurveKind::Cylinder, 2),
+                "ribbon" if values.len() >= 8 => (CurveKind::Ribbon(vector(2), vector(5)), 8),
+                _ => return Err(invalid()),
+            };
+            // 3n + 1 points of 3 coordinates
+            let coordinates = values.len() - first_point;
+            if coordinates < 12 || coordinates % 9 != 3 || values[0] < 0.0 || values[1] < 0.0 {
+                return Err(invalid());
+            }
+            if let CurveKind::Ribbon(n0, n1) = kind {
+                if n0.is_near_zero() || n1.is_near_zero() {
+                    return Err(invalid());
+                }
+            }
+            let points: Vec<Vector> = (first_point..values.len()).step_by(3).map(vector).collect();
+            curves.extend(Curve::strand(&points, values[0], values[1], kind));
+        }
+        if curves.is_empty() {
+            return Err(String::from("no curves"));
+        }
+        Ok(Curves::new(curves, material))
+    }
+
+    pub fn load(path: &Path, material: M) -> Result<Self, String> {
+        let text = fs::read_to_string(path)
+            .map_err(|e| format!("can't read curves file {}: {}", path.display(), e))?;
+        Curves::parse(&text, material).map_err(|e| format!("{}: {}", path.display(), e))
+    }
+}
+
+impl<M: Material + Sync> Hitable for Curves<M> {
+    fn hit(&self, ray: &Ray, t_min: f64, t_max: f64) -> Option<HitRecord<'_>> {
+        let (nearest, (t, u)) = self.bvh.nearest_hit(ray, t_min, t_max, |i, t_max| {
+            let found = self.curves[i].intersect(ray, t_min, t_max)?;
+            Some((found.0, found))
+        })?;
+        let curve = &self.curves[nearest];
+
+        let p = ray.at(t);
+        let (centre, tangent) = bezier(&curve.points, u);
+        let along = tangent.unit();
+        // the side facing the ray, or the ribbon's side
+        let facing = match curve.normal(u) {
+            Some(normal) => normal,
+            None => ray.direction().reverse(),
+        };
+        let mut front = (facing - facing.dot(along) * along).unit();
+        if front.dot(ray.direction()) > 0.0 {
+            front = front.reverse();
+        }
+        // offset from the middle of the curve, from -1 to 1
+        let across = front.cross(along);
+        let h = ((p - centre).dot(across) / (0.5 * curve.width(u))).clamp(-1.0, 1.0);
+        let normal = match curve.kind {
+            // bent around the tube
+            CurveKind::Cylinder => h * across + (1.0 - h * h).sqrt() * front,
+            CurveKind::Ribbon(_, _) => front,
+        };
+        let (u0, u1) = curve.u_range;
+        Some(
+            HitRecord::new(t, p, normal, &self.material)
+                .with_uv(lerp(u, u0, u1), 0.5 * (h + 1.0))
+                .with_tangent(along),
+        )
+    }
+
+    fn bounding_box(&self, _time0: f64, _time1: f64) -> Option<Aabb> {
+        self.bvh.bounds()
+    }
+}
+
+fn lerp(t: f64, a: f64, b: f64) -> f64 {
+    (1.0 - t) * a + t * b
+}
+
+// point and derivative of a cubic Bézier at u
+fn bezier(points: &[Vector; 4], u: f64) -> (Vector, Vector) {
+    let mix = |a: Vector, b: Vector| (1.0 - u) * a + u * b;
+    let [p0, p1, p2, p3] = *points;
+    let (a, b, c) = (mix(p0, p1), mix(p1, p2), mix(p2, p3));
+    let (d, e) = (mix(a, b), mix(b, c));
+    let derivative = if (e - d).squared_length() > 0.0 {
+        3.0 * (e - d)
+    } else {
+        p3 - p0
+    };
+    (mix(d, e), derivative)
+}
+
+// spherical interpolation between unit vectors
+fn slerp(a: Vector, b: Vector, t: f64) -> Vector {
+    let cos = a.dot(b).clamp(-1.0, 1.0);
+    let angle = cos.acos();
+    if angle < 1e-6 {
+        return a;
+    }
+    let sin = angle.sin();
+    if sin < 1e-6 {
+        // opposite normals, any way around will do
+        let helper = if a.x().abs() > 0.9 {
+            Vector::new(0.0, 1.0, 0.0)
+        } else {
+            Vector::new(1.0, 0.0, 0.0)
+        };
+        let perpendicular = a.cross(helper).unit();
+        let angle = t * std::f64::consts::PI;
+        return angle.cos() * a + angle.sin() * perpendicular;
+    }
+    ((((1.0 - t) * angle).sin() / sin) * a + ((t * angle).sin() / sin) * b).unit()
+}
@@ -0,0 +1,118 @@
+use crate::aov::Aov;
+use crate::film::Film;
+use crate::vector::Vector;
+use rayon::prelude::*;
+
+/// Edge-avoiding À-trous wavelet filter (Dammertz et al. 2010).
+///
+/// The beauty pass is divided by the albedo, so only lighting gets blurred
+/// and texture detail survives, and then filtered by a 5x5 B3 spline kernel
+/// whose taps are spread further apart every iteration. Each tap is weighted by
+/// how similar its color, normal and depth are to the center pixel, which keeps
+/// edges sharp. The result is multiplied by the albedo again.
+#[derive(Copy, Clone, Debug)]
+pub struct Denoiser {
+    pub iterations: u32,
+    // scale of the color differences that are smoothed over, halved every iteration
+    pub sigma_color: f64,
+    pub sigma_normal: f64,
+    // relative depth difference
+    pub sigma_depth: f64,
+}
+
+impl Default for Denoiser {
+    fn default() -> Self {
+        Denoiser {
+            iterations: 5,
+            sigma_color: 0.6,
+            sigma_normal: 0.1,
+            sigma_depth: 0.1,
+        }
+    }
+}
+
+const KERNEL: [f64; 5] = [1.0 / 16.0, 1.0 / 4.0, 3.0 / 8.0, 1.0 / 4.0, 1.0 / 16.0];
+
+impl Denoiser {
+    // feature buffers the film needs to have
+    pub const FEATURES: [Aov; 3] = [Aov::Albedo, Aov::Normal, Aov::Depth];
+
+    /// Returns a copy of the film with a denoised beauty pass. Features the film
+    /// didn't record are ignored, so this works without them, just less well.
+    pub fn apply(&self, film: &Film) -> Film {
+        let bounds = film.bounds();
+        let (width, height) = (bounds.width(), bounds.height());
+        let area = bounds.area();
+
+        let feature =
+            |aov: Aov| -> Option<Vec<Vector>> { (0..area).map(|i| film.aov(aov, i)).collect() };
+        let albedo = feature(Aov::Albedo);
+        let normal = feature(Aov::Normal);
+        let depth = feature(Aov::Depth);
+
+        let demodulate = |i: usize, c: Vector| match &albedo {
+            Some(albedo) => Vector::new(
+                c.x() / albedo[i].x().max(1e-3),
+                c.y() / albedo[i].y().max(1e-3),
+                c.z() / albedo[i].z().max(1e-3),
+            ),
+            None => c,
+        };
+        let mut color: Vec<Vector> = (0..area).map(|i| demodulate(i, film.color(i))).collect();
+
+        for iteration in 0..self.iterations {
+            let step = 1i64 << iteration;
+            let sigma_color = self.sigma_color * 2f64.powi(-(iteration as i32));
+            let input = &color;
+            let output: Vec<Vector> = (0..area)
+                .into_par_iter()
+                .map(|i| {
+                    let (x, y) = ((i % width) as i64, (i / width) as i64);
+                    let mut sum = Vector::default();
+                    let mut total_weight = 0.0;
+                    for (ky, hy) in KERNEL.iter().enumerate() {
+                        for (kx, hx) in KERNEL.iter().enumerate() {
+                            let qx = x + (kx as i64 - 2) * step;
+                            let qy = y + (ky as i64 - 2) * step;
+                            if qx < 0 || qy < 0 || qx >= width as i64 || qy >= height as i64 {
+                                continue;
+                            }
+                            let j = qy as usize * width + qx as usize;
+
+                            let dc = (input[i] - input[j]).squared_length();
+                            let mut weight = hx * hy * (-dc / (sigma_color * sigma_color)).exp();
+                            if let Some(normal) = &normal {
+                                let dn = (normal[i] - normal[j]).squared_length();
+                                weight *= (-dn / (self.sigma_normal * self.sigma_normal)).exp();
+                            }
+                            if let Some(depth) = &depth {
+                                let (zi, zj) = (depth[i].x(), depth[j].x());
+                                let dz = (zi - zj).abs() / zi.abs().max(zj.abs()).max(1e-6);
+                                weight *= (-dz / self.sigma_depth).exp();
+                            }
+                            sum = sum + weight * input[j];
+                            total_weight += weight;
+                        }
+                    }
+                    if total_weight > 0.0 {
+                        sum.scale(1.0 / total_weight)
+                    } else {
+                        input[i]
+                    }
+                })
+                .collect();
+            color = output;
+        }
+
+        if let Some(albedo) = &albedo {
+            for (c, a) in color.iter_mut().zip(albedo.iter()) {
+                *c = Vector::new(
+                    c.x() * a.x().max(1e-3),
+                    c.y() * a.y().max(1e-3),
+                    c.z() * a.z().max(1e-3),
+                );
+            }
+        }
+        film.with_colors(&color)
+    }
+}
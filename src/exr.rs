@@ -0,0 +1,110 @@
+use crate::render::PixelBounds;
+use std::io::{self, Write};
+
+/// A named channel of 32 bit float samples, one per pixel in row-major order.
+pub struct Channel {
+    pub name: String,
+    pub data: Vec<f32>,
+}
+
+/// Writes a single part, uncompressed, scanline OpenEXR image.
+///
+/// `data_window` is the rectangle of pixels stored in the channels and
+/// `display_window` the full frame it belongs to, which lets a cropped render
+/// keep its position in the frame. Channel names with a dot, like
+/// "albedo.R", are shown as layers by compositing applications.
+pub fn write_exr(
+    out: &mut impl Write,
+    display_window: PixelBounds,
+    data_window: PixelBounds,
+    channels: &mut [Channel],
+) -> io::Result<()> {
+    // the spec requires channels sorted by name, both in the header and in the data
+    channels.sort_by(|a, b| a.name.cmp(&b.name));
+    for channel in channels.iter() {
+        assert_eq!(channel.data.len(), data_window.area());
+    }
+
+    let mut chlist = Vec::new();
+    for channel in channels.iter() {
+        chlist.extend_from_slice(channel.name.as_bytes());
+        chlist.push(0);
+        chlist.extend_from_slice(&2i32.to_le_bytes()); // FLOAT
+        chlist.extend_from_slice(&[0, 0, 0, 0]); // pLinear and reserved
+        chlist.extend_from_slice(&1i32.to_le_bytes()); // xSampling
+        chlist.extend_from_slice(&1i32.to_le_bytes()); // ySampling
+    }
+    chlist.push(0);
+
+    let mut header = vec![0x76, 0x2f, 0x31, 0x01];
+    header.extend_from_slice(&2u32.to_le_bytes());
+    attribute(&mut header, "channels", "chlist", &chlist);
+    attribute(&mut header, "compression", "compression", &[0]);
+    attribute(&mut header, "dataWindow", "box2i", &box2i(data_window));
+    attribute(
+        &mut header,
+        "displayWindow",
+        "box2i",
+        &box2i(display_window),
+    );
+    attribute(&mut header, "lineOrder", "lineOrder", &[0]);
+    attribute(
+        &mut header,
+        "pixelAspectRatio",
+        "float",
+        &1f32.to_le_bytes(),
+    );
+    attribute(&mut header, "screenWindowCenter", "v2f", &[0; 8]);
+    attribute(
+        &mut header,
+        "screenWindowWidth",
+        "float",
+        &1f32.to_le_bytes(),
+    );
+    header.push(0);
+    out.write_all(&header)?;
+
+    // offset table, one uncompressed scanline per chunk
+    let width = data_window.width();
+    let line_size = (width * channels.len() * 4) as u64;
+    let header_size = header.len() as u64;
+    let table_size = 8 * data_window.height() as u64;
+    for line in 0..data_window.height() as u64 {
+        let offset = header_size + table_size + line * (8 + line_size);
+        out.write_all(&offset.to_le_bytes())?;
+    }
+
+    for line in 0..data_window.height() {
+        out.write_all(&((data_window.y0 + line) as i32).to_le_bytes())?;
+        out.write_all(&(line_size as i32).to_le_bytes())?;
+        for channel in channels.iter() {
+            for value in &channel.data[line * width..(line + 1) * width] {
+                out.write_all(&value.to_le_bytes())?;
+            }
+        }
+    }
+    Ok(())
+}
+
+fn attribute(header: &mut Vec<u8>, name: &str, kind: &str, value: &[u8]) {
+    header.extend_from_slice(name.as_bytes());
+    header.push(0);
+    header.extend_from_slice(kind.as_bytes());
+    header.push(0);
+    header.extend_from_slice(&(value.len() as i32).to_le_bytes());
+    header.extend_from_slice(value);
+}
+
+// inclusive pixel coordinates
+fn box2i(bounds: PixelBounds) -> Vec<u8> {
+    let mut bytes = Vec::with_capacity(16);
+    for v in [
+        bounds.x0 as i32,
+        bounds.y0 as i32,
+        bounds.x1 as i32 - 1,
+        bounds.y1 as i32 - 1,
+    ] {
+        bytes.extend_from_slice(&v.to_le_bytes());
+    }
+    bytes
+}
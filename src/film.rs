@@ -0,0 +1,348 @@
+use crate::aov::{Aov, AovFilter, AovSample};
+use crate::exr::{write_exr, Channel};
+use crate::filter::Filter;
+use crate::postprocess::PostProcess;
+use crate::render::PixelBounds;
+use crate::vector::Vector;
+use std::io::{self, Write};
+
+/// Accumulated values of one AOV, normalised according to `Aov::filter`.
+#[derive(Clone)]
+pub struct AovBuffer {
+    pub aov: Aov,
+    pub sums: Vec<Vector>,
+}
+
+/// Floating point accumulation buffer for the pixels inside `bounds`.
+/// Every sample is splatted into the pixels around it with the weight given by
+/// the reconstruction filter, and the weighted radiance and the sum of weights
+/// are kept separately so the film can be normalised at any time. The number
+/// of samples taken inside each pixel is tracked too, along with the AOVs the
+/// film was created with.
+#[derive(Clone)]
+pub struct Film {
+    bounds: PixelBounds,
+    sums: Vec<Vector>,
+    weights: Vec<f64>,
+    counts: Vec<u32>,
+    aovs: Vec<AovBuffer>,
+}
+
+impl Film {
+    pub fn new(bounds: PixelBounds, aovs: &[Aov]) -> Film {
+        Film {
+            bounds,
+            sums: vec![Vector::default(); bounds.area()],
+            weights: vec![0.0; bounds.area()],
+            counts: vec![0; bounds.area()],
+            aovs: empty_aov_buffers(aovs, bounds.area()),
+        }
+    }
+
+    pub fn from_parts(
+        bounds: PixelBounds,
+        sums: Vec<Vector>,
+        weights: Vec<f64>,
+        counts: Vec<u32>,
+        aovs: Vec<AovBuffer>,
+    ) -> Film {
+        assert_eq!(sums.len(), bounds.area());
+        assert_eq!(weights.len(), bounds.area());
+        assert_eq!(counts.len(), bounds.area());
+        for buffer in aovs.iter() {
+            assert_eq!(buffer.sums.len(), bounds.area());
+        }
+        Film {
+            bounds,
+            sums,
+            weights,
+            counts,
+            aovs,
+        }
+    }
+
+    pub fn bounds(&self) -> PixelBounds {
+        self.bounds
+    }
+
+    pub fn sums(&self) -> &[Vector] {
+        &self.sums
+    }
+
+    pub fn weights(&self) -> &[f64] {
+        &self.weights
+    }
+
+    pub fn counts(&self) -> &[u32] {
+        &self.counts
+    }
+
+    pub fn aov_buffers(&self) -> &[AovBuffer] {
+        &self.aovs
+    }
+
+    pub fn index(&self, x: usize, y: usize) -> Option<usize> {
+        if self.bounds.contains(x, y) {
+            Some((y - self.bounds.y0) * self.bounds.width() + x - self.bounds.x0)
+        } else {
+            None
+        }
+    }
+
+    // smallest number of samples taken by any pixel
+    pub fn min_samples(&self) -> u32 {
+        self.counts.iter().copied().min().unwrap_or(0)
+    }
+
+    // filtered color of the pixel at `index`
+    pub fn color(&self, index: usize) -> Vector {
+        let weight = self.weights[index];
+        if weight <= 0.0 {
+            return Vector::default();
+        }
+        self.sums[index].scale(1.0 / weight)
+    }
+
+    // copy of the film whose beauty pass is `colors`, keeping the weights, counts and AOVs
+    pub fn with_colors(&self, colors: &[Vector]) -> Film {
+        assert_eq!(colors.len(), self.bounds.area());
+        let mut film = self.clone();
+        for (i, color) in colors.iter().enumerate() {
+            film.sums[i] = color.scale(film.weights[i]);
+        }
+        film
+    }
+
+    // value of `aov` at the pixel at `index`, None if the film doesn't record it
+    pub fn aov(&self, aov: Aov, index: usize) -> Option<Vector> {
+        let buffer = self.aovs.iter().find(|b| b.aov == aov)?;
+        let sum = buffer.sums[index];
+        let value = match aov.filter() {
+            AovFilter::Reconstruction if self.weights[index] > 0.0 => {
+                sum.scale(1.0 / self.weights[index])
+            }
+            AovFilter::Average if self.counts[index] > 0 => {
+                sum.scale(1.0 / self.counts[index] as f64)
+            }
+            AovFilter::First => sum,
+            _ => Vector::default(),
+        };
+        Some(value)
+    }
+
+    /// Empty tile covering every film pixel that samples taken inside `pixels`
+    /// can reach through `filter`.
+    pub fn tile(&self, pixels: PixelBounds, filter: &Filter) -> FilmTile {
+        let reach = filter.pixel_reach();
+        let bounds = PixelBounds {
+            x0: pixels.x0.saturating_sub(reach),
+            y0: pixels.y0.saturating_sub(reach),
+            x1: pixels.x1 + reach,
+            y1: pixels.y1 + reach,
+        }
+        .intersect(&self.bounds);
+        let aovs: Vec<Aov> = self.aovs.iter().map(|b| b.aov).collect();
+        FilmTile {
+            bounds,
+            filter: *filter,
+            sums: vec![Vector::default(); bounds.area()],
+            weights: vec![0.0; bounds.area()],
+            counts: vec![0; bounds.area()],
+            aovs: empty_aov_buffers(&aovs, bounds.area()),
+        }
+    }
+
+    pub fn merge_tile(&mut self, tile: &FilmTile) {
+        for y in tile.bounds.y0..tile.bounds.y1 {
+            for x in tile.bounds.x0..tile.bounds.x1 {
+                let t = (y - tile.bounds.y0) * tile.bounds.width() + x - tile.bounds.x0;
+                if let Some(i) = self.index(x, y) {
+                    for (buffer, tile_buffer) in self.aovs.iter_mut().zip(tile.aovs.iter()) {
+                        match buffer.aov.filter() {
+                            AovFilter::First => {
+                                if self.counts[i] == 0 && tile.counts[t] > 0 {
+                                    buffer.sums[i] = tile_buffer.sums[t];
+                                }
+                            }
+                            _ => buffer.sums[i] = buffer.sums[i] + tile_buffer.sums[t],
+                        }
+                    }
+                    self.sums[i] = self.sums[i] + tile.sums[t];
+                    self.weights[i] += tile.weights[t];
+                    self.counts[i] += tile.counts[t];
+                }
+            }
+        }
+    }
+
+    /// Writes the pixels in `output` as a plain PPM image, normalising every pixel
+    /// by its own filter weight. Pixels outside of the film bounds are written black.
+    pub fn write_ppm(
+        &self,
+        out: &mut impl Write,
+        output: PixelBounds,
+        post: &PostProcess,
+    ) -> io::Result<()> {
+        write!(out, "P3\n{} {}\n255\n", output.width(), output.height())?;
+        for y in output.y0..output.y1 {
+            for x in output.x0..output.x1 {
+                let color = match self.index(x, y) {
+                    Some(i) => self.color(i),
+                    None => Vector::default(),
+                };
+                let [r, g, b] = post.to_rgb8(color, x, y);
+                writeln!(out, "{} {} {}", r, g, b)?;
+            }
+        }
+        Ok(())
+    }
+
+    /// Writes the pixels in `output` as a little endian PFM image of linear
+    /// floats, either the beauty pass or one of the film's AOVs. Scalar AOVs are
+    /// written as greyscale images.
+    pub fn write_pfm(
+        &self,
+        out: &mut impl Write,
+        output: PixelBounds,
+        aov: Option<Aov>,
+    ) -> io::Result<()> {
+        let greyscale = aov.map(|aov| aov.channels().len() == 1).unwrap_or(false);
+        let magic = if greyscale { "Pf" } else { "PF" };
+        write!(
+            out,
+            "{}\n{} {}\n-1.0\n",
+            magic,
+            output.width(),
+            output.height()
+        )?;
+        // PFM stores the bottom row first
+        for y in (output.y0..output.y1).rev() {
+            for x in output.x0..output.x1 {
+                let value = self.pixel_value(aov, x, y);
+                if greyscale {
+                    out.write_all(&(value.x() as f32).to_le_bytes())?;
+                } else {
+                    for c in [value.x(), value.y(), value.z()] {
+                        out.write_all(&(c as f32).to_le_bytes())?;
+                    }
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// Writes the beauty pass as the R, G and B channels of an OpenEXR image,
+    /// and every AOV as a layer of its own. `frame` is the full frame, so a
+    /// cropped `output` keeps its place in it.
+    pub fn write_exr(
+        &self,
+        out: &mut impl Write,
+        frame: PixelBounds,
+        output: PixelBounds,
+    ) -> io::Result<()> {
+        let mut channels = Vec::new();
+        let layers = std::iter::once(None).chain(self.aovs.iter().map(|b| Some(b.aov)));
+        for aov in layers {
+            let names: &[&str] = match aov {
+                Some(aov) => aov.channels(),
+                None => &["R", "G", "B"],
+            };
+            let mut data = vec![Vec::with_capacity(output.area()); names.len()];
+            for y in output.y0..output.y1 {
+                for x in output.x0..output.x1 {
+                    let value = self.pixel_value(aov, x, y);
+                    for (c, channel) in data.iter_mut().enumerate() {
+                        let v = [value.x(), value.y(), value.z()][c];
+                        channel.push(v as f32);
+                    }
+                }
+            }
+            for (name, data) in names.iter().zip(data) {
+                let name = match aov {
+                    Some(aov) => format!("{}.{}", aov.name(), name),
+                    None => name.to_string(),
+                };
+                channels.push(Channel { name, data });
+            }
+        }
+        write_exr(out, frame, output, &mut channels)
+    }
+
+    // beauty or AOV value of a pixel, black outside of the film
+    fn pixel_value(&self, aov: Option<Aov>, x: usize, y: usize) -> Vector {
+        match (self.index(x, y), aov) {
+            (Some(i), None) => self.color(i),
+            (Some(i), Some(aov)) => self.aov(aov, i).unwrap_or_default(),
+            (None, _) => Vector::default(),
+        }
+    }
+}
+
+fn empty_aov_buffers(aovs: &[Aov], area: usize) -> Vec<AovBuffer> {
+    aovs.iter()
+        .map(|aov| AovBuffer {
+            aov: *aov,
+            sums: vec![Vector::default(); area],
+        })
+        .collect()
+}
+
+/// Part of the film that a group of pixels is rendered into independently of
+/// the others, so tiles can be traced in parallel and merged afterwards.
+pub struct FilmTile {
+    bounds: PixelBounds,
+    filter: Filter,
+    sums: Vec<Vector>,
+    weights: Vec<f64>,
+    counts: Vec<u32>,
+    aovs: Vec<AovBuffer>,
+}
+
+impl FilmTile {
+    /// Splats a sample taken at the continuous film position (x, y), where
+    /// pixel (i, j) covers [i, i + 1) x [j, j + 1), into every pixel of the tile
+    /// whose center is within the filter radius. AOVs that aren't filtered only
+    /// go to the pixel the sample was taken in.
+    pub fn add_sample(&mut self, x: f64, y: f64, color: Vector, aovs: &AovSample) {
+        if !(color.x().is_finite() && color.y().is_finite() && color.z().is_finite()) {
+            return;
+        }
+        let r = self.filter.radius();
+        let b = self.bounds;
+        let x0 = (x - 0.5 - r).ceil().max(b.x0 as f64) as usize;
+        let y0 = (y - 0.5 - r).ceil().max(b.y0 as f64) as usize;
+        let x1 = ((x - 0.5 + r).floor() + 1.0).clamp(b.x0 as f64, b.x1 as f64) as usize;
+        let y1 = ((y - 0.5 + r).floor() + 1.0).clamp(b.y0 as f64, b.y1 as f64) as usize;
+        for py in y0..y1 {
+            for px in x0..x1 {
+                let weight = self
+                    .filter
+                    .evaluate(px as f64 + 0.5 - x, py as f64 + 0.5 - y);
+                if weight != 0.0 {
+                    let i = (py - b.y0) * b.width() + px - b.x0;
+                    self.sums[i] = self.sums[i] + weight * color;
+                    self.weights[i] += weight;
+                    for buffer in self.aovs.iter_mut() {
+                        if buffer.aov.filter() == AovFilter::Reconstruction {
+                            buffer.sums[i] = buffer.sums[i] + weight * aovs.value(buffer.aov);
+                        }
+                    }
+                }
+            }
+        }
+        let (px, py) = (x as usize, y as usize);
+        if b.contains(px, py) {
+            let i = (py - b.y0) * b.width() + px - b.x0;
+            for buffer in self.aovs.iter_mut() {
+                match buffer.aov.filter() {
+                    AovFilter::Average => buffer.sums[i] = buffer.sums[i] + aovs.value(buffer.aov),
+                    AovFilter::First if self.counts[i] == 0 => {
+                        buffer.sums[i] = aovs.value(buffer.aov)
+                    }
+                    _ => {}
+                }
+            }
+            self.counts[i] += 1;
+        }
+    }
+}
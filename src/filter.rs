@@ -0,0 +1,141 @@
+use std::f64::consts::PI;
+use std::fmt;
+use std::str::FromStr;
+
+/// Pixel reconstruction filters. All of them are separable, the weight of a
+/// sample is the product of the 1D filter along x and along y.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum FilterKind {
+    Box,
+    Tent,
+    Gaussian { sigma: f64 },
+    // Mitchell-Netravali with the B and C parameters of the family
+    Mitchell { b: f64, c: f64 },
+    // sinc windowed by a sinc stretched `tau` times, the classic Lanczos when radius == tau
+    Lanczos { tau: f64 },
+    BlackmanHarris,
+}
+
+impl FilterKind {
+    pub fn default_radius(self) -> f64 {
+        match self {
+            FilterKind::Box => 0.5,
+            FilterKind::Tent => 1.0,
+            FilterKind::Gaussian { .. } => 1.5,
+            FilterKind::Mitchell { .. } => 2.0,
+            FilterKind::Lanczos { tau } => tau,
+            FilterKind::BlackmanHarris => 2.0,
+        }
+    }
+}
+
+impl FromStr for FilterKind {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "box" => Ok(FilterKind::Box),
+            "tent" | "triangle" => Ok(FilterKind::Tent),
+            "gaussian" => Ok(FilterKind::Gaussian { sigma: 0.5 }),
+            "mitchell" => Ok(FilterKind::Mitchell {
+                b: 1.0 / 3.0,
+                c: 1.0 / 3.0,
+            }),
+            "lanczos" => Ok(FilterKind::Lanczos { tau: 3.0 }),
+            "blackman-harris" => Ok(FilterKind::BlackmanHarris),
+            _ => Err(format!(
+                "unknown filter {:?}, expected one of box, tent, gaussian, mitchell, lanczos, blackman-harris",
+                s
+            )),
+        }
+    }
+}
+
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct Filter {
+    kind: FilterKind,
+    radius: f64,
+}
+
+impl Filter {
+    pub fn new(kind: FilterKind, radius: f64) -> Filter {
+        Filter { kind, radius }
+    }
+
+    pub fn kind(&self) -> FilterKind {
+        self.kind
+    }
+
+    // in pixels, the filter is zero further than this from the sample
+    pub fn radius(&self) -> f64 {
+        self.radius
+    }
+
+    // how many pixels away from the one it was taken in a sample can contribute
+    pub fn pixel_reach(&self) -> usize {
+        (self.radius - 0.5).ceil().max(0.0) as usize
+    }
+
+    /// Weight of a sample at offset (dx, dy), in pixels, from a pixel center.
+    /// Mitchell and Lanczos have negative lobes, so this can be negative.
+    pub fn evaluate(&self, dx: f64, dy: f64) -> f64 {
+        self.evaluate_1d(dx) * self.evaluate_1d(dy)
+    }
+
+    fn evaluate_1d(&self, x: f64) -> f64 {
+        let r = self.radius;
+        let x = x.abs();
+        if x > r {
+            return 0.0;
+        }
+        match self.kind {
+            FilterKind::Box => 1.0,
+            FilterKind::Tent => r - x,
+            FilterKind::Gaussian { sigma } => {
+                let gaussian = |x: f64| (-x * x / (2.0 * sigma * sigma)).exp();
+                (gaussian(x) - gaussian(r)).max(0.0)
+            }
+            FilterKind::Mitchell { b, c } => mitchell(2.0 * x / r, b, c),
+            FilterKind::Lanczos { tau } => sinc(x) * sinc(x / tau),
+            FilterKind::BlackmanHarris => {
+                let t = 2.0 * PI * (x + r) / (2.0 * r);
+                0.35875 - 0.48829 * t.cos() + 0.14128 * (2.0 * t).cos() - 0.01168 * (3.0 * t).cos()
+            }
+        }
+    }
+}
+
+impl Default for Filter {
+    fn default() -> Self {
+        Filter::new(FilterKind::Box, FilterKind::Box.default_radius())
+    }
+}
+
+impl fmt::Display for Filter {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{:?} radius={}", self.kind, self.radius)
+    }
+}
+
+// x in [0, 2]
+fn mitchell(x: f64, b: f64, c: f64) -> f64 {
+    if x > 1.0 {
+        ((-b - 6.0 * c) * x.powi(3)
+            + (6.0 * b + 30.0 * c) * x.powi(2)
+            + (-12.0 * b - 48.0 * c) * x
+            + (8.0 * b + 24.0 * c))
+            / 6.0
+    } else {
+        ((12.0 - 9.0 * b - 6.0 * c) * x.powi(3)
+            + (-18.0 + 12.0 * b + 6.0 * c) * x.powi(2)
+            + (6.0 - 2.0 * b))
+            / 6.0
+    }
+}
+
+fn sinc(x: f64) -> f64 {
+    if x.abs() < 1e-5 {
+        return 1.0;
+    }
+    (PI * x).sin() / (PI * x)
+}
@@ -0,0 +1,1012 @@
+//! glTF 2.0 scenes, from .gltf files (JSON, with buffers and images in
+//! files next to them or embedded as data URIs) and .glb files (JSON and
+//! binary data in one file), as exported by Blender and most 3D tools.
+//!
+//! The node hierarchy is flattened: every mesh primitive becomes a
+//! `TriangleMesh` placed by its node's transform, with a
+//! `MetallicRoughness` material. Perspective cameras become `Camera`s
+//! focused on what lies at the centre of their view. KHR_lights_punctual
+//! lights become emitters, as the renderer only has lights with an area:
+//! point and spot lights small spheres, and directional lights distant
+//! disks. Light intensities are photometric in glTF, candela and lux, and
+//! are turned into the renderer's units at 683 lumens per watt.
+//!
+//! Parts of the file that can't be rendered as they are, like animations or
+//! transparency, are left out with a warning.
+
+use crate::camera::Camera;
+use crate::hitable::{Hitable, HitableList};
+use crate::json::Json;
+use crate::light;
+use crate::material::MetallicRoughness;
+use crate::mesh::{Mesh, TriangleMesh};
+use crate::ray::Ray;
+use crate::texture::{Texture, Wrap};
+use crate::transform::{Matrix4, Quaternion, Transform, Transformed};
+use crate::vector::Vector;
+use std::collections::{HashMap, HashSet};
+use std::f64::consts::PI;
+use std::fs;
+use std::path::Path;
+use std::sync::Arc;
+
+/// What a glTF file imports as.
+pub struct GltfScene {
+    pub objects: HitableList,
+    /// One for each node with a perspective camera, or one looking at the
+    /// scene from the front when there are none.
+    pub cameras: Vec<Camera>,
+    /// The parts of the file that were left out or approximated.
+    pub warnings: Vec<String>,
+}
+
+const LUMENS_PER_WATT: f64 = 683.0;
+
+const SUPPORTED_EXTENSIONS: [&str; 2] = ["KHR_lights_punctual", "KHR_materials_emissive_strength"];
+
+/// Cameras are made for images of `aspect_ratio`, keeping the vertical
+/// field of view of the file's.
+pub fn load(path: &Path, aspect_ratio: f64) -> Result<GltfScene, String> {
+    let bytes =
+        fs::read(path).map_err(|e| format!("can't read glTF file {}: {}", path.display(), e))?;
+    let directory = path.parent().unwrap_or_else(|| Path::new("."));
+    parse(&bytes, directory, aspect_ratio).map_err(|e| format!("{}: {}", path.display(), e))
+}
+
+/// Imports a .gltf or .glb file read into `bytes`, looking for the files it
+/// refers to in `directory`.
+pub fn parse(bytes: &[u8], directory: &Path, aspect_ratio: f64) -> Result<GltfScene, String> {
+    let (text, binary) = if bytes.starts_with(b"glTF") {
+        split_glb(bytes)?
+    } else {
+        let text = std::str::from_utf8(bytes).map_err(|_| "the glTF file isn't text")?;
+        (text, None)
+    };
+    let document = Json::parse(text)?;
+    let version = document
+        .get("asset")
+        .and_then(|asset| asset.get("version"))
+        .and_then(Json::as_str)
+        .ok_or("the asset has no version")?;
+    if !version.starts_with("2.") {
+        return Err(format!("glTF {} isn't supported, only 2.x", version));
+    }
+
+    let mut importer = Importer {
+        document: &document,
+        directory,
+        buffers: Vec::new(),
+        textures: HashMap::new(),
+        materials: HashMap::new(),
+        lights: Vec::new(),
+        views: Vec::new(),
+        visited: HashSet::new(),
+        scene: GltfScene {
+            objects: HitableList::default(),
+            cameras: Vec::new(),
+            warnings: Vec::new(),
+        },
+    };
+    importer.check_extensions()?;
+    importer.read_buffers(binary)?;
+    importer.nodes()?;
+    importer.add_lights()?;
+    importer.add_cameras(aspect_ratio);
+    Ok(importer.scene)
+}
+
+// the JSON and binary chunks of a .glb file
+fn split_glb(bytes: &[u8]) -> Result<(&str, Option<&[u8]>), String> {
+    let word = |offset: usize| {
+        bytes
+            .get(offset..offset + 4)
+            .map(|b| u32::from_le_bytes([b[0], b[1], b[2], b[3]]) as usize)
+            .ok_or_else(|| String::from("the GLB file ends early"))
+    };
+    if word(4)? != 2 {
+        return Err(format!("GLB version {} isn't supported, only 2", word(4)?));
+    }
+    let length = word(8)?.min(bytes.len());
+    let (mut offset, mut json, mut binary) = (12, None, None);
+    while offset + 8 <= length {
+        let (size, kind) = (word(offset)?, word(offset + 4)?);
+        let data = bytes
+            .get(offset + 8..offset + 8 + size)
+            .ok_or("a GLB chunk runs past the end of the file")?;
+        match kind {
+            0x4e4f_534a if json.is_none() => json = Some(data),
+            0x004e_4942 if binary.is_none() => binary = Some(data),
+            _ => {}
+        }
+        offset += 8 + size;
+    }
+    let json = json.ok_or("the GLB file has no JSON chunk")?;
+    let text = std::str::from_utf8(json).map_err(|_| "the GLB JSON chunk isn't text")?;
+    Ok((text, binary))
+}
+
+struct Importer<'a> {
+    document: &'a Json,
+    directory: &'a Path,
+    buffers: Vec<Vec<u8>>,
+    // by texture index and whether they hold sRGB colours
+    textures: HashMap<(usize, bool), Arc<Texture>>,
+    materials: HashMap<usize, MetallicRoughness>,
+    // light indices and the world transforms of their nodes
+    lights: Vec<(usize, Transform)>,
+    // vertical fields of view and the world transforms of the camera nodes
+    views: Vec<(f64, Transform)>,
+    // the nodes reached so far, which a tree reaches once each
+    visited: HashSet<usize>,
+    scene: GltfScene,
+}
+
+impl<'a> Importer<'a> {
+    fn warn(&mut self, warning: String) {
+        if !self.scene.warnings.contains(&warning) {
+            self.scene.warnings.push(warning);
+        }
+    }
+
+    // `document.key[index]`
+    fn item(&self, key: &str, index: usize) -> Result<&'a Json, String> {
+        self.document
+            .get(key)
+            .and_then(Json::as_array)
+            .and_then(|items| items.get(index))
+            .ok_or_else(|| format!("{}[{}] doesn't exist", key, index))
+    }
+
+    fn check_extensions(&mut self) -> Result<(), String> {
+        let names = |key: &str| -> Vec<String> {
+            self.document
+                .get(key)
+                .and_then(Json::as_array)
+                .unwrap_or_default()
+                .iter()
+                .filter_map(|name| name.as_str().map(String::from))
+                .collect()
+        };
+        let (used, required) = (names("extensionsUsed"), names("extensionsRequired"));
+        if let Some(name) = required
+            .iter()
+            .find(|name| !SUPPORTED_EXTENSIONS.contains(&name.as_str()))
+        {
+            return Err(format!(
+                "the file needs extension {}, which isn't supported",
+                name
+            ));
+        }
+        for name in used {
+            if !SUPPORTED_EXTENSIONS.contains(&name.as_str()) {
+                self.warn(format!("extension {} is ignored", name));
+            }
+        }
+        for key in ["animations", "skins"] {
+            if self
+                .document
+                .get(key)
+                .and_then(Json::as_array)
+                .is_some_and(|items| !items.is_empty())
+            {
+                self.warn(format!("{} are ignored", key));
+            }
+        }
+        Ok(())
+    }
+
+    fn read_buffers(&mut self, binary: Option<&[u8]>) -> Result<(), String> {
+        let buffers = self
+            .document
+            .get("buffers")
+            .and_then(Json::as_array)
+            .unwrap_or_default();
+        for (i, buffer) in buffers.iter().enumerate() {
+            let context = |e: String| format!("buffers[{}]: {}", i, e);
+            let data = match buffer.get("uri").and_then(Json::as_str) {
+                Some(uri) => self.read_uri(uri).map_err(context)?,
+                // the binary chunk of a .glb file
+                None if i == 0 && binary.is_some() => binary.unwrap_or_default().to_vec(),
+                None => return Err(context(String::from("it has no uri"))),
+            };
+            let length = byte_count(buffer, "byteLength").map_err(context)?;
+            if data.len() < length {
+                return Err(context(format!(
+                    "it should be {} bytes, but is only {}",
+                    length,
+                    data.len()
+                )));
+            }
+            self.buffers.push(data);
+        }
+        Ok(())
+    }
+
+    // the data of a data URI, or of the file a relative URI names
+    fn read_uri(&self, uri: &str) -> Result<Vec<u8>, String> {
+        if let Some(data) = uri.strip_prefix("data:") {
+            let (_, encoded) = data
+                .split_once(";base64,")
+                .ok_or("only base64 data URIs are supported")?;
+            return decode_base64(encoded);
+        }
+        let path = self.directory.join(decode_percent(uri));
+        fs::read(&path).map_err(|e| format!("can't read {}: {}", path.display(), e))
+    }
+
+    // the bytes of a buffer view
+    fn view(&self, index: usize) -> Result<(&[u8], usize), String> {
+        let view = self.item("bufferViews", index)?;
+        let context = |e: String| format!("bufferViews[{}]: {}", index, e);
+        let buffer = required_index(view, "buffer").map_err(context)?;
+        let offset = byte_count(view, "byteOffset").map_err(context)?;
+        let length = byte_count(view, "byteLength").map_err(context)?;
+        let stride = byte_count(view, "byteStride").map_err(context)?;
+        let data = self
+            .buffers
+            .get(buffer)
+            .ok_or_else(|| context(format!("buffers[{}] doesn't exist", buffer)))?;
+        let bytes = offset
+            .checked_add(length)
+            .and_then(|end| data.get(offset..end))
+            .ok_or_else(|| context(String::from("it runs past the end of its buffer")))?;
+        Ok((bytes, stride))
+    }
+
+    // the elements of an accessor, all their components one after the other,
+    // and how many components each has
+    fn accessor(&self, index: usize) -> Result<(Vec<f64>, usize), String> {
+        let accessor = self.item("accessors", index)?;
+        let context = |e: String| format!("accessors[{}]: {}", index, e);
+        if accessor.get("sparse").is_some() {
+            return Err(context(String::from("sparse accessors aren't supported")));
+        }
+        let count = required_index(accessor, "count").map_err(context)?;
+        let components = match accessor.get("type").and_then(Json::as_str) {
+            Some("SCALAR") => 1,
+            Some("VEC2") => 2,
+            Some("VEC3") => 3,
+            Some("VEC4") | Some("MAT2") => 4,
+            Some("MAT3") => 9,
+            Some("MAT4") => 16,
+            _ => return Err(context(String::from("missing or unknown type"))),
+        };
+        let component_type = required_index(accessor, "componentType").map_err(context)?;
+        let size = match component_type {
+            5120 | 5121 => 1,
+            5122 | 5123 => 2,
+            5125 | 5126 => 4,
+            _ => return Err(context(format!("unknown componentType {}", component_type))),
+        };
+        let normalized = accessor
+            .get("normalized")
+            .and_then(Json::as_bool)
+            .unwrap_or(false);
+
+        let len = count
+            .checked_mul(components)
+            .ok_or_else(|| context(format!("its count of {} is too large", count)))?;
+        let view = match index_of(accessor, "bufferView").map_err(context)? {
+            Some(view) => view,
+            // accessors without data are all zeros
+            None => return Ok((vec![0.0; len], components)),
+        };
+        let (bytes, stride) = self.view(view).map_err(context)?;
+        let offset = byte_count(accessor, "byteOffset").map_err(context)?;
+        let element = size * components;
+        let stride = if stride == 0 { element } else { stride };
+        // the last element must end within the view, before anything is read
+        let end = match count.checked_sub(1) {
+            Some(last) => last
+                .checked_mul(stride)
+                .and_then(|n| n.checked_add(offset))
+                .and_then(|n| n.checked_add(element)),
+            None => Some(offset),
+        };
+        if end.is_none_or(|end| end > bytes.len()) {
+            return Err(context(String::from(
+                "it runs past the end of its buffer view",
+            )));
+        }
+        let mut values = Vec::with_capacity(len);
+        for i in 0..count {
+            for c in 0..components {
+                let start = offset + i * stride + c * size;
+                let b = &bytes[start..start + size];
+                let value = match component_type {
+                    5120 => b[0] as i8 as f64,
+                    5121 => b[0] as f64,
+                    5122 => i16::from_le_bytes([b[0], b[1]]) as f64,
+                    5123 => u16::from_le_bytes([b[0], b[1]]) as f64,
+                    5125 => u32::from_le_bytes([b[0], b[1], b[2], b[3]]) as f64,
+                    _ => f32::from_le_bytes([b[0], b[1], b[2], b[3]]) as f64,
+                };
+                values.push(match (normalized, component_type) {
+                    (true, 5120) => (value / 127.0).max(-1.0),
+                    (true, 5121) => value / 255.0,
+                    (true, 5122) => (value / 32767.0).max(-1.0),
+                    (true, 5123) => value / 65535.0,
+                    _ => value,
+                });
+            }
+        }
+        Ok((values, components))
+    }
+
+    // the scene's root nodes, with the transforms they start from
+    fn root_nodes(&mut self) -> Result<Vec<(usize, Matrix4)>, String> {
+        let scenes = self
+            .document
+            .get("scenes")
+            .and_then(Json::as_array)
+            .unwrap_or_default();
+        let roots = if scenes.is_empty() {
+            // without scenes, every node that isn't a child is a root
+            let nodes = self
+                .document
+                .get("nodes")
+                .and_then(Json::as_array)
+                .unwrap_or_default();
+            let mut is_child = vec![false; nodes.len()];
+            for node in nodes {
+                for child in indices(node, "children")? {
+                    if let Some(flag) = is_child.get_mut(child) {
+                        *flag = true;
+                    }
+                }
+            }
+            (0..nodes.len()).filter(|&i| !is_child[i]).collect()
+        } else {
+            let scene = index_of(self.document, "scene")?.unwrap_or(0);
+            indices(self.item("scenes", scene)?, "nodes")?
+        };
+        Ok(roots
+            .into_iter()
+            .map(|root| (root, Matrix4::identity()))
+            .collect())
+    }
+
+    // the scene's nodes, depth first and parents before children, kept on
+    // a stack rather than recursing so that long chains of nodes can't
+    // overflow the call stack
+    fn nodes(&mut self) -> Result<(), String> {
+        let mut stack = self.root_nodes()?;
+        stack.reverse();
+        while let Some((index, parent)) = stack.pop() {
+            let children = self.node(index, &parent)?;
+            stack.extend(children.into_iter().rev());
+        }
+        Ok(())
+    }
+
+    // adds what the node holds, and gives its children with its world matrix
+    fn node(&mut self, index: usize, parent: &Matrix4) -> Result<Vec<(usize, Matrix4)>, String> {
+        let node = self.item("nodes", index)?;
+        let context = |e: String| format!("nodes[{}]: {}", index, e);
+        if !self.visited.insert(index) {
+            return Err(context(String::from(
+                "the node is reached twice, so the node hierarchy isn't a tree",
+            )));
+        }
+        let world = parent.mul(&local_matrix(node).map_err(context)?);
+        let mesh = index_of(node, "mesh").map_err(context)?;
+        let camera = index_of(node, "camera").map_err(context)?;
+        let light = match node
+            .get("extensions")
+            .and_then(|e| e.get("KHR_lights_punctual"))
+        {
+            Some(light) => Some(required_index(light, "light").map_err(context)?),
+            None => None,
+        };
+        let children = indices(node, "children").map_err(context)?;
+
+        if mesh.is_some() || camera.is_some() || light.is_some() {
+            match Transform::new(world) {
+                Some(transform) => {
+                    if let Some(mesh) = mesh {
+                        self.mesh(mesh, &transform)?;
+                    }
+                    if let Some(camera) = camera {
+                        self.camera(camera, &transform)?;
+                    }
+                    if let Some(light) = light {
+                        self.lights.push((light, transform));
+                    }
+                }
+                None => self.warn(format!(
+                    "nodes[{}] is left out as its transform can't be inverted",
+                    index
+                )),
+            }
+        }
+        Ok(children.into_iter().map(|child| (child, world)).collect())
+    }
+
+    fn mesh(&mut self, index: usize, transform: &Transform) -> Result<(), String> {
+        let primitives = self
+            .item("meshes", index)?
+            .get("primitives")
+            .and_then(Json::as_array)
+            .unwrap_or_default();
+        for (p, primitive) in primitives.iter().enumerate() {
+            let context = |e: String| format!("meshes[{}].primitives[{}]: {}", index, p, e);
+            let mesh = match self.primitive(primitive).map_err(context)? {
+                Some(mesh) => mesh,
+                None => {
+                    self.warn(context(String::from(
+                        "only triangles are rendered, not points or lines",
+                    )));
+                    continue;
+                }
+            };
+            if mesh.triangles.is_empty() {
+                continue;
+            }
+            let material = self
+                .material(index_of(primitive, "material").map_err(context)?)
+                .map_err(context)?;
+            self.scene.objects.push(Transformed::new(
+                TriangleMesh::new(mesh, material),
+                *transform,
+            ));
+        }
+        Ok(())
+    }
+
+    // the triangles of a primitive, None if it is made of points or lines
+    fn primitive(&self, primitive: &Json) -> Result<Option<Mesh>, String> {
+        let mode = number(primitive, "mode", 4.0)? as usize;
+        if mode < 4 {
+            return Ok(None);
+        }
+        let attributes = primitive.get("attributes").ok_or("it has no attributes")?;
+        let attribute = |name: &str, sizes: &[usize]| -> Result<Option<Vec<f64>>, String> {
+            let index = match index_of(attributes, name)? {
+                Some(index) => index,
+                None => return Ok(None),
+            };
+            let (values, components) = self.accessor(index)?;
+            if !sizes.contains(&components) {
+                return Err(format!("{} has {} components per vertex", name, components));
+            }
+            // colours may have alpha, which is dropped
+            if components == 4 {
+                return Ok(Some(
+                    values
+                        .chunks_exact(4)
+                        .flat_map(|c| [c[0], c[1], c[2]])
+                        .collect(),
+                ));
+            }
+            Ok(Some(values))
+        };
+        let vectors = |values: Vec<f64>| -> Vec<Vector> {
+            values
+                .chunks_exact(3)
+                .map(|c| Vector::new(c[0], c[1], c[2]))
+                .collect()
+        };
+
+        let mut mesh = Mesh {
+            positions: vectors(attribute("POSITION", &[3])?.ok_or("it has no POSITION")?),
+            ..Mesh::default()
+        };
+        if let Some(normals) = attribute("NORMAL", &[3])? {
+            mesh.normals = vectors(normals);
+        }
+        if let Some(uvs) = attribute("TEXCOORD_0", &[2])? {
+            mesh.uvs = uvs.chunks_exact(2).map(|c| (c[0], c[1])).collect();
+        }
+        if let Some(colors) = attribute("COLOR_0", &[3, 4])? {
+            mesh.colors = vectors(colors);
+        }
+
+        let vertices: Vec<usize> = match index_of(primitive, "indices")? {
+            Some(indices) => self
+                .accessor(indices)?
+                .0
+                .into_iter()
+                .map(|i| i as usize)
+                .collect(),
+            None => (0..mesh.positions.len()).collect(),
+        };
+        mesh.triangles = match mode {
+            4 => vertices
+                .chunks_exact(3)
+                .map(|t| [t[0], t[1], t[2]])
+                .collect(),
+            // strips flip every other triangle to keep the winding
+            5 => (2..vertices.len())
+                .map(|i| {
+                    let (a, b, c) = (vertices[i - 2], vertices[i - 1], vertices[i]);
+                    if i % 2 == 0 {
+                        [a, b, c]
+                    } else {
+                        [b, a, c]
+                    }
+                })
+                .collect(),
+            6 => (2..vertices.len())
+                .map(|i| [vertices[0], vertices[i - 1], vertices[i]])
+                .collect(),
+            _ => return Err(format!("unknown mode {}", mode)),
+        };
+        mesh.check()?;
+        Ok(Some(mesh))
+    }
+
+    fn material(&mut self, index: Option<usize>) -> Result<MetallicRoughness, String> {
+        let index = match index {
+            Some(index) => index,
+            // the default material of the specification
+            None => return Ok(MetallicRoughness::new(Vector::new(1.0, 1.0, 1.0), 1.0, 1.0)),
+        };
+        if let Some(material) = self.materials.get(&index) {
+            return Ok(material.clone());
+        }
+        let json = self.item("materials", index)?;
+        let context = |e: String| format!("materials[{}]: {}", index, e);
+        let null = Json::Null;
+        let pbr = json.get("pbrMetallicRoughness").unwrap_or(&null);
+        let base_color = numbers(pbr, "baseColorFactor", 4)
+            .map_err(context)?
+            .map_or(Vector::new(1.0, 1.0, 1.0), |c| {
+                Vector::new(c[0], c[1], c[2])
+            });
+        let metallic = number(pbr, "metallicFactor", 1.0).map_err(context)?;
+        let roughness = number(pbr, "roughnessFactor", 1.0).map_err(context)?;
+        let strength = match json
+            .get("extensions")
+            .and_then(|e| e.get("KHR_materials_emissive_strength"))
+        {
+            Some(extension) => number(extension, "emissiveStrength", 1.0).map_err(context)?,
+            None => 1.0,
+        };
+        let emission = numbers(json, "emissiveFactor", 3)
+            .map_err(context)?
+            .map_or(Vector::default(), |e| {
+                strength * Vector::new(e[0], e[1], e[2])
+            });
+
+        let mut material =
+            MetallicRoughness::new(base_color, metallic, roughness).with_emission(emission);
+        if let Some(info) = pbr.get("baseColorTexture") {
+            if let Some(texture) = self.texture(info, true).map_err(context)? {
+                material = material.with_base_color_texture(texture);
+            }
+        }
+        if let Some(info) = pbr.get("metallicRoughnessTexture") {
+            if let Some(texture) = self.texture(info, false).map_err(context)? {
+                material = material.with_metallic_roughness_texture(texture);
+            }
+        }
+        if let Some(info) = json.get("emissiveTexture") {
+            if let Some(texture) = self.texture(info, true).map_err(context)? {
+                material = material.with_emission_texture(texture);
+            }
+        }
+        match json.get("alphaMode").and_then(Json::as_str) {
+            None | Some("OPAQUE") => {}
+            Some(mode) => self.warn(format!(
+                "materials[{}]: alphaMode {} is rendered opaque",
+                index, mode
+            )),
+        }
+        if json.get("normalTexture").is_some() || json.get("occlusionTexture").is_some() {
+            self.warn(String::from("normal and occlusion textures are ignored"));
+        }
+        self.materials.insert(index, material.clone());
+        Ok(material)
+    }
+
+    // the texture of a material's texture info, None if it can't be used
+    fn texture(&mut self, info: &Json, srgb: bool) -> Result<Option<Arc<Texture>>, String> {
+        let index = required_index(info, "index")?;
+        if number(info, "texCoord", 0.0)? != 0.0 {
+            self.warn(String::from("textures only use TEXCOORD_0"));
+        }
+        if let Some(texture) = self.textures.get(&(index, srgb)) {
+            return Ok(Some(texture.clone()));
+        }
+        let json = self.item("textures", index)?;
+        let context = |e: String| format!("textures[{}]: {}", index, e);
+        let source = match index_of(json, "source").map_err(context)? {
+            Some(source) => source,
+            None => {
+                self.warn(context(String::from(
+                    "it has no image in a supported format, so it's ignored",
+                )));
+                return Ok(None);
+            }
+        };
+        let wrap = |mode: f64| match mode as usize {
+            33071 => Wrap::Clamp,
+            33648 => Wrap::Mirror,
+            _ => Wrap::Repeat,
+        };
+        let (wrap_u, wrap_v) = match index_of(json, "sampler").map_err(context)? {
+            Some(sampler) => {
+                let sampler = self.item("samplers", sampler).map_err(context)?;
+                (
+                    wrap(number(sampler, "wrapS", 10497.0).map_err(context)?),
+                    wrap(number(sampler, "wrapT", 10497.0).map_err(context)?),
+                )
+            }
+            None => (Wrap::Repeat, Wrap::Repeat),
+        };
+
+        let image = self.item("images", source).map_err(context)?;
+        let context = |e: String| format!("images[{}]: {}", source, e);
+        let bytes = match (
+            image.get("uri").and_then(Json::as_str),
+            index_of(image, "bufferView").map_err(context)?,
+        ) {
+            (Some(uri), _) => self.read_uri(uri).map_err(context)?,
+            (None, Some(view)) => self.view(view).map_err(context)?.0.to_vec(),
+            (None, None) => return Err(context(String::from("it has no uri or bufferView"))),
+        };
+        let decoded = image::load_from_memory(&bytes)
+            .map_err(|e| context(format!("can't decode it: {}", e)))?;
+        let texture = Arc::new(Texture::from_image(&decoded, srgb).with_wrap(wrap_u, wrap_v));
+        self.textures.insert((index, srgb), texture.clone());
+        Ok(Some(texture))
+    }
+
+    fn camera(&mut self, index: usize, transform: &Transform) -> Result<(), String> {
+        let camera = self.item("cameras", index)?;
+        let context = |e: String| format!("cameras[{}]: {}", index, e);
+        match camera.get("perspective") {
+            Some(perspective) => {
+                let yfov = number(perspective, "yfov", f64::NAN).map_err(context)?;
+                if !(yfov > 0.0 && yfov < PI) {
+                    return Err(context(String::from("yfov should be between 0 and pi")));
+                }
+                self.views.push((yfov.to_degrees(), *transform));
+            }
+            None => self.warn(context(String::from(
+                "only perspective cameras are supported",
+            ))),
+        }
+        Ok(())
+    }
+
+    fn add_lights(&mut self) -> Result<(), String> {
+        let bounds = self.scene.objects.bounding_box(0.0, 1.0);
+        for (index, transform) in std::mem::take(&mut self.lights) {
+            let light = self
+                .document
+                .get("extensions")
+                .and_then(|e| e.get("KHR_lights_punctual"))
+                .and_then(|e| e.get("lights"))
+                .and_then(Json::as_array)
+                .and_then(|lights| lights.get(index))
+                .ok_or_else(|| format!("KHR_lights_punctual light {} doesn't exist", index))?;
+            let context = |e: String| format!("KHR_lights_punctual light {}: {}", index, e);
+            let color = numbers(light, "color", 3)
+                .map_err(context)?
+                .map_or(Vector::new(1.0, 1.0, 1.0), |c| {
+                    Vector::new(c[0], c[1], c[2])
+                });
+            let intensity =
+                number(light, "intensity", 1.0).map_err(context)? / LUMENS_PER_WATT * color;
+            let position = transform.point(Vector::default());
+            // lights shine down their node's -z
+            let direction = transform.vector(Vector::new(0.0, 0.0, -1.0)).unit();
+
+            match light.get("type").and_then(Json::as_str) {
+                Some("point") => self
+                    .scene
+                    .objects
+                    .push(light::point_light(position, intensity)),
+                Some("spot") => {
+                    let null = Json::Null;
+                    let spot = light.get("spot").unwrap_or(&null);
+                    let inner = number(spot, "innerConeAngle", 0.0).map_err(context)?;
+                    let outer = number(spot, "outerConeAngle", PI / 4.0).map_err(context)?;
+                    self.scene.objects.push(light::spot_light(
+                        position,
+                        direction,
+                        intensity,
+                        inner.cos(),
+                        outer.cos(),
+                    ))
+                }
+                Some("directional") => self
+                    .scene
+                    .objects
+                    .push(light::distant_light(direction, intensity, bounds)),
+                _ => return Err(context(String::from("missing or unknown type"))),
+            }
+        }
+        Ok(())
+    }
+
+    fn add_cameras(&mut self, aspect_ratio: f64) {
+        let mut views = std::mem::take(&mut self.views);
+        if views.is_empty() {
+            // back from the middle of the scene far enough to see all of it
+            let (center, size) = self
+                .scene
+                .objects
+                .bounding_box(0.0, 1.0)
+                .map_or((Vector::default(), 1.0), |b| {
+                    (0.5 * (b.min + b.max), (b.max - b.min).length().max(1e-3))
+                });
+            let eye = center + Vector::new(0.0, 0.0, 1.2 * size);
+            views.push((50.0, Transform::translate(eye)));
+            self.warn(String::from(
+                "the scene has no camera, so it's seen from the front",
+            ));
+        }
+        for (fov, transform) in views {
+            let lookfrom = transform.point(Vector::default());
+            let forward = transform.vector(Vector::new(0.0, 0.0, -1.0)).unit();
+            let vup = transform.vector(Vector::new(0.0, 1.0, 0.0)).unit();
+            let focus = self
+                .scene
+                .objects
+                .hit(&Ray::new(lookfrom, forward, 0.0), 1e-3, f64::MAX)
+                .map_or(10.0, |hit| hit.t);
+            self.scene.cameras.push(Camera::new(
+                lookfrom,
+                lookfrom + forward,
+                vup,
+                fov,
+                aspect_ratio,
+                0.0,
+                focus,
+                0.0,
+                1.0,
+            ));
+        }
+    }
+}
+
+// a node's transform relative to its parent, from its matrix or its
+// translation, rotation and scale
+fn local_matrix(node: &Json) -> Result<Matrix4, String> {
+    if let Some(m) = numbers(node, "matrix", 16)? {
+        // glTF matrices are column-major
+        let mut matrix = Matrix4::identity();
+        for (i, row) in matrix.m.iter_mut().enumerate() {
+            for (j, value) in row.iter_mut().enumerate() {
+                *value = m[4 * j + i];
+            }
+        }
+        return Ok(matrix);
+    }
+    let t = numbers(node, "translation", 3)?.unwrap_or_else(|| vec![0.0; 3]);
+    let r = numbers(node, "rotation", 4)?.unwrap_or_else(|| vec![0.0, 0.0, 0.0, 1.0]);
+    let s = numbers(node, "scale", 3)?.unwrap_or_else(|| vec![1.0; 3]);
+    let scale = Matrix4 {
+        m: [
+            [s[0], 0.0, 0.0, 0.0],
+            [0.0, s[1], 0.0, 0.0],
+            [0.0, 0.0, s[2], 0.0],
+            [0.0, 0.0, 0.0, 1.0],
+        ],
+    };
+    let rotation = Transform::from_quaternion(Quaternion::new(r[3], r[0], r[1], r[2]));
+    let translation = Transform::translate(Vector::new(t[0], t[1], t[2]));
+    Ok(translation.matrix().mul(rotation.matrix()).mul(&scale))
+}
+
+// a number member, `default` if it's missing
+fn number(json: &Json, key: &str, default: f64) -> Result<f64, String> {
+    match json.get(key) {
+        None => Ok(default),
+        Some(value) => value
+            .as_f64()
+            .ok_or_else(|| format!("{} should be a number", key)),
+    }
+}
+
+// an array member of `len` numbers
+fn numbers(json: &Json, key: &str, len: usize) -> Result<Option<Vec<f64>>, String> {
+    let values = match json.get(key) {
+        None => return Ok(None),
+        Some(values) => values.as_array(),
+    };
+    values
+        .filter(|values| values.len() == len)
+        .and_then(|values| {
+            values
+                .iter()
+                .map(Json::as_f64)
+                .collect::<Option<Vec<f64>>>()
+        })
+        .map(Some)
+        .ok_or_else(|| format!("{} should be an array of {} numbers", key, len))
+}
+
+// an index member, None if it's missing
+fn index_of(json: &Json, key: &str) -> Result<Option<usize>, String> {
+    match json.get(key) {
+        None => Ok(None),
+        Some(value) => value
+            .as_usize()
+            .map(Some)
+            .ok_or_else(|| format!("{} should be an index", key)),
+    }
+}
+
+fn required_index(json: &Json, key: &str) -> Result<usize, String> {
+    index_of(json, key)?.ok_or_else(|| format!("{} is missing", key))
+}
+
+// a length, offset or stride in bytes, 0 if it's missing
+fn byte_count(json: &Json, key: &str) -> Result<usize, String> {
+    match json.get(key) {
+        None => Ok(0),
+        Some(value) => value
+            .as_usize()
+            .ok_or_else(|| format!("{} should be a whole number of bytes", key)),
+    }
+}
+
+// an array member of indices, empty if it's missing
+fn indices(json: &Json, key: &str) -> Result<Vec<usize>, String> {
+    match json.get(key) {
+        None => Ok(Vec::new()),
+        Some(values) => values
+            .as_array()
+            .and_then(|values| values.iter().map(Json::as_usize).collect())
+            .ok_or_else(|| format!("{} should be an array of indices", key)),
+    }
+}
+
+fn decode_base64(text: &str) -> Result<Vec<u8>, String> {
+    let mut bytes = Vec::with_capacity(text.len() * 3 / 4);
+    let (mut bits, mut count) = (0u32, 0);
+    for c in text.bytes() {
+        let value = match c {
+            b'A'..=b'Z' => c - b'A',
+            b'a'..=b'z' => c - b'a' + 26,
+            b'0'..=b'9' => c - b'0' + 52,
+            b'+' => 62,
+            b'/' => 63,
+            b'=' => break,
+            _ => return Err(format!("invalid character {:?} in base64 data", c as char)),
+        };
+        bits = (bits << 6) | value as u32;
+        count += 6;
+        if count >= 8 {
+            count -= 8;
+            bytes.push((bits >> count) as u8);
+            bits &= (1 << count) - 1;
+        }
+    }
+    Ok(bytes)
+}
+
+// URIs may have escapes like %20 for spaces
+fn decode_percent(uri: &str) -> String {
+    let bytes = uri.as_bytes();
+    let mut decoded = Vec::with_capacity(bytes.len());
+    let mut i = 0;
+    while i < bytes.len() {
+        let escape = bytes
+            .get(i + 1..i + 3)
+            .filter(|_| bytes[i] == b'%')
+            .and_then(|hex| std::str::from_utf8(hex).ok())
+            .and_then(|hex| u8::from_str_radix(hex, 16).ok());
+        match escape {
+            Some(byte) => {
+                decoded.push(byte);
+                i += 3;
+            }
+            None => {
+                decoded.push(bytes[i]);
+                i += 1;
+            }
+        }
+    }
+    String::from_utf8_lossy(&decoded).into_owned()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::hitable::Hitable;
+    use crate::ray::Ray;
+
+    // A triangle from (0, 0, 0) to (1, 0, 0) and (0, 1, 0), in a buffer of
+    // 36 bytes, with `{buffer}`, `{view}` and `{accessor}` replaced by the
+    // byte counts of each.
+    const TRIANGLE: &str = r#"{
+        "asset": {"version": "2.0"},
+        "scenes": [{"nodes": [0]}],
+        "nodes": [{"mesh": 0}],
+        "meshes": [{"primitives": [{"attributes": {"POSITION": 0}}]}],
+        "accessors": [{"bufferView": 0, "componentType": 5126, "count": 3, "type": "VEC3"{accessor}}],
+        "bufferViews": [{"buffer": 0, {view}}],
+        "buffers": [{"uri": "data:application/octet-stream;base64,AAAAAAAAAAAAAAAAAACAPwAAAAAAAAAAAAAAAAAAgD8AAAAA", {buffer}}]
+    }"#;
+
+    const LENGTH: &str = r#""byteLength": 36"#;
+
+    fn import(buffer: &str, view: &str, accessor: &str) -> Result<GltfScene, String> {
+        let text = TRIANGLE
+            .replace("{buffer}", buffer)
+            .replace("{view}", view)
+            .replace("{accessor}", accessor);
+        parse(text.as_bytes(), Path::new("."), 1.0)
+    }
+
+    #[test]
+    fn imports_a_triangle() {
+        let scene = import(LENGTH, LENGTH, "").unwrap();
+        let ray = Ray::new(
+            Vector::new(0.25, 0.25, -1.0),
+            Vector::new(0.0, 0.0, 1.0),
+            0.0,
+        );
+        let hit = scene.objects.hit(&ray, 0.001, f64::MAX).unwrap();
+        assert!((hit.t - 1.0).abs() < 1e-9);
+        // with a default camera looking at it
+        assert_eq!(scene.cameras.len(), 1);
+    }
+
+    #[test]
+    fn rejects_byte_counts_that_arent_whole_numbers() {
+        for (buffer, view, accessor, member) in [
+            (r#""byteLength": -1"#, LENGTH, "", "buffers[0]: byteLength"),
+            (
+                LENGTH,
+                r#""byteLength": 36, "byteOffset": -4"#,
+                "",
+                "bufferViews[0]: byteOffset",
+            ),
+            (
+                LENGTH,
+                r#""byteLength": 35.5"#,
+                "",
+                "bufferViews[0]: byteLength",
+            ),
+            (
+                LENGTH,
+                r#""byteLength": 36, "byteStride": -12"#,
+                "",
+                "bufferViews[0]: byteStride",
+            ),
+            (
+                LENGTH,
+                LENGTH,
+                r#", "byteOffset": 0.5"#,
+                "accessors[0]: byteOffset",
+            ),
+        ] {
+            let error = import(buffer, view, accessor).err().unwrap();
+            assert!(
+                error.contains(&format!("{} should be a whole number of bytes", member)),
+                "{}",
+                error
+            );
+        }
+    }
+
+    #[test]
+    fn rejects_views_past_the_end_of_their_buffer() {
+        for view in [
+            r#""byteLength": 36, "byteOffset": 4"#,
+            r#""byteLength": 40"#,
+            // so far out that adding the length overflows
+            r#""byteLength": 36, "byteOffset": 18446744073709549568"#,
+        ] {
+            let error = import(LENGTH, view, "").err().unwrap();
+            assert!(
+                error.contains("runs past the end of its buffer"),
+                "{}",
+                error
+            );
+        }
+        let error = import(LENGTH, LENGTH, r#", "byteOffset": 4"#)
+            .err()
+            .unwrap();
+        assert!(
+            error.contains("runs past the end of its buffer view"),
+            "{}",
+            error
+        );
+    }
+}
@@ -0,0 +1,297 @@
+//! Terrain from a grid of heights, traced through a quadtree of the lowest
+//! and highest height over blocks of cells rather than cell by cell. The
+//! quadtree is visited nearest block first, and blocks the ray passes over
+//! or under are skipped whole.
+//!
+//! In its own space the grid covers 0..1 in x and z with the heights along
+//! y, so that it can be stretched to size with `Transformed`. Each cell is
+//! two triangles, with normals smoothly interpolated from the corners, and
+//! hits have (u, v) = (x, z).
+
+use crate::aabb::Aabb;
+use crate::hitable::{HitRecord, Hitable};
+use crate::material::Material;
+use crate::mesh::intersect_triangle;
+use crate::ray::Ray;
+use crate::vector::Vector;
+use std::path::Path;
+
+// lowest and highest heights over the blocks of one level of the quadtree
+struct Level {
+    width: usize,
+    depth: usize,
+    ranges: Vec<(f64, f64)>,
+}
+
+pub struct Heightfield<M: Material> {
+    // samples along x and z
+    width: usize,
+    depth: usize,
+    heights: Vec<f64>,
+    normals: Vec<Vector>,
+    // from single cells up to one block covering the whole grid
+    levels: Vec<Level>,
+    material: M,
+}
+
+impl<M: Material> Heightfield<M> {
+    /// `heights` are given row by row along x, `width` samples to a row and
+    /// `depth` rows. Panics unless there are at least 2 by 2 of them.
+    pub fn new(width: usize, depth: usize, heights: Vec<f64>, material: M) -> Self {
+        assert!(
+            width >= 2 && depth >= 2 && heights.len() == width * depth,
+            "a heightfield needs a grid of at least 2 by 2 heights"
+        );
+        let mut field = Heightfield {
+            width,
+            depth,
+            heights,
+            normals: Vec::new(),
+            levels: Vec::new(),
+            material,
+        };
+        field.normals = (0..width * depth)
+            .map(|index| field.vertex_normal(index % width, index / width))
+            .collect();
+
+        let mut level = Level {
+            width: width - 1,
+            depth: depth - 1,
+            ranges: Vec::with_capacity((width - 1) * (depth - 1)),
+        };
+        for j in 0..depth - 1 {
+            for i in 0..width - 1 {
+                let corners = [
+                    field.height(i, j),
+                    field.height(i + 1, j),
+                    field.height(i, j + 1),
+                    field.height(i + 1, j + 1),
+                ];
+                let low = corners.iter().copied().fold(f64::MAX, f64::min);
+                let high = corners.iter().copied().fold(f64::MIN, f64::max);
+                level.ranges.push((low, high));
+            }
+        }
+        while level.width > 1 || level.depth > 1 {
+            let coarser = level.coarser();
+            field.levels.push(level);
+            level = coarser;
+        }
+        field.levels.push(level);
+        field
+    }
+
+    /// Samples `height(x, z)` at `width` by `depth` points spread over 0..1,
+    /// for instance noise from `crate::noise`.
+    pub fn from_fn(
+        width: usize,
+        depth: usize,
+        height: impl Fn(f64, f64) -> f64,
+        material: M,
+    ) -> Self {
+        let mut heights = Vec::with_capacity(width * depth);
+        for j in 0..depth {
+            for i in 0..width {
+                let x = i as f64 / (width.max(2) - 1) as f64;
+                let z = j as f64 / (depth.max(2) - 1) as f64;
+                heights.push(height(x, z));
+            }
+        }
+        Heightfield::new(width, depth, heights, material)
+    }
+
+    /// Reads the heights from a greyscale image, black being 0 and white 1,
+    /// with x to the right and z down the image. 16 bit images keep their
+    /// precision.
+    pub fn from_image(path: &Path, material: M) -> Result<Self, String> {
+        let image = image::open(path)
+            .map_err(|e| format!("can't read heightfield image {}: {}", path.display(), e))?
+            .into_luma16();
+        let (width, depth) = (image.width() as usize, image.height() as usize);
+        if width < 2 || depth < 2 {
+            return Err(format!(
+                "heightfield image {} is smaller than 2 by 2 pixels",
+                path.display()
+            ));
+        }
+        let heights = image.pixels().map(|p| p.0[0] as f64 / 65535.0).collect();
+        Ok(Heightfield::new(width, depth, heights, material))
+    }
+
+    fn height(&self, i: usize, j: usize) -> f64 {
+        self.heights[j * self.width + i]
+    }
+
+    fn vertex(&self, i: usize, j: usize) -> Vector {
+        Vector::new(
+            i as f64 / (self.width - 1) as f64,
+            self.height(i, j),
+            j as f64 / (self.depth - 1) as f64,
+        )
+    }
+
+    // from the slope between the neighbours on either side
+    fn vertex_normal(&self, i: usize, j: usize) -> Vector {
+        let (i0, i1) = (i.saturating_sub(1), (i + 1).min(self.width - 1));
+        let (j0, j1) = (j.saturating_sub(1), (j + 1).min(self.depth - 1));
+        let dx = (i1 - i0) as f64 / (self.width - 1) as f64;
+        let dz = (j1 - j0) as f64 / (self.depth - 1) as f64;
+        let slope_x = (self.height(i1, j) - self.height(i0, j)) / dx;
+        let slope_z = (self.height(i, j1) - self.height(i, j0)) / dz;
+        Vector::new(-slope_x, 1.0, -slope_z).unit()
+    }
+
+    // the box around block (i, j) of a level
+    fn block(&self, level: usize, i: usize, j: usize) -> Aabb {
+        let size = 1 << level;
+        let cells_x = (self.width - 1) as f64;
+        let cells_z = (self.depth - 1) as f64;
+        let (low, high) = self.levels[level].ranges[j * self.levels[level].width + i];
+        Aabb::new(
+            Vector::new(
+                (i * size) as f64 / cells_x,
+                low,
+                (j * size) as f64 / cells_z,
+            ),
+            Vector::new(
+                (((i + 1) * size) as f64 / cells_x).min(1.0),
+                high,
+                (((j + 1) * size) as f64 / cells_z).min(1.0),
+            ),
+        )
+        // flat blocks still need some thickness to be hit
+        .padded(1e-9)
+    }
+
+    // looks for a hit nearer than `best` in block (i, j) of a level
+    fn visit(
+        &self,
+        ray: &Ray,
+        level: usize,
+        (i, j): (usize, usize),
+        (t_min, t_max): (f64, f64),
+        best: &mut Option<CellHit>,
+    ) {
+        let t_max = best.as_ref().map_or(t_max, |hit| hit.t);
+        if self.block(level, i, j).range(ray, t_min, t_max).is_none() {
+            return;
+        }
+        if level == 0 {
+            if let Some(hit) = self.cell_hit(ray, i, j, t_min, t_max) {
+                *best = Some(hit);
+            }
+            return;
+        }
+
+        let finer = &self.levels[level - 1];
+        let mut children = [(0.0, 0, 0); 4];
+        let mut count = 0;
+        for (ci, cj) in [
+            (2 * i, 2 * j),
+            (2 * i + 1, 2 * j),
+            (2 * i, 2 * j + 1),
+            (2 * i + 1, 2 * j + 1),
+        ] {
+            if ci >= finer.width || cj >= finer.depth {
+                continue;
+            }
+            if let Some((entry, _)) = self.block(level - 1, ci, cj).range(ray, t_min, t_max) {
+                children[count] = (entry, ci, cj);
+                count += 1;
+            }
+        }
+        let children = &mut children[..count];
+        children.sort_by(|a, b| a.0.total_cmp(&b.0));
+        for &(entry, ci, cj) in children.iter() {
+            if best.as_ref().is_some_and(|hit| hit.t <= entry) {
+                break;
+            }
+            self.visit(ray, level - 1, (ci, cj), (t_min, t_max), best);
+        }
+    }
+
+    fn cell_hit(&self, ray: &Ray, i: usize, j: usize, t_min: f64, t_max: f64) -> Option<CellHit> {
+        let corners = [(i, j), (i + 1, j), (i + 1, j + 1), (i, j + 1)];
+        let mut best: Option<CellHit> = None;
+        for triangle in [
+            [corners[0], corners[1], corners[2]],
+            [corners[0], corners[2], corners[3]],
+        ] {
+            let limit = best.as_ref().map_or(t_max, |hit| hit.t);
+            let [a, b, c] = triangle.map(|(i, j)| self.vertex(i, j));
+            if let Some((t, b1, b2)) = intersect_triangle(ray, a, b, c, t_min, limit) {
+                best = Some(CellHit {
+                    t,
+                    corners: triangle,
+                    barycentrics: (1.0 - b1 - b2, b1, b2),
+                });
+            }
+        }
+        best
+    }
+}
+
+impl Level {
+    fn coarser(&self) -> Level {
+        let width = self.width.div_ceil(2);
+        let depth = self.depth.div_ceil(2);
+        let mut ranges = Vec::with_capacity(width * depth);
+        for j in 0..depth {
+            for i in 0..width {
+                let mut range = (f64::MAX, f64::MIN);
+                for (ci, cj) in [
+                    (2 * i, 2 * j),
+                    (2 * i + 1, 2 * j),
+                    (2 * i, 2 * j + 1),
+                    (2 * i + 1, 2 * j + 1),
+                ] {
+                    if ci < self.width && cj < self.depth {
+                        let (low, high) = self.ranges[cj * self.width + ci];
+                        range = (range.0.min(low), range.1.max(high));
+                    }
+                }
+                ranges.push(range);
+            }
+        }
+        Level {
+            width,
+            depth,
+            ranges,
+        }
+    }
+}
+
+struct CellHit {
+    t: f64,
+    corners: [(usize, usize); 3],
+    barycentrics: (f64, f64, f64),
+}
+
+impl<M: Material + Sync> Hitable for Heightfield<M> {
+    fn hit(&self, ray: &Ray, t_min: f64, t_max: f64) -> Option<HitRecord<'_>> {
+        let mut best = None;
+        self.visit(
+            ray,
+            self.levels.len() - 1,
+            (0, 0),
+            (t_min, t_max),
+            &mut best,
+        );
+        let hit = best?;
+
+        let p = ray.at(hit.t);
+        let (w0, w1, w2) = hit.barycentrics;
+        let [n0, n1, n2] = hit.corners.map(|(i, j)| self.normals[j * self.width + i]);
+        let normal = (w0 * n0 + w1 * n1 + w2 * n2).unit();
+        let tangent = Vector::new(1.0, 0.0, 0.0) - normal.x() * normal;
+        Some(
+            HitRecord::new(hit.t, p, normal, &self.material)
+                .with_uv(p.x().clamp(0.0, 1.0), p.z().clamp(0.0, 1.0))
+                .with_tangent(tangent),
+        )
+    }
+
+    fn bounding_box(&self, _time0: f64, _time1: f64) -> Option<Aabb> {
+        Some(self.block(self.levels.len() - 1, 0, 0))
+    }
+}
@@ -1,51 +1,152 @@
+use crate::aabb::Aabb;
 use crate::material::Material;
 use crate::ray::Ray;
 use crate::vector::Vector;
+use std::sync::Arc;
 
 #[derive(Copy, Clone)]
 pub struct HitRecord<'a> {
     pub t: f64,
     pub p: Vector,
     pub normal: Vector,
+    // surface coordinates, each in 0..1
+    pub u: f64,
+    pub v: f64,
+    // direction of increasing u, zero when the surface doesn't say
+    pub tangent: Vector,
+    // colour of the surface where it has one, like meshes with vertex colours
+    pub color: Option<Vector>,
     pub material: &'a dyn Material,
+    // which object of the scene was hit, counting from 1, for the object id
+    // AOV; shapes leave it at 0 for the lists and instances holding them to
+    // number
+    pub object_id: u32,
 }
 
 impl HitRecord<'_> {
-    pub fn new(t: f64, p: Vector, normal: Vector, material: &dyn Material) -> HitRecord {
+    pub fn new(t: f64, p: Vector, normal: Vector, material: &dyn Material) -> HitRecord<'_> {
         HitRecord {
             t,
             p,
             normal,
+            u: 0.0,
+            v: 0.0,
+            tangent: Vector::default(),
+            color: None,
             material,
+            object_id: 0,
+        }
+    }
+
+    pub fn with_uv(self, u: f64, v: f64) -> Self {
+        HitRecord { u, v, ..self }
+    }
+
+    pub fn with_tangent(self, tangent: Vector) -> Self {
+        HitRecord { tangent, ..self }
+    }
+
+    pub fn with_color(self, color: Vector) -> Self {
+        HitRecord {
+            color: Some(color),
+            ..self
         }
     }
 }
 
 pub trait Hitable: Sync {
-    fn hit(&self, r: &Ray, t_min: f64, t_max: f64) -> Option<HitRecord>;
+    fn hit(&self, r: &Ray, t_min: f64, t_max: f64) -> Option<HitRecord<'_>>;
+
+    /// Box holding the object at any time between time0 and time1, None if
+    /// it's unbounded, as objects are unless they say otherwise.
+    fn bounding_box(&self, _time0: f64, _time1: f64) -> Option<Aabb> {
+        None
+    }
+
+    /// Number of object ids the object's hits take, counting from 1. Lists
+    /// and instances give every object in them ids of their own.
+    fn object_count(&self) -> u32 {
+        1
+    }
+}
+
+// Numbers a hit on an object of a list, which comes after objects that took
+// `ids_before` ids. Shapes that don't number their hits take a single id.
+pub(crate) fn number_hit(hit: &mut HitRecord, ids_before: u32) {
+    hit.object_id = ids_before.saturating_add(hit.object_id.max(1));
+}
+
+// objects whose type is only known at run time, like the shapes of a scene
+// graph
+impl<H: Hitable + ?Sized> Hitable for Box<H> {
+    fn hit(&self, r: &Ray, t_min: f64, t_max: f64) -> Option<HitRecord<'_>> {
+        (**self).hit(r, t_min, t_max)
+    }
+
+    fn bounding_box(&self, time0: f64, time1: f64) -> Option<Aabb> {
+        (**self).bounding_box(time0, time1)
+    }
+
+    fn object_count(&self) -> u32 {
+        (**self).object_count()
+    }
+}
+
+/// Geometry shared by many objects, like a mesh placed over and over with
+/// `Transformed` or as the `Instances` of a scene.
+pub type SharedGeometry = Arc<dyn Hitable + Send + Sync>;
+
+impl<H: Hitable + Send + ?Sized> Hitable for Arc<H> {
+    fn hit(&self, r: &Ray, t_min: f64, t_max: f64) -> Option<HitRecord<'_>> {
+        (**self).hit(r, t_min, t_max)
+    }
+
+    fn bounding_box(&self, time0: f64, time1: f64) -> Option<Aabb> {
+        (**self).bounding_box(time0, time1)
+    }
+
+    fn object_count(&self) -> u32 {
+        (**self).object_count()
+    }
 }
 
 #[derive(Default)]
 pub struct HitableList {
     list: Vec<Box<dyn Hitable>>,
+    // ids taken by the objects before each one
+    ids_before: Vec<u32>,
+    object_count: u32,
 }
 
 impl HitableList {
     pub fn push(&mut self, v: impl Hitable + 'static) {
+        self.ids_before.push(self.object_count);
+        self.object_count = self.object_count.saturating_add(v.object_count());
         self.list.push(Box::new(v));
     }
 }
 
 impl Hitable for HitableList {
-    fn hit(&self, r: &Ray, t_min: f64, t_max: f64) -> Option<HitRecord> {
+    fn hit(&self, r: &Ray, t_min: f64, t_max: f64) -> Option<HitRecord<'_>> {
         let mut closest_so_far = t_max;
         let mut hit_anything: Option<HitRecord> = None;
-        for h in self.list.iter() {
-            if let Some(hit) = h.hit(r, t_min, closest_so_far) {
+        for (index, h) in self.list.iter().enumerate() {
+            if let Some(mut hit) = h.hit(r, t_min, closest_so_far) {
                 closest_so_far = hit.t;
+                number_hit(&mut hit, self.ids_before[index]);
                 hit_anything = Some(hit);
             }
         }
-        return hit_anything;
+        hit_anything
+    }
+
+    fn bounding_box(&self, time0: f64, time1: f64) -> Option<Aabb> {
+        let mut boxes = self.list.iter().map(|h| h.bounding_box(time0, time1));
+        let first = boxes.next()??;
+        boxes.try_fold(first, |aabb, other| Some(aabb.surrounding(&other?)))
+    }
+
+    fn object_count(&self) -> u32 {
+        self.object_count
     }
 }
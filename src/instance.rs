@@ -0,0 +1,272 @@
+//! Many copies of the same geometry without copying it. An `Instance` is
+//! only the top three rows of the affine matrix taking the world to its
+//! geometry's space, the index of the shared geometry it places and
+//! optionally that of a material to use instead of the geometry's own.
+//! `Instances` finds the ones a ray may hit with a bounding volume hierarchy
+//! over their boxes, then the geometry's own hierarchy, as a mesh's, finds
+//! the triangle: two levels, so that the memory used grows with the number
+//! of distinct meshes rather than with every copy of their triangles.
+
+use crate::aabb::Aabb;
+use crate::bvh::Bvh;
+use crate::hitable::{HitRecord, Hitable, SharedGeometry};
+use crate::material::SharedMaterial;
+use crate::ray::Ray;
+use crate::transform::{Matrix4, Transform};
+use crate::vector::Vector;
+use std::convert::TryFrom;
+
+/// One copy of a piece of shared geometry.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct Instance {
+    // the bottom row of an affine matrix is always 0 0 0 1, and the matrix
+    // to the world is only needed for the nearest hit, so it is worked out
+    // then rather than kept
+    to_local: [[f64; 4]; 3],
+    geometry: u32,
+    material: Option<u32>,
+}
+
+impl Instance {
+    /// Geometry number `geometry` of the `Instances`, moved by `transform`.
+    pub fn new(geometry: usize, transform: Transform) -> Instance {
+        let [a, b, c, _] = transform.inverse().matrix().m;
+        Instance {
+            to_local: [a, b, c],
+            geometry: u32::try_from(geometry).expect("too many pieces of geometry to instance"),
+            material: None,
+        }
+    }
+
+    /// Renders the geometry with material number `material` of the
+    /// `Instances` in place of its own.
+    pub fn with_material(self, material: usize) -> Instance {
+        Instance {
+            material: Some(u32::try_from(material).expect("too many materials to instance")),
+            ..self
+        }
+    }
+
+    pub fn transform(&self) -> Transform {
+        let [a, b, c] = self.to_local;
+        let to_local = Matrix4 {
+            m: [a, b, c, [0.0, 0.0, 0.0, 1.0]],
+        };
+        Transform::new(to_local)
+            .expect("an instance's transform can be inverted")
+            .inverse()
+    }
+
+    fn ray_to_local(&self, ray: &Ray) -> Ray {
+        let m = &self.to_local;
+        let apply = |v: Vector, w: f64| {
+            let row = |r: &[f64; 4]| r[0] * v.x() + r[1] * v.y() + r[2] * v.z() + r[3] * w;
+            Vector::new(row(&m[0]), row(&m[1]), row(&m[2]))
+        };
+        Ray::new(
+            apply(ray.origin(), 1.0),
+            apply(ray.direction(), 0.0),
+            ray.time(),
+        )
+    }
+
+    // like `Transform::hit_to_world`: normals go by the transpose of the
+    // matrix to local space, and tangents by its inverse, with the rows'
+    // cross products as the columns of the inverse times the determinant
+    fn hit_to_world<'a>(&self, ray: &Ray, hit: HitRecord<'a>) -> HitRecord<'a> {
+        let [r0, r1, r2] = self.to_local.map(|r| Vector::new(r[0], r[1], r[2]));
+        let n = hit.normal;
+        let t = hit.tangent;
+        let (c0, c1, c2) = (r1.cross(r2), r2.cross(r0), r0.cross(r1));
+        HitRecord {
+            p: ray.at(hit.t),
+            normal: (n.x() * r0 + n.y() * r1 + n.z() * r2).unit(),
+            tangent: (t.x() * c0 + t.y() * c1 + t.z() * c2) / r0.dot(c0),
+            ..hit
+        }
+    }
+}
+
+/// Instances of a list of shared geometry, some with a material from a list
+/// of shared materials.
+pub struct Instances {
+    geometry: Vec<SharedGeometry>,
+    materials: Vec<SharedMaterial>,
+    instances: Vec<Instance>,
+    bvh: Bvh,
+}
+
+impl Instances {
+    /// Panics if an instance refers to geometry or a material that isn't in
+    /// the lists, or to geometry without a bounding box, which can't be
+    /// placed in the hierarchy.
+    pub fn new(
+        geometry: Vec<SharedGeometry>,
+        materials: Vec<SharedMaterial>,
+        instances: Vec<Instance>,
+    ) -> Instances {
+        let bounds: Vec<Option<Aabb>> = geometry.iter().map(|g| g.bounding_box(0.0, 1.0)).collect();
+        let boxes: Vec<Aabb> = instances
+            .iter()
+            .enumerate()
+            .map(|(i, instance)| {
+                if let Some(m) = instance.material.filter(|&m| m as usize >= materials.len()) {
+                    panic!(
+                        "instance {} uses material {}, but there are only {}",
+                        i,
+                        m,
+                        materials.len()
+                    );
+                }
+                let aabb = match bounds.get(instance.geometry as usize) {
+                    Some(Some(aabb)) => aabb,
+                    Some(None) => panic!(
+                        "instance {} places geometry {}, which is unbounded",
+                        i, instance.geometry
+                    ),
+                    None => panic!(
+                        "instance {} places geometry {}, but there is only {}",
+                        i,
+                        instance.geometry,
+                        geometry.len()
+                    ),
+                };
+                aabb.transformed(&instance.transform())
+            })
+            .collect();
+        Instances {
+            bvh: Bvh::new(&boxes),
+            geometry,
+            materials,
+            instances,
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        self.instances.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.instances.is_empty()
+    }
+}
+
+impl Hitable for Instances {
+    fn hit(&self, ray: &Ray, t_min: f64, t_max: f64) -> Option<HitRecord<'_>> {
+        let (i, hit) = self.bvh.nearest_hit(ray, t_min, t_max, |i, t_max| {
+            let instance = &self.instances[i];
+            let local = instance.ray_to_local(ray);
+            let hit = self.geometry[instance.geometry as usize].hit(&local, t_min, t_max)?;
+            Some((hit.t, hit))
+        })?;
+
+        let instance = &self.instances[i];
+        let mut hit = instance.hit_to_world(ray, hit);
+        hit.object_id = u32::try_from(i + 1).unwrap_or(u32::MAX);
+        if let Some(material) = instance.material {
+            hit.material = &*self.materials[material as usize];
+        }
+        Some(hit)
+    }
+
+    fn bounding_box(&self, _time0: f64, _time1: f64) -> Option<Aabb> {
+        self.bvh.bounds()
+    }
+
+    // one id for each instance, whatever geometry it places
+    fn object_count(&self) -> u32 {
+        u32::try_from(self.instances.len()).unwrap_or(u32::MAX)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::cuboid::Cuboid;
+    use crate::hitable::HitableList;
+    use crate::material::Lambertian;
+    use crate::sphere::Sphere;
+    use crate::transform::Transformed;
+    use rand::rngs::SmallRng;
+    use rand::{Rng, SeedableRng};
+    use std::sync::Arc;
+
+    fn random_point(rng: &mut SmallRng, size: f64) -> Vector {
+        Vector::new(
+            rng.gen_range(-size..size),
+            rng.gen_range(-size..size),
+            rng.gen_range(-size..size),
+        )
+    }
+
+    #[test]
+    fn finds_the_same_hits_as_each_placement_on_its_own() {
+        let material = || Lambertian::new(Vector::new(0.5, 0.5, 0.5));
+        let geometry: Vec<SharedGeometry> = vec![
+            Arc::new(Sphere::new(Vector::default(), 1.0, material())),
+            Arc::new(Cuboid::new(
+                Vector::new(-1.0, -0.5, -0.2),
+                Vector::new(1.0, 0.5, 0.2),
+                material(),
+            )),
+        ];
+        let mut rng = SmallRng::seed_from_u64(2);
+        let mut placements = Vec::new();
+        let mut brute_force = HitableList::default();
+        for i in 0..100 {
+            let transform = Transform::scale(Vector::new(1.0, rng.gen_range(0.5..2.0), 1.0))
+                .then(&Transform::rotate(
+                    random_point(&mut rng, 1.0),
+                    rng.gen_range(0.0..360.0),
+                ))
+                .then(&Transform::translate(random_point(&mut rng, 10.0)));
+            placements.push(Instance::new(i % 2, transform));
+            brute_force.push(Transformed::new(geometry[i % 2].clone(), transform));
+        }
+        let instances = Instances::new(geometry, Vec::new(), placements);
+
+        let mut hits = 0;
+        for _ in 0..1000 {
+            let origin = random_point(&mut rng, 15.0);
+            let ray = Ray::new(origin, random_point(&mut rng, 1.0) - 0.05 * origin, 0.0);
+            let expected = brute_force.hit(&ray, 0.001, f64::MAX);
+            let found = instances.hit(&ray, 0.001, f64::MAX);
+            assert_eq!(
+                found.map(|hit| hit.object_id),
+                expected.map(|hit| hit.object_id)
+            );
+            if let (Some(found), Some(expected)) = (found, expected) {
+                assert!((found.t - expected.t).abs() < 1e-9);
+                assert!((found.p - expected.p).length() < 1e-9);
+                assert!((found.normal - expected.normal).length() < 1e-9);
+                hits += 1;
+            }
+        }
+        assert!(hits > 100, "only {} rays hit anything", hits);
+    }
+
+    #[test]
+    fn numbers_each_instance() {
+        let material = Lambertian::new(Vector::new(0.5, 0.5, 0.5));
+        let sphere: SharedGeometry = Arc::new(Sphere::new(Vector::default(), 1.0, material));
+        let instances = Instances::new(
+            vec![sphere],
+            Vec::new(),
+            (0..3)
+                .map(|i| {
+                    Instance::new(
+                        0,
+                        Transform::translate(Vector::new(3.0 * i as f64, 0.0, 0.0)),
+                    )
+                })
+                .collect(),
+        );
+        assert_eq!(instances.object_count(), 3);
+        for i in 0..3 {
+            let origin = Vector::new(3.0 * i as f64, 0.0, -10.0);
+            let ray = Ray::new(origin, Vector::new(0.0, 0.0, 1.0), 0.0);
+            let hit = instances.hit(&ray, 0.001, f64::MAX).unwrap();
+            assert_eq!(hit.object_id, i + 1);
+        }
+    }
+}
@@ -1,8 +1,45 @@
+pub mod aabb;
+pub mod animation;
+pub mod aov;
+pub mod aperture;
+pub mod bvh;
 pub mod camera;
+pub mod checkpoint;
+pub mod csg;
+pub mod cuboid;
+pub mod curve;
+pub mod denoise;
+pub mod exr;
+pub mod film;
+pub mod filter;
+pub mod gltf;
+pub mod heightfield;
 pub mod hitable;
+pub mod instance;
+pub mod json;
+pub mod light;
 pub mod material;
+pub mod mesh;
+pub mod mitsuba;
+pub mod noise;
+pub mod obj;
+pub mod pbrt;
+pub mod ply;
+pub mod polynomial;
+pub mod postprocess;
+pub mod quadric;
 pub mod ray;
+pub mod realistic;
+pub mod render;
+pub mod scatter;
+pub mod scenegraph;
 pub mod scenes;
+pub mod sdf;
+pub mod spectrum;
 pub mod sphere;
+pub mod stl;
+pub mod texture;
 pub mod tracer;
+pub mod transform;
 pub mod vector;
+pub mod xml;
@@ -0,0 +1,132 @@
+//! Lights that scene files give as a point or a direction, made into
+//! geometry the renderer can hit, as it only has lights with an area:
+//! point and spot lights are small glowing spheres, directional lights
+//! distant disks, and light from all around a sphere enclosing the scene.
+//! They are found only by rays bouncing into them.
+
+use crate::aabb::Aabb;
+use crate::hitable::HitRecord;
+use crate::material::{material_id, DiffuseLight, Material};
+use crate::quadric::Disk;
+use crate::ray::Ray;
+use crate::sphere::Sphere;
+use crate::transform::{Transform, Transformed};
+use crate::vector::Vector;
+use std::f64::consts::PI;
+
+/// The radius of the spheres standing in for point and spot lights.
+pub const LIGHT_RADIUS: f64 = 0.1;
+/// The angular radius of the disks standing in for directional lights, in
+/// degrees.
+pub const SUN_RADIUS: f64 = 5.0;
+
+/// A light at `position` giving off `intensity`, in watts per steradian,
+/// in every direction.
+pub fn point_light(position: Vector, intensity: Vector) -> Sphere<DiffuseLight> {
+    Sphere::new(
+        position,
+        LIGHT_RADIUS,
+        DiffuseLight::new(sphere_radiance(intensity)),
+    )
+}
+
+/// A point light shining along `direction`, full strength within the cone
+/// whose cosine is `cos_inner` and dark outside the one of `cos_outer`.
+pub fn spot_light(
+    position: Vector,
+    direction: Vector,
+    intensity: Vector,
+    cos_inner: f64,
+    cos_outer: f64,
+) -> Sphere<SpotLight> {
+    Sphere::new(
+        position,
+        LIGHT_RADIUS,
+        SpotLight {
+            emit: sphere_radiance(intensity),
+            direction: direction.unit(),
+            cos_inner,
+            cos_outer,
+        },
+    )
+}
+
+/// Parallel light travelling along `direction`, giving `irradiance`, in
+/// watts per square metre, to surfaces facing it. The disk is placed far
+/// enough from the scene within `bounds` for its light to come in parallel.
+pub fn distant_light(
+    direction: Vector,
+    irradiance: Vector,
+    bounds: Option<Aabb>,
+) -> Transformed<Disk<DiffuseLight>> {
+    let direction = direction.unit();
+    let (center, size) = bounds.map_or((Vector::default(), 1.0), |b| {
+        (0.5 * (b.min + b.max), (b.max - b.min).length().max(1.0))
+    });
+    let distance = 100.0 * size;
+    let angle = SUN_RADIUS.to_radians();
+    // irradiance E comes from a disk of radiance E / (pi sin^2)
+    let radiance = 1.0 / (PI * angle.sin().powi(2)) * irradiance;
+    let up = Vector::new(0.0, 1.0, 0.0);
+    let facing = direction.reverse();
+    let axis = up.cross(facing);
+    let rotation = if axis.length() < 1e-9 {
+        // already facing up, or straight down
+        Transform::rotate_x(if facing.y() > 0.0 { 0.0 } else { 180.0 })
+    } else {
+        Transform::rotate(axis, up.dot(facing).clamp(-1.0, 1.0).acos().to_degrees())
+    };
+    Transformed::new(
+        Disk::new(distance * angle.tan(), DiffuseLight::new(radiance)),
+        rotation.then(&Transform::translate(center - distance * direction)),
+    )
+}
+
+/// Light of `radiance` coming from every direction, as a glowing sphere
+/// around the scene within `bounds` and the camera at `eye`. Scenes with
+/// one don't see the renderer's sky, which is what files from renderers
+/// whose background is black expect even with zero radiance.
+pub fn environment(radiance: Vector, bounds: Option<Aabb>, eye: Vector) -> Sphere<DiffuseLight> {
+    let (center, size) = bounds.map_or((eye, 1.0), |b| {
+        let center = 0.5 * (b.min + b.max);
+        (center, (b.max - b.min).length() + (eye - center).length())
+    });
+    Sphere::new(center, 10.0 * size.max(1.0), DiffuseLight::new(radiance))
+}
+
+// a sphere of radiance L gives off pi r^2 L in every direction
+fn sphere_radiance(intensity: Vector) -> Vector {
+    1.0 / (PI * LIGHT_RADIUS * LIGHT_RADIUS) * intensity
+}
+
+/// A spot light seen as a small sphere: the points of the sphere facing
+/// away from the spot's direction are dark, with a smooth falloff between
+/// the inner and outer cones.
+#[derive(Copy, Clone)]
+pub struct SpotLight {
+    emit: Vector,
+    direction: Vector,
+    cos_inner: f64,
+    cos_outer: f64,
+}
+
+impl Material for SpotLight {
+    fn scatter(&self, _ray: &Ray, _hit: &HitRecord) -> Option<(Ray, Vector)> {
+        None
+    }
+
+    fn emitted(&self, hit: &HitRecord) -> Vector {
+        let cosine = hit.normal.dot(self.direction);
+        let t = ((cosine - self.cos_outer) / (self.cos_inner - self.cos_outer).max(1e-6))
+            .clamp(0.0, 1.0);
+        (t * t) * self.emit
+    }
+
+    fn id(&self) -> u32 {
+        let e = self.emit;
+        material_id(
+            "spot_light",
+            &[e.x(), e.y(), e.z(), self.cos_inner, self.cos_outer],
+        )
+    }
+}
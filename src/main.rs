@@ -1,22 +1,727 @@
 use indicatif::{ProgressBar, ProgressStyle};
-use rayon::prelude::*;
+use std::env;
+use std::fs::{self, File};
+use std::io::{self, BufWriter, Write};
+use std::path::{Path, PathBuf};
+use std::process;
+use std::str::FromStr;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+use tracer::animation::{CameraRig, FrameRange};
+use tracer::aov::Aov;
+use tracer::aperture::{ApertureImage, ApertureShape};
+use tracer::camera::{Camera, CameraModel, Projection, Stereo};
+use tracer::checkpoint::Checkpoint;
+use tracer::denoise::Denoiser;
+use tracer::film::Film;
+use tracer::filter::{Filter, FilterKind};
+use tracer::gltf;
+use tracer::hitable::HitableList;
+use tracer::mitsuba;
+use tracer::pbrt;
+use tracer::postprocess::{PostProcess, ToneMap};
+use tracer::realistic::{LensSystem, Realistic};
+use tracer::render::{self, CropOutput, CropWindow, RenderSettings};
 use tracer::scenes;
-use tracer::tracer::{random_float, write_color};
-use tracer::vector::Vector;
+use tracer::tracer::{mix_seed, seed_rng};
+
+struct Options {
+    output: Option<PathBuf>,
+    checkpoint: Option<PathBuf>,
+    checkpoint_interval: Duration,
+    resume: Option<PathBuf>,
+    time_limit: Option<Duration>,
+    post: PostProcess,
+    denoiser: Option<Denoiser>,
+    frames: Option<FrameRange>,
+    fps: f64,
+    // fraction of the frame the shutter is open for, in degrees
+    shutter_angle: f64,
+    // a scene file that sets the image size too, loaded along with the options
+    imported: Option<(HitableList, CameraRig)>,
+}
+
+fn usage() -> ! {
+    eprintln!("usage: tracer [options] > image.ppm");
+    eprintln!();
+    eprintln!(
+        "  --scene NAME              scene1 (default), scene2, scene3 (animated), scene4 (CSG),"
+    );
+    eprintln!(
+        "                            scene5 (distance fields), scene6 (quadrics), scene7 (hair),"
+    );
+    eprintln!("                            scene8 (terrain), scene9 (meshes), scene10 (scene");
+    eprintln!("                            graph), scene11 (instancing), scene12 (scatter), a");
+    eprintln!("                            glTF file (.gltf or .glb) seen through its first");
+    eprintln!("                            camera, or a PBRT-v4 (.pbrt) or Mitsuba (.xml) file,");
+    eprintln!("                            which also sets the image size and samples");
+    eprintln!("  --frames A-B              render frames A to B to numbered files, the #s in the");
+    eprintln!("                            --output name being replaced by the frame number");
+    eprintln!("  --fps N                   frames per second (default 24)");
+    eprintln!(
+        "  --shutter-angle DEG       part of each frame the shutter is open for (default 180)"
+    );
+    eprintln!(
+        "  --shutter-curve CURVE     how the shutter opens and closes: box (default), triangle,"
+    );
+    eprintln!(
+        "                            trapezoid=OPENING,CLOSING (fractions of the open time) or"
+    );
+    eprintln!("                            curve=V,V,... (openness at evenly spaced times)");
+    eprintln!(
+        "  --camera MODEL            perspective (default), orthographic[=HEIGHT], fisheye[=FOV],"
+    );
+    eprintln!(
+        "                            fisheye-equisolid[=FOV] or equirectangular (a 2:1 panorama)"
+    );
+    eprintln!("  --aperture D              lens diameter, overriding the scene's");
+    eprintln!("  --aperture-blades N       polygonal aperture with N blades");
+    eprintln!("  --aperture-rotation DEG   rotation of the blades");
+    eprintln!("  --aperture-curvature C    from straight (0, default) to circular (1) blades");
+    eprintln!(
+        "  --aperture-image FILE     aperture shape from a greyscale PNG or JPEG, white is open"
+    );
+    eprintln!(
+        "  --cats-eye K              clip the aperture toward the corners of the frame, 0..1"
+    );
+    eprintln!("  --lateral-ca A            magnification difference between red and blue");
+    eprintln!(
+        "  --longitudinal-ca A       relative focus distance difference between red and blue"
+    );
+    eprintln!(
+        "  --lens-file FILE          look through the lens system in FILE (see lenses/) instead of"
+    );
+    eprintln!("                            the thin lens, which makes the options above ignored");
+    eprintln!("  --lens-focus D            focus distance in scene units (default: the scene's)");
+    eprintln!("  --film-diagonal MM        film size for --lens-file (default 43.27, full frame)");
+    eprintln!("  --lens-scale S            size of a millimeter in scene units (default 0.001)");
+    eprintln!("  --stereo LAYOUT           render both eyes, top-bottom or side-by-side: an off-axis pair");
+    eprintln!(
+        "                            with the perspective camera, omni-directional stereo with the"
+    );
+    eprintln!("                            equirectangular one");
+    eprintln!(
+        "  --ipd D                   distance between the eyes in scene units (default 0.064)"
+    );
+    eprintln!(
+        "  --convergence D           distance at which the eyes converge (default: the focus"
+    );
+    eprintln!("                            distance, or infinity for panoramas)");
+    eprintln!("  --samples N               samples per pixel (default 100)");
+    eprintln!("  --samples-per-pass N      samples per pixel added by each pass (default 4)");
+    eprintln!("  --seed N                  seed for the scene and the samples (default 0)");
+    eprintln!(
+        "  --crop x0,y0,x1,y1        only trace this rectangle, in pixels or in 0..1 coordinates"
+    );
+    eprintln!(
+        "  --crop-full-frame         output the full frame with only the crop window filled in"
+    );
+    eprintln!(
+        "  --output FILE             write the image to FILE after every pass instead of stdout"
+    );
+    eprintln!("  --checkpoint FILE         save the accumulated film to FILE while rendering");
+    eprintln!("  --checkpoint-interval S   seconds between checkpoints (default 60)");
+    eprintln!("  --resume FILE             continue the render saved in FILE");
+    eprintln!(
+        "  --time-limit T            keep adding passes until T (e.g. 90s, 2m, 1h) has passed,"
+    );
+    eprintln!("                            ignoring --samples");
+    eprintln!("  --filter NAME             pixel filter: box (default), tent, gaussian, mitchell, lanczos");
+    eprintln!("                            or blackman-harris");
+    eprintln!(
+        "  --filter-radius R         filter radius in pixels (default depends on the filter)"
+    );
+    eprintln!(
+        "  --aov NAME[,NAME...]      also render albedo, normal, position, depth, object_id,"
+    );
+    eprintln!("                            material_id, direct_diffuse, indirect_diffuse,");
+    eprintln!("                            direct_specular, indirect_specular or emission (needs --output)");
+    eprintln!(
+        "  --denoise                 denoise the output, guided by the albedo, normal and depth"
+    );
+    eprintln!("                            AOVs (which are rendered and written out as well)");
+    eprintln!(
+        "  --denoise-iterations N    passes of the wavelet filter, each twice as wide (default 5)"
+    );
+    eprintln!(
+        "  --denoise-sigma-color S   how strongly color differences stop the blur (default 0.6)"
+    );
+    eprintln!(
+        "  --denoise-sigma-normal S  how strongly normal differences stop the blur (default 0.1)"
+    );
+    eprintln!(
+        "  --denoise-sigma-depth S   how strongly depth differences stop the blur (default 0.1)"
+    );
+    eprintln!("  --exposure EV             exposure compensation in stops (default 0)");
+    eprintln!("  --white-balance K         temperature of the light that should look white (default 6504)");
+    eprintln!("  --tint T                  green (-) / magenta (+) correction (default 0)");
+    eprintln!(
+        "  --tonemap NAME            clamp (default), reinhard, extended-reinhard[=WHITE], aces,"
+    );
+    eprintln!("                            agx or uncharted2");
+    eprintln!("  --dither                  dither the 8 bit output to hide banding");
+    process::exit(2);
+}
+
+fn parse<T: FromStr>(flag: &str, value: Option<String>) -> T
+where
+    T::Err: std::fmt::Display,
+{
+    let value = value.unwrap_or_else(|| usage());
+    value.parse().unwrap_or_else(|e| {
+        eprintln!("invalid value {:?} for {}: {}", value, flag, e);
+        usage();
+    })
+}
+
+// Like `parse`, for values that only make sense above zero.
+fn parse_positive(flag: &str, value: Option<String>) -> f64 {
+    let value: f64 = parse(flag, value);
+    if !(value > 0.0 && value.is_finite()) {
+        eprintln!("{} must be positive", flag);
+        usage();
+    }
+    value
+}
+
+// Parses durations such as "90", "90s", "2.5m" or "1h".
+fn parse_duration(s: &str) -> Result<Duration, String> {
+    let (number, unit) = match s.find(|c: char| c.is_ascii_alphabetic()) {
+        Some(i) => s.split_at(i),
+        None => (s, "s"),
+    };
+    let seconds = match unit {
+        "s" => 1.0,
+        "m" => 60.0,
+        "h" => 3600.0,
+        _ => return Err(format!("unknown unit {:?}", unit)),
+    };
+    let value: f64 = number.trim().parse().map_err(|e| format!("{}", e))?;
+    if !(value > 0.0 && value.is_finite()) {
+        return Err(String::from("must be positive"));
+    }
+    Ok(Duration::from_secs_f64(value * seconds))
+}
+
+fn parse_args(settings: &mut RenderSettings) -> Options {
+    let mut options = Options {
+        output: None,
+        checkpoint: None,
+        checkpoint_interval: Duration::from_secs(60),
+        resume: None,
+        time_limit: None,
+        post: PostProcess::default(),
+        denoiser: None,
+        frames: None,
+        fps: 24.0,
+        shutter_angle: 180.0,
+        imported: None,
+    };
+    let mut filter_kind = None;
+    let mut filter_radius = None;
+    let mut stereo_layout = None;
+    let mut ipd = None;
+    let mut convergence = None;
+    let mut blades = None;
+    let mut blade_rotation = 0.0;
+    let mut blade_curvature = 0.0;
+    let mut aperture_image: Option<PathBuf> = None;
+    let mut lens_file: Option<PathBuf> = None;
+    let mut lens_focus = None;
+    let mut film_diagonal = None;
+    let mut lens_scale = None;
+    let mut samples = None;
+    let mut args = env::args().skip(1);
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--scene" => settings.scene = parse(&arg, args.next()),
+            "--camera" => settings.projection = parse(&arg, args.next()),
+            "--stereo" => stereo_layout = Some(parse(&arg, args.next())),
+            "--ipd" => ipd = Some(parse(&arg, args.next())),
+            "--convergence" => convergence = Some(parse(&arg, args.next())),
+            "--aperture" => settings.lens.aperture = Some(parse(&arg, args.next())),
+            "--aperture-blades" => blades = Some(parse(&arg, args.next())),
+            "--aperture-rotation" => blade_rotation = parse(&arg, args.next()),
+            "--aperture-curvature" => blade_curvature = parse(&arg, args.next()),
+            "--aperture-image" => aperture_image = Some(parse(&arg, args.next())),
+            "--cats-eye" => settings.lens.cats_eye = parse(&arg, args.next()),
+            "--lateral-ca" => settings.lens.lateral_ca = parse(&arg, args.next()),
+            "--longitudinal-ca" => settings.lens.longitudinal_ca = parse(&arg, args.next()),
+            "--lens-file" => lens_file = Some(parse(&arg, args.next())),
+            "--lens-focus" => lens_focus = Some(parse(&arg, args.next())),
+            "--film-diagonal" => film_diagonal = Some(parse(&arg, args.next())),
+            "--lens-scale" => lens_scale = Some(parse(&arg, args.next())),
+            "--samples" => samples = Some(parse(&arg, args.next())),
+            "--samples-per-pass" => settings.samples_per_pass = parse(&arg, args.next()),
+            "--seed" => settings.seed = parse(&arg, args.next()),
+            "--crop" => settings.crop = Some(parse::<CropWindow>(&arg, args.next())),
+            "--crop-full-frame" => settings.crop_output = CropOutput::FullFrame,
+            "--output" => options.output = Some(parse(&arg, args.next())),
+            "--checkpoint" => options.checkpoint = Some(parse(&arg, args.next())),
+            "--checkpoint-interval" => {
+                let seconds: f64 = parse(&arg, args.next());
+                // also rejects NaN, and times too long for a Duration
+                match Duration::try_from_secs_f64(seconds) {
+                    Ok(interval) => options.checkpoint_interval = interval,
+                    Err(_) => {
+                        eprintln!("--checkpoint-interval must be a number of seconds, at least 0");
+                        usage();
+                    }
+                }
+            }
+            "--resume" => options.resume = Some(parse(&arg, args.next())),
+            "--time-limit" => {
+                let value = args.next().unwrap_or_else(|| usage());
+                match parse_duration(&value) {
+                    Ok(limit) => options.time_limit = Some(limit),
+                    Err(e) => {
+                        eprintln!("invalid value {:?} for {}: {}", value, arg, e);
+                        usage();
+                    }
+                }
+            }
+            "--frames" => options.frames = Some(parse(&arg, args.next())),
+            "--fps" => options.fps = parse(&arg, args.next()),
+            "--shutter-angle" => options.shutter_angle = parse(&arg, args.next()),
+            "--shutter-curve" => settings.shutter_curve = parse(&arg, args.next()),
+            "--filter" => filter_kind = Some(parse(&arg, args.next())),
+            "--filter-radius" => filter_radius = Some(parse::<f64>(&arg, args.next())),
+            "--aov" => {
+                let value: String = parse(&arg, args.next());
+                for name in value.split(',') {
+                    let aov = parse::<Aov>(&arg, Some(name.trim().to_string()));
+                    if !settings.aovs.contains(&aov) {
+                        settings.aovs.push(aov);
+                    }
+                }
+            }
+            "--denoise" => {
+                options.denoiser.get_or_insert_with(Denoiser::default);
+            }
+            "--denoise-iterations" => {
+                let denoiser = options.denoiser.get_or_insert_with(Denoiser::default);
+                denoiser.iterations = parse(&arg, args.next());
+            }
+            "--denoise-sigma-color" => {
+                let denoiser = options.denoiser.get_or_insert_with(Denoiser::default);
+                denoiser.sigma_color = parse_positive(&arg, args.next());
+            }
+            "--denoise-sigma-normal" => {
+                let denoiser = options.denoiser.get_or_insert_with(Denoiser::default);
+                denoiser.sigma_normal = parse_positive(&arg, args.next());
+            }
+            "--denoise-sigma-depth" => {
+                let denoiser = options.denoiser.get_or_insert_with(Denoiser::default);
+                denoiser.sigma_depth = parse_positive(&arg, args.next());
+            }
+            "--exposure" => options.post.exposure = parse(&arg, args.next()),
+            "--white-balance" => options.post.white_balance = parse(&arg, args.next()),
+            "--tint" => options.post.tint = parse(&arg, args.next()),
+            "--tonemap" => options.post.tone_map = parse::<ToneMap>(&arg, args.next()),
+            "--dither" => options.post.dither = true,
+            _ => usage(),
+        }
+    }
+    if settings.scene.ends_with(".pbrt") {
+        let imported = pbrt::load(Path::new(&settings.scene)).unwrap_or_else(|e| {
+            eprintln!("{}", e);
+            process::exit(1);
+        });
+        for warning in &imported.warnings {
+            eprintln!("warning: {}", warning);
+        }
+        (settings.image_width, settings.image_height) = imported.resolution;
+        settings.samples_per_pixel = imported.samples;
+        options.imported = Some((imported.objects, CameraRig::Still(imported.camera)));
+    }
+    let mut file_filter = None;
+    if settings.scene.ends_with(".xml") {
+        let imported = mitsuba::load(Path::new(&settings.scene)).unwrap_or_else(|e| {
+            eprintln!("{}", e);
+            process::exit(1);
+        });
+        for warning in &imported.warnings {
+            eprintln!("warning: {}", warning);
+        }
+        (settings.image_width, settings.image_height) = imported.resolution;
+        settings.samples_per_pixel = imported.samples;
+        settings.max_depth = imported.max_depth.unwrap_or(settings.max_depth);
+        file_filter = imported.filter;
+        options.imported = Some((imported.objects, CameraRig::Still(imported.camera)));
+    }
+    if let Some(samples) = samples {
+        settings.samples_per_pixel = samples;
+    }
+    // the scene file's filter is used unless one is asked for
+    let file_filter = file_filter.filter(|_| filter_kind.is_none() && filter_radius.is_none());
+    let filter_kind = filter_kind.unwrap_or(FilterKind::Box);
+    let filter_radius = filter_radius.unwrap_or_else(|| filter_kind.default_radius());
+    if filter_radius <= 0.0 {
+        eprintln!("--filter-radius must be positive");
+        usage();
+    }
+    settings.filter = match file_filter {
+        Some(filter) => filter,
+        None => Filter::new(filter_kind, filter_radius),
+    };
+    if options.frames.is_some() {
+        let numbered = options
+            .output
+            .as_ref()
+            .is_some_and(|path| path.to_string_lossy().contains('#'));
+        if !numbered {
+            eprintln!(
+                "--frames needs an --output name with #s for the frame number, e.g. frame.####.ppm"
+            );
+            usage();
+        }
+        if options.checkpoint.is_some() || options.resume.is_some() {
+            eprintln!("--frames can't be combined with --checkpoint or --resume");
+            usage();
+        }
+    }
+    if options.fps.is_nan() || options.fps <= 0.0 || !(0.0..=360.0).contains(&options.shutter_angle)
+    {
+        eprintln!("--fps must be positive and --shutter-angle in 0..360");
+        usage();
+    }
+    if !settings.aovs.is_empty() && options.output.is_none() {
+        eprintln!("--aov needs --output");
+        usage();
+    }
+    if options.denoiser.is_some() {
+        for aov in Denoiser::FEATURES {
+            if !settings.aovs.contains(&aov) {
+                settings.aovs.push(aov);
+            }
+        }
+    }
+    if settings.projection == Projection::Equirectangular {
+        settings.image_height = settings.image_width / 2;
+    }
+    settings.lens.shape = match (blades, aperture_image) {
+        (Some(_), Some(_)) => {
+            eprintln!("--aperture-blades and --aperture-image can't be used together");
+            usage();
+        }
+        (Some(blades), None) => {
+            if blades < 3 || !(0.0..=1.0).contains(&blade_curvature) {
+                eprintln!("--aperture-blades must be at least 3 and --aperture-curvature in 0..1");
+                usage();
+            }
+            ApertureShape::Polygon {
+                blades,
+                rotation: blade_rotation,
+                curvature: blade_curvature,
+            }
+        }
+        (None, Some(path)) => match ApertureImage::load(&path) {
+            Ok(image) => ApertureShape::Image(Arc::new(image)),
+            Err(e) => {
+                eprintln!("{}", e);
+                process::exit(1);
+            }
+        },
+        (None, None) => ApertureShape::Circle,
+    };
+    if settings.lens.aperture.is_some_and(|a: f64| a < 0.0)
+        || !(0.0..=1.0).contains(&settings.lens.cats_eye)
+    {
+        eprintln!("--aperture can't be negative and --cats-eye must be in 0..1");
+        usage();
+    }
+    match lens_file {
+        Some(path) => {
+            let lens = LensSystem::load(&path).unwrap_or_else(|e| {
+                eprintln!("{}", e);
+                process::exit(1);
+            });
+            let mut realistic = Realistic::new(lens);
+            realistic.focus_distance = lens_focus;
+            realistic.film_diagonal = film_diagonal.unwrap_or(realistic.film_diagonal);
+            realistic.scale = lens_scale.unwrap_or(realistic.scale);
+            if realistic.film_diagonal <= 0.0
+                || realistic.scale <= 0.0
+                || realistic.focus_distance.is_some_and(|d: f64| d <= 0.0)
+            {
+                eprintln!("--lens-focus, --film-diagonal and --lens-scale must be positive");
+                usage();
+            }
+            if settings.projection != Projection::Perspective || stereo_layout.is_some() {
+                eprintln!("--lens-file can't be combined with --camera or --stereo");
+                usage();
+            }
+            settings.realistic = Some(realistic);
+        }
+        None if lens_focus.is_some() || film_diagonal.is_some() || lens_scale.is_some() => {
+            eprintln!("--lens-focus, --film-diagonal and --lens-scale need --lens-file");
+            usage();
+        }
+        None => {}
+    }
+    match stereo_layout {
+        Some(layout) => {
+            let mut stereo = Stereo::new(layout);
+            stereo.ipd = ipd.unwrap_or(stereo.ipd);
+            stereo.convergence = convergence;
+            if stereo.ipd < 0.0 || stereo.convergence.is_some_and(|c: f64| c <= 0.0) {
+                eprintln!("--ipd can't be negative and --convergence must be positive");
+                usage();
+            }
+            let (width, height) = layout.frame_size(settings.image_width, settings.image_height);
+            settings.image_width = width;
+            settings.image_height = height;
+            settings.stereo = Some(stereo);
+        }
+        None if ipd.is_some() || convergence.is_some() => {
+            eprintln!("--ipd and --convergence need --stereo");
+            usage();
+        }
+        None => {}
+    }
+    if settings.samples_per_pass == 0 {
+        eprintln!("--samples-per-pass must be at least 1");
+        usage();
+    }
+    options
+}
+
+fn load_scene(name: &str, aspect_ratio: f64) -> (HitableList, CameraRig) {
+    match name {
+        "scene1" => {
+            let (scene, camera) = scenes::scene1::scene();
+            (scene, CameraRig::Still(camera))
+        }
+        "scene2" => {
+            let (scene, camera) = scenes::scene2::scene();
+            (scene, CameraRig::Still(camera))
+        }
+        "scene3" => {
+            let (scene, camera) = scenes::scene3::scene();
+            (scene, CameraRig::Animated(camera))
+        }
+        "scene4" => {
+            let (scene, camera) = scenes::scene4::scene();
+            (scene, CameraRig::Still(camera))
+        }
+        "scene5" => {
+            let (scene, camera) = scenes::scene5::scene();
+            (scene, CameraRig::Still(camera))
+        }
+        "scene6" => {
+            let (scene, camera) = scenes::scene6::scene();
+            (scene, CameraRig::Still(camera))
+        }
+        "scene7" => {
+            let (scene, camera) = scenes::scene7::scene();
+            (scene, CameraRig::Still(camera))
+        }
+        "scene8" => {
+            let (scene, camera) = scenes::scene8::scene();
+            (scene, CameraRig::Still(camera))
+        }
+        "scene9" => {
+            let (scene, camera) = scenes::scene9::scene();
+            (scene, CameraRig::Still(camera))
+        }
+        "scene10" => {
+            let (scene, camera) = scenes::scene10::scene();
+            (scene, CameraRig::Still(camera))
+        }
+        "scene11" => {
+            let (scene, camera) = scenes::scene11::scene();
+            (scene, CameraRig::Still(camera))
+        }
+        "scene12" => {
+            let (scene, camera) = scenes::scene12::scene();
+            (scene, CameraRig::Still(camera))
+        }
+        _ if name.ends_with(".gltf") || name.ends_with(".glb") => {
+            let imported = gltf::load(Path::new(name), aspect_ratio).unwrap_or_else(|e| {
+                eprintln!("{}", e);
+                process::exit(1);
+            });
+            for warning in &imported.warnings {
+                eprintln!("warning: {}", warning);
+            }
+            let camera = imported
+                .cameras
+                .into_iter()
+                .next()
+                .expect("there is always a camera");
+            (imported.objects, CameraRig::Still(camera))
+        }
+        _ => {
+            eprintln!("unknown scene {:?}", name);
+            usage();
+        }
+    }
+}
+
+// Writes to a temporary file and renames it, so viewers never see a half written image.
+fn write_atomically(
+    path: &Path,
+    write: impl FnOnce(&mut BufWriter<File>) -> io::Result<()>,
+) -> io::Result<()> {
+    let tmp = path.with_extension("tmp");
+    {
+        let mut out = BufWriter::new(File::create(&tmp)?);
+        write(&mut out)?;
+        out.flush()?;
+    }
+    fs::rename(&tmp, path)
+}
+
+// The format follows the extension: OpenEXR with every AOV as a layer, linear
+// PFM, or tone mapped PPM. With the last two, AOVs go to "<name>.<aov>.pfm".
+fn write_image(
+    film: &Film,
+    settings: &RenderSettings,
+    post: &PostProcess,
+    path: &Path,
+) -> io::Result<()> {
+    let output = settings.output_bounds();
+    match path.extension().and_then(|e| e.to_str()) {
+        Some("exr") => {
+            return write_atomically(path, |out| {
+                film.write_exr(out, settings.full_bounds(), output)
+            })
+        }
+        Some("pfm") => write_atomically(path, |out| film.write_pfm(out, output, None))?,
+        _ => write_atomically(path, |out| film.write_ppm(out, output, post))?,
+    }
+    for aov in settings.aovs.iter() {
+        let aov_path = path.with_extension(format!("{}.pfm", aov.name()));
+        write_atomically(&aov_path, |out| film.write_pfm(out, output, Some(*aov)))?;
+    }
+    Ok(())
+}
 
 fn main() {
     // Image
     let aspect_ratio = 16.0 / 9.0;
-    let image_width = 800;
-    let image_height = (image_width as f64 / aspect_ratio) as usize;
-    let samples_per_pixel = 100;
-    let max_depth = 50;
+    let mut settings = RenderSettings::new(800, aspect_ratio);
+    let mut options = parse_args(&mut settings);
 
-    let (scene, camera) = scenes::scene1::scene();
+    let resumed = options.resume.as_ref().map(|path| {
+        let checkpoint = Checkpoint::load(path, &settings).unwrap_or_else(|e| {
+            eprintln!("can't resume from {}: {}", path.display(), e);
+            process::exit(1);
+        });
+        (checkpoint.film, checkpoint.passes)
+    });
+
+    // The scene is built from the seed too, so that resumed renders see the same scene.
+    seed_rng(settings.seed);
+    let (scene, rig) = match options.imported.take() {
+        Some(imported) => imported,
+        None => load_scene(&settings.scene, aspect_ratio),
+    };
+
+    let frames = match options.frames {
+        Some(frames) => frames,
+        None => {
+            let camera = rig.model(None, |camera| build_camera(&settings, camera));
+            let (film, first_pass) =
+                resumed.unwrap_or_else(|| (Film::new(settings.render_bounds(), &settings.aovs), 0));
+            let film = render(
+                &scene,
+                camera.as_ref(),
+                &settings,
+                &options,
+                film,
+                first_pass,
+                options.output.as_deref(),
+            );
+            if options.output.is_none() {
+                let stdout = io::stdout();
+                let mut out = BufWriter::new(stdout.lock());
+                let denoised = options.denoiser.map(|d| d.apply(&film));
+                let image = denoised.as_ref().unwrap_or(&film);
+                image
+                    .write_ppm(&mut out, settings.output_bounds(), &options.post)
+                    .and_then(|_| out.flush())
+                    .expect("failed to write image");
+            }
+            return;
+        }
+    };
+
+    // Every frame gets its own samples, so the noise doesn't stay in place.
+    let seed = settings.seed;
+    let pattern = options.output.as_ref().expect("--frames needs --output");
+    for frame in frames.first..=frames.last {
+        let open = frame as f64 / options.fps;
+        let close = open + options.shutter_angle / 360.0 / options.fps;
+        let camera = rig.model(Some((open, close)), |camera| {
+            build_camera(&settings, camera)
+        });
+        settings.seed = mix_seed(seed, frame as u64);
+        let path = frame_path(pattern, frame);
+        eprintln!("frame {} -> {}", frame, path.display());
+        let film = Film::new(settings.render_bounds(), &settings.aovs);
+        render(
+            &scene,
+            camera.as_ref(),
+            &settings,
+            &options,
+            film,
+            0,
+            Some(&path),
+        );
+    }
+}
+
+// The camera the image is rendered through, from the scene's one and the settings.
+fn build_camera(settings: &RenderSettings, camera: Camera) -> Box<dyn CameraModel> {
+    let camera = camera
+        .with_lens(settings.lens.clone())
+        .with_shutter_curve(settings.shutter_curve.clone());
+    match (&settings.realistic, settings.stereo) {
+        (Some(realistic), _) => camera.realistic(realistic),
+        (None, Some(stereo)) => camera.stereo_pair(settings.projection, stereo),
+        (None, None) => Ok(camera.with_projection(settings.projection)),
+    }
+    .unwrap_or_else(|e| {
+        eprintln!("{}", e);
+        process::exit(1);
+    })
+}
 
-    print!("P3\n{} {}\n255\n", image_width, image_height);
+// "frame.####.ppm" for frame 12 is "frame.0012.ppm"
+fn frame_path(pattern: &Path, frame: u32) -> PathBuf {
+    let pattern = pattern.to_string_lossy();
+    let start = pattern.find('#').unwrap_or(pattern.len());
+    let width = pattern[start..].chars().take_while(|c| *c == '#').count();
+    let number = format!("{:0width$}", frame, width = width);
+    PathBuf::from(format!(
+        "{}{}{}",
+        &pattern[..start],
+        number,
+        &pattern[start + width..]
+    ))
+}
 
-    let progress_bar = ProgressBar::new((image_height * image_width) as u64);
+/// Adds passes to `film` until the sample count or the time limit is
+/// reached, writing it to `output` after every pass, and returns it.
+fn render(
+    scene: &HitableList,
+    camera: &dyn CameraModel,
+    settings: &RenderSettings,
+    options: &Options,
+    mut film: Film,
+    first_pass: u32,
+    output: Option<&Path>,
+) -> Film {
+    let start = Instant::now();
+    let pixels = settings.render_bounds().area() as u64;
+    let passes = settings.passes();
+    // With a time limit the number of passes isn't known up front, so the bar
+    // tracks the pass in progress instead of the whole render.
+    let progress_bar = match options.time_limit {
+        Some(_) => ProgressBar::new(pixels),
+        None => ProgressBar::new(pixels * passes.saturating_sub(first_pass) as u64),
+    };
     let style = ProgressStyle::with_template(
         "[{elapsed_precise}] {bar:100.cyan/blue} {percent}% {pos:>7}/{len:7} [{eta_precise}] {msg}",
     )
@@ -24,28 +729,55 @@ fn main() {
     .progress_chars("#>-");
     progress_bar.set_style(style);
 
-    let mut screen = vec![Vector::default(); image_height * image_width];
-
-    screen
-        .par_iter_mut()
-        .enumerate()
-        .for_each(|(index, pixel)| {
-            let i = index % image_width;
-            let j = image_height - index / image_width;
-            let mut pixel_color = Vector::default();
-            for _ in 0..samples_per_pixel {
-                let u = (i as f64 + random_float()) / (image_width - 1) as f64;
-                let v = (j as f64 + random_float()) / (image_height - 1) as f64;
-                let ray = camera.get_ray(u, v);
-                pixel_color = pixel_color + ray.color(&scene, max_depth);
-            }
-            *pixel = pixel_color;
-            progress_bar.inc(1);
-        });
+    let save_checkpoint = |film: &Film, pass: u32| {
+        if let Some(path) = &options.checkpoint {
+            let checkpoint = Checkpoint::new(settings, pass, film.clone());
+            if let Err(e) = checkpoint.save(path) {
+                progress_bar.println(format!("failed to save checkpoint: {}", e));
+            }
+        }
+    };
 
-    for pixel_color in screen {
-        write_color(pixel_color, samples_per_pixel);
+    // A pass that has started always runs to completion, the time limit is only
+    // checked before starting the next one.
+    let more_passes = |pass: u32| match options.time_limit {
+        Some(limit) => start.elapsed() < limit,
+        None => pass < passes,
+    };
+
+    let mut pass = first_pass;
+    let mut last_checkpoint = Instant::now();
+    while more_passes(pass) {
+        match options.time_limit {
+            Some(_) => {
+                progress_bar.reset();
+                progress_bar.set_message(format!("pass {}, {} spp", pass + 1, film.min_samples()));
+            }
+            None => progress_bar.set_message(format!("pass {}/{}", pass + 1, passes)),
+        }
+        render::render_pass(scene, camera, settings, &mut film, pass, &progress_bar);
+        pass += 1;
+
+        if let Some(path) = output {
+            // the checkpoints keep the noisy film, denoising only affects what is written out
+            let denoised = options.denoiser.map(|d| d.apply(&film));
+            let image = denoised.as_ref().unwrap_or(&film);
+            if let Err(e) = write_image(image, settings, &options.post, path) {
+                progress_bar.println(format!("failed to write {}: {}", path.display(), e));
+            }
+        }
+        if last_checkpoint.elapsed() >= options.checkpoint_interval {
+            save_checkpoint(&film, pass);
+            last_checkpoint = Instant::now();
+        }
     }
+    save_checkpoint(&film, pass);
 
     progress_bar.finish_with_message("");
+    eprintln!(
+        "reached {} samples per pixel in {:.1}s",
+        film.min_samples(),
+        start.elapsed().as_secs_f64()
+    );
+    film
 }
@@ -1,10 +1,75 @@
 use crate::hitable::HitRecord;
 use crate::ray::Ray;
+use crate::texture::Texture;
+use crate::tracer::{fnv1a, random_float};
 use crate::vector::{random_in_unit_sphere, random_unit_vector, Vector};
-use rand::Rng;
+use std::f64::consts::{LN_2, PI};
+use std::sync::Arc;
 
 pub trait Material {
     fn scatter(&self, ray: &Ray, hit: &HitRecord) -> Option<(Ray, Vector)>;
+
+    // light given off by the surface, on top of whatever it scatters
+    fn emitted(&self, _hit: &HitRecord) -> Vector {
+        Vector::default()
+    }
+
+    // surface color, for the albedo AOV and the denoiser
+    fn albedo(&self, _hit: &HitRecord) -> Vector {
+        Vector::new(1.0, 1.0, 1.0)
+    }
+
+    // which lighting AOVs the light scattered by this material goes to
+    fn lobe(&self) -> Lobe {
+        Lobe::Diffuse
+    }
+
+    // identifies materials with the same parameters, for the material id AOV;
+    // by default every material of a type shares one
+    fn id(&self) -> u32 {
+        material_id(std::any::type_name::<Self>(), &[])
+    }
+}
+
+/// A material shared by many objects, as when scene files define one and use
+/// it over and over.
+pub type SharedMaterial = Arc<dyn Material + Send + Sync>;
+
+impl<M: Material + ?Sized> Material for Arc<M> {
+    fn scatter(&self, ray: &Ray, hit: &HitRecord) -> Option<(Ray, Vector)> {
+        (**self).scatter(ray, hit)
+    }
+
+    fn emitted(&self, hit: &HitRecord) -> Vector {
+        (**self).emitted(hit)
+    }
+
+    fn albedo(&self, hit: &HitRecord) -> Vector {
+        (**self).albedo(hit)
+    }
+
+    fn lobe(&self) -> Lobe {
+        (**self).lobe()
+    }
+
+    fn id(&self) -> u32 {
+        (**self).id()
+    }
+}
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum Lobe {
+    Diffuse,
+    Specular,
+}
+
+// 24 bit hash of a material's kind and parameters, so it survives being stored as a float
+pub fn material_id(kind: &str, parameters: &[f64]) -> u32 {
+    let mut bytes = kind.as_bytes().to_vec();
+    for p in parameters {
+        bytes.extend_from_slice(&p.to_le_bytes());
+    }
+    (fnv1a(&bytes) & 0x00ff_ffff) as u32
 }
 
 #[derive(Copy, Clone)]
@@ -12,11 +77,19 @@ pub struct Lambertian {
     albedo: Vector,
 }
 
-// Lambertian is a diffuse material
+// Lambertian is a diffuse material, its albedo tinted by the surface's own
+// colour where it has one
 impl Lambertian {
     pub fn new(v: Vector) -> Lambertian {
         Lambertian { albedo: v }
     }
+
+    fn color(&self, hit: &HitRecord) -> Vector {
+        match hit.color {
+            Some(color) => self.albedo.hadamard_product(color),
+            None => self.albedo,
+        }
+    }
 }
 
 impl Material for Lambertian {
@@ -29,7 +102,16 @@ impl Material for Lambertian {
         }
 
         let scattered = Ray::new(hit.p, scatter_direction, ray.time());
-        Some((scattered, self.albedo))
+        Some((scattered, self.color(hit)))
+    }
+
+    fn albedo(&self, hit: &HitRecord) -> Vector {
+        self.color(hit)
+    }
+
+    fn id(&self) -> u32 {
+        let a = self.albedo;
+        material_id("lambertian", &[a.x(), a.y(), a.z()])
     }
 }
 
@@ -47,6 +129,25 @@ impl Metal {
             fuzziness_factor: f,
         }
     }
+
+    /// The reflectance at normal incidence of a common metal, by chemical
+    /// symbol, for scene files that name the metal rather than its colour.
+    pub fn reflectance(symbol: &str) -> Option<Vector> {
+        let (r, g, b) = match symbol {
+            "Ag" => (0.972, 0.960, 0.915),
+            "Al" => (0.913, 0.922, 0.924),
+            "Au" => (1.000, 0.766, 0.336),
+            "Cr" => (0.549, 0.556, 0.554),
+            "Cu" => (0.955, 0.638, 0.538),
+            "CuZn" => (0.910, 0.778, 0.423),
+            "Fe" => (0.562, 0.565, 0.578),
+            "Ni" => (0.660, 0.609, 0.526),
+            "Pt" => (0.673, 0.637, 0.585),
+            "Ti" => (0.542, 0.497, 0.449),
+            _ => return None,
+        };
+        Some(Vector::new(r, g, b))
+    }
 }
 
 impl Material for Metal {
@@ -60,6 +161,19 @@ impl Material for Metal {
         let attenuation = self.albedo;
         Some((scattered, attenuation))
     }
+
+    fn albedo(&self, _hit: &HitRecord) -> Vector {
+        self.albedo
+    }
+
+    fn lobe(&self) -> Lobe {
+        Lobe::Specular
+    }
+
+    fn id(&self) -> u32 {
+        let a = self.albedo;
+        material_id("metal", &[a.x(), a.y(), a.z(), self.fuzziness_factor])
+    }
 }
 
 #[derive(Copy, Clone)]
@@ -102,7 +216,7 @@ impl Material for Dielectric {
             // uses Schlick's approximation to estimate the probability that the ray reflects rather than refracts.
             let reflection_probability = schlick(cosine, self.refraction_index);
             // generates a random number to decide whether to reflect or refract based on reflection_probability.
-            if rand::thread_rng().gen::<f64>() >= reflection_probability {
+            if random_float() >= reflection_probability {
                 // ff the random number indicates refraction, creates a new refracted Ray.
                 let scattered = Ray::new(hit.p, refracted, ray.time());
                 // returns the refracted ray and the attenuation vector.
@@ -115,6 +229,632 @@ impl Material for Dielectric {
         let scattered = Ray::new(hit.p, reflected, ray.time());
         Some((scattered, attenuation))
     }
+
+    fn lobe(&self) -> Lobe {
+        Lobe::Specular
+    }
+
+    fn id(&self) -> u32 {
+        material_id("dielectric", &[self.refraction_index])
+    }
+}
+
+#[derive(Copy, Clone)]
+pub struct DiffuseLight {
+    emit: Vector,
+}
+
+// DiffuseLight is an emissive material that doesn't scatter light
+impl DiffuseLight {
+    pub fn new(emit: Vector) -> DiffuseLight {
+        DiffuseLight { emit }
+    }
+}
+
+impl Material for DiffuseLight {
+    fn scatter(&self, _ray: &Ray, _hit: &HitRecord) -> Option<(Ray, Vector)> {
+        None
+    }
+
+    fn emitted(&self, _hit: &HitRecord) -> Vector {
+        self.emit
+    }
+
+    fn id(&self) -> u32 {
+        let e = self.emit;
+        material_id("diffuse_light", &[e.x(), e.y(), e.z()])
+    }
+}
+
+/// The metallic-roughness model of glTF: a base colour that is the albedo
+/// of a diffuse surface under a thin clear coat for dielectrics, and the
+/// colour of the reflection for metals, with everything in between mixed.
+/// Each parameter is a factor, multiplied by its texture where there is
+/// one, and the base colour also by the surface's own colour.
+#[derive(Clone)]
+pub struct MetallicRoughness {
+    base_color: Vector,
+    metallic: f64,
+    roughness: f64,
+    emission: Vector,
+    base_color_texture: Option<Arc<Texture>>,
+    // roughness in the green channel and metallic in the blue one
+    metallic_roughness_texture: Option<Arc<Texture>>,
+    emission_texture: Option<Arc<Texture>>,
+}
+
+impl MetallicRoughness {
+    pub fn new(base_color: Vector, metallic: f64, roughness: f64) -> MetallicRoughness {
+        MetallicRoughness {
+            base_color,
+            metallic: metallic.clamp(0.0, 1.0),
+            roughness: roughness.clamp(0.0, 1.0),
+            emission: Vector::default(),
+            base_color_texture: None,
+            metallic_roughness_texture: None,
+            emission_texture: None,
+        }
+    }
+
+    pub fn with_emission(self, emission: Vector) -> MetallicRoughness {
+        MetallicRoughness { emission, ..self }
+    }
+
+    pub fn with_base_color_texture(self, texture: Arc<Texture>) -> MetallicRoughness {
+        MetallicRoughness {
+            base_color_texture: Some(texture),
+            ..self
+        }
+    }
+
+    pub fn with_metallic_roughness_texture(self, texture: Arc<Texture>) -> MetallicRoughness {
+        MetallicRoughness {
+            metallic_roughness_texture: Some(texture),
+            ..self
+        }
+    }
+
+    pub fn with_emission_texture(self, texture: Arc<Texture>) -> MetallicRoughness {
+        MetallicRoughness {
+            emission_texture: Some(texture),
+            ..self
+        }
+    }
+
+    fn base_color(&self, hit: &HitRecord) -> Vector {
+        let mut color = self.base_color;
+        if let Some(texture) = &self.base_color_texture {
+            color = color.hadamard_product(texture.sample(hit.u, hit.v));
+        }
+        if let Some(surface) = hit.color {
+            color = color.hadamard_product(surface);
+        }
+        color
+    }
+
+    fn metallic_roughness(&self, hit: &HitRecord) -> (f64, f64) {
+        match &self.metallic_roughness_texture {
+            Some(texture) => {
+                let texel = texture.sample(hit.u, hit.v);
+                (self.metallic * texel.z(), self.roughness * texel.y())
+            }
+            None => (self.metallic, self.roughness),
+        }
+    }
+}
+
+impl Material for MetallicRoughness {
+    fn scatter(&self, ray: &Ray, hit: &HitRecord) -> Option<(Ray, Vector)> {
+        let base_color = self.base_color(hit);
+        let (metallic, roughness) = self.metallic_roughness(hit);
+        // glTF surfaces may be seen from either side
+        let direction = ray.direction().unit();
+        let normal = if direction.dot(hit.normal) > 0.0 {
+            hit.normal.reverse()
+        } else {
+            hit.normal
+        };
+
+        // the reflection is the metal's, or the coat's with Schlick's
+        // approximation of a 1.5 refraction index
+        let reflectance = schlick(-direction.dot(normal), 1.5);
+        let (reflects, attenuation) = if random_float() < metallic {
+            (true, base_color)
+        } else if random_float() < reflectance {
+            (true, Vector::new(1.0, 1.0, 1.0))
+        } else {
+            (false, base_color)
+        };
+        let scattered = if reflects {
+            let reflected =
+                direction.reflect(normal) + roughness * roughness * random_in_unit_sphere();
+            if reflected.dot(normal) <= 0.0 {
+                return None;
+            }
+            reflected
+        } else {
+            let scattered = normal + random_unit_vector();
+            if scattered.is_near_zero() {
+                normal
+            } else {
+                scattered
+            }
+        };
+        Some((Ray::new(hit.p, scattered, ray.time()), attenuation))
+    }
+
+    fn emitted(&self, hit: &HitRecord) -> Vector {
+        match &self.emission_texture {
+            Some(texture) => self.emission.hadamard_product(texture.sample(hit.u, hit.v)),
+            None => self.emission,
+        }
+    }
+
+    fn albedo(&self, hit: &HitRecord) -> Vector {
+        self.base_color(hit)
+    }
+
+    fn lobe(&self) -> Lobe {
+        if self.metallic >= 0.5 {
+            Lobe::Specular
+        } else {
+            Lobe::Diffuse
+        }
+    }
+
+    fn id(&self) -> u32 {
+        let (c, e) = (self.base_color, self.emission);
+        material_id(
+            "metallic_roughness",
+            &[
+                c.x(),
+                c.y(),
+                c.z(),
+                self.metallic,
+                self.roughness,
+                e.x(),
+                e.y(),
+                e.z(),
+            ],
+        )
+    }
+}
+
+// hair scatters light along these paths: reflected (R), through the fibre
+// (TT), reflected once inside (TRT), and everything longer lumped together
+const HAIR_LOBES: usize = 3;
+
+/// What gives hair its colour.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum Pigment {
+    /// Absorption coefficient inside the fibre, per unit of its diameter.
+    Absorption(Vector),
+    /// Concentrations of eumelanin (black to brown, about 0.3 for blond, 1.3
+    /// for brown and 8 for black hair) and pheomelanin (red).
+    Melanin { eumelanin: f64, pheomelanin: f64 },
+    /// The colour the hair should look, for which the absorption is worked
+    /// out from the azimuthal roughness.
+    Color(Vector),
+}
+
+/// A fibre scattering light like real hair, after d'Eon et al. and Chiang
+/// et al., as in PBRT's `HairBSDF`. It's meant for cylinder curves, as it
+/// needs the tangent of the fibre and takes the offset across it from v.
+#[derive(Copy, Clone)]
+pub struct Hair {
+    pigment: Pigment,
+    // index of refraction of the fibre
+    eta: f64,
+    // longitudinal and azimuthal roughness, from 0 to 1
+    beta_m: f64,
+    beta_n: f64,
+    // tilt of the cuticle scales, in degrees
+    alpha: f64,
+}
+
+impl Hair {
+    pub fn new(pigment: Pigment) -> Hair {
+        Hair {
+            pigment,
+            eta: 1.55,
+            beta_m: 0.3,
+            beta_n: 0.3,
+            alpha: 2.0,
+        }
+    }
+
+    pub fn from_melanin(eumelanin: f64, pheomelanin: f64) -> Hair {
+        Hair::new(Pigment::Melanin {
+            eumelanin,
+            pheomelanin,
+        })
+    }
+
+    pub fn from_color(color: Vector) -> Hair {
+        Hair::new(Pigment::Color(color))
+    }
+
+    pub fn with_roughness(self, longitudinal: f64, azimuthal: f64) -> Hair {
+        Hair {
+            beta_m: longitudinal.clamp(0.0, 1.0),
+            beta_n: azimuthal.clamp(0.0, 1.0),
+            ..self
+        }
+    }
+
+    pub fn with_scale_angle(self, degrees: f64) -> Hair {
+        Hair {
+            alpha: degrees,
+            ..self
+        }
+    }
+
+    pub fn with_refraction_index(self, eta: f64) -> Hair {
+        Hair { eta, ..self }
+    }
+
+    fn sigma_a(&self) -> Vector {
+        match self.pigment {
+            Pigment::Absorption(sigma_a) => sigma_a,
+            Pigment::Melanin {
+                eumelanin,
+                pheomelanin,
+            } => {
+                eumelanin * Vector::new(0.419, 0.697, 1.37)
+                    + pheomelanin * Vector::new(0.187, 0.4, 1.05)
+            }
+            Pigment::Color(color) => {
+                let b = self.beta_n;
+                let d = 5.969 - 0.215 * b + 2.532 * b.powi(2) - 10.73 * b.powi(3)
+                    + 5.574 * b.powi(4)
+                    + 0.245 * b.powi(5);
+                let channel = |c: f64| (c.max(1e-4).ln() / d).powi(2);
+                Vector::new(channel(color.x()), channel(color.y()), channel(color.z()))
+            }
+        }
+    }
+}
+
+// the scattering function for one hit, in the fibre's frame: x along the
+// fibre, z toward the viewer and y across it
+struct HairBsdf {
+    h: f64,
+    gamma_o: f64,
+    eta: f64,
+    sigma_a: Vector,
+    // longitudinal variance and azimuthal logistic scale
+    v: [f64; HAIR_LOBES + 1],
+    s: f64,
+    // sin and cos of 2^k alpha
+    sin_2k_alpha: [f64; 3],
+    cos_2k_alpha: [f64; 3],
+}
+
+impl HairBsdf {
+    fn new(hair: &Hair, h: f64) -> HairBsdf {
+        let beta_m = hair.beta_m;
+        let beta_n = hair.beta_n;
+        let v0 = (0.726 * beta_m + 0.812 * beta_m.powi(2) + 3.7 * beta_m.powi(20)).powi(2);
+        let mut sin_2k_alpha = [0.0; 3];
+        let mut cos_2k_alpha = [0.0; 3];
+        sin_2k_alpha[0] = hair.alpha.to_radians().sin();
+        cos_2k_alpha[0] = safe_sqrt(1.0 - sin_2k_alpha[0].powi(2));
+        for i in 1..3 {
+            sin_2k_alpha[i] = 2.0 * cos_2k_alpha[i - 1] * sin_2k_alpha[i - 1];
+            cos_2k_alpha[i] = cos_2k_alpha[i - 1].powi(2) - sin_2k_alpha[i - 1].powi(2);
+        }
+        HairBsdf {
+            h,
+            gamma_o: h.clamp(-1.0, 1.0).asin(),
+            eta: hair.eta,
+            sigma_a: hair.sigma_a(),
+            v: [v0, 0.25 * v0, 4.0 * v0, 4.0 * v0],
+            s: (PI / 8.0).sqrt()
+                * (0.265 * beta_n + 1.194 * beta_n.powi(2) + 5.372 * beta_n.powi(22)),
+            sin_2k_alpha,
+            cos_2k_alpha,
+        }
+    }
+
+    // the outgoing angle tilted by the cuticle scales, for lobe p
+    fn tilted(&self, p: usize, sin_theta_o: f64, cos_theta_o: f64) -> (f64, f64) {
+        let (sin, cos) = match p {
+            0 => (
+                sin_theta_o * self.cos_2k_alpha[1] - cos_theta_o * self.sin_2k_alpha[1],
+                cos_theta_o * self.cos_2k_alpha[1] + sin_theta_o * self.sin_2k_alpha[1],
+            ),
+            1 => (
+                sin_theta_o * self.cos_2k_alpha[0] + cos_theta_o * self.sin_2k_alpha[0],
+                cos_theta_o * self.cos_2k_alpha[0] - sin_theta_o * self.sin_2k_alpha[0],
+            ),
+            2 => (
+                sin_theta_o * self.cos_2k_alpha[2] + cos_theta_o * self.sin_2k_alpha[2],
+                cos_theta_o * self.cos_2k_alpha[2] - sin_theta_o * self.sin_2k_alpha[2],
+            ),
+            _ => (sin_theta_o, cos_theta_o),
+        };
+        (sin, cos.abs())
+    }
+
+    // refracted angle inside the fibre and the transmittance across it
+    fn inside(&self, sin_theta_o: f64, cos_theta_o: f64) -> (f64, Vector) {
+        let sin_theta_t = sin_theta_o / self.eta;
+        let cos_theta_t = safe_sqrt(1.0 - sin_theta_t.powi(2));
+        let etap = (self.eta * self.eta - sin_theta_o.powi(2)).sqrt() / cos_theta_o;
+        let sin_gamma_t = self.h / etap;
+        let cos_gamma_t = safe_sqrt(1.0 - sin_gamma_t.powi(2));
+        let gamma_t = sin_gamma_t.clamp(-1.0, 1.0).asin();
+        let path = 2.0 * cos_gamma_t / cos_theta_t;
+        let a = self.sigma_a;
+        let transmittance = Vector::new(
+            (-a.x() * path).exp(),
+            (-a.y() * path).exp(),
+            (-a.z() * path).exp(),
+        );
+        (gamma_t, transmittance)
+    }
+
+    // how much of the light each lobe takes
+    fn attenuations(&self, cos_theta_o: f64, transmittance: Vector) -> [Vector; HAIR_LOBES + 1] {
+        let cos_gamma_o = safe_sqrt(1.0 - self.h * self.h);
+        let f = fresnel(cos_theta_o * cos_gamma_o, self.eta);
+        let mut ap = [Vector::default(); HAIR_LOBES + 1];
+        ap[0] = Vector::new(f, f, f);
+        ap[1] = (1.0 - f).powi(2) * transmittance;
+        for p in 2..HAIR_LOBES {
+            ap[p] = f * ap[p - 1].hadamard_product(transmittance);
+        }
+        let t = transmittance;
+        let rest = |a: f64, t: f64| a * f * t / (1.0 - t * f);
+        let last = ap[HAIR_LOBES - 1];
+        ap[HAIR_LOBES] = Vector::new(
+            rest(last.x(), t.x()),
+            rest(last.y(), t.y()),
+            rest(last.z(), t.z()),
+        );
+        ap
+    }
+
+    // the scattering function times the cosine, and the density `sample`
+    // picks wi with, for wo and wi in the fibre's frame
+    fn evaluate(&self, wo: Vector, wi: Vector) -> (Vector, f64) {
+        let sin_theta_o = wo.x();
+        let cos_theta_o = safe_sqrt(1.0 - sin_theta_o.powi(2));
+        let phi_o = wo.z().atan2(wo.y());
+        let sin_theta_i = wi.x();
+        let cos_theta_i = safe_sqrt(1.0 - sin_theta_i.powi(2));
+        let phi_i = wi.z().atan2(wi.y());
+        let (gamma_t, transmittance) = self.inside(sin_theta_o, cos_theta_o);
+        let ap = self.attenuations(cos_theta_o, transmittance);
+        let phi = phi_i - phi_o;
+
+        let mut sum = Vector::default();
+        let mut pdf = 0.0;
+        let weights = lobe_weights(&ap);
+        for p in 0..=HAIR_LOBES {
+            let (sin_theta_op, cos_theta_op) = self.tilted(p, sin_theta_o, cos_theta_o);
+            let m = longitudinal(
+                cos_theta_i,
+                cos_theta_op,
+                sin_theta_i,
+                sin_theta_op,
+                self.v[p],
+            );
+            let n = if p < HAIR_LOBES {
+                azimuthal(phi, p, self.s, self.gamma_o, gamma_t)
+            } else {
+                1.0 / (2.0 * PI)
+            };
+            sum = sum + (m * n) * ap[p];
+            pdf += m * n * weights[p];
+        }
+        (sum, pdf)
+    }
+
+    // samples an incoming direction for wo, weighted by the scattering
+    // function and the cosine over the density it was picked with
+    fn sample(&self, wo: Vector) -> Option<(Vector, Vector)> {
+        let sin_theta_o = wo.x();
+        let cos_theta_o = safe_sqrt(1.0 - sin_theta_o.powi(2));
+        let phi_o = wo.z().atan2(wo.y());
+        let (gamma_t, transmittance) = self.inside(sin_theta_o, cos_theta_o);
+        let weights = lobe_weights(&self.attenuations(cos_theta_o, transmittance));
+
+        let mut pick = random_float();
+        let mut p = 0;
+        while p < HAIR_LOBES && pick >= weights[p] {
+            pick -= weights[p];
+            p += 1;
+        }
+
+        let (sin_theta_op, cos_theta_op) = self.tilted(p, sin_theta_o, cos_theta_o);
+        let u = random_float().max(1e-5);
+        let cos_theta = 1.0 + self.v[p] * (u + (1.0 - u) * (-2.0 / self.v[p]).exp()).ln();
+        let sin_theta = safe_sqrt(1.0 - cos_theta.powi(2));
+        let cos_phi = (2.0 * PI * random_float()).cos();
+        let sin_theta_i = -cos_theta * sin_theta_op + sin_theta * cos_phi * cos_theta_op;
+        let cos_theta_i = safe_sqrt(1.0 - sin_theta_i.powi(2));
+
+        let dphi = if p < HAIR_LOBES {
+            phi_lobe(p, self.gamma_o, gamma_t) + sample_trimmed_logistic(random_float(), self.s)
+        } else {
+            2.0 * PI * random_float()
+        };
+        let phi_i = phi_o + dphi;
+        let wi = Vector::new(
+            sin_theta_i,
+            cos_theta_i * phi_i.cos(),
+            cos_theta_i * phi_i.sin(),
+        );
+
+        let (f, pdf) = self.evaluate(wo, wi);
+        if pdf <= 0.0 || !pdf.is_finite() {
+            return None;
+        }
+        Some((wi, f / pdf))
+    }
+}
+
+impl Material for Hair {
+    fn scatter(&self, ray: &Ray, hit: &HitRecord) -> Option<(Ray, Vector)> {
+        // the fibre's frame, falling back on any tangent for surfaces that
+        // don't have one
+        let wo = ray.direction().unit().reverse();
+        let mut x = hit.tangent - hit.tangent.dot(hit.normal) * hit.normal;
+        if x.is_near_zero() {
+            let helper = if hit.normal.x().abs() > 0.9 {
+                Vector::new(0.0, 1.0, 0.0)
+            } else {
+                Vector::new(1.0, 0.0, 0.0)
+            };
+            x = hit.normal.cross(helper);
+        }
+        let x = x.unit();
+        let mut z = wo - wo.dot(x) * x;
+        if z.is_near_zero() {
+            z = hit.normal - hit.normal.dot(x) * x;
+        }
+        let z = z.unit();
+        let y = z.cross(x);
+
+        let bsdf = HairBsdf::new(self, 2.0 * hit.v - 1.0);
+        let local = Vector::new(wo.dot(x), wo.dot(y), wo.dot(z));
+        let (wi, weight) = bsdf.sample(local)?;
+        let direction = wi.x() * x + wi.y() * y + wi.z() * z;
+        Some((Ray::new(hit.p, direction, ray.time()), weight))
+    }
+
+    fn albedo(&self, _hit: &HitRecord) -> Vector {
+        let a = self.sigma_a();
+        Vector::new((-a.x()).exp(), (-a.y()).exp(), (-a.z()).exp())
+    }
+
+    fn lobe(&self) -> Lobe {
+        Lobe::Specular
+    }
+
+    fn id(&self) -> u32 {
+        let a = self.sigma_a();
+        material_id(
+            "hair",
+            &[
+                a.x(),
+                a.y(),
+                a.z(),
+                self.eta,
+                self.beta_m,
+                self.beta_n,
+                self.alpha,
+            ],
+        )
+    }
+}
+
+fn safe_sqrt(x: f64) -> f64 {
+    x.max(0.0).sqrt()
+}
+
+// share of the sampled directions going to each lobe, by luminance
+fn lobe_weights(ap: &[Vector; HAIR_LOBES + 1]) -> [f64; HAIR_LOBES + 1] {
+    let luminance = |c: Vector| 0.212671 * c.x() + 0.715160 * c.y() + 0.072169 * c.z();
+    let total: f64 = ap.iter().map(|&a| luminance(a)).sum();
+    let mut weights = [0.0; HAIR_LOBES + 1];
+    for (w, &a) in weights.iter_mut().zip(ap) {
+        *w = if total > 0.0 {
+            luminance(a) / total
+        } else {
+            0.0
+        };
+    }
+    weights
+}
+
+// Fresnel reflectance of a dielectric, from outside
+fn fresnel(cos_theta_i: f64, eta: f64) -> f64 {
+    let cos_i = cos_theta_i.clamp(-1.0, 1.0).abs();
+    let sin_t = safe_sqrt(1.0 - cos_i * cos_i) / eta;
+    if sin_t >= 1.0 {
+        return 1.0;
+    }
+    let cos_t = safe_sqrt(1.0 - sin_t * sin_t);
+    let parallel = (eta * cos_i - cos_t) / (eta * cos_i + cos_t);
+    let perpendicular = (cos_i - eta * cos_t) / (cos_i + eta * cos_t);
+    0.5 * (parallel * parallel + perpendicular * perpendicular)
+}
+
+// modified Bessel function of the first kind, order 0
+fn bessel_i0(x: f64) -> f64 {
+    let mut value = 0.0;
+    let mut x2i = 1.0;
+    let mut factorial: f64 = 1.0;
+    let mut four_i = 1.0;
+    for i in 0..10 {
+        if i > 1 {
+            factorial *= i as f64;
+        }
+        value += x2i / (four_i * factorial * factorial);
+        x2i *= x * x;
+        four_i *= 4.0;
+    }
+    value
+}
+
+fn log_bessel_i0(x: f64) -> f64 {
+    if x > 12.0 {
+        x + 0.5 * (-(2.0 * PI).ln() + (1.0 / x).ln() + 1.0 / (8.0 * x))
+    } else {
+        bessel_i0(x).ln()
+    }
+}
+
+// longitudinal scattering, spread by the variance v
+fn longitudinal(
+    cos_theta_i: f64,
+    cos_theta_o: f64,
+    sin_theta_i: f64,
+    sin_theta_o: f64,
+    v: f64,
+) -> f64 {
+    let a = cos_theta_i * cos_theta_o / v;
+    let b = sin_theta_i * sin_theta_o / v;
+    if v <= 0.1 {
+        (log_bessel_i0(a) - b - 1.0 / v + LN_2 + (1.0 / (2.0 * v)).ln()).exp()
+    } else {
+        ((-b).exp() * bessel_i0(a)) / ((1.0 / v).sinh() * 2.0 * v)
+    }
+}
+
+// the azimuth lobe p leaves at
+fn phi_lobe(p: usize, gamma_o: f64, gamma_t: f64) -> f64 {
+    let p = p as f64;
+    2.0 * p * gamma_t - 2.0 * gamma_o + p * PI
+}
+
+fn logistic(x: f64, s: f64) -> f64 {
+    let x = x.abs();
+    (-x / s).exp() / (s * (1.0 + (-x / s).exp()).powi(2))
+}
+
+fn logistic_cdf(x: f64, s: f64) -> f64 {
+    1.0 / (1.0 + (-x / s).exp())
+}
+
+// azimuthal scattering of lobe p, a logistic around its azimuth cut to -pi..pi
+fn azimuthal(phi: f64, p: usize, s: f64, gamma_o: f64, gamma_t: f64) -> f64 {
+    let mut dphi = phi - phi_lobe(p, gamma_o, gamma_t);
+    while dphi > PI {
+        dphi -= 2.0 * PI;
+    }
+    while dphi < -PI {
+        dphi += 2.0 * PI;
+    }
+    logistic(dphi, s) / (logistic_cdf(PI, s) - logistic_cdf(-PI, s))
+}
+
+fn sample_trimmed_logistic(u: f64, s: f64) -> f64 {
+    let k = logistic_cdf(PI, s) - logistic_cdf(-PI, s);
+    let x = -s * (1.0 / (u * k + logistic_cdf(-PI, s)) - 1.0).ln();
+    x.clamp(-PI, PI)
 }
 
 //  Schlick's approximation for reflectance.
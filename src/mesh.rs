@@ -0,0 +1,192 @@
+//! Triangle meshes: the geometry as loaded from a file (`Mesh`), and that
+//! geometry with a material and a bounding volume hierarchy to be rendered
+//! (`TriangleMesh`).
+
+use crate::aabb::Aabb;
+use crate::bvh::Bvh;
+use crate::hitable::{HitRecord, Hitable};
+use crate::material::Material;
+use crate::ray::Ray;
+use crate::vector::Vector;
+use crate::{obj, ply, stl};
+use std::path::Path;
+
+/// Triangles indexing into per vertex data. Normals, texture coordinates and
+/// colours are optional: each is either empty or has one entry per position.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct Mesh {
+    pub positions: Vec<Vector>,
+    pub normals: Vec<Vector>,
+    pub uvs: Vec<(f64, f64)>,
+    pub colors: Vec<Vector>,
+    // counter-clockwise seen from outside
+    pub triangles: Vec<[usize; 3]>,
+}
+
+impl Mesh {
+    /// Loads an OBJ, PLY or STL file, going by its extension.
+    pub fn load(path: &Path) -> Result<Mesh, String> {
+        match path
+            .extension()
+            .and_then(|e| e.to_str())
+            .map(str::to_lowercase)
+        {
+            Some(e) if e == "obj" => obj::load(path),
+            Some(e) if e == "ply" => ply::load(path),
+            Some(e) if e == "stl" => stl::load(path),
+            _ => Err(format!(
+                "can't tell the format of mesh {}, expected an .obj, .ply or .stl file",
+                path.display()
+            )),
+        }
+    }
+
+    // whether the optional data and the indices fit the positions
+    pub fn check(&self) -> Result<(), String> {
+        let vertices = self.positions.len();
+        for (name, len) in [
+            ("normals", self.normals.len()),
+            ("texture coordinates", self.uvs.len()),
+            ("colours", self.colors.len()),
+        ] {
+            if len != 0 && len != vertices {
+                return Err(format!(
+                    "the mesh has {} {} for {} vertices",
+                    len, name, vertices
+                ));
+            }
+        }
+        for (i, triangle) in self.triangles.iter().enumerate() {
+            if let Some(index) = triangle.iter().find(|&&index| index >= vertices) {
+                return Err(format!(
+                    "triangle {} uses vertex {}, but there are only {}",
+                    i, index, vertices
+                ));
+            }
+        }
+        Ok(())
+    }
+
+    fn corners(&self, triangle: usize) -> [Vector; 3] {
+        self.triangles[triangle].map(|i| self.positions[i])
+    }
+}
+
+/// A mesh with one material.
+pub struct TriangleMesh<M: Material> {
+    mesh: Mesh,
+    bvh: Bvh,
+    material: M,
+}
+
+impl<M: Material> TriangleMesh<M> {
+    /// Panics if the mesh doesn't pass `Mesh::check`, which the loaders
+    /// already make sure of.
+    pub fn new(mesh: Mesh, material: M) -> Self {
+        if let Err(e) = mesh.check() {
+            panic!("invalid mesh: {}", e);
+        }
+        let boxes: Vec<Aabb> = (0..mesh.triangles.len())
+            .map(|i| {
+                let [a, b, c] = mesh.corners(i);
+                Aabb::new(a, a)
+                    .surrounding(&Aabb::new(b, b))
+                    .surrounding(&Aabb::new(c, c))
+                    // flat boxes still need some thickness to be hit
+                    .padded(1e-9)
+            })
+            .collect();
+        TriangleMesh {
+            bvh: Bvh::new(&boxes),
+            mesh,
+            material,
+        }
+    }
+
+    pub fn mesh(&self) -> &Mesh {
+        &self.mesh
+    }
+}
+
+impl<M: Material + Sync> Hitable for TriangleMesh<M> {
+    fn hit(&self, ray: &Ray, t_min: f64, t_max: f64) -> Option<HitRecord<'_>> {
+        let (nearest, (t, b1, b2)) = self.bvh.nearest_hit(ray, t_min, t_max, |i, t_max| {
+            let [a, b, c] = self.mesh.corners(i);
+            let found = intersect_triangle(ray, a, b, c, t_min, t_max)?;
+            Some((found.0, found))
+        })?;
+
+        let mesh = &self.mesh;
+        let [i0, i1, i2] = mesh.triangles[nearest];
+        let [a, b, c] = mesh.corners(nearest);
+        let b0 = 1.0 - b1 - b2;
+        let mix = |values: &[Vector]| b0 * values[i0] + b1 * values[i1] + b2 * values[i2];
+        let (edge1, edge2) = (b - a, c - a);
+        let normal = if mesh.normals.is_empty() {
+            edge1.cross(edge2).unit()
+        } else {
+            mix(&mesh.normals).unit()
+        };
+        let (u, v, tangent) = if mesh.uvs.is_empty() {
+            (b1, b2, edge1)
+        } else {
+            let [(u0, v0), (u1, v1), (u2, v2)] = [mesh.uvs[i0], mesh.uvs[i1], mesh.uvs[i2]];
+            let u = b0 * u0 + b1 * u1 + b2 * u2;
+            let v = b0 * v0 + b1 * v1 + b2 * v2;
+            // dp/du, from the two edges' changes in position and (u, v)
+            let (du1, dv1, du2, dv2) = (u1 - u0, v1 - v0, u2 - u0, v2 - v0);
+            let determinant = du1 * dv2 - dv1 * du2;
+            let tangent = if determinant.abs() < 1e-12 {
+                edge1
+            } else {
+                (dv2 * edge1 - dv1 * edge2) / determinant
+            };
+            (u, v, tangent)
+        };
+        let mut hit = HitRecord::new(t, ray.at(t), normal, &self.material)
+            .with_uv(u, v)
+            .with_tangent(tangent);
+        if !mesh.colors.is_empty() {
+            hit = hit.with_color(mix(&mesh.colors));
+        }
+        Some(hit)
+    }
+
+    fn bounding_box(&self, _time0: f64, _time1: f64) -> Option<Aabb> {
+        self.bvh.bounds()
+    }
+}
+
+/// Möller-Trumbore: the distance to triangle abc and the barycentric
+/// coordinates of b and c at the hit.
+pub fn intersect_triangle(
+    ray: &Ray,
+    a: Vector,
+    b: Vector,
+    c: Vector,
+    t_min: f64,
+    t_max: f64,
+) -> Option<(f64, f64, f64)> {
+    let (edge1, edge2) = (b - a, c - a);
+    let p = ray.direction().cross(edge2);
+    let determinant = edge1.dot(p);
+    if determinant == 0.0 {
+        return None;
+    }
+    let inverse = 1.0 / determinant;
+    let s = ray.origin() - a;
+    let b1 = s.dot(p) * inverse;
+    if !(0.0..=1.0).contains(&b1) {
+        return None;
+    }
+    let q = s.cross(edge1);
+    let b2 = ray.direction().dot(q) * inverse;
+    if b2 < 0.0 || b1 + b2 > 1.0 {
+        return None;
+    }
+    let t = edge2.dot(q) * inverse;
+    if t <= t_min || t >= t_max {
+        return None;
+    }
+    Some((t, b1, b2))
+}
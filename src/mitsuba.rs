@@ -0,0 +1,1267 @@
+//! A subset of Mitsuba scene files, the XML format many academic benchmark
+//! scenes come in: the perspective sensor with its film, reconstruction
+//! filter and sampler, the path tracer's maximum depth, sphere, rectangle,
+//! disk, cube, OBJ and PLY shapes, diffuse, conductor, dielectric, plastic
+//! and principled BSDFs, and area, constant, point, spot and directional
+//! emitters. Both Mitsuba 3's parameter names and Mitsuba 0.6's camelCase
+//! ones are understood. Anything else is skipped with a warning.
+//!
+//! Outside the scene is black unless a constant emitter says otherwise,
+//! rather than this renderer's sky.
+
+use crate::camera::Camera;
+use crate::cuboid::Cuboid;
+use crate::filter::{Filter, FilterKind};
+use crate::hitable::{Hitable, HitableList};
+use crate::light;
+use crate::material::{
+    Dielectric, DiffuseLight, Lambertian, Metal, MetallicRoughness, SharedMaterial,
+};
+use crate::mesh::{Mesh, TriangleMesh};
+use crate::quadric::Disk;
+use crate::ray::Ray;
+use crate::spectrum;
+use crate::sphere::Sphere;
+use crate::transform::{Matrix4, Transform, Transformed};
+use crate::vector::Vector;
+use crate::xml::Element;
+use std::collections::HashMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+
+/// What a Mitsuba file imports as.
+pub struct MitsubaScene {
+    pub objects: HitableList,
+    pub camera: Camera,
+    /// Image width and height, from the film.
+    pub resolution: (usize, usize),
+    /// Samples per pixel, from the sampler.
+    pub samples: u32,
+    /// The integrator's maximum path length, None where it's unlimited.
+    pub max_depth: Option<i64>,
+    /// The film's reconstruction filter, None where it isn't given.
+    pub filter: Option<Filter>,
+    /// The elements and properties that were skipped or approximated, each
+    /// with the file and line it's on.
+    pub warnings: Vec<String>,
+}
+
+pub fn load(path: &Path) -> Result<MitsubaScene, String> {
+    let mut importer = Importer::default();
+    importer.file(path)?;
+    Ok(importer.finish())
+}
+
+// elements giving a property of the object they are in, rather than a
+// nested object
+const PROPERTIES: [&str; 12] = [
+    "float",
+    "integer",
+    "boolean",
+    "string",
+    "rgb",
+    "srgb",
+    "spectrum",
+    "blackbody",
+    "point",
+    "vector",
+    "transform",
+    "texture",
+];
+
+// Mitsuba 0.6's camelCase names in Mitsuba 3's snake_case, like toWorld as
+// to_world and intIOR as int_ior
+fn snake_case(name: &str) -> String {
+    let mut snake = String::new();
+    let mut previous_lower = false;
+    for c in name.chars() {
+        if c.is_ascii_uppercase() && previous_lower {
+            snake.push('_');
+        }
+        previous_lower = c.is_ascii_lowercase() || c.is_ascii_digit();
+        snake.push(c.to_ascii_lowercase());
+    }
+    snake
+}
+
+// numbers separated by commas and whitespace
+fn numbers(text: &str) -> Option<Vec<f64>> {
+    text.split(|c: char| c == ',' || c.is_whitespace())
+        .filter(|w| !w.is_empty())
+        .map(|w| w.parse().ok())
+        .collect()
+}
+
+// the properties of an object, and which of them have been looked at
+struct Props<'e> {
+    element: &'e Element,
+    used: Vec<String>,
+}
+
+impl<'e> Props<'e> {
+    fn new(element: &'e Element) -> Props<'e> {
+        Props {
+            element,
+            used: Vec::new(),
+        }
+    }
+
+    fn kind(&self) -> &'e str {
+        self.element.attribute("type").unwrap_or("")
+    }
+
+    // the objects inside this one, like BSDFs in shapes
+    fn nested(&self) -> impl Iterator<Item = &'e Element> {
+        self.element
+            .children
+            .iter()
+            .filter(|c| !PROPERTIES.contains(&c.name.as_str()))
+    }
+
+    fn find(&mut self, name: &str) -> Option<&'e Element> {
+        self.used.push(name.to_string());
+        self.element.children.iter().find(|c| {
+            PROPERTIES.contains(&c.name.as_str())
+                && c.attribute("name").is_some_and(|n| snake_case(n) == name)
+        })
+    }
+
+    // marks properties as used when they make no difference here
+    fn ignore(&mut self, names: &[&str]) {
+        self.used.extend(names.iter().map(|n| n.to_string()));
+    }
+
+    // marks every property as used, for objects that are skipped
+    fn ignore_all(&mut self) {
+        let names: Vec<String> = self.unused().iter().map(|n| snake_case(n)).collect();
+        self.used.extend(names);
+    }
+
+    fn unused(&self) -> Vec<String> {
+        self.element
+            .children
+            .iter()
+            .filter(|c| PROPERTIES.contains(&c.name.as_str()))
+            .filter_map(|c| c.attribute("name"))
+            .filter(|n| !self.used.contains(&snake_case(n)))
+            .map(str::to_string)
+            .collect()
+    }
+}
+
+struct Sensor {
+    world_from_camera: Matrix4,
+    // the vertical field of view in degrees
+    fov: f64,
+    aperture_radius: f64,
+    focus_distance: Option<f64>,
+}
+
+struct Importer {
+    // the file being read, for messages, and substitutions from its
+    // default elements
+    file: PathBuf,
+    defaults: Vec<(String, String)>,
+    bsdfs: HashMap<String, SharedMaterial>,
+    sensor: Option<Sensor>,
+    // mirrors the scene when the sensor's transform does, as the camera
+    // can't
+    world: Matrix4,
+    resolution: (usize, usize),
+    samples: u32,
+    max_depth: Option<i64>,
+    filter: Option<Filter>,
+    environment: Vector,
+    // directional emitters, directions and irradiances, added once the
+    // scene's size is known
+    distant: Vec<(Vector, Vector)>,
+    objects: HitableList,
+    warnings: Vec<String>,
+}
+
+impl Default for Importer {
+    fn default() -> Importer {
+        Importer {
+            file: PathBuf::new(),
+            defaults: Vec::new(),
+            bsdfs: HashMap::new(),
+            sensor: None,
+            world: Matrix4::identity(),
+            // Mitsuba's defaults
+            resolution: (768, 576),
+            samples: 4,
+            max_depth: None,
+            filter: None,
+            environment: Vector::default(),
+            distant: Vec::new(),
+            objects: HitableList::default(),
+            warnings: Vec::new(),
+        }
+    }
+}
+
+impl Importer {
+    fn file(&mut self, path: &Path) -> Result<(), String> {
+        let text = fs::read_to_string(path)
+            .map_err(|e| format!("can't read Mitsuba file {}: {}", path.display(), e))?;
+        let root = Element::parse(&text).map_err(|e| format!("{}: {}", path.display(), e))?;
+        let including = std::mem::replace(&mut self.file, path.to_path_buf());
+        let result = self.scene(&root);
+        self.file = including;
+        result
+    }
+
+    fn at(&self, element: &Element, message: &str) -> String {
+        format!("{}:{}: {}", self.file.display(), element.line, message)
+    }
+
+    fn warn(&mut self, element: &Element, message: &str) {
+        let warning = self.at(element, message);
+        self.warnings.push(warning);
+    }
+
+    fn warn_unused(&mut self, props: &Props) {
+        let unused = props.unused();
+        if !unused.is_empty() {
+            self.warn(
+                props.element,
+                &format!(
+                    "{} {} properties {} are ignored",
+                    props.kind(),
+                    props.element.name,
+                    unused.join(", ")
+                ),
+            );
+        }
+    }
+
+    // an attribute, with $name replaced by the value of its default
+    fn attribute(&self, element: &Element, name: &str) -> Option<String> {
+        let mut value = element.attribute(name)?.to_string();
+        if value.contains('$') {
+            let mut defaults: Vec<&(String, String)> = self.defaults.iter().collect();
+            // longest first, so $spp_high isn't taken for $spp
+            defaults.sort_by_key(|(name, _)| std::cmp::Reverse(name.len()));
+            for (name, default) in defaults {
+                value = value.replace(&format!("${}", name), default);
+            }
+        }
+        Some(value)
+    }
+
+    fn required(&self, element: &Element, name: &str) -> Result<String, String> {
+        self.attribute(element, name).ok_or_else(|| {
+            self.at(
+                element,
+                &format!("<{}> needs a {} attribute", element.name, name),
+            )
+        })
+    }
+
+    fn scene(&mut self, root: &Element) -> Result<(), String> {
+        if root.name != "scene" {
+            return Err(self.at(root, "the root element should be <scene>"));
+        }
+        // the sensor first, as its transform decides whether the rest is
+        // mirrored
+        for element in &root.children {
+            match element.name.as_str() {
+                "default" => {
+                    let name = self.required(element, "name")?;
+                    let value = self.required(element, "value")?;
+                    if !self.defaults.iter().any(|(n, _)| *n == name) {
+                        self.defaults.push((name, value));
+                    }
+                }
+                "sensor" => self.sensor(element)?,
+                _ => {}
+            }
+        }
+        for element in &root.children {
+            match element.name.as_str() {
+                "default" | "sensor" => {}
+                "include" => {
+                    let filename = self.required(element, "filename")?;
+                    let directory = self.file.parent().unwrap_or_else(|| Path::new("."));
+                    self.file(&directory.join(filename))?;
+                }
+                "integrator" => self.integrator(element)?,
+                "bsdf" => {
+                    let bsdf = self.bsdf(element)?;
+                    match self.attribute(element, "id") {
+                        Some(id) => {
+                            self.bsdfs.insert(id, bsdf);
+                        }
+                        None => self.warn(element, "a bsdf without an id is never used"),
+                    }
+                }
+                "shape" => self.shape(element)?,
+                "emitter" => self.emitter(element)?,
+                _ => self.warn(element, &format!("<{}> is ignored", element.name)),
+            }
+        }
+        Ok(())
+    }
+
+    // Properties
+
+    fn value(&self, element: &Element) -> Result<String, String> {
+        self.required(element, "value")
+    }
+
+    fn float(&mut self, props: &mut Props, name: &str, default: f64) -> Result<f64, String> {
+        let Some(element) = props.find(name) else {
+            return Ok(default);
+        };
+        let value = self.value(element)?;
+        match (element.name.as_str(), value.trim().parse::<f64>()) {
+            ("float" | "integer", Ok(value)) => Ok(value),
+            _ => Err(self.at(element, &format!("{} should be a number", name))),
+        }
+    }
+
+    fn boolean(&mut self, props: &mut Props, name: &str, default: bool) -> Result<bool, String> {
+        let Some(element) = props.find(name) else {
+            return Ok(default);
+        };
+        match (element.name.as_str(), self.value(element)?.as_str()) {
+            ("boolean", "true") => Ok(true),
+            ("boolean", "false") => Ok(false),
+            _ => Err(self.at(element, &format!("{} should be true or false", name))),
+        }
+    }
+
+    fn string(&mut self, props: &mut Props, name: &str) -> Result<Option<String>, String> {
+        match props.find(name) {
+            None => Ok(None),
+            Some(element) if element.name == "string" => self.value(element).map(Some),
+            Some(element) => Err(self.at(element, &format!("{} should be a string", name))),
+        }
+    }
+
+    // a point or vector, as a value or x, y and z attributes
+    fn xyz(&self, element: &Element, default: f64) -> Result<Vector, String> {
+        if let Some(value) = self.attribute(element, "value") {
+            return match numbers(&value).as_deref() {
+                Some(&[v]) => Ok(Vector::new(v, v, v)),
+                Some(&[x, y, z]) => Ok(Vector::new(x, y, z)),
+                _ => Err(self.at(element, "expected 1 or 3 numbers")),
+            };
+        }
+        let mut xyz = [default; 3];
+        for (value, axis) in xyz.iter_mut().zip(["x", "y", "z"]) {
+            if let Some(text) = self.attribute(element, axis) {
+                *value = text
+                    .trim()
+                    .parse()
+                    .map_err(|_| self.at(element, &format!("{} should be a number", axis)))?;
+            }
+        }
+        Ok(Vector::new(xyz[0], xyz[1], xyz[2]))
+    }
+
+    fn point(&mut self, props: &mut Props, name: &str) -> Result<Option<Vector>, String> {
+        match props.find(name) {
+            None => Ok(None),
+            Some(element) => self.xyz(element, 0.0).map(Some),
+        }
+    }
+
+    // an rgb, srgb, spectrum, blackbody or float property as RGB, None where
+    // it's missing or a texture
+    fn color(&mut self, props: &mut Props, name: &str) -> Result<Option<Vector>, String> {
+        let Some(element) = props.find(name) else {
+            return Ok(None);
+        };
+        let invalid = || self.at(element, &format!("{} isn't a valid colour", name));
+        let color = match element.name.as_str() {
+            "rgb" | "float" => match numbers(&self.value(element)?).as_deref() {
+                Some(&[v]) => Vector::new(v, v, v),
+                Some(&[r, g, b]) => Vector::new(r, g, b),
+                _ => return Err(invalid()),
+            },
+            "srgb" => {
+                let value = self.value(element)?;
+                let channels = match value.strip_prefix('#') {
+                    Some(hex) if hex.len() == 6 => (0..3)
+                        .map(|i| u8::from_str_radix(&hex[2 * i..2 * i + 2], 16).ok())
+                        .map(|c| c.map(|c| c as f64 / 255.0))
+                        .collect::<Option<Vec<f64>>>(),
+                    Some(_) => None,
+                    None => numbers(&value),
+                };
+                let decode = |c: f64| {
+                    if c <= 0.04045 {
+                        c / 12.92
+                    } else {
+                        ((c + 0.055) / 1.055).powf(2.4)
+                    }
+                };
+                match channels.as_deref() {
+                    Some(&[r, g, b]) => Vector::new(decode(r), decode(g), decode(b)),
+                    _ => return Err(invalid()),
+                }
+            }
+            "spectrum" => {
+                if self.attribute(element, "filename").is_some() {
+                    self.warn(element, "spectra from files aren't supported");
+                    return Ok(None);
+                }
+                let value = self.value(element)?;
+                if let Some(&[v]) = numbers(&value).as_deref() {
+                    Vector::new(v, v, v)
+                } else {
+                    let samples = value
+                        .split(',')
+                        .map(|pair| {
+                            let (lambda, v) = pair.split_once(':')?;
+                            Some((lambda.trim().parse().ok()?, v.trim().parse().ok()?))
+                        })
+                        .collect::<Option<Vec<(f64, f64)>>>()
+                        .filter(|s| !s.is_empty())
+                        .ok_or_else(invalid)?;
+                    spectrum::from_samples(&samples)
+                }
+            }
+            "blackbody" => {
+                let temperature = self.required(element, "temperature")?;
+                let kelvin = temperature
+                    .trim()
+                    .trim_end_matches(['k', 'K'])
+                    .trim()
+                    .parse::<f64>()
+                    .ok()
+                    .filter(|&k| k > 0.0)
+                    .ok_or_else(invalid)?;
+                spectrum::blackbody(kelvin)
+            }
+            "texture" => {
+                self.warn(
+                    element,
+                    &format!("{} is a texture, which isn't supported", name),
+                );
+                return Ok(None);
+            }
+            _ => return Err(invalid()),
+        };
+        Ok(Some(color))
+    }
+
+    fn transform(&mut self, props: &mut Props, name: &str) -> Result<Matrix4, String> {
+        let Some(element) = props.find(name) else {
+            return Ok(Matrix4::identity());
+        };
+        let mut m = Matrix4::identity();
+        // each step goes after the ones before it
+        for step in &element.children {
+            let next = match step.name.as_str() {
+                "translate" => *Transform::translate(self.xyz(step, 0.0)?).matrix(),
+                "scale" => {
+                    let scale = self.xyz(step, 1.0)?;
+                    match Transform::new(*Transform::scale(scale).matrix()) {
+                        Some(t) => *t.matrix(),
+                        None => return Err(self.at(step, "scales can't be zero")),
+                    }
+                }
+                "rotate" => {
+                    let axis = self.xyz(step, 0.0)?;
+                    let angle = self
+                        .required(step, "angle")?
+                        .trim()
+                        .parse::<f64>()
+                        .map_err(|_| self.at(step, "angle should be a number"))?;
+                    if axis.length() < 1e-12 {
+                        return Err(self.at(step, "rotations need an axis"));
+                    }
+                    *Transform::rotate(axis, angle).matrix()
+                }
+                "matrix" => {
+                    let values = numbers(&self.value(step)?).unwrap_or_default();
+                    let mut m = Matrix4::identity();
+                    match values.len() {
+                        16 => {
+                            for (i, v) in values.iter().enumerate() {
+                                m.m[i / 4][i % 4] = *v;
+                            }
+                        }
+                        9 => {
+                            for (i, v) in values.iter().enumerate() {
+                                m.m[i / 3][i % 3] = *v;
+                            }
+                        }
+                        _ => return Err(self.at(step, "matrices need 9 or 16 numbers")),
+                    }
+                    m
+                }
+                "lookat" => self.look_at(step)?,
+                _ => {
+                    self.warn(step, &format!("<{}> transforms are ignored", step.name));
+                    continue;
+                }
+            };
+            m = next.mul(&m);
+        }
+        Ok(m)
+    }
+
+    // the camera's axes: +z towards the target, +y up and +x to the left
+    fn look_at(&self, element: &Element) -> Result<Matrix4, String> {
+        let vector = |name: &str| -> Result<Option<Vector>, String> {
+            match self.attribute(element, name) {
+                None => Ok(None),
+                Some(text) => match numbers(&text).as_deref() {
+                    Some(&[x, y, z]) => Ok(Some(Vector::new(x, y, z))),
+                    _ => Err(self.at(element, &format!("{} needs 3 numbers", name))),
+                },
+            }
+        };
+        let origin = vector("origin")?.ok_or_else(|| self.at(element, "lookat needs an origin"))?;
+        let target = vector("target")?.ok_or_else(|| self.at(element, "lookat needs a target"))?;
+        let dir = target - origin;
+        if dir.length() < 1e-12 {
+            return Err(self.at(element, "lookat's origin and target are the same"));
+        }
+        let dir = dir.unit();
+        let up = match vector("up")? {
+            Some(up) => up,
+            // any direction across the view
+            None if dir.y().abs() < 0.9 => Vector::new(0.0, 1.0, 0.0),
+            None => Vector::new(1.0, 0.0, 0.0),
+        };
+        let left = up.cross(dir);
+        if left.length() < 1e-9 {
+            return Err(self.at(element, "lookat's up is along the view direction"));
+        }
+        let left = left.unit();
+        let new_up = dir.cross(left);
+        let mut m = Matrix4::identity();
+        for (i, axis) in [left, new_up, dir, origin].iter().enumerate() {
+            m.m[0][i] = axis.x();
+            m.m[1][i] = axis.y();
+            m.m[2][i] = axis.z();
+        }
+        Ok(m)
+    }
+
+    // Objects
+
+    fn sensor(&mut self, element: &Element) -> Result<(), String> {
+        let mut props = Props::new(element);
+        let kind = props.kind();
+        if kind != "perspective" && kind != "thinlens" {
+            self.warn(
+                element,
+                &format!("{} sensors aren't supported, perspective is used", kind),
+            );
+        }
+        let world_from_camera = self.transform(&mut props, "to_world")?;
+
+        let mut film_size = self.resolution;
+        for nested in props.nested() {
+            match nested.name.as_str() {
+                "film" => film_size = self.film(nested)?,
+                "sampler" => {
+                    let mut sampler = Props::new(nested);
+                    let samples = self.float(&mut sampler, "sample_count", 4.0)?;
+                    if samples < 1.0 {
+                        return Err(self.at(nested, "sample_count must be at least 1"));
+                    }
+                    self.samples = samples as u32;
+                    sampler.ignore(&["seed"]);
+                    self.warn_unused(&sampler);
+                }
+                _ => self.warn(nested, &format!("<{}> in a sensor is ignored", nested.name)),
+            }
+        }
+        self.resolution = film_size;
+        let aspect = film_size.0 as f64 / film_size.1 as f64;
+
+        // fov across the axis it's given for, made vertical
+        let (tan_half, axis) = match self.string(&mut props, "focal_length")? {
+            Some(text) => {
+                let millimetres = text
+                    .trim()
+                    .trim_end_matches("mm")
+                    .parse::<f64>()
+                    .ok()
+                    .filter(|&f| f > 0.0)
+                    .ok_or_else(|| self.at(element, "focal_length should be like \"50mm\""))?;
+                // across the diagonal of 35 mm film
+                (
+                    36.0f64.hypot(24.0) / 2.0 / millimetres,
+                    String::from("diagonal"),
+                )
+            }
+            None => {
+                let fov = self.float(&mut props, "fov", 39.6)?;
+                if !(fov > 0.0 && fov < 180.0) {
+                    return Err(self.at(element, "fov must be in 0..180"));
+                }
+                let axis = self
+                    .string(&mut props, "fov_axis")?
+                    .unwrap_or_else(|| String::from("x"));
+                ((fov.to_radians() / 2.0).tan(), axis)
+            }
+        };
+        let vertical = match axis.as_str() {
+            "x" => tan_half / aspect,
+            "y" => tan_half,
+            "diagonal" => tan_half / aspect.hypot(1.0),
+            "smaller" if aspect >= 1.0 => tan_half,
+            "smaller" => tan_half / aspect,
+            "larger" if aspect >= 1.0 => tan_half / aspect,
+            "larger" => tan_half,
+            _ => return Err(self.at(element, &format!("unknown fov_axis {:?}", axis))),
+        };
+        let aperture_radius = self.float(&mut props, "aperture_radius", 0.0)?;
+        let focus_distance = match props.find("focus_distance") {
+            Some(_) => Some(self.float(&mut props, "focus_distance", 0.0)?),
+            None => None,
+        };
+        props.ignore(&["near_clip", "far_clip", "shutter_open", "shutter_close"]);
+        self.warn_unused(&props);
+
+        // a transform that mirrors would flip the image, which the camera
+        // can't do, so the scene is mirrored instead
+        let m = &world_from_camera.m;
+        let determinant = m[0][0] * (m[1][1] * m[2][2] - m[1][2] * m[2][1])
+            - m[0][1] * (m[1][0] * m[2][2] - m[1][2] * m[2][0])
+            + m[0][2] * (m[1][0] * m[2][1] - m[1][1] * m[2][0]);
+        self.world = if determinant < 0.0 {
+            *Transform::scale(Vector::new(-1.0, 1.0, 1.0)).matrix()
+        } else {
+            Matrix4::identity()
+        };
+        self.sensor = Some(Sensor {
+            world_from_camera: self.world.mul(&world_from_camera),
+            fov: 2.0 * vertical.atan().to_degrees(),
+            aperture_radius,
+            focus_distance,
+        });
+        Ok(())
+    }
+
+    fn film(&mut self, element: &Element) -> Result<(usize, usize), String> {
+        let mut props = Props::new(element);
+        let width = self.float(&mut props, "width", 768.0)?;
+        let height = self.float(&mut props, "height", 576.0)?;
+        if width < 1.0 || height < 1.0 {
+            return Err(self.at(element, "the film must be at least 1 by 1"));
+        }
+        for nested in props.nested() {
+            if nested.name == "rfilter" {
+                self.filter = self.rfilter(nested)?;
+            } else {
+                self.warn(nested, &format!("<{}> in a film is ignored", nested.name));
+            }
+        }
+        // where and how Mitsuba would write the image
+        props.ignore(&[
+            "file_format",
+            "pixel_format",
+            "component_format",
+            "banner",
+            "high_quality_edges",
+            "sample_border",
+        ]);
+        self.warn_unused(&props);
+        Ok((width as usize, height as usize))
+    }
+
+    fn rfilter(&mut self, element: &Element) -> Result<Option<Filter>, String> {
+        let mut props = Props::new(element);
+        let filter = match props.kind() {
+            "box" => Some(Filter::new(FilterKind::Box, 0.5)),
+            "tent" => Some(Filter::new(FilterKind::Tent, 1.0)),
+            "gaussian" => {
+                let sigma = self.float(&mut props, "stddev", 0.5)?;
+                Some(Filter::new(FilterKind::Gaussian { sigma }, 4.0 * sigma))
+            }
+            "mitchell" => {
+                let b = self.float(&mut props, "b", 1.0 / 3.0)?;
+                let c = self.float(&mut props, "c", 1.0 / 3.0)?;
+                Some(Filter::new(FilterKind::Mitchell { b, c }, 2.0))
+            }
+            "catmullrom" => Some(Filter::new(FilterKind::Mitchell { b: 0.0, c: 0.5 }, 2.0)),
+            "lanczos" => {
+                let lobes = self.float(&mut props, "lobes", 3.0)?;
+                Some(Filter::new(FilterKind::Lanczos { tau: lobes }, lobes))
+            }
+            kind => {
+                self.warn(element, &format!("{} filters aren't supported", kind));
+                None
+            }
+        };
+        self.warn_unused(&props);
+        Ok(filter)
+    }
+
+    fn integrator(&mut self, element: &Element) -> Result<(), String> {
+        let mut props = Props::new(element);
+        match props.kind() {
+            "path" | "volpath" | "volpathmis" | "prb" => {
+                let depth = self.float(&mut props, "max_depth", -1.0)?;
+                self.max_depth = (depth >= 0.0).then_some(depth as i64);
+                // settings that trade noise for speed
+                props.ignore(&["rr_depth", "strict_normals"]);
+            }
+            kind => {
+                self.warn(
+                    element,
+                    &format!(
+                        "{} integrators aren't supported, a path tracer is used",
+                        kind
+                    ),
+                );
+                props.ignore_all();
+            }
+        }
+        self.warn_unused(&props);
+        Ok(())
+    }
+
+    fn bsdf(&mut self, element: &Element) -> Result<SharedMaterial, String> {
+        let mut props = Props::new(element);
+        let kind = props.kind();
+        let grey = Vector::new(0.5, 0.5, 0.5);
+        let surface: SharedMaterial = match kind {
+            "diffuse" => {
+                let reflectance = self.color(&mut props, "reflectance")?.unwrap_or(grey);
+                Arc::new(Lambertian::new(reflectance))
+            }
+            "conductor" | "roughconductor" => {
+                let reflectance = self.conductor(&mut props)?;
+                let scale = self
+                    .color(&mut props, "specular_reflectance")?
+                    .unwrap_or(Vector::new(1.0, 1.0, 1.0));
+                let alpha = if kind == "roughconductor" {
+                    let alpha = self.float(&mut props, "alpha", 0.1)?;
+                    let u = self.float(&mut props, "alpha_u", alpha)?;
+                    let v = self.float(&mut props, "alpha_v", alpha)?;
+                    props.ignore(&["distribution", "sample_visible"]);
+                    (u + v) / 2.0
+                } else {
+                    0.0
+                };
+                Arc::new(Metal::new(
+                    reflectance.hadamard_product(scale),
+                    alpha.min(1.0),
+                ))
+            }
+            "dielectric" | "roughdielectric" | "thindielectric" => {
+                let interior = self.ior(&mut props, "int_ior", 1.5046)?;
+                let exterior = self.ior(&mut props, "ext_ior", 1.000277)?;
+                if kind == "roughdielectric" {
+                    self.warn(element, "rough dielectrics are rendered smooth");
+                    props.ignore(&["alpha", "alpha_u", "alpha_v", "distribution"]);
+                } else if kind == "thindielectric" {
+                    self.warn(element, "thin dielectrics are rendered as solid ones");
+                }
+                Arc::new(Dielectric::new(interior / exterior))
+            }
+            // a diffuse base under a clear coat, as in glTF's model
+            "plastic" | "roughplastic" => {
+                let base = self
+                    .color(&mut props, "diffuse_reflectance")?
+                    .unwrap_or(grey);
+                let alpha = if kind == "roughplastic" {
+                    props.ignore(&["distribution"]);
+                    self.float(&mut props, "alpha", 0.1)?
+                } else {
+                    0.0
+                };
+                props.ignore(&["int_ior", "ext_ior", "nonlinear", "specular_reflectance"]);
+                Arc::new(MetallicRoughness::new(base, 0.0, alpha.sqrt()))
+            }
+            "principled" => {
+                let base = self.color(&mut props, "base_color")?.unwrap_or(grey);
+                let metallic = self.float(&mut props, "metallic", 0.0)?;
+                let roughness = self.float(&mut props, "roughness", 0.5)?;
+                Arc::new(MetallicRoughness::new(base, metallic, roughness))
+            }
+            // both sides are the same anyway, and maps on top are dropped
+            "twosided" | "mask" | "bumpmap" | "normalmap" => {
+                if kind != "twosided" {
+                    self.warn(element, &format!("{} is ignored, its BSDF is used", kind));
+                    props.ignore(&["opacity", "normalmap"]);
+                    props.ignore_all();
+                }
+                let inner = props.nested().find(|e| e.name == "bsdf" || e.name == "ref");
+                match inner {
+                    Some(inner) if inner.name == "bsdf" => self.bsdf(inner)?,
+                    Some(inner) => self.reference(inner)?,
+                    None => return Err(self.at(element, &format!("{} needs a bsdf", kind))),
+                }
+            }
+            _ => {
+                self.warn(
+                    element,
+                    &format!("{} BSDFs aren't supported, diffuse grey is used", kind),
+                );
+                props.ignore_all();
+                Arc::new(Lambertian::new(grey))
+            }
+        };
+        self.warn_unused(&props);
+        Ok(surface)
+    }
+
+    // a conductor's reflectance at normal incidence, from its material or
+    // its eta and k
+    fn conductor(&mut self, props: &mut Props) -> Result<Vector, String> {
+        let eta = self.color(props, "eta")?;
+        let k = self.color(props, "k")?;
+        if let Some(eta) = eta {
+            let k = k.unwrap_or_default();
+            let f = |n: f64, k: f64| ((n - 1.0).powi(2) + k * k) / ((n + 1.0).powi(2) + k * k);
+            return Ok(Vector::new(
+                f(eta.x(), k.x()),
+                f(eta.y(), k.y()),
+                f(eta.z(), k.z()),
+            ));
+        }
+        let material = self
+            .string(props, "material")?
+            .unwrap_or_else(|| String::from("none"));
+        if material == "none" {
+            return Ok(Vector::new(1.0, 1.0, 1.0));
+        }
+        match Metal::reflectance(material.trim_end_matches("_palik")) {
+            Some(reflectance) => Ok(reflectance),
+            None => {
+                self.warn(
+                    props.element,
+                    &format!("unknown conductor {:?}, a perfect mirror is used", material),
+                );
+                Ok(Vector::new(1.0, 1.0, 1.0))
+            }
+        }
+    }
+
+    // a refraction index given as a number or by the name of the material
+    fn ior(&mut self, props: &mut Props, name: &str, default: f64) -> Result<f64, String> {
+        let Some(element) = props.find(name) else {
+            return Ok(default);
+        };
+        let value = self.value(element)?;
+        if let Ok(ior) = value.trim().parse::<f64>() {
+            return Ok(ior);
+        }
+        let ior = match value.trim() {
+            "vacuum" => 1.0,
+            "helium" => 1.000036,
+            "hydrogen" => 1.000132,
+            "air" => 1.000277,
+            "carbon dioxide" => 1.00045,
+            "water" => 1.333,
+            "acetone" => 1.36,
+            "ethanol" => 1.361,
+            "carbon tetrachloride" => 1.461,
+            "glycerol" => 1.4729,
+            "benzene" => 1.501,
+            "silicone oil" => 1.52045,
+            "bromine" => 1.661,
+            "water ice" => 1.31,
+            "fused quartz" => 1.458,
+            "pyrex" => 1.47,
+            "acrylic glass" => 1.49,
+            "polypropylene" => 1.49,
+            "bk7" => 1.5046,
+            "sodium chloride" => 1.544,
+            "amber" => 1.55,
+            "pet" => 1.575,
+            "diamond" => 2.419,
+            _ => return Err(self.at(element, &format!("unknown material {:?}", value))),
+        };
+        Ok(ior)
+    }
+
+    fn reference(&self, element: &Element) -> Result<SharedMaterial, String> {
+        let id = self.required(element, "id")?;
+        self.bsdfs
+            .get(&id)
+            .cloned()
+            .ok_or_else(|| self.at(element, &format!("there is no bsdf with id {:?}", id)))
+    }
+
+    fn shape(&mut self, element: &Element) -> Result<(), String> {
+        let mut props = Props::new(element);
+        let kind = props.kind();
+        let mut material: SharedMaterial = Arc::new(Lambertian::new(Vector::new(0.5, 0.5, 0.5)));
+        let mut radiance = None;
+        for nested in props.nested() {
+            match nested.name.as_str() {
+                "bsdf" => material = self.bsdf(nested)?,
+                "ref" => material = self.reference(nested)?,
+                "emitter" => {
+                    let mut emitter = Props::new(nested);
+                    if emitter.kind() != "area" {
+                        self.warn(nested, "only area emitters can be attached to shapes");
+                        continue;
+                    }
+                    radiance = Some(
+                        self.color(&mut emitter, "radiance")?
+                            .unwrap_or(Vector::new(1.0, 1.0, 1.0)),
+                    );
+                    self.warn_unused(&emitter);
+                }
+                _ => self.warn(nested, &format!("<{}> in a shape is ignored", nested.name)),
+            }
+        }
+        // both sides of a shape glow, not only the front
+        if let Some(radiance) = radiance {
+            material = Arc::new(DiffuseLight::new(radiance));
+        }
+        let local = self.transform(&mut props, "to_world")?;
+        let to_world = self.world.mul(&local);
+        let Some(transform) = Transform::new(to_world) else {
+            self.warn(
+                element,
+                "a shape whose transform can't be inverted is skipped",
+            );
+            return Ok(());
+        };
+        // these only change which side area lights and textures are on
+        props.ignore(&["flip_normals", "flip_tex_coords"]);
+
+        match kind {
+            "sphere" => {
+                let center = self.point(&mut props, "center")?.unwrap_or_default();
+                let radius = self.float(&mut props, "radius", 1.0)?;
+                self.objects.push(Transformed::new(
+                    Sphere::new(center, radius, material),
+                    transform,
+                ));
+            }
+            "rectangle" => {
+                let corners = [(-1.0, -1.0), (1.0, -1.0), (1.0, 1.0), (-1.0, 1.0)];
+                let mesh = Mesh {
+                    positions: corners
+                        .iter()
+                        .map(|&(x, y)| Vector::new(x, y, 0.0))
+                        .collect(),
+                    uvs: corners
+                        .iter()
+                        .map(|&(x, y)| ((x + 1.0) / 2.0, (y + 1.0) / 2.0))
+                        .collect(),
+                    triangles: vec![[0, 1, 2], [0, 2, 3]],
+                    ..Mesh::default()
+                };
+                self.objects.push(Transformed::new(
+                    TriangleMesh::new(mesh, material),
+                    transform,
+                ));
+            }
+            "cube" => {
+                let corner = Vector::new(1.0, 1.0, 1.0);
+                self.objects.push(Transformed::new(
+                    Cuboid::new(corner.reverse(), corner, material),
+                    transform,
+                ));
+            }
+            // the renderer's disks face +y, Mitsuba's +z
+            "disk" => self.objects.push(Transformed::new(
+                Disk::new(1.0, material),
+                Transform::rotate_x(90.0).then(&transform),
+            )),
+            "obj" | "ply" => {
+                let filename = self
+                    .string(&mut props, "filename")?
+                    .ok_or_else(|| self.at(element, &format!("{} shapes need a filename", kind)))?;
+                let directory = self.file.parent().unwrap_or_else(|| Path::new("."));
+                let mut mesh = Mesh::load(&directory.join(filename))?;
+                if self.boolean(&mut props, "face_normals", false)? {
+                    mesh.normals.clear();
+                }
+                self.objects.push(Transformed::new(
+                    TriangleMesh::new(mesh, material),
+                    transform,
+                ));
+            }
+            _ => {
+                self.warn(element, &format!("{} shapes aren't supported", kind));
+                props.ignore_all();
+            }
+        }
+        self.warn_unused(&props);
+        Ok(())
+    }
+
+    fn emitter(&mut self, element: &Element) -> Result<(), String> {
+        let mut props = Props::new(element);
+        let white = Vector::new(1.0, 1.0, 1.0);
+        let local = self.transform(&mut props, "to_world")?;
+        let to_world = self.world.mul(&local);
+        let Some(transform) = Transform::new(to_world) else {
+            return Err(self.at(element, "the emitter's transform can't be inverted"));
+        };
+        match props.kind() {
+            "constant" => {
+                let radiance = self.color(&mut props, "radiance")?.unwrap_or(white);
+                self.environment = self.environment + radiance;
+            }
+            "envmap" => {
+                self.warn(
+                    element,
+                    "environment maps aren't supported, the light is uniform",
+                );
+                let scale = self.float(&mut props, "scale", 1.0)?;
+                props.ignore(&["filename"]);
+                self.environment = self.environment + scale * white;
+            }
+            "point" => {
+                let intensity = self.color(&mut props, "intensity")?.unwrap_or(white);
+                let position = match self.point(&mut props, "position")? {
+                    Some(position) => {
+                        Transform::new(self.world).map_or(position, |world| world.point(position))
+                    }
+                    None => transform.point(Vector::default()),
+                };
+                self.objects.push(light::point_light(position, intensity));
+            }
+            "spot" => {
+                let intensity = self.color(&mut props, "intensity")?.unwrap_or(white);
+                let cutoff = self.float(&mut props, "cutoff_angle", 20.0)?;
+                let beam = self.float(&mut props, "beam_width", cutoff * 0.75)?;
+                self.objects.push(light::spot_light(
+                    transform.point(Vector::default()),
+                    transform.vector(Vector::new(0.0, 0.0, 1.0)),
+                    intensity,
+                    beam.to_radians().cos(),
+                    cutoff.to_radians().cos(),
+                ));
+            }
+            "directional" => {
+                let irradiance = self.color(&mut props, "irradiance")?.unwrap_or(white);
+                let direction = match self.point(&mut props, "direction")? {
+                    Some(direction) => Transform::new(self.world)
+                        .map_or(direction, |world| world.vector(direction)),
+                    None => transform.vector(Vector::new(0.0, 0.0, 1.0)),
+                };
+                if direction.length() < 1e-12 {
+                    return Err(self.at(element, "directional emitters need a direction"));
+                }
+                self.distant.push((direction, irradiance));
+            }
+            "area" => self.warn(element, "area emitters only work inside shapes"),
+            kind => {
+                self.warn(element, &format!("{} emitters aren't supported", kind));
+                props.ignore_all();
+            }
+        }
+        self.warn_unused(&props);
+        Ok(())
+    }
+
+    fn finish(mut self) -> MitsubaScene {
+        let (width, height) = self.resolution;
+        let aspect_ratio = width as f64 / height as f64;
+        // Mitsuba's default sensor is at the origin looking down +z
+        let sensor = self.sensor.take().unwrap_or(Sensor {
+            world_from_camera: Matrix4::identity(),
+            fov: 2.0
+                * ((39.6f64.to_radians() / 2.0).tan() / aspect_ratio)
+                    .atan()
+                    .to_degrees(),
+            aperture_radius: 0.0,
+            focus_distance: None,
+        });
+        let to_world = Transform::new(sensor.world_from_camera).unwrap_or_else(Transform::identity);
+        let eye = to_world.point(Vector::default());
+        let forward = to_world.vector(Vector::new(0.0, 0.0, 1.0)).unit();
+        let up = to_world.vector(Vector::new(0.0, 1.0, 0.0)).unit();
+        let focus = sensor.focus_distance.unwrap_or_else(|| {
+            self.objects
+                .hit(&Ray::new(eye, forward, 0.0), 1e-3, f64::MAX)
+                .map_or(10.0, |hit| hit.t)
+        });
+        let camera = Camera::new(
+            eye,
+            eye + forward,
+            up,
+            sensor.fov,
+            aspect_ratio,
+            2.0 * sensor.aperture_radius,
+            focus,
+            0.0,
+            1.0,
+        );
+
+        let bounds = self.objects.bounding_box(0.0, 1.0);
+        for (direction, irradiance) in std::mem::take(&mut self.distant) {
+            self.objects
+                .push(light::distant_light(direction, irradiance, bounds));
+        }
+        self.objects
+            .push(light::environment(self.environment, bounds, eye));
+
+        MitsubaScene {
+            objects: self.objects,
+            camera,
+            resolution: self.resolution,
+            samples: self.samples,
+            max_depth: self.max_depth,
+            filter: self.filter,
+            warnings: self.warnings,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // imports a scene given as text, as if it were read from scene.xml
+    fn import(text: &str) -> Result<Importer, String> {
+        let mut importer = Importer {
+            file: PathBuf::from("scene.xml"),
+            ..Importer::default()
+        };
+        importer.scene(&Element::parse(text)?)?;
+        Ok(importer)
+    }
+
+    // the albedo of the first shape hit along +z from (x, 0, -10), short of
+    // the environment light around the scene
+    fn albedo_at(scene: &MitsubaScene, x: f64) -> Option<Vector> {
+        let ray = Ray::new(Vector::new(x, 0.0, -10.0), Vector::new(0.0, 0.0, 1.0), 0.0);
+        let hit = scene.objects.hit(&ray, 0.001, 20.0)?;
+        Some(hit.material.albedo(&hit))
+    }
+
+    #[test]
+    fn uses_named_bsdfs_where_they_are_referenced() {
+        let importer = import(
+            r#"<scene version="3.0.0">
+                <bsdf type="diffuse" id="red">
+                    <rgb name="reflectance" value="0.8, 0.1, 0.1"/>
+                </bsdf>
+                <bsdf type="twosided" id="both sides">
+                    <ref id="red"/>
+                </bsdf>
+                <bsdf type="conductor"/>
+                <shape type="sphere">
+                    <point name="center" x="-3"/>
+                    <ref id="red"/>
+                </shape>
+                <shape type="sphere">
+                    <point name="center" x="3"/>
+                    <ref id="both sides"/>
+                </shape>
+                <shape type="sphere"/>
+            </scene>"#,
+        )
+        .unwrap();
+        assert_eq!(
+            importer.warnings,
+            ["scene.xml:8: a bsdf without an id is never used"]
+        );
+        // the scene is mirrored in x only when the sensor is
+        let scene = importer.finish();
+        let red = Vector::new(0.8, 0.1, 0.1);
+        assert_eq!(albedo_at(&scene, -3.0), Some(red));
+        assert_eq!(albedo_at(&scene, 3.0), Some(red));
+        assert_eq!(albedo_at(&scene, 0.0), Some(Vector::new(0.5, 0.5, 0.5)));
+
+        let error = import(
+            r#"<scene>
+                <shape type="sphere"><ref id="blue"/></shape>
+                <bsdf type="diffuse" id="blue"/>
+            </scene>"#,
+        )
+        .err()
+        .unwrap();
+        assert_eq!(error, "scene.xml:2: there is no bsdf with id \"blue\"");
+        let error = import(r#"<scene><bsdf type="twosided" id="empty"/></scene>"#)
+            .err()
+            .unwrap();
+        assert_eq!(error, "scene.xml:1: twosided needs a bsdf");
+    }
+
+    #[test]
+    fn substitutes_defaults() {
+        let importer = import(
+            r#"<scene version="3.0.0">
+                <default name="spp" value="16"/>
+                <default name="spp" value="1024"/>
+                <default name="res" value="100"/>
+                <default name="resy" value="50"/>
+                <sensor type="perspective">
+                    <sampler type="independent">
+                        <integer name="sample_count" value="$spp"/>
+                    </sampler>
+                    <film type="hdrfilm">
+                        <integer name="width" value="$res"/>
+                        <integer name="height" value="$resy"/>
+                    </film>
+                </sensor>
+            </scene>"#,
+        )
+        .unwrap();
+        // the first of a repeated default is kept, and $resy isn't $res
+        // followed by a y
+        assert_eq!((importer.samples, importer.resolution), (16, (100, 50)));
+        let error = import(r#"<scene><default name="spp"/></scene>"#)
+            .err()
+            .unwrap();
+        assert_eq!(error, "scene.xml:1: <default> needs a value attribute");
+    }
+
+    #[test]
+    fn falls_back_on_mitsubas_default_values() {
+        let importer = import(
+            r#"<scene version="0.6.0">
+                <integrator type="path"/>
+                <shape type="sphere"/>
+            </scene>"#,
+        )
+        .unwrap();
+        assert!(importer.warnings.is_empty(), "{:?}", importer.warnings);
+        let scene = importer.finish();
+        assert_eq!((scene.resolution, scene.samples), ((768, 576), 4));
+        assert_eq!(scene.max_depth, None);
+        assert!(scene.filter.is_none());
+        // a unit sphere at the origin
+        let ray = Ray::new(
+            Vector::new(0.0, 0.0, -10.0),
+            Vector::new(0.0, 0.0, 1.0),
+            0.0,
+        );
+        assert_eq!(
+            scene.objects.hit(&ray, 0.001, 20.0).map(|hit| hit.t),
+            Some(9.0)
+        );
+    }
+
+    #[test]
+    fn reads_camel_case_names_and_warns_about_the_rest() {
+        let importer = import(
+            r#"<scene version="0.6.0">
+                <integrator type="path">
+                    <integer name="maxDepth" value="5"/>
+                    <integer name="rrDepth" value="3"/>
+                </integrator>
+                <bsdf type="diffuse" id="grey">
+                    <float name="roughness" value="0.5"/>
+                </bsdf>
+                <medium type="homogeneous"/>
+            </scene>"#,
+        )
+        .unwrap();
+        assert_eq!(importer.max_depth, Some(5));
+        assert_eq!(
+            importer.warnings,
+            [
+                "scene.xml:6: diffuse bsdf properties roughness are ignored",
+                "scene.xml:9: <medium> is ignored"
+            ]
+        );
+        let error = import(
+            r#"<scene><integrator type="path"><integer name="maxDepth" value="deep"/></integrator></scene>"#,
+        )
+        .err()
+        .unwrap();
+        assert_eq!(error, "scene.xml:1: max_depth should be a number");
+    }
+}
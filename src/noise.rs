@@ -0,0 +1,84 @@
+//! Gradient noise and fractal sums of it, for terrain and other procedural
+//! shapes. The noise doesn't use the random generator: the same point and
+//! seed always give the same value, and different seeds different fields.
+
+use crate::tracer::mix_seed;
+use crate::vector::Vector;
+
+// the edges of a cube, Perlin's gradient directions
+const GRADIENTS: [(f64, f64, f64); 12] = [
+    (1.0, 1.0, 0.0),
+    (-1.0, 1.0, 0.0),
+    (1.0, -1.0, 0.0),
+    (-1.0, -1.0, 0.0),
+    (1.0, 0.0, 1.0),
+    (-1.0, 0.0, 1.0),
+    (1.0, 0.0, -1.0),
+    (-1.0, 0.0, -1.0),
+    (0.0, 1.0, 1.0),
+    (0.0, -1.0, 1.0),
+    (0.0, 1.0, -1.0),
+    (0.0, -1.0, -1.0),
+];
+
+/// Perlin's improved noise: smooth, roughly from -1 to 1, and 0 on the
+/// integer lattice.
+pub fn perlin(p: Vector, seed: u64) -> f64 {
+    let (xf, yf, zf) = (p.x().floor(), p.y().floor(), p.z().floor());
+    let (x, y, z) = (xf as i64, yf as i64, zf as i64);
+    let (dx, dy, dz) = (p.x() - xf, p.y() - yf, p.z() - zf);
+
+    // the gradient at a lattice corner dotted with the offset to p
+    let corner = |i: i64, j: i64, k: i64| {
+        let hash = mix_seed(
+            mix_seed(mix_seed(seed, (x + i) as u64), (y + j) as u64),
+            (z + k) as u64,
+        );
+        let (gx, gy, gz) = GRADIENTS[(hash % 12) as usize];
+        gx * (dx - i as f64) + gy * (dy - j as f64) + gz * (dz - k as f64)
+    };
+    let lerp = |t: f64, a: f64, b: f64| a + t * (b - a);
+    let (u, v, w) = (fade(dx), fade(dy), fade(dz));
+    lerp(
+        w,
+        lerp(
+            v,
+            lerp(u, corner(0, 0, 0), corner(1, 0, 0)),
+            lerp(u, corner(0, 1, 0), corner(1, 1, 0)),
+        ),
+        lerp(
+            v,
+            lerp(u, corner(0, 0, 1), corner(1, 0, 1)),
+            lerp(u, corner(0, 1, 1), corner(1, 1, 1)),
+        ),
+    )
+}
+
+/// Fractional Brownian motion: `octaves` layers of noise, each at twice the
+/// frequency and half the amplitude of the one before, scaled back to about
+/// -1 to 1. Gives rolling hills.
+pub fn fbm(p: Vector, seed: u64, octaves: u32) -> f64 {
+    octave_sum(p, seed, octaves, |n| n)
+}
+
+/// Like `fbm`, but folding each layer so that its zero crossings become
+/// sharp crests, from 0 to about 1. Gives mountain ridges.
+pub fn ridged(p: Vector, seed: u64, octaves: u32) -> f64 {
+    octave_sum(p, seed, octaves, |n| 1.0 - n.abs())
+}
+
+fn octave_sum(p: Vector, seed: u64, octaves: u32, shape: impl Fn(f64) -> f64) -> f64 {
+    let (mut sum, mut total, mut amplitude, mut frequency) = (0.0, 0.0, 1.0, 1.0);
+    for octave in 0..octaves.max(1) {
+        sum += amplitude * shape(perlin(frequency * p, mix_seed(seed, octave as u64)));
+        total += amplitude;
+        amplitude *= 0.5;
+        frequency *= 2.0;
+    }
+    sum / total
+}
+
+// 6t^5 - 15t^4 + 10t^3, flat at both ends so the noise has smooth derivatives
+fn fade(t: f64) -> f64 {
+    t * t * t * (t * (t * 6.0 - 15.0) + 10.0)
+}
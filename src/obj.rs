@@ -0,0 +1,243 @@
+//! Wavefront OBJ meshes: positions (v, optionally followed by a colour),
+//! texture coordinates (vt), normals (vn) and polygonal faces (f) indexing
+//! them, split into triangles. OBJ indexes each separately, so every
+//! distinct combination becomes a vertex of the mesh. Objects, groups,
+//! smoothing groups, lines and materials are skipped: the file is one mesh.
+
+use crate::mesh::Mesh;
+use crate::vector::Vector;
+use std::collections::HashMap;
+use std::fs;
+use std::path::Path;
+
+pub fn load(path: &Path) -> Result<Mesh, String> {
+    let bytes =
+        fs::read(path).map_err(|e| format!("can't read OBJ file {}: {}", path.display(), e))?;
+    parse(&String::from_utf8_lossy(&bytes)).map_err(|e| format!("{}: {}", path.display(), e))
+}
+
+// a face corner: indices of its position, texture coordinates and normal
+type Corner = (usize, Option<usize>, Option<usize>);
+
+pub fn parse(text: &str) -> Result<Mesh, String> {
+    let mut positions = Vec::new();
+    let mut colors = Vec::new();
+    let mut uvs = Vec::new();
+    let mut normals = Vec::new();
+    let mut faces: Vec<Vec<Corner>> = Vec::new();
+
+    for (number, line) in text.lines().enumerate() {
+        let error =
+            |message: &str| format!("line {}: {} in {:?}", number + 1, message, line.trim());
+        let mut words = line.split_whitespace();
+        let Some(keyword) = words.next() else {
+            continue;
+        };
+        let words: Vec<&str> = words.collect();
+        let numbers = |count: usize| {
+            words
+                .iter()
+                .take(count)
+                .map(|w| w.parse::<f64>())
+                .collect::<Result<Vec<f64>, _>>()
+                .ok()
+                .filter(|n| n.len() == count)
+                .ok_or_else(|| error("invalid number"))
+        };
+        match keyword {
+            "v" => {
+                let v = numbers(3)?;
+                positions.push(Vector::new(v[0], v[1], v[2]));
+                // a colour after the position, which some tools write
+                if words.len() >= 6 {
+                    let c = numbers(6)?;
+                    colors.push(Vector::new(c[3], c[4], c[5]));
+                }
+            }
+            "vt" => {
+                // the third coordinate, for 3D textures, isn't used
+                let t = numbers(if words.len() == 1 { 1 } else { 2 })?;
+                uvs.push((t[0], t.get(1).copied().unwrap_or(0.0)));
+            }
+            "vn" => {
+                let n = numbers(3)?;
+                normals.push(Vector::new(n[0], n[1], n[2]));
+            }
+            "f" => {
+                if words.len() < 3 {
+                    return Err(error("faces need at least 3 corners"));
+                }
+                let corner = |word: &str| -> Result<Corner, String> {
+                    let mut parts = word.split('/');
+                    let mut index = |count: usize, name: &str| match parts.next() {
+                        None | Some("") => Ok(None),
+                        Some(part) => resolve(part, count)
+                            .map(Some)
+                            .ok_or_else(|| error(&format!("there is no {} {}", name, part))),
+                    };
+                    let position = index(positions.len(), "position")?
+                        .ok_or_else(|| error("a corner has no position"))?;
+                    let uv = index(uvs.len(), "texture coordinate")?;
+                    let normal = index(normals.len(), "normal")?;
+                    Ok((position, uv, normal))
+                };
+                faces.push(words.iter().map(|w| corner(w)).collect::<Result<_, _>>()?);
+            }
+            // materials, grouping and smoothing, points, lines and curves
+            "mtllib" | "usemtl" | "o" | "g" | "s" | "p" | "l" | "vp" | "cstype" | "deg"
+            | "curv" | "curv2" | "surf" | "parm" | "end" => {}
+            _ if keyword.starts_with('#') => {}
+            _ => return Err(error("unexpected line")),
+        }
+    }
+    if !colors.is_empty() && colors.len() != positions.len() {
+        return Err(String::from("some positions have a colour and some don't"));
+    }
+
+    // texture coordinates and normals are kept only if every corner has them
+    let corners = || faces.iter().flatten();
+    let with_uvs = corners().all(|c| c.1.is_some());
+    let with_normals = corners().all(|c| c.2.is_some());
+    let mut mesh = Mesh::default();
+    let mut vertices: HashMap<Corner, usize> = HashMap::new();
+    for face in &faces {
+        let mut indices = Vec::with_capacity(face.len());
+        for &(p, t, n) in face {
+            let key = (p, t.filter(|_| with_uvs), n.filter(|_| with_normals));
+            let index = *vertices.entry(key).or_insert_with(|| {
+                mesh.positions.push(positions[p]);
+                if let Some(t) = key.1 {
+                    mesh.uvs.push(uvs[t]);
+                }
+                if let Some(n) = key.2 {
+                    mesh.normals.push(normals[n]);
+                }
+                if !colors.is_empty() {
+                    mesh.colors.push(colors[p]);
+                }
+                mesh.positions.len() - 1
+            });
+            indices.push(index);
+        }
+        for i in 1..indices.len() - 1 {
+            mesh.triangles
+                .push([indices[0], indices[i], indices[i + 1]]);
+        }
+    }
+    if mesh.triangles.is_empty() {
+        return Err(String::from("there are no faces"));
+    }
+    Ok(mesh)
+}
+
+// a 1-based index, or a negative one counting back from the last of `count`
+fn resolve(text: &str, count: usize) -> Option<usize> {
+    let index: i64 = text.parse().ok()?;
+    let index = if index < 0 {
+        count as i64 + index
+    } else {
+        index - 1
+    };
+    (0..count as i64).contains(&index).then_some(index as usize)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SQUARE: &str = "# a unit square
+mtllib square.mtl
+o square
+v 0 0 0
+v 1 0 0
+v 1 1 0
+v 0 1 0
+vt 0 0
+vt 1 0
+vt 1 1
+vt 0 1
+vn 0 0 1
+g front
+usemtl grey
+s off
+f 1/1/1 2/2/1 3/3/1 4/4/1
+";
+
+    #[test]
+    fn splits_polygons_into_triangles() {
+        let mesh = parse(SQUARE).unwrap();
+        assert_eq!(mesh.positions[2], Vector::new(1.0, 1.0, 0.0));
+        assert_eq!(mesh.uvs[3], (0.0, 1.0));
+        assert_eq!(mesh.normals, [Vector::new(0.0, 0.0, 1.0); 4]);
+        assert_eq!(mesh.triangles, [[0, 1, 2], [0, 2, 3]]);
+        let pentagon = parse("v 0 0 0\nv 1 0 0\nv 2 1 0\nv 1 2 0\nv 0 1 0\nf 1 2 3 4 5\n");
+        assert_eq!(
+            pentagon.unwrap().triangles,
+            [[0, 1, 2], [0, 2, 3], [0, 3, 4]]
+        );
+    }
+
+    #[test]
+    fn counts_negative_indices_back_from_the_last_element() {
+        let relative = SQUARE.replace(
+            "f 1/1/1 2/2/1 3/3/1 4/4/1",
+            "f -4/-4/-1 -3/-3/-1 -2/-2/-1 -1/-1/-1",
+        );
+        assert_eq!(parse(&relative).unwrap(), parse(SQUARE).unwrap());
+        // counting from the elements read so far, not from the whole file
+        let mesh = parse("v 0 0 0\nv 1 0 0\nv 0 1 0\nf -3 -2 -1\nv 5 5 5\nf 1 -2 -1\n").unwrap();
+        assert_eq!(mesh.positions.len(), 4);
+        assert_eq!(mesh.positions[3], Vector::new(5.0, 5.0, 5.0));
+        assert_eq!(mesh.triangles, [[0, 1, 2], [0, 2, 3]]);
+        let error = parse("v 0 0 0\nv 1 0 0\nv 0 1 0\nf -4 -2 -1\n").unwrap_err();
+        assert!(error.contains("there is no position -4"), "{}", error);
+        let error = parse("v 0 0 0\nv 1 0 0\nv 0 1 0\nf 0 2 3\n").unwrap_err();
+        assert!(error.contains("there is no position 0"), "{}", error);
+    }
+
+    #[test]
+    fn makes_a_vertex_for_each_distinct_corner() {
+        // the same position with two normals is two vertices
+        let mesh = parse(
+            "v 0 0 0\nv 1 0 0\nv 0 1 0\nv 0 0 1\nvn 0 0 1\nvn 0 1 0\n\
+             f 1//1 2//1 3//1\nf 1//2 4//2 2//2\n",
+        )
+        .unwrap();
+        assert_eq!(mesh.positions.len(), 6);
+        assert_eq!(mesh.triangles, [[0, 1, 2], [3, 4, 5]]);
+        // normals only some corners have are dropped, and corners merge again
+        let mesh =
+            parse("v 0 0 0\nv 1 0 0\nv 0 1 0\nv 1 1 0\nvn 0 0 1\nf 1//1 2//1 3//1\nf 3 2 4\n")
+                .unwrap();
+        assert!(mesh.normals.is_empty());
+        assert_eq!(mesh.triangles, [[0, 1, 2], [2, 1, 3]]);
+    }
+
+    #[test]
+    fn reads_vertex_colours() {
+        let mesh = parse("v 0 0 0 1 0 0\nv 1 0 0 0 1 0\nv 0 1 0 0 0 1\nf 1 2 3\n").unwrap();
+        assert_eq!(mesh.colors[1], Vector::new(0.0, 1.0, 0.0));
+        let error = parse("v 0 0 0 1 0 0\nv 1 0 0\nv 0 1 0\nf 1 2 3\n").unwrap_err();
+        assert_eq!(error, "some positions have a colour and some don't");
+    }
+
+    #[test]
+    fn rejects_malformed_lines() {
+        let end = SQUARE.find("f ").unwrap();
+        assert_eq!(parse(&SQUARE[..end]).unwrap_err(), "there are no faces");
+        let error = parse(&SQUARE.replace("v 1 1 0", "v 1 1,5 0")).unwrap_err();
+        assert_eq!(error, "line 6: invalid number in \"v 1 1,5 0\"");
+        let error = parse(&SQUARE.replace(" 3/3/1 4/4/1", "")).unwrap_err();
+        assert!(error.contains("faces need at least 3 corners"), "{}", error);
+        let error = parse(&SQUARE.replace("3/3/1", "3/9/1")).unwrap_err();
+        assert!(
+            error.contains("there is no texture coordinate 9"),
+            "{}",
+            error
+        );
+        let error = parse(&SQUARE.replace("3/3/1", "/3/1")).unwrap_err();
+        assert!(error.contains("a corner has no position"), "{}", error);
+        let error = parse(&SQUARE.replace("s off", "bevel on")).unwrap_err();
+        assert!(error.starts_with("line 15: unexpected line"), "{}", error);
+    }
+}
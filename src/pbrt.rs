@@ -0,0 +1,1173 @@
+//! A subset of PBRT-v4 scene files, for comparing against PBRT and reusing
+//! its test scenes: the camera, film resolution and sample count, the
+//! transform directives and attribute blocks, sphere, triangle and PLY
+//! shapes, diffuse, conductor and dielectric materials, and diffuse area
+//! and uniform infinite lights. Anything else is skipped with a warning.
+//!
+//! PBRT's space is left-handed, so the scene is mirrored in x to come out
+//! as PBRT renders it. Outside the scene is black unless infinite lights
+//! say otherwise, rather than this renderer's sky.
+
+use crate::camera::Camera;
+use crate::hitable::{Hitable, HitableList};
+use crate::light;
+use crate::material::{Dielectric, DiffuseLight, Lambertian, Metal, SharedMaterial};
+use crate::mesh::{Mesh, TriangleMesh};
+use crate::ply;
+use crate::ray::Ray;
+use crate::spectrum;
+use crate::sphere::Sphere;
+use crate::transform::{Matrix4, Transform, Transformed};
+use crate::vector::Vector;
+use std::collections::HashMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+
+/// What a PBRT file imports as.
+pub struct PbrtScene {
+    pub objects: HitableList,
+    pub camera: Camera,
+    /// Image width and height, from Film.
+    pub resolution: (usize, usize),
+    /// Samples per pixel, from Sampler.
+    pub samples: u32,
+    /// The directives and parameters that were skipped or approximated,
+    /// each with the file and line it's on.
+    pub warnings: Vec<String>,
+}
+
+pub fn load(path: &Path) -> Result<PbrtScene, String> {
+    let mut builder = Builder::default();
+    builder.file(path)?;
+    Ok(builder.finish())
+}
+
+// Tokens
+
+#[derive(Clone, Debug, PartialEq)]
+enum Token {
+    Word(String),
+    Quoted(String),
+    Number(f64),
+    Open,
+    Close,
+}
+
+// tokens with their line numbers
+fn tokenize(text: &str) -> Result<Vec<(Token, usize)>, String> {
+    let mut tokens = Vec::new();
+    let mut chars = text.chars().peekable();
+    let mut line = 1;
+    while let Some(&c) = chars.peek() {
+        match c {
+            '\n' => {
+                line += 1;
+                chars.next();
+            }
+            c if c.is_whitespace() => {
+                chars.next();
+            }
+            '#' => {
+                while chars.peek().is_some_and(|&c| c != '\n') {
+                    chars.next();
+                }
+            }
+            '[' | ']' => {
+                chars.next();
+                tokens.push((if c == '[' { Token::Open } else { Token::Close }, line));
+            }
+            '"' => {
+                chars.next();
+                let start = line;
+                let mut text = String::new();
+                loop {
+                    match chars.next() {
+                        None => return Err(format!("{}: unterminated string", start)),
+                        Some('"') => break,
+                        Some('\\') => match chars.next() {
+                            Some('n') => text.push('\n'),
+                            Some('t') => text.push('\t'),
+                            Some(c) => text.push(c),
+                            None => return Err(format!("{}: unterminated string", start)),
+                        },
+                        Some(c) => {
+                            if c == '\n' {
+                                line += 1;
+                            }
+                            text.push(c);
+                        }
+                    }
+                }
+                tokens.push((Token::Quoted(text), start));
+            }
+            _ => {
+                let mut word = String::new();
+                while let Some(&c) = chars.peek() {
+                    if c.is_whitespace() || "[]\"#".contains(c) {
+                        break;
+                    }
+                    word.push(c);
+                    chars.next();
+                }
+                let token = match word.parse::<f64>() {
+                    Ok(number) if !word.starts_with(|c: char| c.is_alphabetic()) => {
+                        Token::Number(number)
+                    }
+                    _ => Token::Word(word),
+                };
+                tokens.push((token, line));
+            }
+        }
+    }
+    Ok(tokens)
+}
+
+// Directives
+
+#[derive(Clone, Debug)]
+enum Value {
+    Number(f64),
+    Text(String),
+    Bool(bool),
+}
+
+#[derive(Debug)]
+enum Argument {
+    Number(f64),
+    Text(String),
+    List(Vec<Value>),
+}
+
+#[derive(Debug)]
+struct Param {
+    kind: String,
+    name: String,
+    values: Vec<Value>,
+}
+
+const PARAM_KINDS: [&str; 17] = [
+    "integer",
+    "float",
+    "point2",
+    "vector2",
+    "point3",
+    "vector3",
+    "normal3",
+    "normal",
+    "point",
+    "vector",
+    "bool",
+    "string",
+    "texture",
+    "spectrum",
+    "rgb",
+    "blackbody",
+    "color",
+];
+
+// a directive, its positional arguments and its parameter list
+struct Directive {
+    name: String,
+    line: usize,
+    arguments: Vec<Argument>,
+    params: Vec<Param>,
+    // the names of the parameters that were looked at
+    used: Vec<String>,
+    // parameters read but not supported, for the builder to warn about
+    warnings: Vec<String>,
+}
+
+fn value(token: &Token) -> Option<Value> {
+    match token {
+        Token::Number(n) => Some(Value::Number(*n)),
+        Token::Quoted(s) if s == "true" || s == "false" => Some(Value::Bool(s == "true")),
+        Token::Quoted(s) => Some(Value::Text(s.clone())),
+        Token::Word(w) if w == "true" || w == "false" => Some(Value::Bool(w == "true")),
+        _ => None,
+    }
+}
+
+// splits the tokens into directives
+fn directives(tokens: &[(Token, usize)]) -> Result<Vec<Directive>, String> {
+    let mut directives = Vec::new();
+    let mut i = 0;
+    while i < tokens.len() {
+        let (name, line) = match &tokens[i] {
+            (Token::Word(word), line) => (word.clone(), *line),
+            (_, line) => return Err(format!("{}: expected a directive", line)),
+        };
+        i += 1;
+        let mut directive = Directive {
+            name,
+            line,
+            arguments: Vec::new(),
+            params: Vec::new(),
+            used: Vec::new(),
+            warnings: Vec::new(),
+        };
+        // everything up to the next word that isn't a value
+        while i < tokens.len() {
+            let (token, line) = &tokens[i];
+            if matches!(token, Token::Word(w) if w != "true" && w != "false") {
+                break;
+            }
+            i += 1;
+            let declaration = match token {
+                Token::Quoted(s) => {
+                    let words: Vec<&str> = s.split_whitespace().collect();
+                    match words.as_slice() {
+                        [kind, name] if PARAM_KINDS.contains(kind) => {
+                            Some((kind.to_string(), name.to_string()))
+                        }
+                        _ => None,
+                    }
+                }
+                _ => None,
+            };
+            match (declaration, token) {
+                (Some((kind, name)), _) => {
+                    let mut values = Vec::new();
+                    match tokens.get(i) {
+                        Some((Token::Open, _)) => {
+                            i += 1;
+                            loop {
+                                match tokens.get(i) {
+                                    Some((Token::Close, _)) => break,
+                                    Some((token, line)) => {
+                                        values.push(value(token).ok_or_else(|| {
+                                            format!("{}: invalid value for {:?}", line, name)
+                                        })?)
+                                    }
+                                    None => return Err(format!("{}: unterminated list", line)),
+                                }
+                                i += 1;
+                            }
+                            i += 1;
+                        }
+                        Some((token, _)) if value(token).is_some() => {
+                            values.extend(value(token));
+                            i += 1;
+                        }
+                        _ => return Err(format!("{}: {:?} has no value", line, name)),
+                    }
+                    directive.params.push(Param { kind, name, values });
+                }
+                (None, Token::Open) => {
+                    let mut values = Vec::new();
+                    loop {
+                        match tokens.get(i) {
+                            Some((Token::Close, _)) => break,
+                            Some((token, line)) => values.push(
+                                value(token)
+                                    .ok_or_else(|| format!("{}: invalid value in list", line))?,
+                            ),
+                            None => return Err(format!("{}: unterminated list", line)),
+                        }
+                        i += 1;
+                    }
+                    i += 1;
+                    directive.arguments.push(Argument::List(values));
+                }
+                (None, Token::Number(n)) => directive.arguments.push(Argument::Number(*n)),
+                (None, Token::Quoted(s)) => directive.arguments.push(Argument::Text(s.clone())),
+                (None, _) => return Err(format!("{}: unexpected ']'", line)),
+            }
+        }
+        directives.push(directive);
+    }
+    Ok(directives)
+}
+
+impl Directive {
+    fn numbers(&self, count: usize) -> Result<Vec<f64>, String> {
+        let mut numbers = Vec::new();
+        for argument in &self.arguments {
+            match argument {
+                Argument::Number(n) => numbers.push(*n),
+                Argument::List(values) => {
+                    for value in values {
+                        match value {
+                            Value::Number(n) => numbers.push(*n),
+                            _ => numbers.push(f64::NAN),
+                        }
+                    }
+                }
+                Argument::Text(_) => numbers.push(f64::NAN),
+            }
+        }
+        if numbers.len() != count || numbers.iter().any(|n| n.is_nan()) {
+            return Err(format!("{} needs {} numbers", self.name, count));
+        }
+        Ok(numbers)
+    }
+
+    fn text(&self, index: usize) -> Result<&str, String> {
+        match self.arguments.get(index) {
+            Some(Argument::Text(s)) => Ok(s),
+            _ => Err(format!("{} needs a name", self.name)),
+        }
+    }
+
+    fn param(&mut self, name: &str) -> Option<&Param> {
+        self.used.push(name.to_string());
+        self.params.iter().find(|p| p.name == name)
+    }
+
+    fn float(&mut self, name: &str, default: f64) -> Result<f64, String> {
+        match self.param(name).map(|p| p.values.as_slice()) {
+            None => Ok(default),
+            Some([Value::Number(n)]) => Ok(*n),
+            Some(_) => Err(format!("{} should be a number", name)),
+        }
+    }
+
+    fn floats(&mut self, name: &str) -> Result<Option<Vec<f64>>, String> {
+        match self.param(name) {
+            None => Ok(None),
+            Some(param) => param
+                .values
+                .iter()
+                .map(|v| match v {
+                    Value::Number(n) => Some(*n),
+                    _ => None,
+                })
+                .collect::<Option<Vec<f64>>>()
+                .map(Some)
+                .ok_or_else(|| format!("{} should be numbers", name)),
+        }
+    }
+
+    fn string(&mut self, name: &str) -> Result<Option<String>, String> {
+        match self.param(name).map(|p| p.values.as_slice()) {
+            None => Ok(None),
+            Some([Value::Text(s)]) => Ok(Some(s.clone())),
+            Some(_) => Err(format!("{} should be a string", name)),
+        }
+    }
+
+    fn bool(&mut self, name: &str, default: bool) -> Result<bool, String> {
+        match self.param(name).map(|p| p.values.as_slice()) {
+            None => Ok(default),
+            Some([Value::Bool(b)]) => Ok(*b),
+            Some(_) => Err(format!("{} should be true or false", name)),
+        }
+    }
+
+    // an rgb, blackbody or spectrum parameter, as RGB, with textures left at
+    // the default
+    fn color(&mut self, name: &str) -> Result<Option<Vector>, String> {
+        let param = match self.param(name) {
+            None => return Ok(None),
+            Some(param) => param,
+        };
+        let numbers: Option<Vec<f64>> = param
+            .values
+            .iter()
+            .map(|v| match v {
+                Value::Number(n) => Some(*n),
+                _ => None,
+            })
+            .collect();
+        let color = match (
+            param.kind.as_str(),
+            numbers.as_deref(),
+            param.values.as_slice(),
+        ) {
+            ("rgb" | "color", Some([r, g, b]), _) => Vector::new(*r, *g, *b),
+            ("blackbody", Some([kelvin]), _) => spectrum::blackbody(*kelvin),
+            ("spectrum", Some(pairs), _) if pairs.len() >= 2 && pairs.len() % 2 == 0 => {
+                let samples: Vec<(f64, f64)> =
+                    pairs.chunks_exact(2).map(|p| (p[0], p[1])).collect();
+                spectrum::from_samples(&samples)
+            }
+            ("spectrum", _, [Value::Text(named)]) => match named_spectrum(named) {
+                Some(color) => color,
+                None => return Err(format!("unknown spectrum {:?}", named)),
+            },
+            // a single value, for spectra that are constant
+            ("spectrum", Some([value]), _) => Vector::new(*value, *value, *value),
+            ("texture", _, _) => {
+                self.warnings.push(format!(
+                    "{} is a texture, which isn't supported, so its default is used",
+                    name
+                ));
+                return Ok(None);
+            }
+            _ => return Err(format!("{} isn't a valid colour", name)),
+        };
+        Ok(Some(color))
+    }
+
+    // parameters that were given but never looked at
+    fn unused(&self) -> Vec<String> {
+        self.params
+            .iter()
+            .filter(|p| !self.used.contains(&p.name))
+            .map(|p| format!("{} {}", p.kind, p.name))
+            .collect()
+    }
+}
+
+// Spectra
+
+// PBRT's named spectra, as RGB: metal reflectances at normal incidence in
+// place of their eta and k, and white for illuminants
+fn named_spectrum(name: &str) -> Option<Vector> {
+    let metal = name
+        .strip_prefix("metal-")
+        .and_then(|n| n.strip_suffix("-eta").or_else(|| n.strip_suffix("-k")));
+    if let Some(metal) = metal {
+        return Metal::reflectance(metal);
+    }
+    if name.starts_with("stdillum-") || name.starts_with("illum-") {
+        return Some(Vector::new(1.0, 1.0, 1.0));
+    }
+    None
+}
+
+// refraction indices of PBRT's named glasses, at the sodium d-line
+fn named_glass(name: &str) -> Option<f64> {
+    Some(match name {
+        "glass-BK7" => 1.5168,
+        "glass-BAF10" => 1.6700,
+        "glass-FK51A" => 1.4866,
+        "glass-LASF9" => 1.8503,
+        "glass-F5" => 1.6034,
+        "glass-SF5" => 1.6727,
+        "glass-SF10" => 1.7283,
+        "glass-SF11" => 1.7847,
+        _ => return None,
+    })
+}
+
+// Scene
+
+#[derive(Copy, Clone, PartialEq)]
+enum ActiveTransform {
+    All,
+    Start,
+    End,
+}
+
+// what AttributeBegin saves and AttributeEnd restores
+#[derive(Clone)]
+struct State {
+    transform: Matrix4,
+    material: SharedMaterial,
+    area_light: Option<Vector>,
+}
+
+impl Default for State {
+    fn default() -> State {
+        State {
+            transform: Matrix4::identity(),
+            // PBRT's default material
+            material: Arc::new(Lambertian::new(Vector::new(0.5, 0.5, 0.5))),
+            area_light: None,
+        }
+    }
+}
+
+struct CameraSpec {
+    world_from_camera: Matrix4,
+    fov: f64,
+    lens_radius: f64,
+    focal_distance: Option<f64>,
+}
+
+struct Builder {
+    state: State,
+    // saved states, and whether each was saved by TransformBegin
+    stack: Vec<(State, bool)>,
+    coordinate_systems: HashMap<String, Matrix4>,
+    named_materials: HashMap<String, SharedMaterial>,
+    camera: Option<CameraSpec>,
+    resolution: (usize, usize),
+    samples: u32,
+    active: ActiveTransform,
+    // inside ObjectBegin, whose shapes are skipped
+    in_object: bool,
+    environment: Vector,
+    objects: HitableList,
+    // the file and line of the directive being read, for warnings
+    location: String,
+    // the files being read, each included by the one before
+    including: Vec<PathBuf>,
+    warnings: Vec<String>,
+}
+
+impl Default for Builder {
+    fn default() -> Builder {
+        Builder {
+            state: State::default(),
+            stack: Vec::new(),
+            coordinate_systems: HashMap::new(),
+            named_materials: HashMap::new(),
+            camera: None,
+            // PBRT's defaults
+            resolution: (1280, 720),
+            samples: 16,
+            active: ActiveTransform::All,
+            in_object: false,
+            environment: Vector::default(),
+            objects: HitableList::default(),
+            location: String::new(),
+            including: Vec::new(),
+            warnings: Vec::new(),
+        }
+    }
+}
+
+impl Builder {
+    fn file(&mut self, path: &Path) -> Result<(), String> {
+        let canonical = path.canonicalize().unwrap_or_else(|_| path.to_path_buf());
+        if self.including.contains(&canonical) {
+            return Err(format!("{} includes itself", path.display()));
+        }
+        self.including.push(canonical);
+        let text = fs::read_to_string(path)
+            .map_err(|e| format!("can't read PBRT file {}: {}", path.display(), e))?;
+        // the tokenizer's errors start with their line number
+        let at = |e: String| format!("{}:{}", path.display(), e);
+        let directory = path.parent().unwrap_or_else(|| Path::new("."));
+        for mut directive in directives(&tokenize(&text).map_err(at)?).map_err(at)? {
+            let line = directive.line;
+            let location = format!("{}:{}", path.display(), line);
+            self.location.clone_from(&location);
+            self.directive(&mut directive, directory)
+                .map_err(|e| format!("{}: {}", location, e))?;
+            // an Include moves it on
+            self.location = location;
+            for warning in std::mem::take(&mut directive.warnings) {
+                self.warn(warning);
+            }
+            let unused = directive.unused();
+            if !unused.is_empty() {
+                self.warn(format!(
+                    "{} parameters {} are ignored",
+                    directive.name,
+                    unused.join(", ")
+                ));
+            }
+        }
+        self.including.pop();
+        Ok(())
+    }
+
+    fn warn(&mut self, warning: String) {
+        self.warnings
+            .push(format!("{}: {}", self.location, warning));
+    }
+
+    // multiplies the current transform by `m`
+    fn apply(&mut self, m: Matrix4) {
+        if self.active != ActiveTransform::End {
+            self.state.transform = self.state.transform.mul(&m);
+        }
+    }
+
+    fn directive(&mut self, d: &mut Directive, directory: &Path) -> Result<(), String> {
+        match d.name.as_str() {
+            "Identity" => {
+                if self.active != ActiveTransform::End {
+                    self.state.transform = Matrix4::identity();
+                }
+            }
+            "Translate" => {
+                let v = d.numbers(3)?;
+                self.apply(*Transform::translate(Vector::new(v[0], v[1], v[2])).matrix());
+            }
+            "Scale" => {
+                let v = d.numbers(3)?;
+                self.apply(*Transform::scale(Vector::new(v[0], v[1], v[2])).matrix());
+            }
+            "Rotate" => {
+                let v = d.numbers(4)?;
+                self.apply(*Transform::rotate(Vector::new(v[1], v[2], v[3]), v[0]).matrix());
+            }
+            "LookAt" => {
+                let v = d.numbers(9)?;
+                let eye = Vector::new(v[0], v[1], v[2]);
+                let look = Vector::new(v[3], v[4], v[5]);
+                let up = Vector::new(v[6], v[7], v[8]).unit();
+                let dir = (look - eye).unit();
+                let right = up.cross(dir);
+                if right.length() < 1e-9 {
+                    return Err(String::from(
+                        "LookAt's up vector is along the view direction",
+                    ));
+                }
+                let right = right.unit();
+                let new_up = dir.cross(right);
+                // the camera's axes in world space, inverted to go from world
+                // to camera space
+                let mut world_from_camera = Matrix4::identity();
+                for (i, axis) in [right, new_up, dir, eye].iter().enumerate() {
+                    world_from_camera.m[0][i] = axis.x();
+                    world_from_camera.m[1][i] = axis.y();
+                    world_from_camera.m[2][i] = axis.z();
+                }
+                let camera_from_world = world_from_camera
+                    .inverse()
+                    .ok_or("LookAt's transform can't be inverted")?;
+                self.apply(camera_from_world);
+            }
+            "Transform" | "ConcatTransform" => {
+                let v = d.numbers(16)?;
+                // given column by column
+                let mut m = Matrix4::identity();
+                for (i, row) in m.m.iter_mut().enumerate() {
+                    for (j, value) in row.iter_mut().enumerate() {
+                        *value = v[4 * j + i];
+                    }
+                }
+                if d.name == "Transform" {
+                    if self.active != ActiveTransform::End {
+                        self.state.transform = m;
+                    }
+                } else {
+                    self.apply(m);
+                }
+            }
+            "CoordinateSystem" => {
+                let name = d.text(0)?.to_string();
+                self.coordinate_systems.insert(name, self.state.transform);
+            }
+            "CoordSysTransform" => {
+                let name = d.text(0)?;
+                match self.coordinate_systems.get(name) {
+                    Some(m) => self.state.transform = *m,
+                    None => self.warn(format!("coordinate system {:?} isn't defined", name)),
+                }
+            }
+            // its All, StartTime or EndTime is a bare word, which comes
+            // through as a directive of its own
+            "ActiveTransform" => {}
+            "All" => self.active = ActiveTransform::All,
+            "StartTime" => self.active = ActiveTransform::Start,
+            "EndTime" => {
+                self.active = ActiveTransform::End;
+                self.warn(String::from(
+                    "transforms for the end of the frame are ignored, there is no motion blur",
+                ));
+            }
+            "ReverseOrientation" => {}
+            "Camera" => {
+                let kind = d.text(0)?.to_string();
+                if kind != "perspective" {
+                    self.warn(format!(
+                        "{} cameras aren't supported, perspective is used",
+                        kind
+                    ));
+                }
+                let world_from_camera = self
+                    .state
+                    .transform
+                    .inverse()
+                    .ok_or("the camera transform can't be inverted")?;
+                self.coordinate_systems
+                    .insert(String::from("camera"), world_from_camera);
+                let focal_distance = match d.param("focaldistance") {
+                    Some(_) => Some(d.float("focaldistance", 1e6)?),
+                    None => None,
+                };
+                self.camera = Some(CameraSpec {
+                    world_from_camera,
+                    fov: d.float("fov", 90.0)?,
+                    lens_radius: d.float("lensradius", 0.0)?,
+                    focal_distance,
+                });
+            }
+            "Film" => {
+                let width = d.float("xresolution", 1280.0)?;
+                let height = d.float("yresolution", 720.0)?;
+                if width < 1.0 || height < 1.0 {
+                    return Err(String::from("the film resolution must be at least 1 by 1"));
+                }
+                self.resolution = (width as usize, height as usize);
+                // where PBRT would write the image
+                d.param("filename");
+            }
+            "Sampler" => {
+                let samples = d.float("pixelsamples", 16.0)?;
+                if samples < 1.0 {
+                    return Err(String::from("pixelsamples must be at least 1"));
+                }
+                self.samples = samples as u32;
+            }
+            "WorldBegin" => {
+                self.state.transform = Matrix4::identity();
+                self.coordinate_systems
+                    .insert(String::from("world"), Matrix4::identity());
+            }
+            "WorldEnd" => {}
+            "AttributeBegin" | "TransformBegin" => {
+                self.stack
+                    .push((self.state.clone(), d.name == "TransformBegin"));
+            }
+            "AttributeEnd" | "TransformEnd" => {
+                let (saved, transform_only) = self
+                    .stack
+                    .pop()
+                    .ok_or_else(|| format!("{} without a matching begin", d.name))?;
+                if transform_only {
+                    self.state.transform = saved.transform;
+                } else {
+                    self.state = saved;
+                }
+            }
+            "Material" => {
+                let kind = d.text(0)?.to_string();
+                self.state.material = self.material(&kind, d)?;
+            }
+            "MakeNamedMaterial" => {
+                let name = d.text(0)?.to_string();
+                let kind = d.string("type")?.ok_or("MakeNamedMaterial needs a type")?;
+                let material = self.material(&kind, d)?;
+                self.named_materials.insert(name, material);
+            }
+            "NamedMaterial" => {
+                let name = d.text(0)?;
+                match self.named_materials.get(name) {
+                    Some(material) => self.state.material = material.clone(),
+                    None => return Err(format!("material {:?} isn't defined", name)),
+                }
+            }
+            "AreaLightSource" => {
+                let kind = d.text(0)?;
+                if kind != "diffuse" {
+                    return Err(format!("unknown area light {:?}", kind));
+                }
+                let radiance = d.color("L")?.unwrap_or(Vector::new(1.0, 1.0, 1.0));
+                let scale = d.float("scale", 1.0)?;
+                // both sides emit anyway
+                d.param("twosided");
+                self.state.area_light = Some(scale * radiance);
+            }
+            "LightSource" => {
+                let kind = d.text(0)?.to_string();
+                match kind.as_str() {
+                    "infinite" => {
+                        let radiance = d.color("L")?.unwrap_or(Vector::new(1.0, 1.0, 1.0));
+                        let scale = d.float("scale", 1.0)?;
+                        if d.string("filename")?.is_some() {
+                            self.warn(String::from(
+                                "image infinite lights aren't supported, the light is uniform",
+                            ));
+                        }
+                        self.environment = self.environment + scale * radiance;
+                    }
+                    _ => {
+                        self.warn(format!("{} lights aren't supported", kind));
+                        d.used.extend(d.params.iter().map(|p| p.name.clone()));
+                    }
+                }
+            }
+            "Shape" => {
+                let kind = d.text(0)?.to_string();
+                if self.in_object {
+                    self.warn(String::from(
+                        "object instances aren't supported, their shapes are skipped",
+                    ));
+                    d.used.extend(d.params.iter().map(|p| p.name.clone()));
+                    return Ok(());
+                }
+                self.shape(&kind, d, directory)?;
+            }
+            "Include" | "Import" => {
+                let path = directory.join(d.text(0)?);
+                self.file(&path)?;
+            }
+            "ObjectBegin" => {
+                self.in_object = true;
+                self.stack.push((self.state.clone(), false));
+            }
+            "ObjectEnd" => {
+                self.in_object = false;
+                if let Some((saved, _)) = self.stack.pop() {
+                    self.state = saved;
+                }
+            }
+            // ignored as this renderer has its own way to do these
+            "Integrator" | "PixelFilter" | "Accelerator" | "ColorSpace" | "Option"
+            | "Attribute" | "MakeNamedMedium" | "MediumInterface" | "ObjectInstance"
+            | "Texture" => {
+                self.warn(format!("{} is ignored", d.name));
+                d.used.extend(d.params.iter().map(|p| p.name.clone()));
+            }
+            _ => {
+                self.warn(format!("unknown directive {} is ignored", d.name));
+                d.used.extend(d.params.iter().map(|p| p.name.clone()));
+            }
+        }
+        Ok(())
+    }
+
+    fn material(&mut self, kind: &str, d: &mut Directive) -> Result<SharedMaterial, String> {
+        Ok(match kind {
+            "diffuse" => {
+                let reflectance = d
+                    .color("reflectance")?
+                    .unwrap_or(Vector::new(0.5, 0.5, 0.5));
+                Arc::new(Lambertian::new(reflectance))
+            }
+            "conductor" => {
+                let reflectance = match d.color("reflectance")? {
+                    Some(reflectance) => reflectance,
+                    None => {
+                        let eta = d.color("eta")?;
+                        let k = d.color("k")?;
+                        match (eta, k, d.params.iter().find(|p| p.name == "eta")) {
+                            // named metals are reflectances already
+                            (Some(eta), _, Some(p))
+                                if p.kind == "spectrum"
+                                    && matches!(p.values.as_slice(), [Value::Text(_)]) =>
+                            {
+                                eta
+                            }
+                            (Some(eta), Some(k), _) => fresnel_conductor(eta, k),
+                            (Some(eta), None, _) => fresnel_conductor(eta, Vector::default()),
+                            // copper, PBRT's default
+                            _ => named_spectrum("metal-Cu-eta").unwrap_or_default(),
+                        }
+                    }
+                };
+                let remap = d.bool("remaproughness", true)?;
+                let roughness = d.float("roughness", 0.0)?;
+                let u = d.float("uroughness", roughness)?;
+                let v = d.float("vroughness", roughness)?;
+                let roughness = (u + v) / 2.0;
+                let alpha = if remap { roughness.sqrt() } else { roughness };
+                Arc::new(Metal::new(reflectance, alpha.min(1.0)))
+            }
+            "dielectric" | "thindielectric" => {
+                let eta = match d.param("eta").map(|p| (p.kind.clone(), p.values.clone())) {
+                    None => 1.5,
+                    Some((_, values)) => match values.as_slice() {
+                        [Value::Number(eta)] => *eta,
+                        [Value::Text(name)] => named_glass(name)
+                            .ok_or_else(|| format!("unknown spectrum {:?}", name))?,
+                        _ => {
+                            self.warn(String::from(
+                                "dispersion isn't supported, the refraction index is 1.5",
+                            ));
+                            1.5
+                        }
+                    },
+                };
+                if kind == "thindielectric" {
+                    self.warn(String::from("thin dielectrics are rendered as solid ones"));
+                }
+                d.param("roughness");
+                d.param("uroughness");
+                d.param("vroughness");
+                d.param("remaproughness");
+                Arc::new(Dielectric::new(eta))
+            }
+            "" | "none" | "interface" => Arc::new(Lambertian::new(Vector::default())),
+            _ => {
+                self.warn(format!(
+                    "{} materials aren't supported, diffuse grey is used",
+                    kind
+                ));
+                d.used.extend(d.params.iter().map(|p| p.name.clone()));
+                Arc::new(Lambertian::new(Vector::new(0.5, 0.5, 0.5)))
+            }
+        })
+    }
+
+    fn shape(&mut self, kind: &str, d: &mut Directive, directory: &Path) -> Result<(), String> {
+        let material = match self.state.area_light {
+            Some(radiance) => Arc::new(DiffuseLight::new(radiance)),
+            None => self.state.material.clone(),
+        };
+        let transform = match Transform::new(mirror().mul(&self.state.transform)) {
+            Some(transform) => transform,
+            None => {
+                self.warn(format!(
+                    "a {} is skipped as its transform can't be inverted",
+                    kind
+                ));
+                return Ok(());
+            }
+        };
+        // alpha cut outs aren't supported
+        d.param("alpha");
+        match kind {
+            "sphere" => {
+                let radius = d.float("radius", 1.0)?;
+                let zmin = d.float("zmin", -radius)?;
+                let zmax = d.float("zmax", radius)?;
+                let phimax = d.float("phimax", 360.0)?;
+                if zmin > -radius || zmax < radius || phimax < 360.0 {
+                    self.warn(String::from("partial spheres are rendered whole"));
+                }
+                self.objects.push(Transformed::new(
+                    Sphere::new(Vector::default(), radius, material),
+                    transform,
+                ));
+            }
+            "trianglemesh" => {
+                let points = d.floats("P")?.ok_or("trianglemesh needs P")?;
+                let mut mesh = Mesh {
+                    positions: vectors(&points),
+                    ..Mesh::default()
+                };
+                if let Some(normals) = d.floats("N")? {
+                    mesh.normals = vectors(&normals);
+                }
+                if let Some(uvs) = d.floats("uv")? {
+                    mesh.uvs = uvs.chunks_exact(2).map(|c| (c[0], c[1])).collect();
+                }
+                let indices = match d.floats("indices")? {
+                    Some(indices) => indices,
+                    None if mesh.positions.len() == 3 => vec![0.0, 1.0, 2.0],
+                    None => return Err(String::from("trianglemesh needs indices")),
+                };
+                if indices.len() % 3 != 0 || indices.iter().any(|&i| i < 0.0) {
+                    return Err(String::from("indices should be triples of vertex indices"));
+                }
+                mesh.triangles = indices
+                    .chunks_exact(3)
+                    .map(|t| [t[0] as usize, t[1] as usize, t[2] as usize])
+                    .collect();
+                mesh.check()?;
+                self.objects.push(Transformed::new(
+                    TriangleMesh::new(mesh, material),
+                    transform,
+                ));
+            }
+            "plymesh" => {
+                let filename = d.string("filename")?.ok_or("plymesh needs a filename")?;
+                let path: PathBuf = directory.join(filename);
+                let mesh = ply::load(&path)?;
+                for name in ["displacement", "edgelength"] {
+                    if d.param(name).is_some() {
+                        self.warn(format!("plymesh {} is ignored", name));
+                    }
+                }
+                self.objects.push(Transformed::new(
+                    TriangleMesh::new(mesh, material),
+                    transform,
+                ));
+            }
+            _ => {
+                self.warn(format!("{} shapes aren't supported", kind));
+                d.used.extend(d.params.iter().map(|p| p.name.clone()));
+            }
+        }
+        Ok(())
+    }
+
+    fn finish(mut self) -> PbrtScene {
+        let (width, height) = self.resolution;
+        let aspect_ratio = width as f64 / height as f64;
+        // PBRT's default camera looks down +z from the origin
+        let spec = self.camera.take().unwrap_or(CameraSpec {
+            world_from_camera: Matrix4::identity(),
+            fov: 90.0,
+            lens_radius: 0.0,
+            focal_distance: None,
+        });
+        let to_world = Transform::new(mirror().mul(&spec.world_from_camera))
+            .unwrap_or_else(Transform::identity);
+        let eye = to_world.point(Vector::default());
+        let forward = to_world.vector(Vector::new(0.0, 0.0, 1.0)).unit();
+        let up = to_world.vector(Vector::new(0.0, 1.0, 0.0)).unit();
+        // fov is across the shorter side of the image
+        let vertical_fov = if aspect_ratio >= 1.0 {
+            spec.fov
+        } else {
+            2.0 * ((spec.fov.to_radians() / 2.0).tan() / aspect_ratio)
+                .atan()
+                .to_degrees()
+        };
+        let focus = spec.focal_distance.unwrap_or_else(|| {
+            self.objects
+                .hit(&Ray::new(eye, forward, 0.0), 1e-3, f64::MAX)
+                .map_or(10.0, |hit| hit.t)
+        });
+        let camera = Camera::new(
+            eye,
+            eye + forward,
+            up,
+            vertical_fov,
+            aspect_ratio,
+            2.0 * spec.lens_radius,
+            focus,
+            0.0,
+            1.0,
+        );
+
+        let bounds = self.objects.bounding_box(0.0, 1.0);
+        self.objects
+            .push(light::environment(self.environment, bounds, eye));
+
+        PbrtScene {
+            objects: self.objects,
+            camera,
+            resolution: self.resolution,
+            samples: self.samples,
+            warnings: self.warnings,
+        }
+    }
+}
+
+// flips x, from PBRT's left-handed space to this renderer's right-handed one
+fn mirror() -> Matrix4 {
+    *Transform::scale(Vector::new(-1.0, 1.0, 1.0)).matrix()
+}
+
+fn vectors(values: &[f64]) -> Vec<Vector> {
+    values
+        .chunks_exact(3)
+        .map(|c| Vector::new(c[0], c[1], c[2]))
+        .collect()
+}
+
+// reflectance at normal incidence of a metal with refraction index eta and
+// absorption k, per channel
+fn fresnel_conductor(eta: Vector, k: Vector) -> Vector {
+    let f = |n: f64, k: f64| ((n - 1.0).powi(2) + k * k) / ((n + 1.0).powi(2) + k * k);
+    Vector::new(f(eta.x(), k.x()), f(eta.y(), k.y()), f(eta.z(), k.z()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // a directory of files for a test, removed again when it's dropped
+    struct TempDir(PathBuf);
+
+    impl TempDir {
+        fn new(name: &str) -> TempDir {
+            let path =
+                std::env::temp_dir().join(format!("tracer-pbrt-{}-{}", name, std::process::id()));
+            fs::create_dir_all(&path).unwrap();
+            TempDir(path)
+        }
+
+        // writes `text` to `name` in the directory
+        fn file(&self, name: &str, text: &str) -> PathBuf {
+            let path = self.0.join(name);
+            fs::create_dir_all(path.parent().unwrap()).unwrap();
+            fs::write(&path, text).unwrap();
+            path
+        }
+    }
+
+    impl Drop for TempDir {
+        fn drop(&mut self) {
+            let _ = fs::remove_dir_all(&self.0);
+        }
+    }
+
+    fn parse(text: &str) -> Result<Vec<Directive>, String> {
+        directives(&tokenize(text)?)
+    }
+
+    // the distance along +z from (x, 0, -10) to the first shape hit, short of
+    // the environment light around the scene
+    fn distance_at(scene: &PbrtScene, x: f64) -> Option<f64> {
+        let ray = Ray::new(Vector::new(x, 0.0, -10.0), Vector::new(0.0, 0.0, 1.0), 0.0);
+        scene.objects.hit(&ray, 0.001, 20.0).map(|hit| hit.t)
+    }
+
+    #[test]
+    fn tokenizes_and_splits_directives() {
+        let d = parse(
+            "# a comment\nTranslate 1 -2 3.5e1\nShape \"sphere\" \"float radius\" [ 2 ]\n\
+             \"bool flag\" true",
+        )
+        .unwrap();
+        assert_eq!(d.len(), 2);
+        assert_eq!((d[0].name.as_str(), d[0].line), ("Translate", 2));
+        assert_eq!(d[0].numbers(3).unwrap(), [1.0, -2.0, 35.0]);
+        assert_eq!(d[1].text(0).unwrap(), "sphere");
+        let mut shape = parse("Shape \"sphere\" \"float radius\" 2").unwrap();
+        assert_eq!(shape[0].float("radius", 1.0).unwrap(), 2.0);
+        assert!(shape[0].unused().is_empty());
+    }
+
+    #[test]
+    fn rejects_malformed_directives() {
+        let error = parse("Scale 1 2 3e").unwrap()[0].numbers(3).unwrap_err();
+        assert_eq!(error, "Scale needs 3 numbers");
+        let error = parse("Shape \"sphere\" \"float radius\" [ 1..5 ]")
+            .err()
+            .unwrap();
+        assert!(error.starts_with("1: invalid value"), "{}", error);
+        for text in ["Shape \"sphere\" \"float radius\" [ 2", "Shape \"sphere"] {
+            assert!(parse(text).is_err(), "{:?} was accepted", text);
+        }
+    }
+
+    #[test]
+    fn includes_files_relative_to_the_one_including_them() {
+        let dir = TempDir::new("include");
+        dir.file(
+            "parts/ball.pbrt",
+            "Include \"material.pbrt\"\nShape \"sphere\" \"float radius\" 2\n",
+        );
+        dir.file(
+            "parts/material.pbrt",
+            "Material \"diffuse\" \"rgb reflectance\" [ 0.1 0.2 0.3 ]\nTexture \"unused\"\n",
+        );
+        let main = dir.file(
+            "main.pbrt",
+            "WorldBegin\nAttributeBegin\nInclude \"parts/ball.pbrt\"\nAttributeEnd\n\
+             Integrator \"path\"\n",
+        );
+        let scene = load(&main).unwrap();
+        assert_eq!(distance_at(&scene, 0.0), Some(8.0));
+        // warnings give the file they're in, and the line
+        let warnings: Vec<&str> = scene
+            .warnings
+            .iter()
+            .map(|w| w.rsplit('/').next().unwrap())
+            .collect();
+        assert_eq!(
+            warnings,
+            [
+                "material.pbrt:2: Texture is ignored",
+                "main.pbrt:5: Integrator is ignored"
+            ]
+        );
+
+        let error = load(&dir.0.join("missing.pbrt")).err().unwrap();
+        assert!(error.starts_with("can't read PBRT file"), "{}", error);
+        dir.file("parts/ball.pbrt", "Include \"../main.pbrt\"\n");
+        let error = load(&main).err().unwrap();
+        assert!(error.ends_with("main.pbrt includes itself"), "{}", error);
+    }
+
+    #[test]
+    fn skips_object_instances() {
+        let dir = TempDir::new("instance");
+        let path = dir.file(
+            "instance.pbrt",
+            "WorldBegin\n\
+             ObjectBegin \"ball\"\nTranslate 5 0 0\nShape \"sphere\"\nObjectEnd\n\
+             ObjectInstance \"ball\"\n\
+             Shape \"sphere\"\n",
+        );
+        let scene = load(&path).unwrap();
+        // the sphere after the object is where the object didn't move it
+        assert_eq!(distance_at(&scene, 0.0), Some(9.0));
+        assert_eq!(distance_at(&scene, 5.0), None);
+        assert_eq!(distance_at(&scene, -5.0), None);
+        let warnings: Vec<&str> = scene
+            .warnings
+            .iter()
+            .map(|w| w.rsplit('/').next().unwrap())
+            .collect();
+        assert_eq!(
+            warnings,
+            [
+                "instance.pbrt:4: object instances aren't supported, their shapes are skipped",
+                "instance.pbrt:6: ObjectInstance is ignored"
+            ]
+        );
+    }
+}
@@ -0,0 +1,551 @@
+//! PLY meshes, in ASCII or binary of either byte order. Vertices may have
+//! normals (nx, ny, nz), texture coordinates (u, v or s, t) and colours
+//! (red, green, blue), and faces are polygons, split into triangles. Other
+//! properties and elements are skipped.
+
+use crate::mesh::Mesh;
+use crate::vector::Vector;
+use std::fs;
+use std::path::Path;
+
+pub fn load(path: &Path) -> Result<Mesh, String> {
+    let bytes =
+        fs::read(path).map_err(|e| format!("can't read PLY file {}: {}", path.display(), e))?;
+    parse(&bytes).map_err(|e| format!("{}: {}", path.display(), e))
+}
+
+pub fn parse(bytes: &[u8]) -> Result<Mesh, String> {
+    let (header, data) = split_header(bytes)?;
+    let (format, elements) = parse_header(header)?;
+    let vertex_count = elements
+        .iter()
+        .find(|e| e.name == "vertex")
+        .map(|e| e.count)
+        .ok_or("there is no vertex element")?;
+
+    let mut reader = match format {
+        Format::Ascii => Reader::Ascii {
+            tokens: std::str::from_utf8(data)
+                .map_err(|_| "the ASCII data isn't text")?
+                .split_whitespace(),
+        },
+        Format::Binary { big_endian } => Reader::Binary {
+            data,
+            position: 0,
+            big_endian,
+        },
+    };
+
+    let mut mesh = Mesh::default();
+    for element in &elements {
+        let is = |names: &[&str]| element.property(names);
+        match element.name.as_str() {
+            "vertex" => {
+                let position = [is(&["x"]), is(&["y"]), is(&["z"])];
+                let normal = [is(&["nx"]), is(&["ny"]), is(&["nz"])];
+                let uv = [
+                    is(&["u", "s", "texture_u", "texture_s"]),
+                    is(&["v", "t", "texture_v", "texture_t"]),
+                ];
+                let color = [
+                    is(&["red", "diffuse_red"]),
+                    is(&["green", "diffuse_green"]),
+                    is(&["blue", "diffuse_blue"]),
+                ];
+                let [Some(x), Some(y), Some(z)] = position else {
+                    return Err(String::from("vertices need x, y and z properties"));
+                };
+                let normal = match normal {
+                    [Some(x), Some(y), Some(z)] => Some([x, y, z]),
+                    _ => None,
+                };
+                let uv = match uv {
+                    [Some(u), Some(v)] => Some([u, v]),
+                    _ => None,
+                };
+                let color = match color {
+                    [Some(r), Some(g), Some(b)] => Some([r, g, b]),
+                    _ => None,
+                };
+
+                let mut values = vec![0.0; element.properties.len()];
+                for i in 0..element.count {
+                    reader
+                        .read_row(element, &mut values)
+                        .map_err(|e| format!("vertex {}: {}", i, e))?;
+                    let vector =
+                        |[a, b, c]: [usize; 3]| Vector::new(values[a], values[b], values[c]);
+                    mesh.positions.push(vector([x, y, z]));
+                    if let Some(normal) = normal {
+                        mesh.normals.push(vector(normal));
+                    }
+                    if let Some([u, v]) = uv {
+                        mesh.uvs.push((values[u], values[v]));
+                    }
+                    if let Some(color) = color {
+                        // integer channels go from 0 to their largest value
+                        let scale = |p: usize| match element.properties[p].kind {
+                            PropertyKind::Scalar(scalar) => scalar.full_scale(),
+                            PropertyKind::List { .. } => 1.0,
+                        };
+                        let [r, g, b] = color;
+                        mesh.colors.push(Vector::new(
+                            values[r] / scale(r),
+                            values[g] / scale(g),
+                            values[b] / scale(b),
+                        ));
+                    }
+                }
+            }
+            "face" => {
+                let indices = is(&["vertex_indices", "vertex_index"])
+                    .ok_or("faces need a vertex_indices list")?;
+                if !matches!(element.properties[indices].kind, PropertyKind::List { .. }) {
+                    return Err(String::from("vertex_indices should be a list"));
+                }
+                let mut values = vec![0.0; element.properties.len()];
+                let mut polygon = Vec::new();
+                for i in 0..element.count {
+                    let error = |e: String| format!("face {}: {}", i, e);
+                    reader
+                        .read_row_with_list(element, &mut values, indices, &mut polygon)
+                        .map_err(error)?;
+                    if polygon.len() < 3 {
+                        return Err(error(format!("it has only {} vertices", polygon.len())));
+                    }
+                    let mut corners = Vec::with_capacity(polygon.len());
+                    for &index in &polygon {
+                        if index < 0.0 || index.fract() != 0.0 || index >= vertex_count as f64 {
+                            return Err(error(format!(
+                                "vertex index {} isn't one of the {} vertices",
+                                index, vertex_count
+                            )));
+                        }
+                        corners.push(index as usize);
+                    }
+                    for k in 1..corners.len() - 1 {
+                        mesh.triangles
+                            .push([corners[0], corners[k], corners[k + 1]]);
+                    }
+                }
+            }
+            _ => {
+                let mut values = vec![0.0; element.properties.len()];
+                for i in 0..element.count {
+                    reader
+                        .read_row(element, &mut values)
+                        .map_err(|e| format!("{} {}: {}", element.name, i, e))?;
+                }
+            }
+        }
+    }
+    if mesh.triangles.is_empty() {
+        return Err(String::from("there are no faces"));
+    }
+    mesh.check()?;
+    Ok(mesh)
+}
+
+enum Format {
+    Ascii,
+    Binary { big_endian: bool },
+}
+
+#[derive(Copy, Clone)]
+enum Scalar {
+    I8,
+    U8,
+    I16,
+    U16,
+    I32,
+    U32,
+    F32,
+    F64,
+}
+
+impl Scalar {
+    fn parse(name: &str) -> Option<Scalar> {
+        Some(match name {
+            "char" | "int8" => Scalar::I8,
+            "uchar" | "uint8" => Scalar::U8,
+            "short" | "int16" => Scalar::I16,
+            "ushort" | "uint16" => Scalar::U16,
+            "int" | "int32" => Scalar::I32,
+            "uint" | "uint32" => Scalar::U32,
+            "float" | "float32" => Scalar::F32,
+            "double" | "float64" => Scalar::F64,
+            _ => return None,
+        })
+    }
+
+    fn size(self) -> usize {
+        match self {
+            Scalar::I8 | Scalar::U8 => 1,
+            Scalar::I16 | Scalar::U16 => 2,
+            Scalar::I32 | Scalar::U32 | Scalar::F32 => 4,
+            Scalar::F64 => 8,
+        }
+    }
+
+    // the value standing for 1, for colours
+    fn full_scale(self) -> f64 {
+        match self {
+            Scalar::I8 => i8::MAX as f64,
+            Scalar::U8 => u8::MAX as f64,
+            Scalar::I16 => i16::MAX as f64,
+            Scalar::U16 => u16::MAX as f64,
+            Scalar::I32 => i32::MAX as f64,
+            Scalar::U32 => u32::MAX as f64,
+            Scalar::F32 | Scalar::F64 => 1.0,
+        }
+    }
+}
+
+enum PropertyKind {
+    Scalar(Scalar),
+    List { count: Scalar, item: Scalar },
+}
+
+struct Property {
+    name: String,
+    kind: PropertyKind,
+}
+
+struct Element {
+    name: String,
+    count: usize,
+    properties: Vec<Property>,
+}
+
+impl Element {
+    // index of the first property going by one of these names
+    fn property(&self, names: &[&str]) -> Option<usize> {
+        names
+            .iter()
+            .find_map(|name| self.properties.iter().position(|p| p.name == *name))
+    }
+}
+
+// the header up to and including its end_header line, and the data after it
+fn split_header(bytes: &[u8]) -> Result<(&str, &[u8]), String> {
+    if !bytes.starts_with(b"ply") {
+        return Err(String::from(
+            "not a PLY file, it doesn't start with \"ply\"",
+        ));
+    }
+    let marker = b"end_header";
+    let start = bytes
+        .windows(marker.len())
+        .position(|w| w == marker)
+        .ok_or("the header has no end_header line")?;
+    let mut end = start + marker.len();
+    while end < bytes.len() && bytes[end] != b'\n' {
+        end += 1;
+    }
+    let header = std::str::from_utf8(&bytes[..end]).map_err(|_| "the header isn't text")?;
+    Ok((header, &bytes[(end + 1).min(bytes.len())..]))
+}
+
+fn parse_header(header: &str) -> Result<(Format, Vec<Element>), String> {
+    let mut format = None;
+    let mut elements: Vec<Element> = Vec::new();
+    for (number, line) in header.lines().enumerate() {
+        let error = |message: &str| {
+            format!(
+                "header line {}: {} in {:?}",
+                number + 1,
+                message,
+                line.trim()
+            )
+        };
+        let words: Vec<&str> = line.split_whitespace().collect();
+        match words.as_slice() {
+            ["ply"] | ["end_header"] | [] => {}
+            ["comment", ..] | ["obj_info", ..] => {}
+            ["format", kind, _version] => {
+                format = Some(match *kind {
+                    "ascii" => Format::Ascii,
+                    "binary_little_endian" => Format::Binary { big_endian: false },
+                    "binary_big_endian" => Format::Binary { big_endian: true },
+                    _ => return Err(error("unknown format")),
+                })
+            }
+            ["element", name, count] => elements.push(Element {
+                name: name.to_string(),
+                count: count.parse().map_err(|_| error("invalid element count"))?,
+                properties: Vec::new(),
+            }),
+            ["property", "list", count, item, name] => {
+                let element = elements
+                    .last_mut()
+                    .ok_or_else(|| error("property before any element"))?;
+                let count = Scalar::parse(count).ok_or_else(|| error("unknown type"))?;
+                let item = Scalar::parse(item).ok_or_else(|| error("unknown type"))?;
+                element.properties.push(Property {
+                    name: name.to_string(),
+                    kind: PropertyKind::List { count, item },
+                });
+            }
+            ["property", kind, name] => {
+                let element = elements
+                    .last_mut()
+                    .ok_or_else(|| error("property before any element"))?;
+                let kind = Scalar::parse(kind).ok_or_else(|| error("unknown type"))?;
+                element.properties.push(Property {
+                    name: name.to_string(),
+                    kind: PropertyKind::Scalar(kind),
+                });
+            }
+            _ => return Err(error("unexpected line")),
+        }
+    }
+    let format = format.ok_or("the header has no format line")?;
+    Ok((format, elements))
+}
+
+enum Reader<'a> {
+    Ascii {
+        tokens: std::str::SplitWhitespace<'a>,
+    },
+    Binary {
+        data: &'a [u8],
+        position: usize,
+        big_endian: bool,
+    },
+}
+
+impl Reader<'_> {
+    fn read(&mut self, scalar: Scalar) -> Result<f64, String> {
+        match self {
+            Reader::Ascii { tokens } => {
+                let token = tokens.next().ok_or("the file ends early")?;
+                token
+                    .parse::<f64>()
+                    .map_err(|_| format!("invalid number {:?}", token))
+            }
+            Reader::Binary {
+                data,
+                position,
+                big_endian,
+            } => {
+                let size = scalar.size();
+                let bytes = data
+                    .get(*position..*position + size)
+                    .ok_or("the file ends early")?;
+                *position += size;
+                let mut buffer = [0; 8];
+                buffer[..size].copy_from_slice(bytes);
+                if *big_endian {
+                    buffer[..size].reverse();
+                }
+                let four = [buffer[0], buffer[1], buffer[2], buffer[3]];
+                let two = [buffer[0], buffer[1]];
+                Ok(match scalar {
+                    Scalar::I8 => buffer[0] as i8 as f64,
+                    Scalar::U8 => buffer[0] as f64,
+                    Scalar::I16 => i16::from_le_bytes(two) as f64,
+                    Scalar::U16 => u16::from_le_bytes(two) as f64,
+                    Scalar::I32 => i32::from_le_bytes(four) as f64,
+                    Scalar::U32 => u32::from_le_bytes(four) as f64,
+                    Scalar::F32 => f32::from_le_bytes(four) as f64,
+                    Scalar::F64 => f64::from_le_bytes(buffer),
+                })
+            }
+        }
+    }
+
+    // reads one instance of the element, keeping its scalar values
+    fn read_row(&mut self, element: &Element, values: &mut [f64]) -> Result<(), String> {
+        let mut ignored = Vec::new();
+        self.read_row_with_list(element, values, usize::MAX, &mut ignored)
+    }
+
+    // like `read_row`, also keeping the items of the list property `list`
+    fn read_row_with_list(
+        &mut self,
+        element: &Element,
+        values: &mut [f64],
+        list: usize,
+        items: &mut Vec<f64>,
+    ) -> Result<(), String> {
+        items.clear();
+        for (i, property) in element.properties.iter().enumerate() {
+            match property.kind {
+                PropertyKind::Scalar(scalar) => values[i] = self.read(scalar)?,
+                PropertyKind::List { count, item } => {
+                    let n = self.read(count)?;
+                    if n < 0.0 || n.fract() != 0.0 {
+                        return Err(format!("invalid list length {}", n));
+                    }
+                    for _ in 0..n as usize {
+                        let value = self.read(item)?;
+                        if i == list {
+                            items.push(value);
+                        }
+                    }
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // a square of mixed property types, with an element and properties the
+    // mesh has no use for in between
+    const HEADER: &str = "element vertex 4
+property float x
+property double y
+property short z
+property uchar flags
+property ushort red
+property ushort green
+property ushort blue
+element edge 1
+property list uchar int vertices
+element face 1
+property list ushort uint vertex_indices
+property uchar group
+end_header
+";
+
+    const VERTEX: &[&str] = &[
+        "float", "double", "short", "uchar", "ushort", "ushort", "ushort",
+    ];
+
+    // the values of each row, with the types they have in `HEADER`
+    const ROWS: [(&[&str], &[f64]); 6] = [
+        (VERTEX, &[0.0, 0.0, 0.0, 7.0, 65535.0, 0.0, 0.0]),
+        (VERTEX, &[1.0, 0.0, 0.0, 7.0, 0.0, 65535.0, 0.0]),
+        (VERTEX, &[1.0, 1.0, 0.0, 7.0, 0.0, 0.0, 65535.0]),
+        (VERTEX, &[0.0, 1.0, -2.0, 7.0, 0.0, 0.0, 0.0]),
+        (&["uchar", "int", "int"], &[2.0, 0.0, 3.0]),
+        (
+            &["ushort", "uint", "uint", "uint", "uint", "uchar"],
+            &[4.0, 0.0, 1.0, 2.0, 3.0, 9.0],
+        ),
+    ];
+
+    // `ROWS` in a file of `format`
+    fn file(format: &str) -> Vec<u8> {
+        let mut bytes =
+            format!("ply\nformat {} 1.0\ncomment a square\n{}", format, HEADER).into_bytes();
+        let big_endian = format == "binary_big_endian";
+        for (types, values) in ROWS {
+            if format == "ascii" {
+                let words: Vec<String> = values.iter().map(|x| x.to_string()).collect();
+                bytes.extend(words.join(" ").bytes());
+                bytes.push(b'\n');
+                continue;
+            }
+            for (&kind, &x) in types.iter().zip(values) {
+                let mut value = match kind {
+                    "uchar" => vec![x as u8],
+                    "short" => (x as i16).to_le_bytes().to_vec(),
+                    "ushort" => (x as u16).to_le_bytes().to_vec(),
+                    "int" => (x as i32).to_le_bytes().to_vec(),
+                    "uint" => (x as u32).to_le_bytes().to_vec(),
+                    "float" => (x as f32).to_le_bytes().to_vec(),
+                    _ => x.to_le_bytes().to_vec(),
+                };
+                if big_endian {
+                    value.reverse();
+                }
+                bytes.extend(value);
+            }
+        }
+        bytes
+    }
+
+    fn square() -> Mesh {
+        Mesh {
+            positions: vec![
+                Vector::new(0.0, 0.0, 0.0),
+                Vector::new(1.0, 0.0, 0.0),
+                Vector::new(1.0, 1.0, 0.0),
+                Vector::new(0.0, 1.0, -2.0),
+            ],
+            colors: vec![
+                Vector::new(1.0, 0.0, 0.0),
+                Vector::new(0.0, 1.0, 0.0),
+                Vector::new(0.0, 0.0, 1.0),
+                Vector::new(0.0, 0.0, 0.0),
+            ],
+            triangles: vec![[0, 1, 2], [0, 2, 3]],
+            ..Mesh::default()
+        }
+    }
+
+    #[test]
+    fn reads_ascii_and_binary_alike() {
+        for format in ["ascii", "binary_little_endian", "binary_big_endian"] {
+            assert_eq!(parse(&file(format)).unwrap(), square(), "{}", format);
+        }
+    }
+
+    #[test]
+    fn reads_normals_and_texture_coordinates() {
+        let text = "ply
+format ascii 1.0
+element vertex 3
+property float x
+property float y
+property float z
+property float nx
+property float ny
+property float nz
+property float s
+property float t
+element face 1
+property list uchar int vertex_index
+end_header
+0 0 0 0 0 1 0 0
+1 0 0 0 0 1 1 0
+0 1 0 0 0 1 0 1
+3 0 1 2
+";
+        let mesh = parse(text.as_bytes()).unwrap();
+        assert_eq!(mesh.normals, vec![Vector::new(0.0, 0.0, 1.0); 3]);
+        assert_eq!(mesh.uvs, [(0.0, 0.0), (1.0, 0.0), (0.0, 1.0)]);
+        assert_eq!(mesh.triangles, [[0, 1, 2]]);
+    }
+
+    #[test]
+    fn rejects_truncated_files() {
+        for format in ["binary_little_endian", "binary_big_endian"] {
+            let bytes = file(format);
+            let error = parse(&bytes[..bytes.len() - 1]).unwrap_err();
+            assert_eq!(error, "face 0: the file ends early");
+            // in the middle of the second vertex, after 21 bytes of the
+            // first; the edge takes 9 bytes and the face 19
+            let data = bytes.len() - (4 * 21 + 9 + 19);
+            let error = parse(&bytes[..data + 30]).unwrap_err();
+            assert_eq!(error, "vertex 1: the file ends early");
+        }
+        let text = file("ascii");
+        let error = parse(&text[..text.len() - 4]).unwrap_err();
+        assert_eq!(error, "face 0: the file ends early");
+        let header = text.windows(10).position(|w| w == b"end_header").unwrap();
+        assert!(parse(&text[..header]).is_err());
+    }
+
+    #[test]
+    fn rejects_bad_values() {
+        let text = String::from_utf8(file("ascii")).unwrap();
+        let error = parse(text.replace("1 1 0 7", "1 1.x 0 7").as_bytes()).unwrap_err();
+        assert_eq!(error, "vertex 2: invalid number \"1.x\"");
+        let error = parse(text.replace("4 0 1 2 3", "4 0 1 2 4").as_bytes()).unwrap_err();
+        assert_eq!(error, "face 0: vertex index 4 isn't one of the 4 vertices");
+        let error = parse(text.replace("4 0 1 2 3", "2.5 0 1 2").as_bytes()).unwrap_err();
+        assert_eq!(error, "face 0: invalid list length 2.5");
+        let error = parse(text.replace("double y", "decimal y").as_bytes()).unwrap_err();
+        assert!(
+            error.starts_with("header line 6: unknown type"),
+            "{}",
+            error
+        );
+    }
+}
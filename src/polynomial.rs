@@ -0,0 +1,178 @@
+use std::f64::consts::PI;
+
+/// Real roots of a x^2 + b x + c in increasing order, None if there are none
+/// or a is 0. Avoids the cancellation of the textbook formula.
+pub fn solve_quadratic(a: f64, b: f64, c: f64) -> Option<(f64, f64)> {
+    if a == 0.0 {
+        return None;
+    }
+    let discriminant = b * b - 4.0 * a * c;
+    if discriminant < 0.0 {
+        return None;
+    }
+    let q = -0.5 * (b + b.signum() * discriminant.sqrt());
+    let (x0, x1) = if q == 0.0 {
+        // b and c are both 0
+        (0.0, 0.0)
+    } else {
+        (q / a, c / q)
+    };
+    Some((x0.min(x1), x0.max(x1)))
+}
+
+/// Real roots of x^3 + a x^2 + b x + c, in no particular order.
+pub fn solve_cubic(a: f64, b: f64, c: f64) -> Vec<f64> {
+    let q = (a * a - 3.0 * b) / 9.0;
+    let r = (2.0 * a * a * a - 9.0 * a * b + 27.0 * c) / 54.0;
+    if r * r < q * q * q {
+        // three real roots
+        let theta = (r / (q * q * q).sqrt()).clamp(-1.0, 1.0).acos();
+        let s = -2.0 * q.sqrt();
+        (0..3)
+            .map(|k| s * ((theta + 2.0 * PI * k as f64) / 3.0).cos() - a / 3.0)
+            .collect()
+    } else {
+        let big_a = -r.signum() * (r.abs() + (r * r - q * q * q).sqrt()).cbrt();
+        let big_b = if big_a == 0.0 { 0.0 } else { q / big_a };
+        vec![big_a + big_b - a / 3.0]
+    }
+}
+
+/// Real roots of a x^4 + b x^3 + c x^2 + d x + e in increasing order, by
+/// Ferrari's method, then polished with a few Newton steps since the closed
+/// form loses a lot of precision.
+pub fn solve_quartic(a: f64, b: f64, c: f64, d: f64, e: f64) -> Vec<f64> {
+    if a == 0.0 {
+        return Vec::new();
+    }
+    let (b, c, d, e) = (b / a, c / a, d / a, e / a);
+    // y^4 + p y^2 + q y + r with x = y - b / 4
+    let b2 = b * b;
+    let p = c - 3.0 * b2 / 8.0;
+    let q = d - b * c / 2.0 + b2 * b / 8.0;
+    let r = e - b * d / 4.0 + b2 * c / 16.0 - 3.0 * b2 * b2 / 256.0;
+
+    let mut roots = Vec::with_capacity(4);
+    let mut push_quadratic = |qb: f64, qc: f64| {
+        if let Some((y0, y1)) = solve_quadratic(1.0, qb, qc) {
+            roots.push(y0);
+            roots.push(y1);
+        }
+    };
+    if q.abs() < 1e-12 {
+        // biquadratic, y^2 is a root of z^2 + p z + r
+        if let Some((z0, z1)) = solve_quadratic(1.0, p, r) {
+            for z in [z0, z1] {
+                if z >= 0.0 {
+                    push_quadratic(0.0, -z);
+                }
+            }
+        }
+    } else {
+        // the resolvent cubic 8m^3 + 8pm^2 + (2p^2 - 8r)m - q^2 has a positive
+        // root, which splits the quartic into two quadratics
+        let m = solve_cubic(p, p * p / 4.0 - r, -q * q / 8.0)
+            .into_iter()
+            .fold(f64::NEG_INFINITY, f64::max);
+        if m <= 0.0 {
+            return Vec::new();
+        }
+        let s = (2.0 * m).sqrt();
+        push_quadratic(-s, p / 2.0 + m + q / (2.0 * s));
+        push_quadratic(s, p / 2.0 + m - q / (2.0 * s));
+    }
+
+    let f = |x: f64| (((x + b) * x + c) * x + d) * x + e;
+    let df = |x: f64| ((4.0 * x + 3.0 * b) * x + 2.0 * c) * x + d;
+    let mut roots: Vec<f64> = roots
+        .into_iter()
+        .map(|y| {
+            let mut x = y - b / 4.0;
+            for _ in 0..3 {
+                let slope = df(x);
+                if slope == 0.0 {
+                    break;
+                }
+                x -= f(x) / slope;
+            }
+            x
+        })
+        .collect();
+    roots.sort_by(f64::total_cmp);
+    roots
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // the coefficients of the quartic with these roots
+    fn quartic(roots: [f64; 4]) -> [f64; 5] {
+        let mut coefficients = [1.0, 0.0, 0.0, 0.0, 0.0];
+        for (i, root) in roots.iter().enumerate() {
+            for j in (1..=i + 1).rev() {
+                coefficients[j] -= root * coefficients[j - 1];
+            }
+        }
+        coefficients
+    }
+
+    fn solve(roots: [f64; 4]) -> Vec<f64> {
+        let [a, b, c, d, e] = quartic(roots);
+        solve_quartic(a, b, c, d, e)
+    }
+
+    fn assert_close(found: &[f64], expected: &[f64], tolerance: f64) {
+        assert_eq!(found.len(), expected.len(), "{:?} vs {:?}", found, expected);
+        for (x, y) in found.iter().zip(expected) {
+            assert!((x - y).abs() < tolerance, "{:?} vs {:?}", found, expected);
+        }
+    }
+
+    #[test]
+    fn finds_distinct_roots() {
+        assert_close(&solve([3.0, -2.0, 0.5, 1.0]), &[-2.0, 0.5, 1.0, 3.0], 1e-12);
+        assert_close(
+            &solve([100.0, 100.5, -0.25, 7.0]),
+            &[-0.25, 7.0, 100.0, 100.5],
+            1e-9,
+        );
+        // biquadratic, with no odd powers
+        assert_close(
+            &solve([-2.0, -1.0, 1.0, 2.0]),
+            &[-2.0, -1.0, 1.0, 2.0],
+            1e-12,
+        );
+        // scaled, and with two roots
+        assert_close(
+            &solve_quartic(2.0, 0.0, -2.0, 0.0, -4.0),
+            &[-2f64.sqrt(), 2f64.sqrt()],
+            1e-12,
+        );
+        assert!(solve_quartic(1.0, 0.0, 5.0, 0.0, 4.0).is_empty());
+        assert!(solve_quartic(0.0, 1.0, 1.0, 1.0, 1.0).is_empty());
+    }
+
+    #[test]
+    fn finds_double_roots() {
+        // a double root is only good to about the square root of the
+        // precision
+        assert_close(&solve([1.0, 1.0, -3.0, 2.0]), &[-3.0, 1.0, 1.0, 2.0], 1e-6);
+        assert_close(
+            &solve([-1.0, -1.0, 4.0, 4.0]),
+            &[-1.0, -1.0, 4.0, 4.0],
+            1e-6,
+        );
+    }
+
+    #[test]
+    fn finds_nearly_tangent_roots() {
+        // like a ray grazing a torus
+        let gap = 1e-4;
+        assert_close(
+            &solve([5.0 - gap, 5.0 + gap, -1.0, 9.0]),
+            &[-1.0, 5.0 - gap, 5.0 + gap, 9.0],
+            1e-7,
+        );
+    }
+}
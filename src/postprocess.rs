@@ -0,0 +1,291 @@
+use crate::tracer::{clamp, mix_seed};
+use crate::vector::Vector;
+use std::str::FromStr;
+
+/// Tone mapping operators, applied to linear scene referred radiance after
+/// exposure and white balance.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum ToneMap {
+    // hard clip to [0, 1]
+    Clamp,
+    // c / (1 + c) per channel
+    Reinhard,
+    // luminance based Reinhard that maps `white` (and anything above it) to 1
+    ExtendedReinhard { white: f64 },
+    // Stephen Hill's fit of the ACES reference rendering and output transforms
+    Aces,
+    // Troy Sobotka's AgX base look, using the polynomial fit of its sigmoid
+    AgX,
+    // John Hable's filmic curve from Uncharted 2
+    Uncharted2,
+}
+
+impl FromStr for ToneMap {
+    type Err = String;
+
+    // "extended-reinhard" takes an optional white point, e.g. "extended-reinhard=8"
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        if let Some(white) = s.strip_prefix("extended-reinhard=") {
+            return match white.parse::<f64>() {
+                Ok(white) if white > 0.0 => Ok(ToneMap::ExtendedReinhard { white }),
+                _ => Err(format!("invalid white point {:?}", white)),
+            };
+        }
+        match s {
+            "clamp" | "none" => Ok(ToneMap::Clamp),
+            "reinhard" => Ok(ToneMap::Reinhard),
+            "extended-reinhard" => Ok(ToneMap::ExtendedReinhard { white: 4.0 }),
+            "aces" => Ok(ToneMap::Aces),
+            "agx" => Ok(ToneMap::AgX),
+            "uncharted2" => Ok(ToneMap::Uncharted2),
+            _ => Err(format!(
+                "unknown tone map {:?}, expected one of clamp, reinhard, extended-reinhard, aces, agx, uncharted2",
+                s
+            )),
+        }
+    }
+}
+
+/// Turns the linear radiance in the film into display ready 8 bit sRGB.
+#[derive(Copy, Clone, Debug)]
+pub struct PostProcess {
+    // exposure compensation in stops, radiance is scaled by 2^exposure
+    pub exposure: f64,
+    // color temperature in kelvin of the light that should appear neutral
+    pub white_balance: f64,
+    // green/magenta shift on top of the temperature, around [-1, 1]
+    pub tint: f64,
+    pub tone_map: ToneMap,
+    // add triangular noise of one quantization step to hide banding
+    pub dither: bool,
+}
+
+impl Default for PostProcess {
+    fn default() -> Self {
+        PostProcess {
+            exposure: 0.0,
+            white_balance: D65_TEMPERATURE,
+            tint: 0.0,
+            tone_map: ToneMap::Clamp,
+            dither: false,
+        }
+    }
+}
+
+impl PostProcess {
+    /// Exposure, white balance and tone mapping. Returns linear display referred
+    /// values in [0, 1].
+    pub fn apply(&self, color: Vector) -> Vector {
+        let color = color.scale(2f64.powf(self.exposure));
+        let color = white_balance(color, self.white_balance, self.tint);
+        let color = match self.tone_map {
+            ToneMap::Clamp => color,
+            ToneMap::Reinhard => map(color, |c| c / (1.0 + c)),
+            ToneMap::ExtendedReinhard { white } => extended_reinhard(color, white),
+            ToneMap::Aces => aces_fitted(color),
+            ToneMap::AgX => agx(color),
+            ToneMap::Uncharted2 => uncharted2(color),
+        };
+        map(color, |c| clamp(c, 0.0, 1.0))
+    }
+
+    /// Full pipeline for the pixel at (x, y): tone mapping, the sRGB transfer
+    /// function, optional dithering and quantization. The dither pattern only
+    /// depends on the pixel position so rewriting an image is stable.
+    pub fn to_rgb8(&self, color: Vector, x: usize, y: usize) -> [u8; 3] {
+        let color = map(self.apply(color), srgb_oetf);
+        let mut rgb = [0u8; 3];
+        for (channel, (value, out)) in [color.x(), color.y(), color.z()]
+            .iter()
+            .zip(rgb.iter_mut())
+            .enumerate()
+        {
+            let noise = if self.dither {
+                let seed = mix_seed(((y as u64) << 32) | x as u64, channel as u64);
+                // sum of two uniforms in [-0.5, 0.5) is triangular in [-1, 1)
+                unit_float(seed) + unit_float(mix_seed(seed, 1)) - 1.0
+            } else {
+                0.0
+            };
+            *out = clamp((value * 255.0 + noise).round(), 0.0, 255.0) as u8;
+        }
+        rgb
+    }
+}
+
+fn unit_float(seed: u64) -> f64 {
+    (seed >> 11) as f64 / (1u64 << 53) as f64
+}
+
+fn map(v: Vector, f: impl Fn(f64) -> f64) -> Vector {
+    Vector::new(f(v.x()), f(v.y()), f(v.z()))
+}
+
+fn luminance(c: Vector) -> f64 {
+    0.2126 * c.x() + 0.7152 * c.y() + 0.0722 * c.z()
+}
+
+// 3x3 matrices are stored row major
+fn mul(m: &[[f64; 3]; 3], v: Vector) -> Vector {
+    Vector::new(
+        m[0][0] * v.x() + m[0][1] * v.y() + m[0][2] * v.z(),
+        m[1][0] * v.x() + m[1][1] * v.y() + m[1][2] * v.z(),
+        m[2][0] * v.x() + m[2][1] * v.y() + m[2][2] * v.z(),
+    )
+}
+
+/// The piecewise sRGB transfer function (IEC 61966-2-1).
+pub fn srgb_oetf(c: f64) -> f64 {
+    if c <= 0.003_130_8 {
+        12.92 * c
+    } else {
+        1.055 * c.powf(1.0 / 2.4) - 0.055
+    }
+}
+
+/// Inverse of `srgb_oetf`, for decoding 8 bit images into linear values.
+pub fn srgb_eotf(c: f64) -> f64 {
+    if c <= 0.040_45 {
+        c / 12.92
+    } else {
+        ((c + 0.055) / 1.055).powf(2.4)
+    }
+}
+
+fn extended_reinhard(color: Vector, white: f64) -> Vector {
+    let l = luminance(color);
+    if l <= 0.0 {
+        return Vector::default();
+    }
+    let mapped = l * (1.0 + l / (white * white)) / (1.0 + l);
+    color.scale(mapped / l)
+}
+
+fn aces_fitted(color: Vector) -> Vector {
+    // sRGB => XYZ => D65_2_D60 => AP1 => RRT_SAT
+    const INPUT: [[f64; 3]; 3] = [
+        [0.59719, 0.35458, 0.04823],
+        [0.07600, 0.90834, 0.01566],
+        [0.02840, 0.13383, 0.83777],
+    ];
+    // ODT_SAT => XYZ => D60_2_D65 => sRGB
+    const OUTPUT: [[f64; 3]; 3] = [
+        [1.60475, -0.53108, -0.07367],
+        [-0.10208, 1.10813, -0.00605],
+        [-0.00327, -0.07276, 1.07602],
+    ];
+    let v = mul(&INPUT, color);
+    let v = map(v, |c| {
+        let a = c * (c + 0.024_578_6) - 0.000_090_537;
+        let b = c * (0.983_729 * c + 0.432_951_0) + 0.238_081;
+        a / b
+    });
+    mul(&OUTPUT, v)
+}
+
+fn agx(color: Vector) -> Vector {
+    const INSET: [[f64; 3]; 3] = [
+        [0.842_479_062_3, 0.078_433_599_9, 0.079_223_745_1],
+        [0.042_328_242_2, 0.878_468_636_5, 0.079_166_127_4],
+        [0.042_375_654_9, 0.078_433_600_0, 0.879_142_973_8],
+    ];
+    const OUTSET: [[f64; 3]; 3] = [
+        [1.196_879_005_1, -0.098_020_881_1, -0.099_029_744_3],
+        [-0.052_896_851_7, 1.151_903_129_9, -0.098_961_176_8],
+        [-0.052_971_635_5, -0.098_043_450_1, 1.151_073_672_2],
+    ];
+    const MIN_EV: f64 = -12.473_931_188;
+    const MAX_EV: f64 = 4.026_068_812;
+
+    let v = mul(&INSET, color);
+    let v = map(v, |c| {
+        let c = clamp(c.max(1e-10).log2(), MIN_EV, MAX_EV);
+        let x = (c - MIN_EV) / (MAX_EV - MIN_EV);
+        // 6th order polynomial approximation of the AgX sigmoid
+        let x2 = x * x;
+        let x4 = x2 * x2;
+        15.5 * x4 * x2 - 40.14 * x4 * x + 31.96 * x4 - 6.868 * x2 * x + 0.4298 * x2 + 0.1191 * x
+            - 0.002_32
+    });
+    // the sigmoid output is display encoded with a 2.2 power, bring it back to linear
+    map(mul(&OUTSET, v), |c| c.max(0.0).powf(2.2))
+}
+
+fn uncharted2(color: Vector) -> Vector {
+    fn partial(x: f64) -> f64 {
+        let (a, b, c, d, e, f) = (0.15, 0.50, 0.10, 0.20, 0.02, 0.30);
+        ((x * (a * x + c * b) + d * e) / (x * (a * x + b) + d * f)) - e / f
+    }
+    let exposure_bias = 2.0;
+    let white_scale = 1.0 / partial(11.2);
+    map(color, |c| partial(c * exposure_bias) * white_scale)
+}
+
+const D65_TEMPERATURE: f64 = 6504.0;
+
+const BRADFORD: [[f64; 3]; 3] = [
+    [0.8951, 0.2664, -0.1614],
+    [-0.7502, 1.7135, 0.0367],
+    [0.0389, -0.0685, 1.0296],
+];
+
+// chromaticity of a black body at `temperature` kelvin (Kim et al. cubic spline fit)
+fn planckian_xy(temperature: f64) -> (f64, f64) {
+    let t = clamp(temperature, 1667.0, 25000.0);
+    let (t2, t3) = (t * t, t * t * t);
+    let x = if t <= 4000.0 {
+        -0.266_123_9e9 / t3 - 0.234_358_9e6 / t2 + 0.877_695_6e3 / t + 0.179_910
+    } else {
+        -3.025_846_9e9 / t3 + 2.107_037_9e6 / t2 + 0.222_634_7e3 / t + 0.240_390
+    };
+    let (x2, x3) = (x * x, x * x * x);
+    let y = if t <= 2222.0 {
+        -1.106_381_4 * x3 - 1.348_110_20 * x2 + 2.185_558_32 * x - 0.202_196_83
+    } else if t <= 4000.0 {
+        -0.954_947_6 * x3 - 1.374_185_93 * x2 + 2.091_370_15 * x - 0.167_488_67
+    } else {
+        3.081_758_0 * x3 - 5.873_386_70 * x2 + 3.751_129_97 * x - 0.370_014_83
+    };
+    (x, y)
+}
+
+// Bradford cone response of a black body white, with the tint shifting it
+// along the green/magenta axis.
+fn white_cone_response(temperature: f64, tint: f64) -> Vector {
+    let (x, y) = planckian_xy(temperature);
+    let y = y + tint * 0.05;
+    let xyz = Vector::new(x / y, 1.0, (1.0 - x - y) / y);
+    mul(&BRADFORD, xyz)
+}
+
+// Von Kries adaptation in Bradford space, so that light of the given temperature
+// maps to the D65 white of sRGB.
+fn white_balance(color: Vector, temperature: f64, tint: f64) -> Vector {
+    if temperature == D65_TEMPERATURE && tint == 0.0 {
+        return color;
+    }
+    const RGB_TO_XYZ: [[f64; 3]; 3] = [
+        [0.412_456_4, 0.357_576_1, 0.180_437_5],
+        [0.212_672_9, 0.715_152_2, 0.072_175_0],
+        [0.019_333_9, 0.119_192_0, 0.950_304_1],
+    ];
+    const XYZ_TO_RGB: [[f64; 3]; 3] = [
+        [3.240_454_2, -1.537_138_5, -0.498_531_4],
+        [-0.969_266_0, 1.876_010_8, 0.041_556_0],
+        [0.055_643_4, -0.204_025_9, 1.057_225_2],
+    ];
+    const BRADFORD_INVERSE: [[f64; 3]; 3] = [
+        [0.986_992_9, -0.147_054_3, 0.159_962_7],
+        [0.432_305_3, 0.518_360_3, 0.049_291_2],
+        [-0.008_528_7, 0.040_042_8, 0.968_486_7],
+    ];
+    let source = white_cone_response(temperature, tint);
+    let target = white_cone_response(D65_TEMPERATURE, 0.0);
+    let lms = mul(&BRADFORD, mul(&RGB_TO_XYZ, color));
+    let lms = Vector::new(
+        lms.x() * target.x() / source.x(),
+        lms.y() * target.y() / source.y(),
+        lms.z() * target.z() / source.z(),
+    );
+    mul(&XYZ_TO_RGB, mul(&BRADFORD_INVERSE, lms))
+}
@@ -0,0 +1,702 @@
+//! Surfaces of revolution around the y axis, in their own space: place them in
+//! a scene with `Transformed`. Every one can be swept partially around the
+//! axis with `with_phi_max`, phi going from +x toward +z, and gives u = phi /
+//! phi_max and v along its profile. Cylinders and tori are also `Solid`s for
+//! CSG, always capped and whole as solids.
+
+use crate::aabb::Aabb;
+use crate::csg::{Interval, Solid};
+use crate::hitable::{HitRecord, Hitable};
+use crate::material::Material;
+use crate::polynomial::{solve_quadratic, solve_quartic};
+use crate::ray::Ray;
+use crate::vector::Vector;
+use std::f64::consts::PI;
+
+// angle of p around the y axis, in 0..2 pi
+fn phi(p: Vector) -> f64 {
+    let phi = p.z().atan2(p.x());
+    if phi < 0.0 {
+        phi + 2.0 * PI
+    } else {
+        phi
+    }
+}
+
+fn phi_max(degrees: f64) -> f64 {
+    degrees.clamp(0.0, 360.0).to_radians()
+}
+
+// the box of a surface of revolution reaching `radius` from the axis
+fn revolution_bounds(radius: f64, y0: f64, y1: f64) -> Aabb {
+    Aabb::new(
+        Vector::new(-radius, y0.min(y1), -radius),
+        Vector::new(radius, y0.max(y1), radius),
+    )
+}
+
+// The nearest of the hits of a ray on the quadric with coefficients (a, b, c)
+// for t that `accept` keeps, as the point and its (u, v).
+fn nearest_root(
+    ray: &Ray,
+    (a, b, c): (f64, f64, f64),
+    t_min: f64,
+    t_max: f64,
+    accept: impl Fn(Vector) -> Option<(f64, f64)>,
+) -> Option<(f64, Vector, (f64, f64))> {
+    let (t0, t1) = if a == 0.0 {
+        // rays parallel to the paraboloid's axis only cross it once
+        if b == 0.0 {
+            return None;
+        }
+        (-c / b, -c / b)
+    } else {
+        solve_quadratic(a, b, c)?
+    };
+    [t0, t1]
+        .iter()
+        .copied()
+        .filter(|t| *t > t_min && *t < t_max)
+        .find_map(|t| {
+            let p = ray.at(t);
+            accept(p).map(|uv| (t, p, uv))
+        })
+}
+
+// hit on the disk cap at height y, facing up or down, between t_min and t_max
+fn cap<'a>(
+    ray: &Ray,
+    y: f64,
+    radius: f64,
+    phi_max: f64,
+    up: bool,
+    (t_min, t_max): (f64, f64),
+    material: &'a dyn Material,
+) -> Option<HitRecord<'a>> {
+    let dy = ray.direction().y();
+    if dy == 0.0 {
+        return None;
+    }
+    let t = (y - ray.origin().y()) / dy;
+    if t <= t_min || t >= t_max {
+        return None;
+    }
+    let p = ray.at(t);
+    let distance = (p.x() * p.x() + p.z() * p.z()).sqrt();
+    if distance > radius || phi(p) > phi_max {
+        return None;
+    }
+    let normal = Vector::new(0.0, if up { 1.0 } else { -1.0 }, 0.0);
+    Some(HitRecord::new(t, p, normal, material).with_uv(phi(p) / phi_max, distance / radius))
+}
+
+/// Cylinder of `radius` from y0 to y1, open unless `with_caps` closes it.
+pub struct Cylinder<M: Material> {
+    radius: f64,
+    y0: f64,
+    y1: f64,
+    phi_max: f64,
+    capped: bool,
+    material: M,
+}
+
+impl<M: Material> Cylinder<M> {
+    pub fn new(radius: f64, y0: f64, y1: f64, material: M) -> Self {
+        Cylinder {
+            radius,
+            y0: y0.min(y1),
+            y1: y0.max(y1),
+            phi_max: 2.0 * PI,
+            capped: false,
+            material,
+        }
+    }
+
+    pub fn with_phi_max(self, degrees: f64) -> Self {
+        Cylinder {
+            phi_max: phi_max(degrees),
+            ..self
+        }
+    }
+
+    pub fn with_caps(self) -> Self {
+        Cylinder {
+            capped: true,
+            ..self
+        }
+    }
+}
+
+impl<M: Material + Sync> Hitable for Cylinder<M> {
+    fn hit(&self, ray: &Ray, t_min: f64, mut t_max: f64) -> Option<HitRecord<'_>> {
+        let (o, d) = (ray.origin(), ray.direction());
+        let coefficients = (
+            d.x() * d.x() + d.z() * d.z(),
+            2.0 * (o.x() * d.x() + o.z() * d.z()),
+            o.x() * o.x() + o.z() * o.z() - self.radius * self.radius,
+        );
+        let side = nearest_root(ray, coefficients, t_min, t_max, |p| {
+            let phi = phi(p);
+            if p.y() < self.y0 || p.y() > self.y1 || phi > self.phi_max {
+                return None;
+            }
+            Some((phi / self.phi_max, (p.y() - self.y0) / (self.y1 - self.y0)))
+        });
+        let mut hit = side.map(|(t, p, (u, v))| {
+            let normal = Vector::new(p.x(), 0.0, p.z()) / self.radius;
+            HitRecord::new(t, p, normal, &self.material).with_uv(u, v)
+        });
+        if self.capped {
+            for (y, up) in [(self.y0, false), (self.y1, true)] {
+                if let Some(h) = &hit {
+                    t_max = h.t;
+                }
+                let range = (t_min, t_max);
+                if let Some(h) = cap(ray, y, self.radius, self.phi_max, up, range, &self.material) {
+                    hit = Some(h);
+                }
+            }
+        }
+        hit
+    }
+
+    fn bounding_box(&self, _time0: f64, _time1: f64) -> Option<Aabb> {
+        Some(revolution_bounds(self.radius, self.y0, self.y1))
+    }
+}
+
+// As a solid a cylinder is always closed and whole: the caps and phi_max
+// only change its surface.
+impl<M: Material + Sync> Solid for Cylinder<M> {
+    // The line is inside the cylinder while it's both within the radius and
+    // between the caps' planes, like the slabs of a box.
+    fn intervals(&self, ray: &Ray) -> Vec<Interval<'_>> {
+        let (o, d) = (ray.origin(), ray.direction());
+        let (a, b, c) = (
+            d.x() * d.x() + d.z() * d.z(),
+            2.0 * (o.x() * d.x() + o.z() * d.z()),
+            o.x() * o.x() + o.z() * o.z() - self.radius * self.radius,
+        );
+        let side = if a == 0.0 {
+            if c >= 0.0 {
+                return Vec::new();
+            }
+            (f64::NEG_INFINITY, f64::INFINITY)
+        } else {
+            match solve_quadratic(a, b, c) {
+                Some(roots) => roots,
+                None => return Vec::new(),
+            }
+        };
+        let caps = if d.y() == 0.0 {
+            if o.y() < self.y0 || o.y() > self.y1 {
+                return Vec::new();
+            }
+            (f64::NEG_INFINITY, f64::INFINITY)
+        } else {
+            let (t0, t1) = ((self.y0 - o.y()) / d.y(), (self.y1 - o.y()) / d.y());
+            (t0.min(t1), t0.max(t1))
+        };
+        let (enter, exit) = (side.0.max(caps.0), side.1.min(caps.1));
+        if enter >= exit {
+            return Vec::new();
+        }
+        let record = |t: f64, on_side: bool| {
+            let p = ray.at(t);
+            let u = phi(p) / self.phi_max;
+            let material = &self.material as &dyn Material;
+            if on_side {
+                let normal = Vector::new(p.x(), 0.0, p.z()) / self.radius;
+                let v = (p.y() - self.y0) / (self.y1 - self.y0);
+                HitRecord::new(t, p, normal, material).with_uv(u, v)
+            } else {
+                let up = 2.0 * p.y() > self.y0 + self.y1;
+                let normal = Vector::new(0.0, if up { 1.0 } else { -1.0 }, 0.0);
+                let v = (p.x() * p.x() + p.z() * p.z()).sqrt() / self.radius;
+                HitRecord::new(t, p, normal, material).with_uv(u, v)
+            }
+        };
+        vec![Interval {
+            enter: record(enter, side.0 >= caps.0),
+            exit: record(exit, side.1 <= caps.1),
+        }]
+    }
+}
+
+/// Cone with a base of `radius` at y = 0 and its tip at y = height, open
+/// at the base unless `with_cap` closes it.
+pub struct Cone<M: Material> {
+    radius: f64,
+    height: f64,
+    phi_max: f64,
+    capped: bool,
+    material: M,
+}
+
+impl<M: Material> Cone<M> {
+    pub fn new(radius: f64, height: f64, material: M) -> Self {
+        Cone {
+            radius,
+            height,
+            phi_max: 2.0 * PI,
+            capped: false,
+            material,
+        }
+    }
+
+    pub fn with_phi_max(self, degrees: f64) -> Self {
+        Cone {
+            phi_max: phi_max(degrees),
+            ..self
+        }
+    }
+
+    pub fn with_cap(self) -> Self {
+        Cone {
+            capped: true,
+            ..self
+        }
+    }
+}
+
+impl<M: Material + Sync> Hitable for Cone<M> {
+    // x^2 + z^2 = k (height - y)^2
+    fn hit(&self, ray: &Ray, t_min: f64, t_max: f64) -> Option<HitRecord<'_>> {
+        let (o, d) = (ray.origin(), ray.direction());
+        let k = (self.radius / self.height).powi(2);
+        let h = self.height - o.y();
+        let coefficients = (
+            d.x() * d.x() + d.z() * d.z() - k * d.y() * d.y(),
+            2.0 * (o.x() * d.x() + o.z() * d.z() + k * h * d.y()),
+            o.x() * o.x() + o.z() * o.z() - k * h * h,
+        );
+        let side = nearest_root(ray, coefficients, t_min, t_max, |p| {
+            let phi = phi(p);
+            if p.y() < 0.0 || p.y() > self.height || phi > self.phi_max {
+                return None;
+            }
+            Some((phi / self.phi_max, p.y() / self.height))
+        });
+        let hit = side.map(|(t, p, (u, v))| {
+            let normal = Vector::new(p.x(), k * (self.height - p.y()), p.z()).unit();
+            HitRecord::new(t, p, normal, &self.material).with_uv(u, v)
+        });
+        if !self.capped {
+            return hit;
+        }
+        let t_max = hit.as_ref().map_or(t_max, |h| h.t);
+        let range = (t_min, t_max);
+        let base = cap(
+            ray,
+            0.0,
+            self.radius,
+            self.phi_max,
+            false,
+            range,
+            &self.material,
+        );
+        base.or(hit)
+    }
+
+    fn bounding_box(&self, _time0: f64, _time1: f64) -> Option<Aabb> {
+        Some(revolution_bounds(self.radius, 0.0, self.height))
+    }
+}
+
+/// Paraboloid y = y1 (x^2 + z^2) / radius^2, cut between y0 and y1, where it
+/// is `radius` wide: a dish opening upward.
+pub struct Paraboloid<M: Material> {
+    radius: f64,
+    y0: f64,
+    y1: f64,
+    phi_max: f64,
+    material: M,
+}
+
+impl<M: Material> Paraboloid<M> {
+    /// A rim at or below 0, where the dish would be flat or upside down,
+    /// is moved to 0, and like a dish cut to nothing by y0 can't be hit.
+    pub fn new(radius: f64, y0: f64, y1: f64, material: M) -> Self {
+        let y1 = y1.max(0.0);
+        Paraboloid {
+            radius: radius.abs(),
+            y0: y0.max(0.0).min(y1),
+            y1,
+            phi_max: 2.0 * PI,
+            material,
+        }
+    }
+
+    pub fn with_phi_max(self, degrees: f64) -> Self {
+        Paraboloid {
+            phi_max: phi_max(degrees),
+            ..self
+        }
+    }
+}
+
+impl<M: Material + Sync> Hitable for Paraboloid<M> {
+    // k (x^2 + z^2) - y = 0
+    fn hit(&self, ray: &Ray, t_min: f64, t_max: f64) -> Option<HitRecord<'_>> {
+        if self.y0 >= self.y1 || self.radius == 0.0 {
+            return None;
+        }
+        let (o, d) = (ray.origin(), ray.direction());
+        let k = self.y1 / (self.radius * self.radius);
+        let coefficients = (
+            k * (d.x() * d.x() + d.z() * d.z()),
+            2.0 * k * (o.x() * d.x() + o.z() * d.z()) - d.y(),
+            k * (o.x() * o.x() + o.z() * o.z()) - o.y(),
+        );
+        let (t, p, (u, v)) = nearest_root(ray, coefficients, t_min, t_max, |p| {
+            let phi = phi(p);
+            if p.y() < self.y0 || p.y() > self.y1 || phi > self.phi_max {
+                return None;
+            }
+            Some((phi / self.phi_max, (p.y() - self.y0) / (self.y1 - self.y0)))
+        })?;
+        // out of the dish, on its convex side
+        let normal = Vector::new(2.0 * k * p.x(), -1.0, 2.0 * k * p.z()).unit();
+        Some(HitRecord::new(t, p, normal, &self.material).with_uv(u, v))
+    }
+
+    fn bounding_box(&self, _time0: f64, _time1: f64) -> Option<Aabb> {
+        Some(revolution_bounds(self.radius, self.y0, self.y1))
+    }
+}
+
+/// Hyperboloid of one sheet, `radius` wide at its waist at y = 0 and widening
+/// to radius * sqrt(1 + y^2 / c^2) at y, like a cooling tower. It is cut between
+/// y0 and y1.
+pub struct Hyperboloid<M: Material> {
+    radius: f64,
+    c: f64,
+    y0: f64,
+    y1: f64,
+    phi_max: f64,
+    material: M,
+}
+
+impl<M: Material> Hyperboloid<M> {
+    pub fn new(radius: f64, c: f64, y0: f64, y1: f64, material: M) -> Self {
+        Hyperboloid {
+            radius,
+            c,
+            y0: y0.min(y1),
+            y1: y0.max(y1),
+            phi_max: 2.0 * PI,
+            material,
+        }
+    }
+
+    pub fn with_phi_max(self, degrees: f64) -> Self {
+        Hyperboloid {
+            phi_max: phi_max(degrees),
+            ..self
+        }
+    }
+}
+
+impl<M: Material + Sync> Hitable for Hyperboloid<M> {
+    // x^2 + z^2 - k y^2 - radius^2 = 0
+    fn hit(&self, ray: &Ray, t_min: f64, t_max: f64) -> Option<HitRecord<'_>> {
+        let (o, d) = (ray.origin(), ray.direction());
+        let k = (self.radius / self.c).powi(2);
+        let coefficients = (
+            d.x() * d.x() + d.z() * d.z() - k * d.y() * d.y(),
+            2.0 * (o.x() * d.x() + o.z() * d.z() - k * o.y() * d.y()),
+            o.x() * o.x() + o.z() * o.z() - k * o.y() * o.y() - self.radius * self.radius,
+        );
+        let (t, p, (u, v)) = nearest_root(ray, coefficients, t_min, t_max, |p| {
+            let phi = phi(p);
+            if p.y() < self.y0 || p.y() > self.y1 || phi > self.phi_max {
+                return None;
+            }
+            Some((phi / self.phi_max, (p.y() - self.y0) / (self.y1 - self.y0)))
+        })?;
+        let normal = Vector::new(p.x(), -k * p.y(), p.z()).unit();
+        Some(HitRecord::new(t, p, normal, &self.material).with_uv(u, v))
+    }
+
+    fn bounding_box(&self, _time0: f64, _time1: f64) -> Option<Aabb> {
+        let widest = self.y0.abs().max(self.y1.abs());
+        let radius = self.radius * (1.0 + (widest / self.c).powi(2)).sqrt();
+        Some(revolution_bounds(radius, self.y0, self.y1))
+    }
+}
+
+/// Flat disk of `radius` at y = 0 facing up, with a hole of `inner_radius`
+/// for an annulus. v goes from the rim to the hole.
+pub struct Disk<M: Material> {
+    radius: f64,
+    inner_radius: f64,
+    phi_max: f64,
+    material: M,
+}
+
+impl<M: Material> Disk<M> {
+    /// A negative radius is taken as 0, leaving nothing to hit.
+    pub fn new(radius: f64, material: M) -> Self {
+        Disk {
+            radius: radius.max(0.0),
+            inner_radius: 0.0,
+            phi_max: 2.0 * PI,
+            material,
+        }
+    }
+
+    pub fn with_inner_radius(self, inner_radius: f64) -> Self {
+        Disk {
+            inner_radius: inner_radius.max(0.0).min(self.radius),
+            ..self
+        }
+    }
+
+    pub fn with_phi_max(self, degrees: f64) -> Self {
+        Disk {
+            phi_max: phi_max(degrees),
+            ..self
+        }
+    }
+}
+
+impl<M: Material + Sync> Hitable for Disk<M> {
+    fn hit(&self, ray: &Ray, t_min: f64, t_max: f64) -> Option<HitRecord<'_>> {
+        let dy = ray.direction().y();
+        // nothing is left of a disk whose hole is as wide as it is
+        if dy == 0.0 || self.inner_radius >= self.radius {
+            return None;
+        }
+        let t = -ray.origin().y() / dy;
+        if t <= t_min || t >= t_max {
+            return None;
+        }
+        let p = ray.at(t);
+        let distance = (p.x() * p.x() + p.z() * p.z()).sqrt();
+        let phi = phi(p);
+        if distance > self.radius || distance < self.inner_radius || phi > self.phi_max {
+            return None;
+        }
+        let (u, v) = (
+            phi / self.phi_max,
+            (self.radius - distance) / (self.radius - self.inner_radius),
+        );
+        let normal = Vector::new(0.0, 1.0, 0.0);
+        Some(HitRecord::new(t, p, normal, &self.material).with_uv(u, v))
+    }
+
+    // a little thickness keeps rays in its plane from missing the box
+    fn bounding_box(&self, _time0: f64, _time1: f64) -> Option<Aabb> {
+        Some(revolution_bounds(self.radius, -1e-4, 1e-4))
+    }
+}
+
+/// Torus around the y axis: a tube of radius `minor` around a circle of
+/// radius `major`. v goes around the tube, from the outside, over the top.
+pub struct Torus<M: Material> {
+    major: f64,
+    minor: f64,
+    phi_max: f64,
+    material: M,
+}
+
+impl<M: Material> Torus<M> {
+    pub fn new(major: f64, minor: f64, material: M) -> Self {
+        Torus {
+            major,
+            minor,
+            phi_max: 2.0 * PI,
+            material,
+        }
+    }
+
+    pub fn with_phi_max(self, degrees: f64) -> Self {
+        Torus {
+            phi_max: phi_max(degrees),
+            ..self
+        }
+    }
+
+    fn bounds(&self) -> Aabb {
+        let r = self.major + self.minor;
+        Aabb::new(
+            Vector::new(-r, -self.minor, -r),
+            Vector::new(r, self.minor, r),
+        )
+    }
+}
+
+impl<M: Material + Sync> Torus<M> {
+    // (|p|^2 + R^2 - r^2)^2 = 4 R^2 (x^2 + z^2), a quartic in t, whose roots
+    // are given in order
+    fn crossings(&self, ray: &Ray, start: f64) -> Vec<f64> {
+        // the roots are much more precise for a unit direction from close by
+        let length = ray.direction().length();
+        let o = ray.at(start);
+        let d = ray.direction() / length;
+
+        let (major2, minor2) = (self.major * self.major, self.minor * self.minor);
+        let f = o.dot(d);
+        let g = o.dot(o) + major2 - minor2;
+        let xz = (
+            d.x() * d.x() + d.z() * d.z(),
+            o.x() * d.x() + o.z() * d.z(),
+            o.x() * o.x() + o.z() * o.z(),
+        );
+        let roots = solve_quartic(
+            1.0,
+            4.0 * f,
+            4.0 * f * f + 2.0 * g - 4.0 * major2 * xz.0,
+            4.0 * f * g - 8.0 * major2 * xz.1,
+            g * g - 4.0 * major2 * xz.2,
+        );
+        roots.into_iter().map(|s| start + s / length).collect()
+    }
+
+    fn record(&self, ray: &Ray, t: f64) -> HitRecord<'_> {
+        let p = ray.at(t);
+        let ring = (p.x() * p.x() + p.z() * p.z()).sqrt();
+        let center = (self.major / ring) * Vector::new(p.x(), 0.0, p.z());
+        let normal = (p - center) / self.minor;
+        let theta = p.y().atan2(ring - self.major);
+        let theta = if theta < 0.0 { theta + 2.0 * PI } else { theta };
+        let (u, v) = (phi(p) / self.phi_max, theta / (2.0 * PI));
+        HitRecord::new(t, p, normal, &self.material).with_uv(u, v)
+    }
+}
+
+impl<M: Material + Sync> Hitable for Torus<M> {
+    fn hit(&self, ray: &Ray, t_min: f64, t_max: f64) -> Option<HitRecord<'_>> {
+        // every crossing is in the box, though rounding can put the last
+        // one a little past where the ray leaves it
+        let (start, _) = self.bounds().range(ray, t_min, t_max)?;
+        let t = self
+            .crossings(ray, start)
+            .into_iter()
+            .filter(|t| *t > t_min && *t < t_max)
+            .find(|t| phi(ray.at(*t)) <= self.phi_max)?;
+        Some(self.record(ray, t))
+    }
+
+    fn bounding_box(&self, _time0: f64, _time1: f64) -> Option<Aabb> {
+        Some(self.bounds())
+    }
+}
+
+// As a solid a torus is always whole, whatever its phi_max.
+impl<M: Material + Sync> Solid for Torus<M> {
+    // the line goes into the tube and out again at every other crossing
+    fn intervals(&self, ray: &Ray) -> Vec<Interval<'_>> {
+        let start = match self.bounds().range(ray, f64::NEG_INFINITY, f64::INFINITY) {
+            Some((start, _)) => start,
+            None => return Vec::new(),
+        };
+        self.crossings(ray, start)
+            .chunks_exact(2)
+            .map(|pair| Interval {
+                enter: self.record(ray, pair[0]),
+                exit: self.record(ray, pair[1]),
+            })
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::material::Lambertian;
+
+    fn grey() -> Lambertian {
+        Lambertian::new(Vector::new(0.5, 0.5, 0.5))
+    }
+
+    fn ray(origin: (f64, f64, f64), direction: (f64, f64, f64)) -> Ray {
+        Ray::new(
+            Vector::new(origin.0, origin.1, origin.2),
+            Vector::new(direction.0, direction.1, direction.2),
+            0.0,
+        )
+    }
+
+    fn assert_near(a: Vector, b: Vector) {
+        assert!((a - b).length() < 1e-9, "{:?} vs {:?}", a, b);
+    }
+
+    #[test]
+    fn hits_the_outside_of_a_torus() {
+        let torus = Torus::new(2.0, 0.5, grey());
+        let hit = torus
+            .hit(&ray((-5.0, 0.0, 0.0), (1.0, 0.0, 0.0)), 0.001, f64::MAX)
+            .unwrap();
+        assert!((hit.t - 2.5).abs() < 1e-9);
+        assert_near(hit.normal, Vector::new(-1.0, 0.0, 0.0));
+
+        // from above, onto the top of the tube, with a longer direction
+        let hit = torus
+            .hit(&ray((2.0, 5.0, 0.0), (0.0, -2.0, 0.0)), 0.001, f64::MAX)
+            .unwrap();
+        assert!((hit.t - 2.25).abs() < 1e-9);
+        assert_near(hit.normal, Vector::new(0.0, 1.0, 0.0));
+
+        // through the hole, and past it
+        assert!(torus
+            .hit(&ray((0.0, 5.0, 0.0), (0.0, -1.0, 0.0)), 0.001, f64::MAX)
+            .is_none());
+        assert!(torus
+            .hit(&ray((-5.0, 0.6, 0.0), (1.0, 0.0, 0.0)), 0.001, f64::MAX)
+            .is_none());
+        // and stopping short of it
+        assert!(torus
+            .hit(&ray((-5.0, 0.0, 0.0), (1.0, 0.0, 0.0)), 0.001, 2.4)
+            .is_none());
+    }
+
+    #[test]
+    fn hits_a_torus_from_inside_and_in_part() {
+        let torus = Torus::new(2.0, 0.5, grey());
+        // out of the tube, from its middle
+        let hit = torus
+            .hit(&ray((2.0, 0.0, 0.0), (1.0, 0.0, 0.0)), 0.001, f64::MAX)
+            .unwrap();
+        assert!((hit.t - 0.5).abs() < 1e-9);
+        assert_near(hit.normal, Vector::new(1.0, 0.0, 0.0));
+
+        // grazing the top of the tube
+        let hit = torus
+            .hit(&ray((-5.0, 0.4999, 0.0), (1.0, 0.0, 0.0)), 0.001, f64::MAX)
+            .unwrap();
+        let dx = (0.5f64 * 0.5 - 0.4999 * 0.4999).sqrt();
+        assert!((hit.t - (3.0 - dx)).abs() < 1e-9);
+
+        // only the half from +x over +z to -x is there, so the ray along z
+        // misses the near side and hits the far one
+        let half = Torus::new(2.0, 0.5, grey()).with_phi_max(180.0);
+        let hit = half
+            .hit(&ray((0.0, 0.0, -5.0), (0.0, 0.0, 1.0)), 0.001, f64::MAX)
+            .unwrap();
+        assert!((hit.t - 6.5).abs() < 1e-9);
+        assert_near(hit.normal, Vector::new(0.0, 0.0, -1.0));
+    }
+
+    #[test]
+    fn takes_degenerate_shapes_as_empty() {
+        let down = ray((0.3, 5.0, 0.1), (0.0, -1.0, 0.0));
+        let paraboloid = Paraboloid::new(1.0, 0.0, -1.0, grey());
+        assert!(paraboloid.hit(&down, 0.001, f64::MAX).is_none());
+        let paraboloid = Paraboloid::new(1.0, 0.5, 0.5, grey());
+        assert!(paraboloid.hit(&down, 0.001, f64::MAX).is_none());
+        let paraboloid = Paraboloid::new(0.0, 0.0, 1.0, grey());
+        assert!(paraboloid.hit(&down, 0.001, f64::MAX).is_none());
+        let disk = Disk::new(-1.0, grey());
+        assert!(disk.hit(&down, 0.001, f64::MAX).is_none());
+        let annulus = Disk::new(1.0, grey()).with_inner_radius(1.0);
+        assert!(annulus.hit(&down, 0.001, f64::MAX).is_none());
+
+        // while the whole shapes are hit
+        let paraboloid = Paraboloid::new(1.0, 0.0, 1.0, grey());
+        let hit = paraboloid.hit(&down, 0.001, f64::MAX).unwrap();
+        assert!((hit.p.y() - 0.1).abs() < 1e-9);
+        let disk = Disk::new(1.0, grey());
+        assert!((disk.hit(&down, 0.001, f64::MAX).unwrap().t - 5.0).abs() < 1e-9);
+    }
+}
@@ -1,4 +1,6 @@
+use crate::aov::AovSample;
 use crate::hitable::{Hitable, HitableList};
+use crate::material::Lobe;
 use crate::vector::Vector;
 
 pub struct Ray {
@@ -33,19 +35,77 @@ impl Ray {
     }
 
     pub fn color(&self, scene: &HitableList, depth: i64) -> Vector {
+        self.radiance(scene, depth).0
+    }
+
+    // Total radiance along the ray, and the part of it emitted by whatever the ray
+    // hits first (or the background).
+    fn radiance(&self, scene: &HitableList, depth: i64) -> (Vector, Vector) {
         if depth <= 0 {
-            return Vector::new(0.0, 0.0, 0.0);
+            return (Vector::default(), Vector::default());
         }
 
-        if let Some(hit) = scene.hit(&self, 0.001, f64::MAX) {
-            if let Some((scattered, attenuation)) = hit.material.scatter(&self, &hit) {
-                return attenuation.hadamard_product(scattered.color(scene, depth - 1));
+        if let Some(hit) = scene.hit(self, 0.001, f64::MAX) {
+            let emitted = hit.material.emitted(&hit);
+            if let Some((scattered, attenuation)) = hit.material.scatter(self, &hit) {
+                let incoming = scattered.color(scene, depth - 1);
+                (emitted + attenuation.hadamard_product(incoming), emitted)
             } else {
-                return Vector::new(0.0, 0.0, 0.0);
+                (emitted, emitted)
             }
         } else {
-            let t = 0.5 * (self.direction.unit().y() + 1.0);
-            return (1.0 - t) * Vector::new(1.0, 1.0, 1.0) + t * Vector::new(0.5, 0.7, 1.0);
+            let background = self.background();
+            (background, background)
+        }
+    }
+
+    fn background(&self) -> Vector {
+        let t = 0.5 * (self.direction.unit().y() + 1.0);
+        (1.0 - t) * Vector::new(1.0, 1.0, 1.0) + t * Vector::new(0.5, 0.7, 1.0)
+    }
+
+    /// Same as `color`, but also records the first hit and splits the radiance
+    /// into the lighting AOVs.
+    pub fn trace(&self, scene: &HitableList, depth: i64) -> (Vector, AovSample) {
+        let mut aovs = AovSample::default();
+        if depth <= 0 {
+            return (Vector::default(), aovs);
+        }
+
+        let hit = match scene.hit(self, 0.001, f64::MAX) {
+            Some(hit) => hit,
+            None => {
+                aovs.emission = self.background();
+                // so the denoiser sees the background as its own color
+                aovs.albedo = aovs.emission;
+                return (aovs.emission, aovs);
+            }
+        };
+        aovs.albedo = hit.material.albedo(&hit);
+        aovs.normal = hit.normal;
+        aovs.position = hit.p;
+        aovs.depth = hit.t * self.direction.length();
+        aovs.object_id = hit.object_id;
+        aovs.material_id = hit.material.id();
+        aovs.emission = hit.material.emitted(&hit);
+
+        let mut color = aovs.emission;
+        if let Some((scattered, attenuation)) = hit.material.scatter(self, &hit) {
+            let (incoming, incoming_emitted) = scattered.radiance(scene, depth - 1);
+            let direct = attenuation.hadamard_product(incoming_emitted);
+            let indirect = attenuation.hadamard_product(incoming - incoming_emitted);
+            match hit.material.lobe() {
+                Lobe::Diffuse => {
+                    aovs.direct_diffuse = direct;
+                    aovs.indirect_diffuse = indirect;
+                }
+                Lobe::Specular => {
+                    aovs.direct_specular = direct;
+                    aovs.indirect_specular = indirect;
+                }
+            }
+            color = color + direct + indirect;
         }
+        (color, aovs)
     }
 }
@@ -0,0 +1,371 @@
+use crate::camera::{CameraModel, Shutter};
+use crate::ray::Ray;
+use crate::vector::{random_in_unit_disk, Vector};
+use std::fs;
+use std::path::Path;
+
+/// One spherical interface of a lens prescription, in millimeters. Elements
+/// are listed from the front of the lens to the back, and `thickness` is the
+/// distance along the axis to the next interface, or to the film for the last
+/// one. `ior` is the index of refraction behind the interface, 0 for air.
+/// The aperture stop has a radius of 0.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct LensElement {
+    pub radius: f64,
+    pub thickness: f64,
+    pub ior: f64,
+    // diameter
+    pub aperture: f64,
+}
+
+impl LensElement {
+    fn is_stop(&self) -> bool {
+        self.radius == 0.0
+    }
+
+    // index of refraction of the medium behind the interface
+    fn medium(&self) -> f64 {
+        if self.ior == 0.0 {
+            1.0
+        } else {
+            self.ior
+        }
+    }
+}
+
+/// A lens made of spherical elements, traced ray by ray like PBRT's
+/// `RealisticCamera`.
+///
+/// The lens is traced in its own space, in millimeters, with the film in the
+/// z = 0 plane and the scene toward -z.
+#[derive(Clone, Debug, PartialEq)]
+pub struct LensSystem {
+    elements: Vec<LensElement>,
+}
+
+impl LensSystem {
+    /// Parses a lens table in PBRT's format: one element per line with its
+    /// curvature radius, thickness, index of refraction and aperture diameter,
+    /// separated by whitespace. Everything after a '#' is a comment.
+    pub fn parse(text: &str) -> Result<LensSystem, String> {
+        let mut elements = Vec::new();
+        for (number, line) in text.lines().enumerate() {
+            let line = line.split('#').next().unwrap_or("").trim();
+            if line.is_empty() {
+                continue;
+            }
+            let values: Result<Vec<f64>, _> = line.split_whitespace().map(str::parse).collect();
+            match values.as_deref() {
+                Ok(&[radius, thickness, ior, aperture])
+                    if thickness >= 0.0 && ior >= 0.0 && aperture > 0.0 =>
+                {
+                    elements.push(LensElement {
+                        radius,
+                        thickness,
+                        ior,
+                        aperture,
+                    })
+                }
+                _ => {
+                    return Err(format!(
+                        "line {}: invalid lens element {:?}",
+                        number + 1,
+                        line
+                    ))
+                }
+            }
+        }
+        if elements.is_empty() {
+            return Err(String::from("no lens elements"));
+        }
+        Ok(LensSystem { elements })
+    }
+
+    pub fn load(path: &Path) -> Result<LensSystem, String> {
+        let text = fs::read_to_string(path)
+            .map_err(|e| format!("can't read lens file {}: {}", path.display(), e))?;
+        LensSystem::parse(&text).map_err(|e| format!("{}: {}", path.display(), e))
+    }
+
+    pub fn elements(&self) -> &[LensElement] {
+        &self.elements
+    }
+
+    // z of the back of the lens
+    fn rear_z(&self) -> f64 {
+        -self.elements[self.elements.len() - 1].thickness
+    }
+
+    fn front_z(&self) -> f64 {
+        -self.elements.iter().map(|e| e.thickness).sum::<f64>()
+    }
+
+    /// Follows a ray leaving the film through the lens, returning the ray that
+    /// comes out of the front, or None if an element or the stop blocks it.
+    fn trace_from_film(&self, ray: &Ray) -> Option<Ray> {
+        let (mut origin, mut direction) = (ray.origin(), ray.direction());
+        let mut z = 0.0;
+        for (i, element) in self.elements.iter().enumerate().rev() {
+            z -= element.thickness;
+            let (t, normal) = intersect(element, z, origin, direction)?;
+            origin = origin + t * direction;
+            if origin.x().powi(2) + origin.y().powi(2) > (element.aperture / 2.0).powi(2) {
+                return None;
+            }
+            if let Some(normal) = normal {
+                let outside = match i {
+                    0 => 1.0,
+                    _ => self.elements[i - 1].medium(),
+                };
+                direction = direction.refract(normal, element.medium() / outside)?;
+            }
+        }
+        Some(Ray::new(origin, direction, ray.time()))
+    }
+
+    // the same from the scene to the film, used to find the focal length
+    fn trace_from_scene(&self, ray: &Ray) -> Option<Ray> {
+        let (mut origin, mut direction) = (ray.origin(), ray.direction());
+        let mut z = self.front_z();
+        for (i, element) in self.elements.iter().enumerate() {
+            let (t, normal) = intersect(element, z, origin, direction)?;
+            origin = origin + t * direction;
+            if origin.x().powi(2) + origin.y().powi(2) > (element.aperture / 2.0).powi(2) {
+                return None;
+            }
+            if let Some(normal) = normal {
+                let outside = match i {
+                    0 => 1.0,
+                    _ => self.elements[i - 1].medium(),
+                };
+                direction = direction.refract(normal, outside / element.medium())?;
+            }
+            z += element.thickness;
+        }
+        Some(Ray::new(origin, direction, ray.time()))
+    }
+
+    /// Effective focal length and z of the front and rear principal planes,
+    /// found by tracing rays parallel to the axis through both sides.
+    pub fn cardinal_points(&self) -> Result<(f64, f64, f64), String> {
+        // just off the axis
+        let height = 0.01;
+        let from_scene = Ray::new(
+            Vector::new(height, 0.0, self.front_z() - 1.0),
+            Vector::new(0.0, 0.0, 1.0),
+            0.0,
+        );
+        let from_film = Ray::new(
+            Vector::new(height, 0.0, 0.0),
+            Vector::new(0.0, 0.0, -1.0),
+            0.0,
+        );
+        let error = || String::from("can't find the focal length of the lens");
+        let rear = self.trace_from_scene(&from_scene).ok_or_else(error)?;
+        let front = self.trace_from_film(&from_film).ok_or_else(error)?;
+        let (rear_principal, rear_focal) = principal_and_focal(height, &rear).ok_or_else(error)?;
+        let (front_principal, _) = principal_and_focal(height, &front).ok_or_else(error)?;
+        let focal_length = rear_focal - rear_principal;
+        if focal_length <= 0.0 {
+            return Err(error());
+        }
+        Ok((focal_length, front_principal, rear_principal))
+    }
+
+    /// The lens moved along its axis so that things `distance` millimeters in
+    /// front of the film are in focus, using the thick lens approximation.
+    pub fn focused(&self, distance: f64) -> Result<LensSystem, String> {
+        let (focal_length, front_principal, rear_principal) = self.cardinal_points()?;
+
+        // Moving the lens forward by `delta` puts the front principal plane
+        // a - delta from the object and the rear one b + delta from the film,
+        // and the thin lens equation 1 / (a - delta) + 1 / (b + delta) = 1 / f
+        // is a quadratic in delta. The smaller root keeps the lens near the film.
+        let (a, b) = (distance + front_principal, -rear_principal);
+        let discriminant = (a + b) * (a + b - 4.0 * focal_length);
+        if discriminant < 0.0 {
+            return Err(format!("the lens can't focus as close as {}mm", distance));
+        }
+        let delta = ((a - b) - discriminant.sqrt()) / 2.0;
+        let mut focused = self.clone();
+        let last = focused.elements.len() - 1;
+        focused.elements[last].thickness += delta;
+        if focused.elements[last].thickness < 0.0 {
+            return Err(format!("the lens can't focus as far as {}mm", distance));
+        }
+        Ok(focused)
+    }
+}
+
+// Distance along the ray to the interface of `element` at `z`, and its normal
+// facing the ray, None for the flat aperture stop.
+fn intersect(
+    element: &LensElement,
+    z: f64,
+    origin: Vector,
+    direction: Vector,
+) -> Option<(f64, Option<Vector>)> {
+    if element.is_stop() {
+        let t = (z - origin.z()) / direction.z();
+        return if t > 0.0 { Some((t, None)) } else { None };
+    }
+    let center = Vector::new(0.0, 0.0, z + element.radius);
+    let oc = origin - center;
+    let a = direction.squared_length();
+    let half_b = oc.dot(direction);
+    let c = oc.squared_length() - element.radius * element.radius;
+    let discriminant = half_b * half_b - a * c;
+    if discriminant < 0.0 {
+        return None;
+    }
+    let (t0, t1) = (
+        (-half_b - discriminant.sqrt()) / a,
+        (-half_b + discriminant.sqrt()) / a,
+    );
+    // the interface is the half of the sphere around its vertex at `z`
+    let closer = (direction.z() > 0.0) != (element.radius < 0.0);
+    let t = if closer { t0 } else { t1 };
+    if t <= 0.0 {
+        return None;
+    }
+    let normal = (origin + t * direction - center).unit();
+    let normal = if normal.dot(direction) > 0.0 {
+        normal.reverse()
+    } else {
+        normal
+    };
+    Some((t, Some(normal)))
+}
+
+// z of the principal plane and of the focal point of a ray that entered the
+// lens parallel to the axis at `height`
+fn principal_and_focal(height: f64, ray: &Ray) -> Option<(f64, f64)> {
+    let (o, d) = (ray.origin(), ray.direction());
+    if d.x() == 0.0 {
+        return None;
+    }
+    let focal = o.z() + (-o.x() / d.x()) * d.z();
+    let principal = o.z() + ((height - o.x()) / d.x()) * d.z();
+    Some((principal, focal))
+}
+
+/// Settings for rendering through a lens system instead of the thin lens.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Realistic {
+    pub lens: LensSystem,
+    // film diagonal in millimeters, 35mm full frame by default
+    pub film_diagonal: f64,
+    // in scene units from the film, the camera's focus distance if None
+    pub focus_distance: Option<f64>,
+    // size of a millimeter in scene units
+    pub scale: f64,
+}
+
+impl Realistic {
+    pub fn new(lens: LensSystem) -> Realistic {
+        Realistic {
+            lens,
+            film_diagonal: 43.27,
+            focus_distance: None,
+            scale: 0.001,
+        }
+    }
+}
+
+/// Camera looking through a `LensSystem`, with the film at the camera
+/// position. The field of view follows from the focal length of the lens and
+/// the film size, and distortion and vignetting from the elements.
+pub struct RealisticCamera {
+    origin: Vector,
+    u: Vector,
+    v: Vector,
+    w: Vector,
+    lens: LensSystem,
+    film_width: f64,
+    film_height: f64,
+    scale: f64,
+    // makes up for the rays the stop blocks at the center of the film
+    exposure: f64,
+    shutter: Shutter,
+}
+
+impl RealisticCamera {
+    // `lens` has to be focused already, `(u, v, w)` is the camera basis with -w
+    // the view direction
+    #[allow(clippy::too_many_arguments)]
+    pub(crate) fn new(
+        origin: Vector,
+        u: Vector,
+        v: Vector,
+        w: Vector,
+        aspect_ratio: f64,
+        settings: &Realistic,
+        lens: LensSystem,
+        shutter: Shutter,
+    ) -> Result<RealisticCamera, String> {
+        let film_height = settings.film_diagonal / (1.0 + aspect_ratio * aspect_ratio).sqrt();
+        let mut camera = RealisticCamera {
+            origin,
+            u,
+            v,
+            w,
+            lens,
+            film_width: film_height * aspect_ratio,
+            film_height,
+            scale: settings.scale,
+            exposure: 1.0,
+            shutter,
+        };
+
+        // fraction of the rear element that light reaches the film center through
+        let n = 64;
+        let mut passed = 0;
+        for j in 0..n {
+            for i in 0..n {
+                let x = 2.0 * (i as f64 + 0.5) / n as f64 - 1.0;
+                let y = 2.0 * (j as f64 + 0.5) / n as f64 - 1.0;
+                if x * x + y * y <= 1.0 && camera.trace(Vector::default(), x, y).is_some() {
+                    passed += 1;
+                }
+            }
+        }
+        if passed == 0 {
+            return Err(String::from("no light goes through the lens"));
+        }
+        camera.exposure = (n * n) as f64 * std::f64::consts::FRAC_PI_4 / passed as f64;
+        Ok(camera)
+    }
+
+    // the ray from `film` through the point (x, y) of the unit disk scaled to the rear element
+    fn trace(&self, film: Vector, x: f64, y: f64) -> Option<Ray> {
+        let elements = self.lens.elements();
+        let rear = &elements[elements.len() - 1];
+        let radius = rear.aperture / 2.0;
+        let target = Vector::new(x * radius, y * radius, self.lens.rear_z());
+        self.lens
+            .trace_from_film(&Ray::new(film, target - film, 0.0))
+    }
+}
+
+impl CameraModel for RealisticCamera {
+    fn get_ray(&self, s: f64, t: f64) -> Option<Ray> {
+        self.get_weighted_ray(s, t).map(|(ray, _)| ray)
+    }
+
+    fn get_weighted_ray(&self, s: f64, t: f64) -> Option<(Ray, Vector)> {
+        // the lens flips the image
+        let film = Vector::new(
+            -(s - 0.5) * self.film_width,
+            -(t - 0.5) * self.film_height,
+            0.0,
+        );
+        let p = random_in_unit_disk();
+        let ray = self.trace(film, p.x(), p.y())?;
+        let (o, d) = (ray.origin(), ray.direction());
+        let origin = self.origin + self.scale * (o.x() * self.u + o.y() * self.v + o.z() * self.w);
+        let direction = d.x() * self.u + d.y() * self.v + d.z() * self.w;
+        let weight = Vector::new(self.exposure, self.exposure, self.exposure);
+        let time = self.shutter.sample();
+        Some((Ray::new(origin, direction, time), weight))
+    }
+}
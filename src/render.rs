@@ -0,0 +1,413 @@
+use crate::aov::{Aov, AovSample};
+use crate::camera::{CameraModel, Lens, Projection, ShutterCurve, Stereo};
+use crate::film::{Film, FilmTile};
+use crate::filter::Filter;
+use crate::hitable::HitableList;
+use crate::realistic::Realistic;
+use crate::tracer::{fnv1a, mix_seed, random_float, seed_rng};
+use crate::vector::Vector;
+use indicatif::ProgressBar;
+use rayon::prelude::*;
+use std::str::FromStr;
+
+/// A rectangle of the frame to trace, in pixels or in normalised [0, 1] coordinates.
+/// In both cases (x0, y0) is the top-left corner and (x1, y1) is exclusive.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum CropWindow {
+    Pixels {
+        x0: usize,
+        y0: usize,
+        x1: usize,
+        y1: usize,
+    },
+    Normalized {
+        x0: f64,
+        y0: f64,
+        x1: f64,
+        y1: f64,
+    },
+}
+
+impl CropWindow {
+    // resolves the window to pixel bounds, clipped to the frame
+    pub fn bounds(self, width: usize, height: usize) -> PixelBounds {
+        let (x0, y0, x1, y1) = match self {
+            CropWindow::Pixels { x0, y0, x1, y1 } => (x0, y0, x1, y1),
+            // with some slack, so that 0.55 of 100 pixels isn't 55.000...1
+            // and rounded out to 56
+            CropWindow::Normalized { x0, y0, x1, y1 } => (
+                (x0 * width as f64 + 1e-9).floor() as usize,
+                (y0 * height as f64 + 1e-9).floor() as usize,
+                (x1 * width as f64 - 1e-9).ceil() as usize,
+                (y1 * height as f64 - 1e-9).ceil() as usize,
+            ),
+        };
+        let x1 = x1.min(width);
+        let y1 = y1.min(height);
+        PixelBounds {
+            x0: x0.min(x1),
+            y0: y0.min(y1),
+            x1,
+            y1,
+        }
+    }
+}
+
+// Parses "x0,y0,x1,y1". Integers are pixels, anything with a decimal point is normalised.
+impl FromStr for CropWindow {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let parts: Vec<&str> = s.split(',').map(|p| p.trim()).collect();
+        if parts.len() != 4 {
+            return Err(format!("expected x0,y0,x1,y1 but got {:?}", s));
+        }
+        if parts.iter().any(|p| p.contains('.')) {
+            let v = parts
+                .iter()
+                .map(|p| p.parse::<f64>())
+                .collect::<Result<Vec<f64>, _>>()
+                .map_err(|e| format!("invalid crop window {:?}: {}", s, e))?;
+            if v.iter().any(|x| !(0.0..=1.0).contains(x)) || v[0] >= v[2] || v[1] >= v[3] {
+                return Err(format!("invalid normalised crop window {:?}", s));
+            }
+            Ok(CropWindow::Normalized {
+                x0: v[0],
+                y0: v[1],
+                x1: v[2],
+                y1: v[3],
+            })
+        } else {
+            let v = parts
+                .iter()
+                .map(|p| p.parse::<usize>())
+                .collect::<Result<Vec<usize>, _>>()
+                .map_err(|e| format!("invalid crop window {:?}: {}", s, e))?;
+            if v[0] >= v[2] || v[1] >= v[3] {
+                return Err(format!("invalid pixel crop window {:?}", s));
+            }
+            Ok(CropWindow::Pixels {
+                x0: v[0],
+                y0: v[1],
+                x1: v[2],
+                y1: v[3],
+            })
+        }
+    }
+}
+
+/// Half-open pixel rectangle [x0, x1) x [y0, y1), with row 0 at the top of the image.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub struct PixelBounds {
+    pub x0: usize,
+    pub y0: usize,
+    pub x1: usize,
+    pub y1: usize,
+}
+
+impl PixelBounds {
+    pub fn width(&self) -> usize {
+        self.x1 - self.x0
+    }
+
+    pub fn height(&self) -> usize {
+        self.y1 - self.y0
+    }
+
+    pub fn area(&self) -> usize {
+        self.width() * self.height()
+    }
+
+    pub fn contains(&self, x: usize, y: usize) -> bool {
+        x >= self.x0 && x < self.x1 && y >= self.y0 && y < self.y1
+    }
+
+    // empty (but still valid) when the bounds don't overlap
+    pub fn intersect(&self, other: &PixelBounds) -> PixelBounds {
+        let x0 = self.x0.max(other.x0);
+        let y0 = self.y0.max(other.y0);
+        let x1 = self.x1.min(other.x1).max(x0);
+        let y1 = self.y1.min(other.y1).max(y0);
+        PixelBounds { x0, y0, x1, y1 }
+    }
+}
+
+/// What to output when a crop window is set.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum CropOutput {
+    // only the pixels inside the window
+    Cropped,
+    // the full frame, black outside the window
+    FullFrame,
+}
+
+pub struct RenderSettings {
+    pub image_width: usize,
+    pub image_height: usize,
+    // total samples per pixel to reach
+    pub samples_per_pixel: u32,
+    // samples per pixel added by each progressive pass
+    pub samples_per_pass: u32,
+    pub max_depth: i64,
+    pub crop: Option<CropWindow>,
+    pub crop_output: CropOutput,
+    pub filter: Filter,
+    // extra outputs recorded next to the beauty pass
+    pub aovs: Vec<Aov>,
+    // seeds both the scene construction and the per pixel sample generators
+    pub seed: u64,
+    // name of the scene being rendered, so checkpoints can't be mixed up between scenes
+    pub scene: String,
+    pub projection: Projection,
+    pub stereo: Option<Stereo>,
+    pub lens: Lens,
+    // renders through a lens system instead of `projection` and `lens`
+    pub realistic: Option<Realistic>,
+    pub shutter_curve: ShutterCurve,
+}
+
+impl RenderSettings {
+    pub fn new(image_width: usize, aspect_ratio: f64) -> RenderSettings {
+        RenderSettings {
+            image_width,
+            image_height: (image_width as f64 / aspect_ratio) as usize,
+            samples_per_pixel: 100,
+            samples_per_pass: 4,
+            max_depth: 50,
+            crop: None,
+            crop_output: CropOutput::Cropped,
+            filter: Filter::default(),
+            aovs: Vec::new(),
+            seed: 0,
+            scene: String::from("scene1"),
+            projection: Projection::default(),
+            stereo: None,
+            lens: Lens::default(),
+            realistic: None,
+            shutter_curve: ShutterCurve::default(),
+        }
+    }
+
+    // pixels that get traced
+    pub fn render_bounds(&self) -> PixelBounds {
+        match self.crop {
+            Some(crop) => crop.bounds(self.image_width, self.image_height),
+            None => self.full_bounds(),
+        }
+    }
+
+    // pixels that get written out
+    pub fn output_bounds(&self) -> PixelBounds {
+        match self.crop_output {
+            CropOutput::Cropped => self.render_bounds(),
+            CropOutput::FullFrame => self.full_bounds(),
+        }
+    }
+
+    pub fn full_bounds(&self) -> PixelBounds {
+        PixelBounds {
+            x0: 0,
+            y0: 0,
+            x1: self.image_width,
+            y1: self.image_height,
+        }
+    }
+
+    // number of passes needed to reach `samples_per_pixel`
+    pub fn passes(&self) -> u32 {
+        self.samples_per_pixel
+            .div_ceil(self.samples_per_pass.max(1))
+    }
+
+    /// Hash of every setting that changes what a pass adds to the film. The total
+    /// sample count is left out on purpose so a resumed render can ask for more.
+    pub fn hash(&self) -> u64 {
+        let bounds = self.render_bounds();
+        let aovs: Vec<&str> = self.aovs.iter().map(|aov| aov.name()).collect();
+        let description = format!(
+            "{}x{} spp/pass={} depth={} bounds={},{},{},{} filter={} aovs={} seed={} scene={} projection={:?} stereo={:?} lens={:?} realistic={:?} shutter={:?}",
+            self.image_width,
+            self.image_height,
+            self.samples_per_pass,
+            self.max_depth,
+            bounds.x0,
+            bounds.y0,
+            bounds.x1,
+            bounds.y1,
+            self.filter,
+            aovs.join(","),
+            self.seed,
+            self.scene,
+            self.projection,
+            self.stereo,
+            self.lens,
+            self.realistic,
+            self.shutter_curve,
+        );
+        fnv1a(description.as_bytes())
+    }
+}
+
+// side of the square tiles that passes are split into
+const TILE_SIZE: usize = 16;
+
+/// Adds `settings.samples_per_pass` samples to every pixel of the film.
+///
+/// The pixels are traced in parallel in tiles, each splatting its samples into
+/// its own `FilmTile` through the reconstruction filter, and the tiles are then
+/// merged in order. Pixels just outside the film are sampled as well when the
+/// filter reaches into it, so the edges of a cropped render match the full frame.
+///
+/// Each pixel reseeds the sample generator from the seed, the pass number and
+/// its position, so a pass always produces the same samples no matter which
+/// thread traces it or whether the render was resumed from a checkpoint.
+/// The camera projection is always that of the full frame, so a cropped render
+/// matches the same pixels of an uncropped one.
+pub fn render_pass(
+    scene: &HitableList,
+    camera: &dyn CameraModel,
+    settings: &RenderSettings,
+    film: &mut Film,
+    pass: u32,
+    progress_bar: &ProgressBar,
+) {
+    let image_width = settings.image_width;
+    let image_height = settings.image_height;
+    let pass_seed = mix_seed(settings.seed, pass as u64);
+
+    let tiles = sample_tiles(film.bounds(), settings);
+    let film_tiles: Vec<FilmTile> = tiles
+        .par_iter()
+        .map(|pixels| {
+            let mut tile = film.tile(*pixels, &settings.filter);
+            for y in pixels.y0..pixels.y1 {
+                for x in pixels.x0..pixels.x1 {
+                    seed_rng(mix_seed(pass_seed, (y * image_width + x) as u64));
+                    for _ in 0..settings.samples_per_pass {
+                        let film_x = x as f64 + random_float();
+                        let film_y = y as f64 + random_float();
+                        let u = film_x / image_width as f64;
+                        let v = 1.0 - film_y / image_height as f64;
+                        // points the camera doesn't see are black
+                        let (color, aovs) = match camera.get_weighted_ray(u, v) {
+                            Some((ray, weight)) => {
+                                let (color, aovs) = ray.trace(scene, settings.max_depth);
+                                (color.hadamard_product(weight), aovs.weighted(weight))
+                            }
+                            None => (Vector::default(), AovSample::default()),
+                        };
+                        tile.add_sample(film_x, film_y, color, &aovs);
+                    }
+                }
+            }
+            progress_bar.inc(pixels.intersect(&film.bounds()).area() as u64);
+            tile
+        })
+        .collect();
+
+    for tile in film_tiles.iter() {
+        film.merge_tile(tile);
+    }
+}
+
+// Tiles covering the film plus the margin around it that the filter reaches from.
+fn sample_tiles(bounds: PixelBounds, settings: &RenderSettings) -> Vec<PixelBounds> {
+    let reach = settings.filter.pixel_reach();
+    let x0 = bounds.x0.saturating_sub(reach);
+    let y0 = bounds.y0.saturating_sub(reach);
+    let x1 = (bounds.x1 + reach).min(settings.image_width);
+    let y1 = (bounds.y1 + reach).min(settings.image_height);
+
+    let mut tiles = Vec::new();
+    for ty in (y0..y1).step_by(TILE_SIZE) {
+        for tx in (x0..x1).step_by(TILE_SIZE) {
+            tiles.push(PixelBounds {
+                x0: tx,
+                y0: ty,
+                x1: (tx + TILE_SIZE).min(x1),
+                y1: (ty + TILE_SIZE).min(y1),
+            });
+        }
+    }
+    tiles
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn bounds(x0: usize, y0: usize, x1: usize, y1: usize) -> PixelBounds {
+        PixelBounds { x0, y0, x1, y1 }
+    }
+
+    #[test]
+    fn parses_pixel_and_normalized_windows() {
+        assert_eq!(
+            "10, 20,30,40".parse::<CropWindow>(),
+            Ok(CropWindow::Pixels {
+                x0: 10,
+                y0: 20,
+                x1: 30,
+                y1: 40
+            })
+        );
+        // a decimal point anywhere makes the whole window normalised
+        assert_eq!(
+            "0,0.25,1,0.75".parse::<CropWindow>(),
+            Ok(CropWindow::Normalized {
+                x0: 0.0,
+                y0: 0.25,
+                x1: 1.0,
+                y1: 0.75
+            })
+        );
+    }
+
+    #[test]
+    fn rejects_malformed_and_empty_windows() {
+        for text in [
+            "",
+            "1,2,3",
+            "1,2,3,4,5",
+            "a,0,10,10",
+            "-1,0,10,10",
+            "10,0,10,10",
+            "0,20,10,10",
+            "0.5,0,0.5,1",
+            "0,0,1.5,1",
+            "-0.1,0,1,1",
+        ] {
+            assert!(
+                text.parse::<CropWindow>().is_err(),
+                "{:?} was accepted",
+                text
+            );
+        }
+    }
+
+    #[test]
+    fn resolves_normalized_windows_outward() {
+        let crop: CropWindow = "0.1,0.1,0.55,0.5".parse().unwrap();
+        assert_eq!(crop.bounds(100, 50), bounds(10, 5, 55, 25));
+        // partly covered pixels are included
+        assert_eq!(crop.bounds(7, 3), bounds(0, 0, 4, 2));
+    }
+
+    #[test]
+    fn clips_windows_to_the_frame() {
+        let crop: CropWindow = "50,10,200,20".parse().unwrap();
+        assert_eq!(crop.bounds(100, 100), bounds(50, 10, 100, 20));
+        // entirely outside, so empty rather than inverted
+        let outside: CropWindow = "150,10,200,20".parse().unwrap();
+        let b = outside.bounds(100, 100);
+        assert_eq!(b, bounds(100, 10, 100, 20));
+        assert_eq!(b.area(), 0);
+    }
+
+    #[test]
+    fn intersects_bounds() {
+        let a = bounds(0, 0, 10, 10);
+        assert_eq!(a.intersect(&bounds(5, 5, 20, 20)), bounds(5, 5, 10, 10));
+        assert_eq!(a.intersect(&bounds(20, 20, 30, 30)).area(), 0);
+    }
+}
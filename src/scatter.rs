@@ -0,0 +1,414 @@
+//! Places to scatter objects so that they don't overlap: Poisson-disk
+//! samples, points that are random but never closer together than a given
+//! spacing. They fill rectangles on the ground and boxes with Bridson's
+//! algorithm, or cover meshes by dart throwing, picking triangles by area.
+//! Points falling near boxes to avoid, like those of objects already in
+//! the scene, are dropped, and a texture can thin them out where it is
+//! dark. The same seed always gives the same points.
+
+use crate::aabb::Aabb;
+use crate::mesh::Mesh;
+use crate::texture::Texture;
+use crate::vector::Vector;
+use rand::rngs::SmallRng;
+use rand::{Rng, SeedableRng};
+use std::collections::HashMap;
+
+/// Settings for scattering points at least `spacing` apart, so that objects
+/// reaching up to half of it from their point don't touch.
+pub struct Scatter {
+    spacing: f64,
+    seed: u64,
+    attempts: usize,
+    avoid: Vec<Aabb>,
+    density: Option<Texture>,
+}
+
+impl Scatter {
+    pub fn new(spacing: f64) -> Scatter {
+        assert!(spacing > 0.0, "the spacing must be positive");
+        Scatter {
+            spacing,
+            seed: 0,
+            attempts: 30,
+            avoid: Vec::new(),
+            density: None,
+        }
+    }
+
+    pub fn with_seed(self, seed: u64) -> Scatter {
+        Scatter { seed, ..self }
+    }
+
+    /// How many places are tried around each point before giving up on
+    /// finding room for another there (30 by default). More give denser
+    /// packings, more slowly.
+    pub fn with_attempts(self, attempts: usize) -> Scatter {
+        Scatter {
+            attempts: attempts.max(1),
+            ..self
+        }
+    }
+
+    /// Drops points closer to `aabb` than half the spacing, keeping objects
+    /// from running into the one it bounds. Points on the ground go by the
+    /// box's footprint, whatever its height.
+    pub fn avoiding(mut self, aabb: Aabb) -> Scatter {
+        self.avoid.push(aabb);
+        self
+    }
+
+    /// Keeps each point with the chance given by the brightness of `texture`
+    /// there, so that white areas are filled and black ones left empty. The
+    /// texture is laid over rectangles and boxes from above, u along x and v
+    /// along z, and on meshes by their texture coordinates, or from above
+    /// too for meshes without them.
+    pub fn with_density(self, texture: Texture) -> Scatter {
+        Scatter {
+            density: Some(texture),
+            ..self
+        }
+    }
+
+    /// Points (x, z) on the ground between the corners `min` and `max`.
+    pub fn in_rectangle(&self, min: (f64, f64), max: (f64, f64)) -> Vec<(f64, f64)> {
+        let bounds = Aabb::new(
+            Vector::new(min.0, 0.0, min.1),
+            Vector::new(max.0, 0.0, max.1),
+        );
+        let mut rng = SmallRng::seed_from_u64(self.seed);
+        let points = self.poisson_disk(&mut rng, &bounds, true);
+        self.filter(&mut rng, points, &bounds, true)
+            .into_iter()
+            .map(|p| (p.x(), p.z()))
+            .collect()
+    }
+
+    /// Points within `bounds`.
+    pub fn in_box(&self, bounds: &Aabb) -> Vec<Vector> {
+        let mut rng = SmallRng::seed_from_u64(self.seed);
+        let points = self.poisson_disk(&mut rng, bounds, false);
+        self.filter(&mut rng, points, bounds, false)
+    }
+
+    /// Points on the surface of `mesh`, each with the surface's normal there.
+    /// Meshes are covered evenly, however their triangles vary in size.
+    pub fn on_mesh(&self, mesh: &Mesh) -> Vec<(Vector, Vector)> {
+        let mut rng = SmallRng::seed_from_u64(self.seed);
+        let corners = |t: &[usize; 3]| t.map(|i| mesh.positions[i]);
+        // running totals of the triangles' areas, to pick them in proportion
+        let mut total = 0.0;
+        let areas: Vec<f64> = mesh
+            .triangles
+            .iter()
+            .map(|t| {
+                let [a, b, c] = corners(t);
+                total += 0.5 * (b - a).cross(c - a).length();
+                total
+            })
+            .collect();
+        let bounds = match mesh.positions.split_first() {
+            Some((&first, rest)) => rest.iter().fold(Aabb::new(first, first), |b, &p| {
+                b.surrounding(&Aabb::new(p, p))
+            }),
+            None => return Vec::new(),
+        };
+
+        // as many darts as would cover the surface many times over
+        let darts = self.attempts as f64 * (total / (self.spacing * self.spacing)).ceil();
+        let mut grid = Grid::new(self.spacing);
+        let mut points = Vec::new();
+        for _ in 0..darts as usize {
+            let area = rng.gen_range(0.0..total);
+            let i = areas.partition_point(|&a| a < area).min(areas.len() - 1);
+            let triangle = mesh.triangles[i];
+            let [a, b, c] = corners(&triangle);
+            // uniform over the triangle
+            let (r1, r2): (f64, f64) = (rng.gen(), rng.gen());
+            let (b1, b2) = ((1.0 - r2) * r1.sqrt(), r2 * r1.sqrt());
+            let b0 = 1.0 - b1 - b2;
+            let p = b0 * a + b1 * b + b2 * c;
+            if !grid.has_room(p) {
+                continue;
+            }
+            grid.insert(p);
+            let [i0, i1, i2] = triangle;
+            let normal = if mesh.normals.is_empty() {
+                (b - a).cross(c - a).unit()
+            } else {
+                (b0 * mesh.normals[i0] + b1 * mesh.normals[i1] + b2 * mesh.normals[i2]).unit()
+            };
+            let uv = if mesh.uvs.is_empty() {
+                seen_from_above(&bounds, p)
+            } else {
+                let [(u0, v0), (u1, v1), (u2, v2)] = [mesh.uvs[i0], mesh.uvs[i1], mesh.uvs[i2]];
+                (b0 * u0 + b1 * u1 + b2 * u2, b0 * v0 + b1 * v1 + b2 * v2)
+            };
+            points.push((p, normal, uv));
+        }
+        points
+            .into_iter()
+            .filter(|&(p, _, uv)| self.keep(&mut rng, p, uv, false))
+            .map(|(p, normal, _)| (p, normal))
+            .collect()
+    }
+
+    // Bridson's algorithm: each new point is tried at between one and two
+    // spacings from one already placed, and points with no room left around
+    // them stop being tried from
+    fn poisson_disk(&self, rng: &mut SmallRng, bounds: &Aabb, flat: bool) -> Vec<Vector> {
+        let mut random_between = |min: f64, max: f64| {
+            if min < max {
+                rng.gen_range(min..max)
+            } else {
+                min
+            }
+        };
+        let first = Vector::new(
+            random_between(bounds.min.x(), bounds.max.x()),
+            random_between(bounds.min.y(), bounds.max.y()),
+            random_between(bounds.min.z(), bounds.max.z()),
+        );
+        let mut grid = Grid::new(self.spacing);
+        grid.insert(first);
+        let mut points = vec![first];
+        let mut active = vec![0];
+        while !active.is_empty() {
+            let k = rng.gen_range(0..active.len());
+            let around = points[active[k]];
+            let mut placed = false;
+            for _ in 0..self.attempts {
+                let offset = loop {
+                    let y = if flat { 0.0 } else { rng.gen_range(-2.0..2.0) };
+                    let v = Vector::new(rng.gen_range(-2.0..2.0), y, rng.gen_range(-2.0..2.0));
+                    if (1.0..4.0).contains(&v.squared_length()) {
+                        break v;
+                    }
+                };
+                let p = around + self.spacing * offset;
+                if contains(bounds, p) && grid.has_room(p) {
+                    grid.insert(p);
+                    active.push(points.len());
+                    points.push(p);
+                    placed = true;
+                    break;
+                }
+            }
+            if !placed {
+                active.swap_remove(k);
+            }
+        }
+        points
+    }
+
+    // drops the points near boxes to avoid, and thins the rest by density
+    fn filter(
+        &self,
+        rng: &mut SmallRng,
+        points: Vec<Vector>,
+        bounds: &Aabb,
+        flat: bool,
+    ) -> Vec<Vector> {
+        points
+            .into_iter()
+            .filter(|&p| self.keep(rng, p, seen_from_above(bounds, p), flat))
+            .collect()
+    }
+
+    // `flat` points are on the ground, and keep clear of whatever stands
+    // over them, at any height
+    fn keep(&self, rng: &mut SmallRng, p: Vector, (u, v): (f64, f64), flat: bool) -> bool {
+        let near = |aabb: &Aabb| {
+            let aabb = aabb.padded(0.5 * self.spacing);
+            let p = if flat {
+                Vector::new(p.x(), aabb.min.y(), p.z())
+            } else {
+                p
+            };
+            contains(&aabb, p)
+        };
+        if self.avoid.iter().any(near) {
+            return false;
+        }
+        match &self.density {
+            Some(texture) => {
+                let c = texture.sample(u, v);
+                // Rec. 709 luminance
+                let density = 0.2126 * c.x() + 0.7152 * c.y() + 0.0722 * c.z();
+                rng.gen::<f64>() < density
+            }
+            None => true,
+        }
+    }
+}
+
+// texture coordinates for `p`, with `bounds` seen from above
+fn seen_from_above(bounds: &Aabb, p: Vector) -> (f64, f64) {
+    let fraction = |x: f64, min: f64, max: f64| {
+        if max > min {
+            (x - min) / (max - min)
+        } else {
+            0.5
+        }
+    };
+    (
+        fraction(p.x(), bounds.min.x(), bounds.max.x()),
+        fraction(p.z(), bounds.min.z(), bounds.max.z()),
+    )
+}
+
+fn contains(bounds: &Aabb, p: Vector) -> bool {
+    (bounds.min.x()..=bounds.max.x()).contains(&p.x())
+        && (bounds.min.y()..=bounds.max.y()).contains(&p.y())
+        && (bounds.min.z()..=bounds.max.z()).contains(&p.z())
+}
+
+// the points placed so far, in cells a spacing wide, so that only those in
+// the neighbouring cells need checking
+struct Grid {
+    spacing: f64,
+    cells: HashMap<[i64; 3], Vec<Vector>>,
+}
+
+impl Grid {
+    fn new(spacing: f64) -> Grid {
+        Grid {
+            spacing,
+            cells: HashMap::new(),
+        }
+    }
+
+    fn cell(&self, p: Vector) -> [i64; 3] {
+        [p.x(), p.y(), p.z()].map(|x| (x / self.spacing).floor() as i64)
+    }
+
+    fn insert(&mut self, p: Vector) {
+        let cell = self.cell(p);
+        self.cells.entry(cell).or_default().push(p);
+    }
+
+    // whether `p` is at least a spacing from every point
+    fn has_room(&self, p: Vector) -> bool {
+        let [x, y, z] = self.cell(p);
+        let spacing2 = self.spacing * self.spacing;
+        (-1..=1).all(|i| {
+            (-1..=1).all(|j| {
+                (-1..=1).all(|k| {
+                    self.cells.get(&[x + i, y + j, z + k]).is_none_or(|points| {
+                        points.iter().all(|&q| (q - p).squared_length() >= spacing2)
+                    })
+                })
+            })
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn assert_spaced(points: &[Vector], spacing: f64) {
+        for (i, p) in points.iter().enumerate() {
+            for q in &points[i + 1..] {
+                assert!(
+                    (*p - *q).length() >= spacing,
+                    "{:?} and {:?} are too close",
+                    p,
+                    q
+                );
+            }
+        }
+    }
+
+    fn on_ground(points: &[(f64, f64)]) -> Vec<Vector> {
+        points
+            .iter()
+            .map(|&(x, z)| Vector::new(x, 0.0, z))
+            .collect()
+    }
+
+    // a square from (0, 0, 0) to (4, 0, 4), facing up
+    fn square() -> Mesh {
+        Mesh {
+            positions: vec![
+                Vector::new(0.0, 0.0, 0.0),
+                Vector::new(4.0, 0.0, 0.0),
+                Vector::new(4.0, 0.0, 4.0),
+                Vector::new(0.0, 0.0, 4.0),
+            ],
+            triangles: vec![[0, 2, 1], [0, 3, 2]],
+            ..Mesh::default()
+        }
+    }
+
+    #[test]
+    fn keeps_points_apart_and_in_bounds() {
+        let points = Scatter::new(1.0).in_rectangle((-5.0, 0.0), (5.0, 8.0));
+        // a loose packing still fills most of the 80 square units
+        assert!(points.len() > 40, "only {} points", points.len());
+        assert!(points
+            .iter()
+            .all(|&(x, z)| (-5.0..=5.0).contains(&x) && (0.0..=8.0).contains(&z)));
+        assert_spaced(&on_ground(&points), 1.0);
+
+        let bounds = Aabb::new(Vector::new(0.0, 0.0, 0.0), Vector::new(4.0, 3.0, 5.0));
+        let points = Scatter::new(0.7).with_seed(3).in_box(&bounds);
+        assert!(points.len() > 40, "only {} points", points.len());
+        assert!(points.iter().all(|&p| contains(&bounds, p)));
+        assert_spaced(&points, 0.7);
+
+        let points = Scatter::new(0.5).on_mesh(&square());
+        assert!(points.len() > 20, "only {} points", points.len());
+        for &(p, normal) in &points {
+            assert!(p.y() == 0.0 && (0.0..=4.0).contains(&p.x()) && (0.0..=4.0).contains(&p.z()));
+            assert!((normal - Vector::new(0.0, 1.0, 0.0)).length() < 1e-12);
+        }
+        let positions: Vec<Vector> = points.iter().map(|&(p, _)| p).collect();
+        assert_spaced(&positions, 0.5);
+    }
+
+    #[test]
+    fn repeats_itself_for_a_seed() {
+        let scatter = Scatter::new(0.8).with_seed(42);
+        let bounds = Aabb::new(Vector::new(0.0, 0.0, 0.0), Vector::new(3.0, 3.0, 3.0));
+        assert_eq!(
+            scatter.in_rectangle((0.0, 0.0), (6.0, 6.0)),
+            scatter.in_rectangle((0.0, 0.0), (6.0, 6.0))
+        );
+        assert_eq!(scatter.in_box(&bounds), scatter.in_box(&bounds));
+        assert_eq!(scatter.on_mesh(&square()), scatter.on_mesh(&square()));
+
+        let other = Scatter::new(0.8).with_seed(43);
+        assert_ne!(
+            scatter.in_rectangle((0.0, 0.0), (6.0, 6.0)),
+            other.in_rectangle((0.0, 0.0), (6.0, 6.0))
+        );
+    }
+
+    #[test]
+    fn keeps_clear_of_boxes_to_avoid() {
+        // a post standing up from the middle of the ground, which points on
+        // the ground avoid by its footprint
+        let post = Aabb::new(Vector::new(2.0, 1.0, 2.0), Vector::new(3.0, 5.0, 3.0));
+        let scatter = Scatter::new(0.5).avoiding(post);
+        let points = scatter.in_rectangle((0.0, 0.0), (5.0, 5.0));
+        assert!(points.len() > 20, "only {} points", points.len());
+        for &(x, z) in &points {
+            assert!(
+                !((1.75..=3.25).contains(&x) && (1.75..=3.25).contains(&z)),
+                "({}, {}) is next to the post",
+                x,
+                z
+            );
+        }
+
+        // while in a box only points near the post itself are dropped
+        let bounds = Aabb::new(Vector::new(0.0, 0.0, 0.0), Vector::new(5.0, 5.0, 5.0));
+        let points = scatter.in_box(&bounds);
+        let near = post.padded(0.25);
+        assert!(points.iter().all(|&p| !contains(&near, p)));
+        assert!(points.iter().any(|&p| p.y() < 0.75
+            && (2.0..=3.0).contains(&p.x())
+            && (2.0..=3.0).contains(&p.z())));
+    }
+}
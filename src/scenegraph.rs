@@ -0,0 +1,287 @@
+//! Scenes built as a tree of named nodes rather than a flat list. Each node
+//! has a transform relative to its parent, may hold a shape and may set the
+//! material of its shape and everything below it, which nodes further down
+//! can set again for their own part. Nodes are found by name to be moved,
+//! given another material or removed, and `SceneGraph::build` flattens the
+//! tree into a list of objects under a bounding volume hierarchy.
+
+use crate::bvh::BvhList;
+use crate::hitable::Hitable;
+use crate::material::{Lambertian, Material, SharedMaterial};
+use crate::transform::{Matrix4, Transform, Transformed};
+use crate::vector::Vector;
+use std::fmt;
+use std::sync::Arc;
+
+// makes a node's shape with the material it ends up with
+type Shape = Arc<dyn Fn(SharedMaterial) -> Box<dyn Hitable> + Send + Sync>;
+
+/// A named group of nodes, which may also have a shape of its own.
+#[derive(Clone)]
+pub struct Node {
+    name: String,
+    transform: Transform,
+    material: Option<SharedMaterial>,
+    shape: Option<Shape>,
+    children: Vec<Node>,
+}
+
+impl Node {
+    /// A node without a shape, for grouping others.
+    pub fn group(name: &str) -> Node {
+        Node {
+            name: name.to_string(),
+            transform: Transform::identity(),
+            material: None,
+            shape: None,
+            children: Vec::new(),
+        }
+    }
+
+    /// A node with a shape, made by `shape` from the material the node
+    /// inherits or sets, like `|m| Sphere::new(center, radius, m)`.
+    pub fn shape<H: Hitable + 'static>(
+        name: &str,
+        shape: impl Fn(SharedMaterial) -> H + Send + Sync + 'static,
+    ) -> Node {
+        Node {
+            shape: Some(Arc::new(move |material| Box::new(shape(material)))),
+            ..Node::group(name)
+        }
+    }
+
+    pub fn with_transform(self, transform: Transform) -> Node {
+        Node { transform, ..self }
+    }
+
+    /// Sets the material of this node's shape and the shapes below it that
+    /// don't set their own.
+    pub fn with_material(self, material: impl Material + Send + Sync + 'static) -> Node {
+        Node {
+            material: Some(Arc::new(material)),
+            ..self
+        }
+    }
+
+    pub fn with_child(mut self, child: Node) -> Node {
+        self.children.push(child);
+        self
+    }
+
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    /// The transform from this node's space to its parent's.
+    pub fn transform(&self) -> &Transform {
+        &self.transform
+    }
+
+    pub fn set_transform(&mut self, transform: Transform) {
+        self.transform = transform;
+    }
+
+    /// The material this node sets, None when it inherits its parent's.
+    pub fn material(&self) -> Option<&SharedMaterial> {
+        self.material.as_ref()
+    }
+
+    pub fn set_material(&mut self, material: impl Material + Send + Sync + 'static) {
+        self.material = Some(Arc::new(material));
+    }
+
+    /// Goes back to inheriting the parent's material.
+    pub fn clear_material(&mut self) {
+        self.material = None;
+    }
+
+    pub fn has_shape(&self) -> bool {
+        self.shape.is_some()
+    }
+
+    pub fn children(&self) -> &[Node] {
+        &self.children
+    }
+
+    // this node and all the nodes below it, parents before children
+    fn walk<'a>(&'a self, visit: &mut impl FnMut(&'a Node, usize)) {
+        fn walk<'a>(node: &'a Node, depth: usize, visit: &mut impl FnMut(&'a Node, usize)) {
+            visit(node, depth);
+            for child in &node.children {
+                walk(child, depth + 1, visit);
+            }
+        }
+        walk(self, 0, visit);
+    }
+
+    fn find(&self, name: &str) -> Option<&Node> {
+        if self.name == name {
+            return Some(self);
+        }
+        self.children.iter().find_map(|c| c.find(name))
+    }
+
+    fn find_mut(&mut self, name: &str) -> Option<&mut Node> {
+        if self.name == name {
+            return Some(self);
+        }
+        self.children.iter_mut().find_map(|c| c.find_mut(name))
+    }
+
+    // the transforms from the root down to the named node, root first
+    fn path_to(&self, name: &str, path: &mut Vec<Transform>) -> bool {
+        path.push(self.transform);
+        if self.name == name || self.children.iter().any(|c| c.path_to(name, path)) {
+            return true;
+        }
+        path.pop();
+        false
+    }
+
+    fn remove(&mut self, name: &str) -> Option<Node> {
+        match self.children.iter().position(|c| c.name == name) {
+            Some(index) => Some(self.children.remove(index)),
+            None => self.children.iter_mut().find_map(|c| c.remove(name)),
+        }
+    }
+
+    fn build(
+        &self,
+        to_world: &Transform,
+        material: &SharedMaterial,
+        list: &mut Vec<Box<dyn Hitable>>,
+    ) {
+        let to_world = self.transform.then(to_world);
+        let material = self.material.as_ref().unwrap_or(material);
+        if let Some(shape) = &self.shape {
+            let shape = shape(material.clone());
+            if *to_world.matrix() == Matrix4::identity() {
+                list.push(shape);
+            } else {
+                list.push(Box::new(Transformed::new(shape, to_world)));
+            }
+        }
+        for child in &self.children {
+            child.build(&to_world, material, list);
+        }
+    }
+}
+
+/// A tree of nodes whose names are unique, under a root group called
+/// "root".
+#[derive(Clone)]
+pub struct SceneGraph {
+    root: Node,
+}
+
+impl Default for SceneGraph {
+    fn default() -> SceneGraph {
+        SceneGraph {
+            root: Node::group("root"),
+        }
+    }
+}
+
+impl SceneGraph {
+    pub fn root(&self) -> &Node {
+        &self.root
+    }
+
+    /// Adds `node`, with the nodes under it, as the last child of the node
+    /// called `parent`. Fails if there is no such node, or if a name in
+    /// `node` is already in the graph or given twice.
+    pub fn insert(&mut self, parent: &str, node: Node) -> Result<(), String> {
+        let mut names = Vec::new();
+        node.walk(&mut |n, _| names.push(n.name.as_str()));
+        names.sort_unstable();
+        if let Some(pair) = names.windows(2).find(|pair| pair[0] == pair[1]) {
+            return Err(format!("there are two nodes called {:?}", pair[0]));
+        }
+        if let Some(name) = names.iter().find(|name| self.get(name).is_some()) {
+            return Err(format!("there is already a node called {:?}", name));
+        }
+        let parent = self
+            .root
+            .find_mut(parent)
+            .ok_or_else(|| format!("there is no node called {:?}", parent))?;
+        parent.children.push(node);
+        Ok(())
+    }
+
+    pub fn get(&self, name: &str) -> Option<&Node> {
+        self.root.find(name)
+    }
+
+    /// The named node, to change its transform or material.
+    pub fn get_mut(&mut self, name: &str) -> Option<&mut Node> {
+        self.root.find_mut(name)
+    }
+
+    /// Takes the named node and the nodes under it out of the graph. The
+    /// root can't be removed.
+    pub fn remove(&mut self, name: &str) -> Option<Node> {
+        self.root.remove(name)
+    }
+
+    /// The transform from the named node's space to the world's.
+    pub fn world_transform(&self, name: &str) -> Option<Transform> {
+        let mut path = Vec::new();
+        if !self.root.path_to(name, &mut path) {
+            return None;
+        }
+        Some(
+            path.iter()
+                .rev()
+                .fold(Transform::identity(), |to_world, t| to_world.then(t)),
+        )
+    }
+
+    /// The material the named node's shape gets: its own, or the nearest
+    /// one set above it. Shapes with none get grey `Lambertian`.
+    pub fn material(&self, name: &str) -> Option<SharedMaterial> {
+        fn find(
+            node: &Node,
+            name: &str,
+            inherited: Option<&SharedMaterial>,
+        ) -> Option<Option<SharedMaterial>> {
+            let material = node.material.as_ref().or(inherited);
+            if node.name == name {
+                return Some(material.cloned());
+            }
+            node.children.iter().find_map(|c| find(c, name, material))
+        }
+        find(&self.root, name, None).map(|m| m.unwrap_or_else(default_material))
+    }
+
+    /// Every shape placed in the world with the material it inherits, under
+    /// a bounding volume hierarchy so that rays only try those they pass
+    /// near.
+    pub fn build(&self) -> BvhList {
+        let mut list = Vec::new();
+        self.root
+            .build(&Transform::identity(), &default_material(), &mut list);
+        BvhList::new(list)
+    }
+}
+
+fn default_material() -> SharedMaterial {
+    Arc::new(Lambertian::new(Vector::new(0.5, 0.5, 0.5)))
+}
+
+// an outline of the tree, one node to a line
+impl fmt::Display for SceneGraph {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let mut result = Ok(());
+        self.root.walk(&mut |node, depth| {
+            let marks = match (node.has_shape(), node.material.is_some()) {
+                (true, true) => " (shape, material)",
+                (true, false) => " (shape)",
+                (false, true) => " (material)",
+                (false, false) => "",
+            };
+            result =
+                result.and_then(|_| writeln!(f, "{}{}{}", "  ".repeat(depth), node.name, marks));
+        });
+        result
+    }
+}
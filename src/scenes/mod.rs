@@ -1,2 +1,12 @@
 pub mod scene1;
+pub mod scene10;
+pub mod scene11;
+pub mod scene12;
 pub mod scene2;
+pub mod scene3;
+pub mod scene4;
+pub mod scene5;
+pub mod scene6;
+pub mod scene7;
+pub mod scene8;
+pub mod scene9;
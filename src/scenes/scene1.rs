@@ -57,5 +57,5 @@ pub fn scene() -> (HitableList, Camera) {
     ));
     scene.push(Sphere::new(Vector::new(1.0, 0.0, -1.0), 0.5, material_gold));
 
-    return (scene, camera);
+    (scene, camera)
 }
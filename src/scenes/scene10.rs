@@ -0,0 +1,113 @@
+use crate::camera::Camera;
+use crate::cuboid::Cuboid;
+use crate::hitable::HitableList;
+use crate::material::{Dielectric, Lambertian, Metal};
+use crate::quadric::Cylinder;
+use crate::scenegraph::{Node, SceneGraph};
+use crate::sphere::Sphere;
+use crate::transform::Transform;
+use crate::vector::Vector;
+
+// A table laid out as a scene graph: the legs take the table's wood, and the
+// fruit in the bowl on top each set their own material.
+pub fn scene() -> (HitableList, Camera) {
+    // Camera
+    let aspect_ratio = 16.0 / 9.0;
+    let lookfrom = Vector::new(0.0, 3.0, 7.0);
+    let lookat = Vector::new(0.0, 1.0, 0.0);
+    let vup = Vector::new(0.0, 1.0, 0.0);
+    let dist_to_focus = (lookfrom - lookat).length();
+    let camera = Camera::new(
+        lookfrom,
+        lookat,
+        vup,
+        35.0,
+        aspect_ratio,
+        0.0,
+        dist_to_focus,
+        0.0,
+        1.0,
+    );
+
+    let mut graph = SceneGraph::default();
+    graph
+        .insert(
+            "root",
+            Node::shape("ground", |m| {
+                Sphere::new(Vector::new(0.0, -1000.0, 0.0), 1000.0, m)
+            }),
+        )
+        .unwrap();
+
+    // the table top's underside is at y = 0 in the table's space
+    let mut table = Node::group("table")
+        .with_material(Lambertian::new(Vector::new(0.45, 0.28, 0.15)))
+        .with_transform(
+            Transform::rotate_y(-20.0).then(&Transform::translate(Vector::new(0.0, 1.1, 0.0))),
+        )
+        .with_child(Node::shape("top", |m| {
+            Cuboid::new(Vector::new(-1.6, 0.0, -0.9), Vector::new(1.6, 0.1, 0.9), m)
+        }));
+    for (i, &(x, z)) in [(-1.4, -0.7), (1.4, -0.7), (-1.4, 0.7), (1.4, 0.7)]
+        .iter()
+        .enumerate()
+    {
+        table = table.with_child(
+            Node::shape(&format!("leg{}", i + 1), |m| {
+                Cylinder::new(0.08, -1.1, 0.0, m)
+            })
+            .with_transform(Transform::translate(Vector::new(x, 0.0, z))),
+        );
+    }
+    graph.insert("root", table).unwrap();
+
+    let bowl = Node::group("bowl")
+        .with_transform(Transform::translate(Vector::new(0.3, 0.1, 0.0)))
+        .with_child(
+            Node::shape("apple", |m| {
+                Sphere::new(Vector::new(-0.3, 0.25, 0.0), 0.25, m)
+            })
+            .with_material(Lambertian::new(Vector::new(0.7, 0.1, 0.1))),
+        )
+        .with_child(
+            Node::shape("orange", |m| {
+                Sphere::new(Vector::new(0.25, 0.27, 0.1), 0.27, m)
+            })
+            .with_material(Lambertian::new(Vector::new(0.9, 0.45, 0.05))),
+        )
+        .with_child(
+            Node::shape("plum", |m| {
+                Sphere::new(Vector::new(0.0, 0.2, -0.35), 0.2, m)
+            })
+            .with_material(Lambertian::new(Vector::new(0.35, 0.1, 0.4))),
+        );
+    graph.insert("table", bowl).unwrap();
+
+    // a glass and a jug beside the bowl, placed then changed by name
+    graph
+        .insert(
+            "table",
+            Node::shape("glass", |m| Sphere::new(Vector::new(0.0, 0.3, 0.0), 0.3, m))
+                .with_material(Dielectric::new(1.5)),
+        )
+        .unwrap();
+    graph
+        .insert(
+            "table",
+            Node::shape("jug", |m| Cylinder::new(0.2, 0.0, 0.6, m))
+                .with_transform(Transform::translate(Vector::new(1.0, 0.1, 0.3))),
+        )
+        .unwrap();
+    graph
+        .get_mut("glass")
+        .unwrap()
+        .set_transform(Transform::translate(Vector::new(-1.0, 0.1, 0.2)));
+    graph
+        .get_mut("jug")
+        .unwrap()
+        .set_material(Metal::new(Vector::new(0.8, 0.8, 0.85), 0.05));
+
+    let mut scene = HitableList::default();
+    scene.push(graph.build());
+    (scene, camera)
+}
@@ -0,0 +1,110 @@
+use crate::camera::Camera;
+use crate::hitable::{HitableList, SharedGeometry};
+use crate::instance::{Instance, Instances};
+use crate::material::{Lambertian, Metal, SharedMaterial};
+use crate::mesh::TriangleMesh;
+use crate::sphere::Sphere;
+use crate::tracer::{random_float, random_float_between};
+use crate::transform::Transform;
+use crate::vector::Vector;
+use crate::{ply, stl};
+use std::sync::Arc;
+
+// A million pebbles and a few knots strewn across a plain, all instances of
+// two meshes.
+pub fn scene() -> (HitableList, Camera) {
+    // Camera
+    let aspect_ratio = 16.0 / 9.0;
+    let lookfrom = Vector::new(0.0, 1.2, 6.0);
+    let lookat = Vector::new(0.0, 0.3, 0.0);
+    let vup = Vector::new(0.0, 1.0, 0.0);
+    let dist_to_focus = (lookfrom - lookat).length();
+    let camera = Camera::new(
+        lookfrom,
+        lookat,
+        vup,
+        35.0,
+        aspect_ratio,
+        0.0,
+        dist_to_focus,
+        0.0,
+        1.0,
+    );
+
+    let mut scene = HitableList::default();
+    const GROUND_RADIUS: f64 = 1000.0;
+    scene.push(Sphere::new(
+        Vector::new(0.0, -GROUND_RADIUS, 0.0),
+        GROUND_RADIUS,
+        Lambertian::new(Vector::new(0.55, 0.5, 0.4)),
+    ));
+
+    // the pebbles' colour comes from their instances, not the sphere's vertices
+    let mut pebble = ply::parse(include_bytes!("../../meshes/icosphere.ply")).unwrap();
+    pebble.colors.clear();
+    let knot = stl::parse(include_bytes!("../../meshes/knot.stl")).unwrap();
+    let geometry: Vec<SharedGeometry> = vec![
+        Arc::new(TriangleMesh::new(
+            pebble,
+            Lambertian::new(Vector::new(0.4, 0.4, 0.4)),
+        )),
+        Arc::new(TriangleMesh::new(
+            knot,
+            Metal::new(Vector::new(0.9, 0.75, 0.45), 0.1),
+        )),
+    ];
+    let materials: Vec<SharedMaterial> = vec![
+        Arc::new(Lambertian::new(Vector::new(0.6, 0.55, 0.5))),
+        Arc::new(Lambertian::new(Vector::new(0.3, 0.28, 0.25))),
+        Arc::new(Lambertian::new(Vector::new(0.55, 0.3, 0.2))),
+        Arc::new(Metal::new(Vector::new(0.95, 0.64, 0.54), 0.2)),
+    ];
+
+    // resting on the ground, which curves away from the origin
+    let ground =
+        |x: f64, z: f64| (GROUND_RADIUS * GROUND_RADIUS - x * x - z * z).sqrt() - GROUND_RADIUS;
+    let mut instances = Vec::new();
+    let (count, spacing) = (1000, 0.2);
+    for i in 0..count {
+        for j in 0..count {
+            let x = (i as f64 - 0.5 * count as f64 + random_float()) * spacing;
+            let z = (j as f64 - 0.9 * count as f64 + random_float()) * spacing;
+            let size = random_float_between(0.02, 0.06);
+            let squash = Vector::new(1.0, random_float_between(0.4, 0.8), 1.0);
+            let transform = Transform::scale(size * squash)
+                .then(&Transform::rotate_y(random_float_between(0.0, 360.0)))
+                .then(&Transform::translate(Vector::new(
+                    x,
+                    ground(x, z) + 0.6 * size * squash.y(),
+                    z,
+                )));
+            let instance = Instance::new(0, transform);
+            // a quarter keep the mesh's grey
+            instances.push(match (4.0 * random_float()) as usize {
+                0 => instance,
+                material => instance.with_material(material - 1),
+            });
+        }
+    }
+    for (k, &(x, z)) in [(-1.2, 0.5), (0.3, -0.6), (1.5, 0.8)].iter().enumerate() {
+        let instance = Instance::new(
+            1,
+            Transform::scale(Vector::new(0.35, 0.35, 0.35))
+                .then(&Transform::rotate_x(70.0))
+                .then(&Transform::rotate_y(40.0 * k as f64))
+                .then(&Transform::translate(Vector::new(
+                    x,
+                    ground(x, z) + 0.36,
+                    z,
+                ))),
+        );
+        instances.push(if k == 1 {
+            instance.with_material(3)
+        } else {
+            instance
+        });
+    }
+    scene.push(Instances::new(geometry, materials, instances));
+
+    (scene, camera)
+}
@@ -0,0 +1,103 @@
+use crate::aabb::Aabb;
+use crate::camera::Camera;
+use crate::hitable::HitableList;
+use crate::material::{Dielectric, Lambertian, Metal};
+use crate::mesh::TriangleMesh;
+use crate::ply;
+use crate::scatter::Scatter;
+use crate::sphere::Sphere;
+use crate::texture::{Texture, Wrap};
+use crate::tracer::{random_float, random_float_between};
+use crate::transform::{Transform, Transformed};
+use crate::vector::Vector;
+use image::{DynamicImage, Rgb, RgbImage};
+
+// Scene2's spheres, scattered so that none of them touch: they keep clear of
+// each other and of the big spheres, thinning out towards the edges, and the
+// mesh sphere is studded with beads evenly over its surface.
+pub fn scene() -> (HitableList, Camera) {
+    // Camera
+    let aspect_ratio = 16.0 / 9.0;
+    let lookfrom = Vector::new(13.0, 2.0, 3.0);
+    let lookat = Vector::new(0.0, 0.0, 0.0);
+    let vup = Vector::new(0.0, 1.0, 0.0);
+    let dist_to_focus = 10.0;
+    let aperture = 0.1;
+    let camera = Camera::new(
+        lookfrom,
+        lookat,
+        vup,
+        20.0,
+        aspect_ratio,
+        aperture,
+        dist_to_focus,
+        0.0,
+        1.0,
+    );
+
+    let mut scene = HitableList::default();
+    scene.push(Sphere::new(
+        Vector::new(0.0, -1000.0, 0.0),
+        1000.0,
+        Lambertian::new(Vector::new(0.5, 0.5, 0.5)),
+    ));
+
+    let centers = [
+        Vector::new(0.0, 1.0, 0.0),
+        Vector::new(-4.0, 1.0, 0.0),
+        Vector::new(4.0, 1.0, 0.0),
+    ];
+    scene.push(Sphere::new(centers[0], 1.0, Dielectric::new(1.5)));
+    let mesh = ply::parse(include_bytes!("../../meshes/icosphere.ply")).unwrap();
+    let studs = Scatter::new(0.3).with_seed(2).on_mesh(&mesh);
+    scene.push(Transformed::new(
+        TriangleMesh::new(mesh, Lambertian::new(Vector::new(0.9, 0.9, 0.9))),
+        Transform::translate(centers[1]),
+    ));
+    for (p, normal) in studs {
+        scene.push(Sphere::new(
+            centers[1] + p + 0.02 * normal,
+            0.06,
+            Metal::new(Vector::new(0.9, 0.75, 0.45), 0.0),
+        ));
+    }
+    scene.push(Sphere::new(
+        centers[2],
+        1.0,
+        Metal::new(Vector::new(0.7, 0.6, 0.5), 0.0),
+    ));
+
+    // dense in the middle, fading to nothing at the edges
+    let size = 64;
+    let fade = RgbImage::from_fn(size, size, |x, y| {
+        let d = |i: u32| (i as f64 + 0.5) / size as f64 - 0.5;
+        let density = (1.0 - 2.0 * (d(x) * d(x) + d(y) * d(y)).sqrt()).clamp(0.0, 1.0);
+        let level = (255.0 * density.sqrt()) as u8;
+        Rgb([level, level, level])
+    });
+    let density = Texture::from_image(&DynamicImage::ImageRgb8(fade), false)
+        .with_wrap(Wrap::Clamp, Wrap::Clamp);
+    let scatter = centers
+        .iter()
+        .fold(Scatter::new(0.5).with_seed(1), |scatter, &c| {
+            let reach = Vector::new(1.0, 1.0, 1.0);
+            scatter.avoiding(Aabb::new(c - reach, c + reach))
+        })
+        .with_density(density);
+    for (x, z) in scatter.in_rectangle((-11.0, -11.0), (11.0, 11.0)) {
+        let center = Vector::new(x, 0.2, z);
+        let choose_mat = random_float();
+        if choose_mat < 0.8 {
+            let albedo = Vector::random().hadamard_product(Vector::random());
+            scene.push(Sphere::new(center, 0.2, Lambertian::new(albedo)));
+        } else if choose_mat < 0.95 {
+            let albedo = Vector::random_between(0.5, 1.0);
+            let fuzz = random_float_between(0.0, 0.5);
+            scene.push(Sphere::new(center, 0.2, Metal::new(albedo, fuzz)));
+        } else {
+            scene.push(Sphere::new(center, 0.2, Dielectric::new(1.5)));
+        }
+    }
+
+    (scene, camera)
+}
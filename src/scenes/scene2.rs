@@ -78,5 +78,5 @@ pub fn scene() -> (HitableList, Camera) {
     let material3 = Metal::new(Vector::new(0.7, 0.6, 0.5), 0.0);
     scene.push(Sphere::new(Vector::new(4.0, 1.0, 0.0), 1.0, material3));
 
-    return (scene, camera);
+    (scene, camera)
 }
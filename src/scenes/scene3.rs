@@ -0,0 +1,124 @@
+use crate::animation::{CameraAnimation, Keyframe, Track, TransformAnimation};
+use crate::hitable::HitableList;
+use crate::material::{Lambertian, Metal};
+use crate::sphere::Sphere;
+use crate::transform::{Animated, Quaternion};
+use crate::vector::Vector;
+
+// A ball bouncing twice next to an orbiting mirror ball and a tumbling disc,
+// with the camera dollying in. Two seconds long.
+pub fn scene() -> (HitableList, CameraAnimation) {
+    // Camera
+    let camera = CameraAnimation {
+        lookfrom: Track::new(vec![
+            Keyframe::catmull_rom(0.0, Vector::new(-6.0, 3.0, 6.0)),
+            Keyframe::catmull_rom(1.0, Vector::new(0.0, 2.5, 7.0)),
+            Keyframe::catmull_rom(2.0, Vector::new(4.0, 1.5, 4.0)),
+        ])
+        .unwrap(),
+        lookat: Track::constant(Vector::new(0.0, 0.8, 0.0)),
+        vup: Vector::new(0.0, 1.0, 0.0),
+        vertical_field_of_view: Track::new(vec![
+            Keyframe::linear(0.0, 30.0),
+            Keyframe::linear(2.0, 40.0),
+        ])
+        .unwrap(),
+        focus_dist: Track::new(vec![
+            Keyframe::catmull_rom(0.0, 8.9),
+            Keyframe::catmull_rom(1.0, 7.3),
+            Keyframe::catmull_rom(2.0, 5.7),
+        ])
+        .unwrap(),
+        aperture: 0.05,
+        aspect_ratio: 16.0 / 9.0,
+    };
+
+    let mut scene = HitableList::default();
+    scene.push(Sphere::new(
+        Vector::new(0.0, -1000.0, 0.0),
+        1000.0,
+        Lambertian::new(Vector::new(0.5, 0.5, 0.5)),
+    ));
+
+    // falls from its highest point, picking up speed until it hits the ground
+    let top = Vector::new(0.0, 2.0, 0.0);
+    let ground = Vector::new(0.0, 0.5, 0.0);
+    let zero = Vector::default();
+    let bounce = Vector::new(0.0, 0.6, 0.0);
+    let mut ball = TransformAnimation::new(
+        Track::new(vec![
+            Keyframe::bezier(0.0, top, zero, zero),
+            Keyframe::bezier(0.5, ground, bounce, bounce),
+            Keyframe::bezier(1.0, top, zero, zero),
+            Keyframe::bezier(1.5, ground, bounce, bounce),
+            Keyframe::bezier(2.0, top, zero, zero),
+        ])
+        .unwrap(),
+    );
+    // squashed when it touches the ground
+    let round = Vector::new(1.0, 1.0, 1.0);
+    let squashed = Vector::new(1.25, 0.75, 1.25);
+    ball.scale = Track::new(vec![
+        Keyframe::linear(0.0, round),
+        Keyframe::linear(0.45, round),
+        Keyframe::linear(0.5, squashed),
+        Keyframe::linear(0.55, round),
+        Keyframe::linear(1.45, round),
+        Keyframe::linear(1.5, squashed),
+        Keyframe::linear(1.55, round),
+    ])
+    .unwrap();
+    scene.push(Animated::new(
+        Sphere::new(
+            Vector::default(),
+            0.5,
+            Lambertian::new(Vector::new(0.8, 0.2, 0.1)),
+        ),
+        ball,
+    ));
+
+    // circles the bouncing ball once
+    let radius = 2.0;
+    let orbit = (0..=8)
+        .map(|i| {
+            let angle = i as f64 / 8.0 * 2.0 * std::f64::consts::PI;
+            Keyframe::catmull_rom(
+                i as f64 / 4.0,
+                Vector::new(radius * angle.cos(), 0.6, radius * angle.sin()),
+            )
+        })
+        .collect();
+    scene.push(Animated::new(
+        Sphere::new(
+            Vector::default(),
+            0.6,
+            Metal::new(Vector::new(0.8, 0.8, 0.9), 0.0),
+        ),
+        TransformAnimation::new(Track::new(orbit).unwrap()),
+    ));
+
+    // a flattened ball tumbling around a tilted axis, a quarter turn every
+    // tenth of a second
+    let axis = Vector::new(1.0, 0.5, 0.0);
+    let tumble = (0..=20)
+        .map(|i| {
+            Keyframe::linear(
+                i as f64 / 10.0,
+                Quaternion::from_axis_angle(axis, 90.0 * i as f64),
+            )
+        })
+        .collect();
+    let mut disc = TransformAnimation::new(Track::constant(Vector::new(-2.5, 1.2, -1.0)));
+    disc.rotation = Track::new(tumble).unwrap();
+    disc.scale = Track::constant(Vector::new(0.7, 0.15, 0.7));
+    scene.push(Animated::new(
+        Sphere::new(
+            Vector::default(),
+            1.0,
+            Lambertian::new(Vector::new(0.2, 0.4, 0.8)),
+        ),
+        disc,
+    ));
+
+    (scene, camera)
+}
@@ -0,0 +1,81 @@
+use crate::camera::Camera;
+use crate::csg::Csg;
+use crate::cuboid::Cuboid;
+use crate::hitable::HitableList;
+use crate::material::{Dielectric, Lambertian, Metal};
+use crate::sphere::Sphere;
+use crate::transform::{Transform, Transformed};
+use crate::vector::Vector;
+
+// Shapes built out of spheres and boxes with constructive solid geometry.
+pub fn scene() -> (HitableList, Camera) {
+    // Camera
+    let aspect_ratio = 16.0 / 9.0;
+    let lookfrom = Vector::new(0.0, 3.0, 8.0);
+    let lookat = Vector::new(0.0, 0.7, 0.0);
+    let vup = Vector::new(0.0, 1.0, 0.0);
+    let dist_to_focus = (lookfrom - lookat).length();
+    let camera = Camera::new(
+        lookfrom,
+        lookat,
+        vup,
+        30.0,
+        aspect_ratio,
+        0.05,
+        dist_to_focus,
+        0.0,
+        1.0,
+    );
+
+    let mut scene = HitableList::default();
+    scene.push(Sphere::new(
+        Vector::new(0.0, -1000.0, 0.0),
+        1000.0,
+        Lambertian::new(Vector::new(0.5, 0.5, 0.5)),
+    ));
+
+    // a box rounded by a sphere, with the three axes drilled through it
+    let red = Lambertian::new(Vector::new(0.8, 0.2, 0.1));
+    let rounded = Csg::intersection(
+        Cuboid::new(
+            Vector::new(-0.8, -0.8, -0.8),
+            Vector::new(0.8, 0.8, 0.8),
+            red,
+        ),
+        Sphere::new(Vector::default(), 1.05, red),
+    );
+    let bar = |size: Vector| Cuboid::new(-1.0 * size, size, red);
+    let drills = Csg::union(
+        Csg::union(
+            bar(Vector::new(1.0, 0.35, 0.35)),
+            bar(Vector::new(0.35, 1.0, 0.35)),
+        ),
+        bar(Vector::new(0.35, 0.35, 1.0)),
+    );
+    scene.push(Transformed::new(
+        Csg::difference(rounded, drills),
+        Transform::rotate_y(30.0).then(&Transform::translate(Vector::new(-2.2, 0.8, 0.0))),
+    ));
+
+    // a mirror ball with a bite taken out of it
+    let silver = Metal::new(Vector::new(0.8, 0.8, 0.9), 0.0);
+    let gold = Metal::new(Vector::new(0.8, 0.6, 0.2), 0.2);
+    scene.push(Csg::difference(
+        Sphere::new(Vector::new(0.0, 0.8, 0.0), 0.8, silver),
+        Sphere::new(Vector::new(0.45, 1.2, 0.6), 0.6, gold),
+    ));
+
+    // a glass lens, where two spheres overlap, and two joined balls
+    let glass = Dielectric::new(1.5);
+    scene.push(Csg::intersection(
+        Sphere::new(Vector::new(2.1, 1.0, -1.2), 1.5, glass),
+        Sphere::new(Vector::new(2.1, 1.0, 1.2), 1.5, glass),
+    ));
+    let blue = Lambertian::new(Vector::new(0.1, 0.2, 0.5));
+    scene.push(Csg::union(
+        Sphere::new(Vector::new(0.9, 0.3, 1.7), 0.3, blue),
+        Sphere::new(Vector::new(1.25, 0.25, 1.9), 0.25, blue),
+    ));
+
+    (scene, camera)
+}
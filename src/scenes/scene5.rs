@@ -0,0 +1,117 @@
+use crate::camera::Camera;
+use crate::hitable::HitableList;
+use crate::material::{Lambertian, Metal};
+use crate::sdf::{
+    Mandelbulb, MengerSponge, Sdf, SdfBox, SdfCapsule, SdfHitable, SdfSphere, SdfTorus,
+};
+use crate::sphere::Sphere;
+use crate::transform::Quaternion;
+use crate::vector::Vector;
+
+// Fractals and blended shapes rendered from their distance fields.
+pub fn scene() -> (HitableList, Camera) {
+    // Camera
+    let aspect_ratio = 16.0 / 9.0;
+    let lookfrom = Vector::new(0.0, 2.2, 7.5);
+    let lookat = Vector::new(0.0, 0.8, 0.0);
+    let vup = Vector::new(0.0, 1.0, 0.0);
+    let dist_to_focus = (lookfrom - lookat).length();
+    let camera = Camera::new(
+        lookfrom,
+        lookat,
+        vup,
+        35.0,
+        aspect_ratio,
+        0.02,
+        dist_to_focus,
+        0.0,
+        1.0,
+    );
+
+    let mut scene = HitableList::default();
+    scene.push(Sphere::new(
+        Vector::new(0.0, -1000.0, 0.0),
+        1000.0,
+        Lambertian::new(Vector::new(0.5, 0.5, 0.5)),
+    ));
+
+    // the bulb's axis is z, stand it up
+    let bulb = Mandelbulb::new(8.0)
+        .rotate(Quaternion::from_axis_angle(
+            Vector::new(1.0, 0.0, 0.0),
+            -90.0,
+        ))
+        .scale(0.95)
+        .translate(Vector::new(0.0, 1.1, 0.0));
+    scene.push(SdfHitable::new(
+        bulb,
+        Lambertian::new(Vector::new(0.8, 0.45, 0.2)),
+    ));
+
+    let sponge = MengerSponge {
+        half_size: 0.7,
+        iterations: 4,
+    }
+    .rotate(Quaternion::from_axis_angle(
+        Vector::new(0.0, 1.0, 0.0),
+        30.0,
+    ))
+    .translate(Vector::new(-2.5, 0.7, -0.3));
+    scene.push(SdfHitable::new(
+        sponge,
+        Lambertian::new(Vector::new(0.7, 0.7, 0.75)),
+    ));
+
+    // a torus melting into two balls
+    let blob = SdfTorus {
+        major: 0.45,
+        minor: 0.15,
+    }
+    .smooth_union(
+        SdfSphere { radius: 0.3 }.translate(Vector::new(0.45, 0.3, 0.0)),
+        0.3,
+    )
+    .smooth_union(
+        SdfSphere { radius: 0.22 }.translate(Vector::new(-0.35, 0.25, 0.25)),
+        0.3,
+    )
+    .translate(Vector::new(2.5, 0.3, -0.3));
+    scene.push(SdfHitable::new(
+        blob,
+        Metal::new(Vector::new(0.8, 0.8, 0.9), 0.05),
+    ));
+
+    // a twisted rounded column and a bumpy ball
+    let column = SdfBox {
+        half_size: Vector::new(0.2, 0.5, 0.2),
+    }
+    .round(0.03)
+    .twist(120.0)
+    .translate(Vector::new(-1.3, 0.53, 1.9));
+    scene.push(SdfHitable::new(
+        column,
+        Lambertian::new(Vector::new(0.2, 0.5, 0.3)),
+    ));
+    let bumpy = SdfSphere { radius: 0.35 }
+        .displace(0.03, 25.0)
+        .translate(Vector::new(1.3, 0.38, 1.9));
+    scene.push(SdfHitable::new(
+        bumpy,
+        Lambertian::new(Vector::new(0.1, 0.2, 0.6)),
+    ));
+
+    // a row of pills in the back
+    let pills = SdfCapsule {
+        a: Vector::new(0.0, -0.15, 0.0),
+        b: Vector::new(0.0, 0.15, 0.0),
+        radius: 0.1,
+    }
+    .repeat(Vector::new(0.6, 0.0, 0.0), Some([6, 0, 0]))
+    .translate(Vector::new(0.0, 0.25, -2.5));
+    scene.push(SdfHitable::new(
+        pills,
+        Lambertian::new(Vector::new(0.8, 0.1, 0.3)),
+    ));
+
+    (scene, camera)
+}
@@ -0,0 +1,84 @@
+use crate::camera::Camera;
+use crate::hitable::HitableList;
+use crate::material::{Dielectric, Lambertian, Metal};
+use crate::quadric::{Cone, Cylinder, Disk, Hyperboloid, Paraboloid, Torus};
+use crate::sphere::Sphere;
+use crate::transform::{Transform, Transformed};
+use crate::vector::Vector;
+
+// A row of quadrics and a torus, some of them partially swept.
+pub fn scene() -> (HitableList, Camera) {
+    // Camera
+    let aspect_ratio = 16.0 / 9.0;
+    let lookfrom = Vector::new(0.0, 2.5, 9.0);
+    let lookat = Vector::new(0.0, 0.7, 0.0);
+    let vup = Vector::new(0.0, 1.0, 0.0);
+    let dist_to_focus = (lookfrom - lookat).length();
+    let camera = Camera::new(
+        lookfrom,
+        lookat,
+        vup,
+        35.0,
+        aspect_ratio,
+        0.02,
+        dist_to_focus,
+        0.0,
+        1.0,
+    );
+
+    let mut scene = HitableList::default();
+    scene.push(Sphere::new(
+        Vector::new(0.0, -1000.0, 0.0),
+        1000.0,
+        Lambertian::new(Vector::new(0.5, 0.5, 0.5)),
+    ));
+    let at = |x: f64, y: f64, z: f64| Transform::translate(Vector::new(x, y, z));
+
+    // a can cut in half, open toward the camera
+    scene.push(Transformed::new(
+        Cylinder::new(0.5, 0.0, 1.2, Lambertian::new(Vector::new(0.8, 0.3, 0.1)))
+            .with_caps()
+            .with_phi_max(180.0),
+        Transform::rotate_y(160.0).then(&at(-3.4, 0.0, 0.0)),
+    ));
+    scene.push(Transformed::new(
+        Cone::new(0.55, 1.4, Lambertian::new(Vector::new(0.9, 0.7, 0.1))).with_cap(),
+        at(-2.0, 0.0, 0.3),
+    ));
+
+    // a glass ring standing up, and a gold one cut open lying down
+    scene.push(Transformed::new(
+        Torus::new(0.55, 0.2, Dielectric::new(1.5)),
+        Transform::rotate_x(75.0).then(&at(-0.6, 0.75, 0.0)),
+    ));
+    scene.push(Transformed::new(
+        Torus::new(0.4, 0.12, Metal::new(Vector::new(0.8, 0.6, 0.2), 0.1)).with_phi_max(270.0),
+        at(-0.6, 0.12, 1.6),
+    ));
+
+    // a mirror dish tilted toward the camera
+    scene.push(Transformed::new(
+        Paraboloid::new(0.7, 0.0, 0.5, Metal::new(Vector::new(0.8, 0.8, 0.9), 0.0)),
+        Transform::rotate_x(50.0).then(&at(0.9, 0.75, 0.0)),
+    ));
+    scene.push(Transformed::new(
+        Hyperboloid::new(
+            0.35,
+            0.5,
+            -0.4,
+            0.8,
+            Lambertian::new(Vector::new(0.7, 0.7, 0.7)),
+        ),
+        at(2.3, 0.4, -0.2),
+    ));
+
+    // a washer with a slice missing, standing up
+    scene.push(Transformed::new(
+        Disk::new(0.6, Lambertian::new(Vector::new(0.1, 0.3, 0.7)))
+            .with_inner_radius(0.25)
+            .with_phi_max(300.0),
+        Transform::rotate_x(90.0).then(&at(3.6, 0.6, 0.3)),
+    ));
+
+    (scene, camera)
+}
@@ -0,0 +1,106 @@
+use crate::camera::Camera;
+use crate::curve::{Curve, Curves};
+use crate::hitable::HitableList;
+use crate::material::{Hair, Lambertian};
+use crate::sphere::Sphere;
+use crate::tracer::{random_float, random_float_between};
+use crate::transform::{Transform, Transformed};
+use crate::vector::{random_unit_vector, Vector};
+
+// A ball of fur sitting in grass, next to a curl of hair.
+pub fn scene() -> (HitableList, Camera) {
+    // Camera
+    let aspect_ratio = 16.0 / 9.0;
+    let lookfrom = Vector::new(0.0, 1.4, 5.0);
+    let lookat = Vector::new(0.0, 0.6, 0.0);
+    let vup = Vector::new(0.0, 1.0, 0.0);
+    let dist_to_focus = (lookfrom - lookat).length();
+    let camera = Camera::new(
+        lookfrom,
+        lookat,
+        vup,
+        35.0,
+        aspect_ratio,
+        0.02,
+        dist_to_focus,
+        0.0,
+        1.0,
+    );
+
+    let mut scene = HitableList::default();
+    scene.push(Sphere::new(
+        Vector::new(0.0, -1000.0, 0.0),
+        1000.0,
+        Lambertian::new(Vector::new(0.35, 0.3, 0.2)),
+    ));
+
+    // hairs growing out of the ball and drooping under their weight
+    let center = Vector::new(-0.3, 0.55, 0.0);
+    let radius = 0.5;
+    scene.push(Sphere::new(
+        center,
+        radius,
+        Lambertian::new(Vector::new(0.15, 0.08, 0.04)),
+    ));
+    let mut fur = Vec::new();
+    for _ in 0..2500 {
+        let out = random_unit_vector();
+        let root = center + (0.98 * radius) * out;
+        let length = random_float_between(0.18, 0.26);
+        let droop = Vector::new(0.0, -0.5 * length, 0.0) + (0.1 * length) * random_unit_vector();
+        let points = [
+            root,
+            root + (0.4 * length) * out,
+            root + (0.75 * length) * out + 0.4 * droop,
+            root + length * out + droop,
+        ];
+        fur.push(Curve::cylinder(points, 0.012, 0.002));
+    }
+    scene.push(Curves::new(
+        fur,
+        Hair::from_melanin(0.8, 0.6).with_roughness(0.25, 0.4),
+    ));
+
+    // blades of grass around it, tapering to a point
+    let mut grass = Vec::new();
+    for _ in 0..1500 {
+        let root = Vector::new(
+            random_float_between(-2.2, 2.2),
+            0.0,
+            random_float_between(-1.0, 1.8),
+        );
+        if (root - Vector::new(center.x(), 0.0, center.z())).length() < 0.45 {
+            continue;
+        }
+        let height = random_float_between(0.15, 0.4);
+        let angle = 2.0 * std::f64::consts::PI * random_float();
+        let lean = Vector::new(angle.cos(), 0.0, angle.sin());
+        let bend = random_float_between(0.05, 0.5) * height;
+        let points = [
+            root,
+            root + Vector::new(0.0, 0.4 * height, 0.0),
+            root + Vector::new(0.0, 0.8 * height, 0.0) + (0.4 * bend) * lean,
+            root + Vector::new(0.0, height, 0.0) + bend * lean,
+        ];
+        // the blades face sideways to the way they lean
+        let normal = Vector::new(-angle.sin(), 0.0, angle.cos());
+        grass.push(Curve::ribbon(points, 0.03, 0.0, normal, normal));
+    }
+    scene.push(Curves::new(
+        grass,
+        Lambertian::new(Vector::new(0.2, 0.45, 0.1)),
+    ));
+
+    // a curl of blond hair, from a file
+    let curl = Curves::parse(
+        include_str!("../../curves/curl.curves"),
+        Hair::from_melanin(0.3, 0.1),
+    )
+    .expect("curves/curl.curves");
+    scene.push(Transformed::new(
+        curl,
+        Transform::translate(Vector::new(1.2, 0.0, 0.2)),
+    ));
+
+    (scene, camera)
+}
@@ -0,0 +1,59 @@
+use crate::camera::Camera;
+use crate::heightfield::Heightfield;
+use crate::hitable::HitableList;
+use crate::material::{Lambertian, Metal};
+use crate::noise::{fbm, ridged};
+use crate::quadric::Disk;
+use crate::transform::{Transform, Transformed};
+use crate::vector::Vector;
+
+// Mountains around a lake, from noise.
+pub fn scene() -> (HitableList, Camera) {
+    // Camera
+    let aspect_ratio = 16.0 / 9.0;
+    let lookfrom = Vector::new(0.0, 2.5, 9.0);
+    let lookat = Vector::new(0.0, 1.0, -2.0);
+    let vup = Vector::new(0.0, 1.0, 0.0);
+    let dist_to_focus = (lookfrom - lookat).length();
+    let camera = Camera::new(
+        lookfrom,
+        lookat,
+        vup,
+        50.0,
+        aspect_ratio,
+        0.0,
+        dist_to_focus,
+        0.0,
+        1.0,
+    );
+
+    let mut scene = HitableList::default();
+
+    // ridges in the back rising from rolling hills, with a dip for the lake
+    let terrain = Heightfield::from_fn(
+        512,
+        512,
+        |x, z| {
+            let p = Vector::new(6.0 * x, 0.0, 6.0 * z);
+            let hills = 0.2 + 0.15 * fbm(2.0 * p, 11, 6);
+            let ridges = ridged(0.7 * p, 12, 8).powi(2);
+            let back = (1.0 - z).clamp(0.0, 1.0);
+            let lake = (-((x - 0.5).powi(2) + (z - 0.68).powi(2)) / 0.01).exp();
+            hills + 0.9 * back * back * ridges - 0.35 * lake
+        },
+        Lambertian::new(Vector::new(0.35, 0.35, 0.25)),
+    );
+    scene.push(Transformed::new(
+        terrain,
+        Transform::scale(Vector::new(24.0, 4.0, 24.0))
+            .then(&Transform::translate(Vector::new(-12.0, 0.0, -16.0))),
+    ));
+
+    // the water, a level disk where the terrain dips under it
+    scene.push(Transformed::new(
+        Disk::new(12.0, Metal::new(Vector::new(0.6, 0.7, 0.8), 0.02)),
+        Transform::translate(Vector::new(0.0, 0.6, 0.0)),
+    ));
+
+    (scene, camera)
+}
@@ -0,0 +1,51 @@
+use crate::camera::Camera;
+use crate::hitable::HitableList;
+use crate::material::{Lambertian, Metal};
+use crate::mesh::TriangleMesh;
+use crate::sphere::Sphere;
+use crate::transform::{Transform, Transformed};
+use crate::vector::Vector;
+use crate::{ply, stl};
+
+// A vertex coloured PLY sphere next to a polished STL knot.
+pub fn scene() -> (HitableList, Camera) {
+    // Camera
+    let aspect_ratio = 16.0 / 9.0;
+    let lookfrom = Vector::new(0.0, 2.0, 7.0);
+    let lookat = Vector::new(0.0, 0.8, 0.0);
+    let vup = Vector::new(0.0, 1.0, 0.0);
+    let dist_to_focus = (lookfrom - lookat).length();
+    let camera = Camera::new(
+        lookfrom,
+        lookat,
+        vup,
+        35.0,
+        aspect_ratio,
+        0.0,
+        dist_to_focus,
+        0.0,
+        1.0,
+    );
+
+    let mut scene = HitableList::default();
+    scene.push(Sphere::new(
+        Vector::new(0.0, -1000.0, 0.0),
+        1000.0,
+        Lambertian::new(Vector::new(0.5, 0.5, 0.5)),
+    ));
+
+    // white, so that the vertex colours show as they are
+    let sphere = ply::parse(include_bytes!("../../meshes/icosphere.ply")).unwrap();
+    scene.push(Transformed::new(
+        TriangleMesh::new(sphere, Lambertian::new(Vector::new(0.9, 0.9, 0.9))),
+        Transform::translate(Vector::new(-1.3, 1.0, 0.0)),
+    ));
+
+    let knot = stl::parse(include_bytes!("../../meshes/knot.stl")).unwrap();
+    scene.push(Transformed::new(
+        TriangleMesh::new(knot, Metal::new(Vector::new(0.9, 0.75, 0.45), 0.1)),
+        Transform::rotate_x(70.0).then(&Transform::translate(Vector::new(1.3, 1.1, 0.0))),
+    ));
+
+    (scene, camera)
+}
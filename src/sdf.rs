@@ -0,0 +1,633 @@
+use crate::aabb::Aabb;
+use crate::csg::CsgOperation;
+use crate::hitable::{HitRecord, Hitable};
+use crate::material::Material;
+use crate::ray::Ray;
+use crate::transform::{Quaternion, Transform};
+use crate::vector::Vector;
+
+/// A shape given by its signed distance field: how far a point is from the
+/// surface, negative inside.
+pub trait Sdf: Sync {
+    fn distance(&self, p: Vector) -> f64;
+
+    /// Box holding the surface, None if it's unbounded.
+    fn bounds(&self) -> Option<Aabb>;
+
+    /// How much faster than the distance to the surface the field can change,
+    /// 1 for exact distances. Marching slows down by as much so that it never
+    /// steps over the surface.
+    fn lipschitz(&self) -> f64 {
+        1.0
+    }
+
+    fn translate(self, offset: Vector) -> Placed<Self>
+    where
+        Self: Sized,
+    {
+        Placed::new(self, Transform::translate(offset), 1.0)
+    }
+
+    fn rotate(self, rotation: Quaternion) -> Placed<Self>
+    where
+        Self: Sized,
+    {
+        Placed::new(self, Transform::from_quaternion(rotation), 1.0)
+    }
+
+    fn scale(self, factor: f64) -> Placed<Self>
+    where
+        Self: Sized,
+    {
+        Placed::new(
+            self,
+            Transform::scale(Vector::new(factor, factor, factor)),
+            factor,
+        )
+    }
+
+    fn union<B: Sdf>(self, other: B) -> Combined<Self, B>
+    where
+        Self: Sized,
+    {
+        Combined::new(self, other, CsgOperation::Union, 0.0)
+    }
+
+    fn intersection<B: Sdf>(self, other: B) -> Combined<Self, B>
+    where
+        Self: Sized,
+    {
+        Combined::new(self, other, CsgOperation::Intersection, 0.0)
+    }
+
+    fn difference<B: Sdf>(self, other: B) -> Combined<Self, B>
+    where
+        Self: Sized,
+    {
+        Combined::new(self, other, CsgOperation::Difference, 0.0)
+    }
+
+    // blends the shapes together over `k` units
+    fn smooth_union<B: Sdf>(self, other: B, k: f64) -> Combined<Self, B>
+    where
+        Self: Sized,
+    {
+        Combined::new(self, other, CsgOperation::Union, k)
+    }
+
+    fn smooth_intersection<B: Sdf>(self, other: B, k: f64) -> Combined<Self, B>
+    where
+        Self: Sized,
+    {
+        Combined::new(self, other, CsgOperation::Intersection, k)
+    }
+
+    fn smooth_difference<B: Sdf>(self, other: B, k: f64) -> Combined<Self, B>
+    where
+        Self: Sized,
+    {
+        Combined::new(self, other, CsgOperation::Difference, k)
+    }
+
+    // twisted around the y axis by `rate` degrees per unit
+    fn twist(self, rate: f64) -> Twist<Self>
+    where
+        Self: Sized,
+    {
+        Twist {
+            sdf: self,
+            rate: rate.to_radians(),
+        }
+    }
+
+    // copies every `period` units along each axis with a non zero period,
+    // `limit` of them on each side, or forever if None
+    fn repeat(self, period: Vector, limit: Option<[u32; 3]>) -> Repeat<Self>
+    where
+        Self: Sized,
+    {
+        Repeat {
+            sdf: self,
+            period,
+            limit,
+        }
+    }
+
+    // bumps of up to `amplitude` units, `frequency` of them per 2 pi units
+    fn displace(self, amplitude: f64, frequency: f64) -> Displace<Self>
+    where
+        Self: Sized,
+    {
+        Displace {
+            sdf: self,
+            amplitude,
+            frequency,
+        }
+    }
+
+    // grown by `radius`, which rounds its edges
+    fn round(self, radius: f64) -> Round<Self>
+    where
+        Self: Sized,
+    {
+        Round { sdf: self, radius }
+    }
+}
+
+fn abs(v: Vector) -> Vector {
+    Vector::new(v.x().abs(), v.y().abs(), v.z().abs())
+}
+
+fn max0(v: Vector) -> Vector {
+    Vector::new(v.x().max(0.0), v.y().max(0.0), v.z().max(0.0))
+}
+
+fn cube(half_size: f64) -> Aabb {
+    let h = Vector::new(half_size, half_size, half_size);
+    Aabb::new(-1.0 * h, h)
+}
+
+// Primitives, centered on the origin
+
+pub struct SdfSphere {
+    pub radius: f64,
+}
+
+impl Sdf for SdfSphere {
+    fn distance(&self, p: Vector) -> f64 {
+        p.length() - self.radius
+    }
+
+    fn bounds(&self) -> Option<Aabb> {
+        Some(cube(self.radius))
+    }
+}
+
+pub struct SdfBox {
+    pub half_size: Vector,
+}
+
+impl Sdf for SdfBox {
+    fn distance(&self, p: Vector) -> f64 {
+        let q = abs(p) - self.half_size;
+        max0(q).length() + q.x().max(q.y()).max(q.z()).min(0.0)
+    }
+
+    fn bounds(&self) -> Option<Aabb> {
+        Some(Aabb::new(-1.0 * self.half_size, self.half_size))
+    }
+}
+
+// a ring of radius `major` around the y axis, `minor` thick
+pub struct SdfTorus {
+    pub major: f64,
+    pub minor: f64,
+}
+
+impl Sdf for SdfTorus {
+    fn distance(&self, p: Vector) -> f64 {
+        let ring = (p.x() * p.x() + p.z() * p.z()).sqrt() - self.major;
+        (ring * ring + p.y() * p.y()).sqrt() - self.minor
+    }
+
+    fn bounds(&self) -> Option<Aabb> {
+        let r = self.major + self.minor;
+        Some(Aabb::new(
+            Vector::new(-r, -self.minor, -r),
+            Vector::new(r, self.minor, r),
+        ))
+    }
+}
+
+// the points within `radius` of the segment from a to b
+pub struct SdfCapsule {
+    pub a: Vector,
+    pub b: Vector,
+    pub radius: f64,
+}
+
+impl Sdf for SdfCapsule {
+    fn distance(&self, p: Vector) -> f64 {
+        let (pa, ba) = (p - self.a, self.b - self.a);
+        let h = (pa.dot(ba) / ba.dot(ba)).clamp(0.0, 1.0);
+        (pa - h * ba).length() - self.radius
+    }
+
+    fn bounds(&self) -> Option<Aabb> {
+        let segment = Aabb::new(self.a, self.a).surrounding(&Aabb::new(self.b, self.b));
+        Some(segment.padded(self.radius))
+    }
+}
+
+/// The 3D Mandelbrot set of z -> z^power + c in spherical coordinates, with
+/// the usual distance estimate. It fits in a sphere of radius 1.2 for power 8.
+pub struct Mandelbulb {
+    pub power: f64,
+    pub iterations: u32,
+}
+
+impl Mandelbulb {
+    pub fn new(power: f64) -> Mandelbulb {
+        Mandelbulb {
+            power,
+            iterations: 12,
+        }
+    }
+}
+
+impl Sdf for Mandelbulb {
+    fn distance(&self, p: Vector) -> f64 {
+        let mut z = p;
+        let mut dr = 1.0;
+        let mut r = z.length();
+        for _ in 0..self.iterations {
+            if r > 2.0 || r == 0.0 {
+                break;
+            }
+            let theta = (z.z() / r).acos() * self.power;
+            let phi = z.y().atan2(z.x()) * self.power;
+            dr = r.powf(self.power - 1.0) * self.power * dr + 1.0;
+            let zr = r.powf(self.power);
+            z =
+                zr * Vector::new(
+                    theta.sin() * phi.cos(),
+                    theta.sin() * phi.sin(),
+                    theta.cos(),
+                ) + p;
+            r = z.length();
+        }
+        if r == 0.0 {
+            return 0.0;
+        }
+        0.5 * r.ln() * r / dr
+    }
+
+    // every point further than 2 from the origin escapes
+    fn bounds(&self) -> Option<Aabb> {
+        Some(cube(2.0))
+    }
+}
+
+/// Menger sponge filling the cube of half side `half_size`: the cube with the
+/// middle of every face drilled through, and again in each of the 20 smaller
+/// cubes left, `iterations` times.
+pub struct MengerSponge {
+    pub half_size: f64,
+    pub iterations: u32,
+}
+
+impl Sdf for MengerSponge {
+    fn distance(&self, p: Vector) -> f64 {
+        // in a cube of half side 1
+        let p = p / self.half_size;
+        let mut d = SdfBox {
+            half_size: Vector::new(1.0, 1.0, 1.0),
+        }
+        .distance(p);
+        let mut s = 1.0;
+        for _ in 0..self.iterations {
+            // position in the current cell, -1..1 on each axis
+            let a = Vector::new(
+                (p.x() * s).rem_euclid(2.0) - 1.0,
+                (p.y() * s).rem_euclid(2.0) - 1.0,
+                (p.z() * s).rem_euclid(2.0) - 1.0,
+            );
+            s *= 3.0;
+            let r = abs(Vector::new(1.0, 1.0, 1.0) - 3.0 * abs(a));
+            // the three crossing bars drilled out of the cell
+            let da = r.x().max(r.y());
+            let db = r.y().max(r.z());
+            let dc = r.z().max(r.x());
+            let c = (da.min(db).min(dc) - 1.0) / s;
+            d = d.max(c);
+        }
+        d * self.half_size
+    }
+
+    fn bounds(&self) -> Option<Aabb> {
+        Some(cube(self.half_size))
+    }
+}
+
+// Operators
+
+/// A shape moved by a rigid transform and a uniform scale.
+pub struct Placed<S: Sdf> {
+    sdf: S,
+    transform: Transform,
+    scale: f64,
+}
+
+impl<S: Sdf> Placed<S> {
+    // `scale` has to be the one `transform` scales by, distances don't survive
+    // other transforms
+    fn new(sdf: S, transform: Transform, scale: f64) -> Self {
+        Placed {
+            sdf,
+            transform,
+            scale,
+        }
+    }
+}
+
+impl<S: Sdf> Sdf for Placed<S> {
+    fn distance(&self, p: Vector) -> f64 {
+        self.sdf.distance(self.transform.inverse().point(p)) * self.scale
+    }
+
+    fn bounds(&self) -> Option<Aabb> {
+        Some(self.sdf.bounds()?.transformed(&self.transform))
+    }
+
+    fn lipschitz(&self) -> f64 {
+        self.sdf.lipschitz()
+    }
+}
+
+/// Two shapes combined like solids (see `Csg`), blended over `smoothness`
+/// units with a polynomial smooth minimum.
+pub struct Combined<A: Sdf, B: Sdf> {
+    a: A,
+    b: B,
+    operation: CsgOperation,
+    smoothness: f64,
+}
+
+impl<A: Sdf, B: Sdf> Combined<A, B> {
+    pub fn new(a: A, b: B, operation: CsgOperation, smoothness: f64) -> Self {
+        Combined {
+            a,
+            b,
+            operation,
+            smoothness,
+        }
+    }
+}
+
+// minimum of a and b, rounded off where they're less than k apart
+fn smooth_min(a: f64, b: f64, k: f64) -> f64 {
+    if k <= 0.0 {
+        return a.min(b);
+    }
+    let h = (0.5 + 0.5 * (b - a) / k).clamp(0.0, 1.0);
+    b + (a - b) * h - k * h * (1.0 - h)
+}
+
+impl<A: Sdf, B: Sdf> Sdf for Combined<A, B> {
+    fn distance(&self, p: Vector) -> f64 {
+        let (a, b, k) = (self.a.distance(p), self.b.distance(p), self.smoothness);
+        match self.operation {
+            CsgOperation::Union => smooth_min(a, b, k),
+            CsgOperation::Intersection => -smooth_min(-a, -b, k),
+            CsgOperation::Difference => -smooth_min(-a, b, k),
+        }
+    }
+
+    fn bounds(&self) -> Option<Aabb> {
+        let (a, b) = (self.a.bounds(), self.b.bounds());
+        match self.operation {
+            // the blend swells by up to a quarter of the smoothness
+            CsgOperation::Union => Some(a?.surrounding(&b?).padded(self.smoothness / 4.0)),
+            CsgOperation::Intersection => match (a, b) {
+                (Some(a), Some(b)) => Some(a.overlap(&b)),
+                (a, b) => a.or(b),
+            },
+            CsgOperation::Difference => a,
+        }
+    }
+
+    fn lipschitz(&self) -> f64 {
+        self.a.lipschitz().max(self.b.lipschitz())
+    }
+}
+
+pub struct Twist<S: Sdf> {
+    sdf: S,
+    // radians per unit
+    rate: f64,
+}
+
+impl<S: Sdf> Twist<S> {
+    // furthest the shape gets from the y axis
+    fn radius(&self) -> Option<f64> {
+        let bounds = self.sdf.bounds()?;
+        Some(
+            bounds
+                .corners()
+                .iter()
+                .map(|c| (c.x() * c.x() + c.z() * c.z()).sqrt())
+                .fold(0.0, f64::max),
+        )
+    }
+}
+
+impl<S: Sdf> Sdf for Twist<S> {
+    fn distance(&self, p: Vector) -> f64 {
+        let (sin, cos) = (-self.rate * p.y()).sin_cos();
+        let q = Vector::new(cos * p.x() - sin * p.z(), p.y(), sin * p.x() + cos * p.z());
+        self.sdf.distance(q)
+    }
+
+    // the cylinder around the y axis the shape turns in
+    fn bounds(&self) -> Option<Aabb> {
+        let bounds = self.sdf.bounds()?;
+        let r = self.radius()?;
+        Some(Aabb::new(
+            Vector::new(-r, bounds.min.y(), -r),
+            Vector::new(r, bounds.max.y(), r),
+        ))
+    }
+
+    // points are dragged sideways faster the further they are from the axis
+    fn lipschitz(&self) -> f64 {
+        let r = self.radius().unwrap_or(1.0);
+        self.sdf.lipschitz() * (1.0 + (self.rate * r).powi(2)).sqrt()
+    }
+}
+
+/// Copies of a shape, which has to fit in a cell of the period around the
+/// origin for the distances to stay right.
+pub struct Repeat<S: Sdf> {
+    sdf: S,
+    period: Vector,
+    limit: Option<[u32; 3]>,
+}
+
+impl<S: Sdf> Sdf for Repeat<S> {
+    fn distance(&self, p: Vector) -> f64 {
+        let axes = [
+            (p.x(), self.period.x()),
+            (p.y(), self.period.y()),
+            (p.z(), self.period.z()),
+        ];
+        let mut q = [0.0; 3];
+        for (i, (v, period)) in axes.iter().enumerate() {
+            q[i] = if *period > 0.0 {
+                let mut cell = (v / period).round();
+                if let Some(limit) = self.limit {
+                    cell = cell.clamp(-(limit[i] as f64), limit[i] as f64);
+                }
+                v - period * cell
+            } else {
+                *v
+            };
+        }
+        self.sdf.distance(Vector::new(q[0], q[1], q[2]))
+    }
+
+    fn bounds(&self) -> Option<Aabb> {
+        let bounds = self.sdf.bounds()?;
+        let p = self.period;
+        if p.x() <= 0.0 && p.y() <= 0.0 && p.z() <= 0.0 {
+            return Some(bounds);
+        }
+        let limit = self.limit?;
+        let reach = Vector::new(
+            p.x().max(0.0) * limit[0] as f64,
+            p.y().max(0.0) * limit[1] as f64,
+            p.z().max(0.0) * limit[2] as f64,
+        );
+        Some(Aabb::new(bounds.min - reach, bounds.max + reach))
+    }
+
+    fn lipschitz(&self) -> f64 {
+        self.sdf.lipschitz()
+    }
+}
+
+pub struct Displace<S: Sdf> {
+    sdf: S,
+    amplitude: f64,
+    frequency: f64,
+}
+
+impl<S: Sdf> Sdf for Displace<S> {
+    fn distance(&self, p: Vector) -> f64 {
+        let f = self.frequency;
+        let bumps = (f * p.x()).sin() * (f * p.y()).sin() * (f * p.z()).sin();
+        self.sdf.distance(p) + self.amplitude * bumps
+    }
+
+    fn bounds(&self) -> Option<Aabb> {
+        Some(self.sdf.bounds()?.padded(self.amplitude.abs()))
+    }
+
+    fn lipschitz(&self) -> f64 {
+        self.sdf.lipschitz() + self.amplitude.abs() * self.frequency.abs() * 3f64.sqrt()
+    }
+}
+
+pub struct Round<S: Sdf> {
+    sdf: S,
+    radius: f64,
+}
+
+impl<S: Sdf> Sdf for Round<S> {
+    fn distance(&self, p: Vector) -> f64 {
+        self.sdf.distance(p) - self.radius
+    }
+
+    fn bounds(&self) -> Option<Aabb> {
+        Some(self.sdf.bounds()?.padded(self.radius.max(0.0)))
+    }
+
+    fn lipschitz(&self) -> f64 {
+        self.sdf.lipschitz()
+    }
+}
+
+/// A signed distance field rendered by sphere tracing: stepping along the
+/// ray by the distance to the surface, which can't be crossed in one step,
+/// until it's within `epsilon`.
+pub struct SdfHitable<S: Sdf, M: Material> {
+    sdf: S,
+    material: M,
+    bounds: Option<Aabb>,
+    epsilon: f64,
+    max_steps: u32,
+}
+
+// how far rays are followed through unbounded fields
+const UNBOUNDED_DISTANCE: f64 = 1000.0;
+
+impl<S: Sdf, M: Material> SdfHitable<S, M> {
+    pub fn new(sdf: S, material: M) -> Self {
+        SdfHitable {
+            bounds: sdf.bounds(),
+            sdf,
+            material,
+            epsilon: 1e-4,
+            max_steps: 1000,
+        }
+    }
+
+    // only the part of the field inside `bounds`, for unbounded ones
+    pub fn with_bounds(self, bounds: Aabb) -> Self {
+        SdfHitable {
+            bounds: Some(bounds),
+            ..self
+        }
+    }
+
+    pub fn with_precision(self, epsilon: f64, max_steps: u32) -> Self {
+        SdfHitable {
+            epsilon,
+            max_steps,
+            ..self
+        }
+    }
+
+    // the gradient of the field, by central differences
+    fn normal(&self, p: Vector) -> Vector {
+        let h = self.epsilon;
+        let d = |x: f64, y: f64, z: f64| self.sdf.distance(p + Vector::new(x, y, z));
+        Vector::new(
+            d(h, 0.0, 0.0) - d(-h, 0.0, 0.0),
+            d(0.0, h, 0.0) - d(0.0, -h, 0.0),
+            d(0.0, 0.0, h) - d(0.0, 0.0, -h),
+        )
+        .unit()
+    }
+}
+
+impl<S: Sdf, M: Material + Sync> Hitable for SdfHitable<S, M> {
+    fn hit(&self, ray: &Ray, t_min: f64, t_max: f64) -> Option<HitRecord<'_>> {
+        let length = ray.direction().length();
+        let (mut t, t_end) = match &self.bounds {
+            Some(bounds) => bounds.range(ray, t_min, t_max)?,
+            None => (t_min, t_max.min(t_min + UNBOUNDED_DISTANCE / length)),
+        };
+        let lipschitz = self.sdf.lipschitz();
+        let field = |t: f64| self.sdf.distance(ray.at(t)) / lipschitz;
+
+        // rays bouncing off or going into the surface start on it, get off
+        // it first to see which side they're on
+        let mut steps = 0;
+        let mut d = field(t);
+        while d.abs() < self.epsilon && steps < self.max_steps {
+            t += self.epsilon / length;
+            d = field(t);
+            steps += 1;
+        }
+        let side = d.signum();
+
+        while steps < self.max_steps && t <= t_end {
+            let d = side * field(t);
+            if d < self.epsilon {
+                let p = ray.at(t);
+                let mut normal = self.normal(p);
+                if normal.is_near_zero() || normal.x().is_nan() {
+                    normal = ray.direction().unit().reverse();
+                }
+                return Some(HitRecord::new(t, p, normal, &self.material));
+            }
+            t += d / length;
+            steps += 1;
+        }
+        None
+    }
+
+    fn bounding_box(&self, _time0: f64, _time1: f64) -> Option<Aabb> {
+        self.bounds
+    }
+}
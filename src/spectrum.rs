@@ -0,0 +1,41 @@
+//! Spectra as scene files give them, reduced to the RGB the renderer works
+//! in. These are rough: a spectrum is averaged over each third of the
+//! visible range rather than weighted by colour matching functions.
+
+use crate::vector::Vector;
+
+/// A spectrum given as (wavelength in nanometres, value) pairs in order of
+/// wavelength, linear in between and constant past the ends, averaged over
+/// 600-700 nm for red, 500-600 nm for green and 400-500 nm for blue.
+pub fn from_samples(samples: &[(f64, f64)]) -> Vector {
+    let at = |lambda: f64| {
+        let i = samples.partition_point(|s| s.0 < lambda);
+        if i == 0 {
+            samples[0].1
+        } else if i == samples.len() {
+            samples[samples.len() - 1].1
+        } else {
+            let ((l0, v0), (l1, v1)) = (samples[i - 1], samples[i]);
+            v0 + (v1 - v0) * (lambda - l0) / (l1 - l0).max(1e-9)
+        }
+    };
+    let band = |from: f64| {
+        (0..10)
+            .map(|k| at(from + 10.0 * k as f64 + 5.0))
+            .sum::<f64>()
+            / 10.0
+    };
+    Vector::new(band(600.0), band(500.0), band(400.0))
+}
+
+/// The colour of a black body at `kelvin`, from Planck's law at red, green
+/// and blue wavelengths, scaled for the brightest channel to be 1.
+pub fn blackbody(kelvin: f64) -> Vector {
+    let planck = |nanometres: f64| {
+        let lambda = nanometres * 1e-9;
+        let (c, h, k) = (299_792_458.0, 6.626_070_15e-34, 1.380_649e-23);
+        2.0 * h * c * c / (lambda.powi(5) * ((h * c / (lambda * k * kelvin)).exp() - 1.0))
+    };
+    let color = Vector::new(planck(610.0), planck(550.0), planck(465.0));
+    color / color.x().max(color.y()).max(color.z())
+}
@@ -1,7 +1,10 @@
+use crate::aabb::Aabb;
+use crate::csg::{Interval, Solid};
 use crate::hitable::{HitRecord, Hitable};
 use crate::material::Material;
 use crate::ray::Ray;
 use crate::vector::Vector;
+use std::f64::consts::PI;
 
 pub struct Sphere<M: Material> {
     center: Vector,
@@ -20,7 +23,7 @@ impl<M: Material> Sphere<M> {
 }
 
 impl<M: Material + Sync> Hitable for Sphere<M> {
-    fn hit(&self, ray: &Ray, t_min: f64, t_max: f64) -> Option<HitRecord> {
+    fn hit(&self, ray: &Ray, t_min: f64, t_max: f64) -> Option<HitRecord<'_>> {
         let oc = ray.origin() - self.center;
         let a = ray.direction().dot(ray.direction());
         let b = oc.dot(ray.direction());
@@ -31,17 +34,24 @@ impl<M: Material + Sync> Hitable for Sphere<M> {
             let t = (-b - sqrt_discriminant) / a;
             if t < t_max && t > t_min {
                 let normal = (ray.at(t) - self.center) / self.radius;
-                return Some(HitRecord::new(t, ray.at(t), normal, &self.material));
+                let (u, v) = sphere_uv(normal);
+                return Some(HitRecord::new(t, ray.at(t), normal, &self.material).with_uv(u, v));
             }
             let t = (-b + sqrt_discriminant) / a;
             if t < t_max && t > t_min {
                 let p = ray.at(t);
                 let normal = (p - self.center) / self.radius;
-                return Some(HitRecord::new(t, p, normal, &self.material));
+                let (u, v) = sphere_uv(normal);
+                return Some(HitRecord::new(t, p, normal, &self.material).with_uv(u, v));
             }
         }
         None
     }
+
+    fn bounding_box(&self, _time0: f64, _time1: f64) -> Option<Aabb> {
+        let r = self.radius.abs() * Vector::new(1.0, 1.0, 1.0);
+        Some(Aabb::new(self.center - r, self.center + r))
+    }
 }
 
 pub struct MovingSphere<M: Material> {
@@ -72,13 +82,13 @@ impl<M: Material> MovingSphere<M> {
         }
     }
     pub fn center(&self, t: f64) -> Vector {
-        return self.center0
-            + ((t - self.time0) / (self.time1 - self.time0)) * (self.center1 - self.center0);
+        self.center0
+            + ((t - self.time0) / (self.time1 - self.time0)) * (self.center1 - self.center0)
     }
 }
 
 impl<M: Material + Sync> Hitable for MovingSphere<M> {
-    fn hit(&self, ray: &Ray, t_min: f64, t_max: f64) -> Option<HitRecord> {
+    fn hit(&self, ray: &Ray, t_min: f64, t_max: f64) -> Option<HitRecord<'_>> {
         let oc = ray.origin() - self.center(ray.time());
         let a = ray.direction().dot(ray.direction());
         let b = oc.dot(ray.direction());
@@ -89,15 +99,72 @@ impl<M: Material + Sync> Hitable for MovingSphere<M> {
             let t = (-b - sqrt_discriminant) / a;
             if t < t_max && t > t_min {
                 let normal = (ray.at(t) - self.center(ray.time())) / self.radius;
-                return Some(HitRecord::new(t, ray.at(t), normal, &self.material));
+                let (u, v) = sphere_uv(normal);
+                return Some(HitRecord::new(t, ray.at(t), normal, &self.material).with_uv(u, v));
             }
             let t = (-b + sqrt_discriminant) / a;
             if t < t_max && t > t_min {
                 let p = ray.at(t);
                 let normal = (p - self.center(ray.time())) / self.radius;
-                return Some(HitRecord::new(t, p, normal, &self.material));
+                let (u, v) = sphere_uv(normal);
+                return Some(HitRecord::new(t, p, normal, &self.material).with_uv(u, v));
             }
         }
         None
     }
+
+    // moves in a straight line, so the boxes at both ends hold it
+    fn bounding_box(&self, time0: f64, time1: f64) -> Option<Aabb> {
+        let r = self.radius.abs() * Vector::new(1.0, 1.0, 1.0);
+        let (c0, c1) = (self.center(time0), self.center(time1));
+        Some(Aabb::new(c0 - r, c0 + r).surrounding(&Aabb::new(c1 - r, c1 + r)))
+    }
+}
+
+impl<M: Material + Sync> Solid for Sphere<M> {
+    fn intervals(&self, ray: &Ray) -> Vec<Interval<'_>> {
+        sphere_intervals(ray, self.center, self.radius, &self.material)
+    }
+}
+
+impl<M: Material + Sync> Solid for MovingSphere<M> {
+    fn intervals(&self, ray: &Ray) -> Vec<Interval<'_>> {
+        sphere_intervals(ray, self.center(ray.time()), self.radius, &self.material)
+    }
+}
+
+// where the ray's line goes through the sphere, if it does
+fn sphere_intervals<'a>(
+    ray: &Ray,
+    center: Vector,
+    radius: f64,
+    material: &'a dyn Material,
+) -> Vec<Interval<'a>> {
+    let oc = ray.origin() - center;
+    let a = ray.direction().dot(ray.direction());
+    let b = oc.dot(ray.direction());
+    let c = oc.dot(oc) - radius.powi(2);
+    let discriminant = b.powi(2) - a * c;
+    if discriminant <= 0.0 {
+        return Vec::new();
+    }
+    let sqrt_discriminant = discriminant.sqrt();
+    let record = |t: f64| {
+        let p = ray.at(t);
+        let normal = (p - center) / radius.abs();
+        let (u, v) = sphere_uv(normal);
+        HitRecord::new(t, p, normal, material).with_uv(u, v)
+    };
+    vec![Interval {
+        enter: record((-b - sqrt_discriminant) / a),
+        exit: record((-b + sqrt_discriminant) / a),
+    }]
+}
+
+// Position on the unit sphere as (u, v): u goes around the y axis from -x,
+// through +z, and v from the bottom to the top.
+fn sphere_uv(p: Vector) -> (f64, f64) {
+    let theta = (-p.y()).clamp(-1.0, 1.0).acos();
+    let phi = (-p.z()).atan2(p.x()) + PI;
+    (phi / (2.0 * PI), theta / PI)
 }
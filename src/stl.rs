@@ -0,0 +1,215 @@
+//! STL meshes, ASCII or binary. STL stores each triangle with its own three
+//! corners, so the mesh has three vertices per triangle, and the facet
+//! normals are left out since they can be worked out from the corners.
+
+use crate::mesh::Mesh;
+use crate::vector::Vector;
+use std::fs;
+use std::path::Path;
+
+pub fn load(path: &Path) -> Result<Mesh, String> {
+    let bytes =
+        fs::read(path).map_err(|e| format!("can't read STL file {}: {}", path.display(), e))?;
+    parse(&bytes).map_err(|e| format!("{}: {}", path.display(), e))
+}
+
+pub fn parse(bytes: &[u8]) -> Result<Mesh, String> {
+    // binary files may also start with "solid", but their size gives them away
+    let binary_size = bytes.get(80..84).map(|count| {
+        84 + 50 * u32::from_le_bytes([count[0], count[1], count[2], count[3]]) as usize
+    });
+    let text = std::str::from_utf8(bytes)
+        .ok()
+        .filter(|text| text.trim_start().starts_with("solid"));
+    let mesh = if binary_size == Some(bytes.len()) {
+        parse_binary(bytes)
+    } else if let Some(text) = text {
+        parse_ascii(text)?
+    } else if let Some(size) = binary_size {
+        return Err(format!(
+            "a binary STL file of {} triangles should be {} bytes, not {}",
+            (size - 84) / 50,
+            size,
+            bytes.len()
+        ));
+    } else {
+        return Err(String::from("not an STL file, it's too short"));
+    };
+    if mesh.triangles.is_empty() {
+        return Err(String::from("there are no triangles"));
+    }
+    Ok(mesh)
+}
+
+// an 80 byte header, the triangle count, then per triangle a normal, three
+// corners and two bytes of attributes
+fn parse_binary(bytes: &[u8]) -> Mesh {
+    let mut mesh = Mesh::default();
+    for record in bytes[84..].chunks_exact(50) {
+        let float = |i: usize| {
+            let b = &record[4 * i..4 * i + 4];
+            f32::from_le_bytes([b[0], b[1], b[2], b[3]]) as f64
+        };
+        let first = mesh.positions.len();
+        for corner in 1..4 {
+            let i = 3 * corner;
+            mesh.positions
+                .push(Vector::new(float(i), float(i + 1), float(i + 2)));
+        }
+        mesh.triangles.push([first, first + 1, first + 2]);
+    }
+    mesh
+}
+
+fn parse_ascii(text: &str) -> Result<Mesh, String> {
+    let mut mesh = Mesh::default();
+    let mut corners = Vec::with_capacity(3);
+    for (number, line) in text.lines().enumerate() {
+        let error =
+            |message: &str| format!("line {}: {} in {:?}", number + 1, message, line.trim());
+        let words: Vec<&str> = line.split_whitespace().collect();
+        match words.as_slice() {
+            [] | ["solid", ..] | ["endsolid", ..] | ["facet", ..] | ["outer", "loop"] => {}
+            ["vertex", x, y, z] => {
+                let coordinate =
+                    |word: &str| word.parse::<f64>().map_err(|_| error("invalid coordinate"));
+                let vertex = Vector::new(coordinate(x)?, coordinate(y)?, coordinate(z)?);
+                if corners.len() == 3 {
+                    return Err(error("more than three vertices in a facet"));
+                }
+                corners.push(vertex);
+            }
+            ["endloop"] => {}
+            ["endfacet"] => {
+                if corners.len() != 3 {
+                    return Err(error(&format!(
+                        "the facet has {} vertices rather than 3",
+                        corners.len()
+                    )));
+                }
+                let first = mesh.positions.len();
+                mesh.positions.append(&mut corners);
+                mesh.triangles.push([first, first + 1, first + 2]);
+            }
+            _ => return Err(error("unexpected line")),
+        }
+    }
+    if !corners.is_empty() {
+        return Err(String::from("the last facet has no endfacet"));
+    }
+    Ok(mesh)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // two triangles of a square, the second with a facet normal that
+    // doesn't match its corners, which is ignored
+    const ASCII: &str = "solid square
+  facet normal 0 0 1
+    outer loop
+      vertex 0 0 0
+      vertex 1 0 0
+      vertex 1 1.5e0 0
+    endloop
+  endfacet
+  facet normal 1 0 0
+    outer loop
+      vertex 0 0 0
+      vertex 1 1.5 0
+      vertex 0 1.5 0
+    endloop
+  endfacet
+endsolid square
+";
+
+    // the triangles of `ASCII`, after a header that starts like an ASCII file
+    fn binary() -> Vec<u8> {
+        let mut bytes = b"solid but binary".to_vec();
+        bytes.resize(80, b' ');
+        bytes.extend(2u32.to_le_bytes());
+        for (normal, corners) in [
+            ([0.0f32, 0.0, 1.0], [[0.0, 0.0], [1.0, 0.0], [1.0, 1.5]]),
+            ([1.0, 0.0, 0.0], [[0.0, 0.0], [1.0, 1.5], [0.0, 1.5]]),
+        ] {
+            for x in normal {
+                bytes.extend(x.to_le_bytes());
+            }
+            for [x, y] in corners {
+                for c in [x, y, 0.0f32] {
+                    bytes.extend(c.to_le_bytes());
+                }
+            }
+            bytes.extend([0, 0]);
+        }
+        bytes
+    }
+
+    fn square() -> Mesh {
+        let corners = [
+            (0.0, 0.0),
+            (1.0, 0.0),
+            (1.0, 1.5),
+            (0.0, 0.0),
+            (1.0, 1.5),
+            (0.0, 1.5),
+        ];
+        Mesh {
+            positions: corners
+                .iter()
+                .map(|&(x, y)| Vector::new(x, y, 0.0))
+                .collect(),
+            triangles: vec![[0, 1, 2], [3, 4, 5]],
+            ..Mesh::default()
+        }
+    }
+
+    #[test]
+    fn reads_ascii_and_binary_alike() {
+        assert_eq!(parse(ASCII.as_bytes()).unwrap(), square());
+        assert_eq!(parse(&binary()).unwrap(), square());
+    }
+
+    #[test]
+    fn tells_binary_files_by_their_size() {
+        let bytes = binary();
+        let error = parse(&bytes[..bytes.len() - 10]).unwrap_err();
+        assert_eq!(
+            error,
+            "a binary STL file of 2 triangles should be 184 bytes, not 174"
+        );
+        // neither ASCII nor long enough to count its triangles
+        let error = parse(&[0; 82]).unwrap_err();
+        assert_eq!(error, "not an STL file, it's too short");
+        let mut empty = bytes[..84].to_vec();
+        empty[80] = 0;
+        assert_eq!(parse(&empty).unwrap_err(), "there are no triangles");
+    }
+
+    #[test]
+    fn rejects_bad_ascii_facets() {
+        let error = parse(ASCII.replace("1.5e0", "1.5e").as_bytes()).unwrap_err();
+        assert_eq!(error, "line 6: invalid coordinate in \"vertex 1 1.5e 0\"");
+        let error = parse(ASCII.replacen("      vertex 0 0 0\n", "", 1).as_bytes()).unwrap_err();
+        assert_eq!(
+            error,
+            "line 7: the facet has 2 vertices rather than 3 in \"endfacet\""
+        );
+        let error = parse(
+            ASCII
+                .replacen("    endloop", "      vertex 2 2 2\n    endloop", 1)
+                .as_bytes(),
+        )
+        .unwrap_err();
+        assert_eq!(
+            error,
+            "line 7: more than three vertices in a facet in \"vertex 2 2 2\""
+        );
+        let end = ASCII.rfind("  endfacet").unwrap();
+        assert_eq!(
+            parse(&ASCII.as_bytes()[..end]).unwrap_err(),
+            "the last facet has no endfacet"
+        );
+    }
+}
@@ -0,0 +1,98 @@
+//! Image textures, looked up by the (u, v) coordinates of hits.
+
+use crate::vector::Vector;
+use image::DynamicImage;
+use std::path::Path;
+
+/// What happens to coordinates outside 0..1.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum Wrap {
+    Repeat,
+    Clamp,
+    Mirror,
+}
+
+impl Wrap {
+    // a texel index from an index that may be off the image
+    fn apply(self, i: i64, size: usize) -> usize {
+        let size = size as i64;
+        let i = match self {
+            Wrap::Repeat => i.rem_euclid(size),
+            Wrap::Clamp => i.clamp(0, size - 1),
+            Wrap::Mirror => {
+                let period = i.rem_euclid(2 * size);
+                if period < size {
+                    period
+                } else {
+                    2 * size - 1 - period
+                }
+            }
+        };
+        i as usize
+    }
+}
+
+/// An image filtered bilinearly, with u going across it and v down it, as in
+/// glTF. Texels are stored linear: images holding colours are usually sRGB
+/// encoded and converted on loading, while data like roughness isn't.
+pub struct Texture {
+    width: usize,
+    height: usize,
+    texels: Vec<Vector>,
+    wrap: (Wrap, Wrap),
+}
+
+impl Texture {
+    pub fn from_image(image: &DynamicImage, srgb: bool) -> Texture {
+        let image = image.to_rgb32f();
+        let decode = |c: f32| {
+            let c = c as f64;
+            if !srgb {
+                c
+            } else if c <= 0.04045 {
+                c / 12.92
+            } else {
+                ((c + 0.055) / 1.055).powf(2.4)
+            }
+        };
+        Texture {
+            width: image.width() as usize,
+            height: image.height() as usize,
+            texels: image
+                .pixels()
+                .map(|p| Vector::new(decode(p.0[0]), decode(p.0[1]), decode(p.0[2])))
+                .collect(),
+            wrap: (Wrap::Repeat, Wrap::Repeat),
+        }
+    }
+
+    pub fn load(path: &Path, srgb: bool) -> Result<Texture, String> {
+        let image = image::open(path)
+            .map_err(|e| format!("can't read texture {}: {}", path.display(), e))?;
+        Ok(Texture::from_image(&image, srgb))
+    }
+
+    /// How coordinates outside 0..1 are brought back onto the image, along u
+    /// and along v. Both repeat by default.
+    pub fn with_wrap(self, u: Wrap, v: Wrap) -> Texture {
+        Texture {
+            wrap: (u, v),
+            ..self
+        }
+    }
+
+    pub fn sample(&self, u: f64, v: f64) -> Vector {
+        // texel centres are at half integers
+        let x = u * self.width as f64 - 0.5;
+        let y = v * self.height as f64 - 0.5;
+        let (x0, y0) = (x.floor(), y.floor());
+        let (fx, fy) = (x - x0, y - y0);
+        let texel = |i: f64, j: f64| {
+            let i = self.wrap.0.apply(i as i64, self.width);
+            let j = self.wrap.1.apply(j as i64, self.height);
+            self.texels[j * self.width + i]
+        };
+        (1.0 - fy) * ((1.0 - fx) * texel(x0, y0) + fx * texel(x0 + 1.0, y0))
+            + fy * ((1.0 - fx) * texel(x0, y0 + 1.0) + fx * texel(x0 + 1.0, y0 + 1.0))
+    }
+}
@@ -1,5 +1,12 @@
-use crate::vector::Vector;
-use rand::Rng; // 0.8.0
+use rand::rngs::SmallRng;
+use rand::{Rng, SeedableRng}; // 0.8.0
+use std::cell::RefCell;
+
+thread_local! {
+    // Every thread draws from its own generator so that renders can be made
+    // reproducible by reseeding it (see `seed_rng`).
+    static RNG: RefCell<SmallRng> = RefCell::new(SmallRng::from_entropy());
+}
 
 pub fn clamp(x: f64, min: f64, max: f64) -> f64 {
     if x < min {
@@ -8,27 +15,36 @@ pub fn clamp(x: f64, min: f64, max: f64) -> f64 {
     if x > max {
         return max;
     }
-    return x;
+    x
 }
 
 pub fn random_float() -> f64 {
-    return random_float_between(0.0, 1.0);
+    random_float_between(0.0, 1.0)
 }
 
 pub fn random_float_between(min: f64, max: f64) -> f64 {
-    let mut rng = rand::thread_rng();
-    return rng.gen_range(min..max);
+    RNG.with(|rng| rng.borrow_mut().gen_range(min..max))
 }
 
-pub fn write_color(mut color: Vector, samples_per_pixel: i64) {
-    // Divide the color by the number of samples
-    let scale = 1.0 / samples_per_pixel as f64;
-    color = color.scale(scale);
+// Reseeds the current thread's generator.
+pub fn seed_rng(seed: u64) {
+    RNG.with(|rng| *rng.borrow_mut() = SmallRng::seed_from_u64(seed));
+}
 
-    // Gamma-correct for gamma=2.0.
-    let r = (256.0 * clamp(color.x().sqrt(), 0.0, 0.999)) as i64;
-    let g = (256.0 * clamp(color.y().sqrt(), 0.0, 0.999)) as i64;
-    let b = (256.0 * clamp(color.z().sqrt(), 0.0, 0.999)) as i64;
+// Combines two values into a well distributed seed (splitmix64 finalizer).
+pub fn mix_seed(a: u64, b: u64) -> u64 {
+    let mut z = a ^ b.wrapping_add(0x9e37_79b9_7f4a_7c15).wrapping_add(a << 6);
+    z = (z ^ (z >> 30)).wrapping_mul(0xbf58_476d_1ce4_e5b9);
+    z = (z ^ (z >> 27)).wrapping_mul(0x94d0_49bb_1331_11eb);
+    z ^ (z >> 31)
+}
 
-    print!("{} {} {}\n", r, g, b);
+// 64-bit FNV-1a, stable across platforms and compiler versions (unlike DefaultHasher).
+pub fn fnv1a(bytes: &[u8]) -> u64 {
+    let mut hash: u64 = 0xcbf2_9ce4_8422_2325;
+    for byte in bytes {
+        hash ^= *byte as u64;
+        hash = hash.wrapping_mul(0x0100_0000_01b3);
+    }
+    hash
 }
@@ -0,0 +1,469 @@
+use crate::aabb::Aabb;
+use crate::animation::TransformAnimation;
+use crate::csg::{Interval, Solid};
+use crate::hitable::{HitRecord, Hitable};
+use crate::ray::Ray;
+use crate::vector::{degrees_to_radians, Vector};
+
+/// Row-major 4x4 matrix, applied to column vectors.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct Matrix4 {
+    pub m: [[f64; 4]; 4],
+}
+
+impl Matrix4 {
+    pub fn identity() -> Matrix4 {
+        let mut m = [[0.0; 4]; 4];
+        for (i, row) in m.iter_mut().enumerate() {
+            row[i] = 1.0;
+        }
+        Matrix4 { m }
+    }
+
+    pub fn mul(&self, other: &Matrix4) -> Matrix4 {
+        let mut m = [[0.0; 4]; 4];
+        for (i, row) in m.iter_mut().enumerate() {
+            for (j, value) in row.iter_mut().enumerate() {
+                *value = (0..4).map(|k| self.m[i][k] * other.m[k][j]).sum();
+            }
+        }
+        Matrix4 { m }
+    }
+
+    pub fn transpose(&self) -> Matrix4 {
+        let mut m = [[0.0; 4]; 4];
+        for (i, row) in m.iter_mut().enumerate() {
+            for (j, value) in row.iter_mut().enumerate() {
+                *value = self.m[j][i];
+            }
+        }
+        Matrix4 { m }
+    }
+
+    // Gauss-Jordan elimination with partial pivoting, None if singular
+    pub fn inverse(&self) -> Option<Matrix4> {
+        let mut a = self.m;
+        let mut inv = Matrix4::identity().m;
+        for col in 0..4 {
+            let pivot = (col..4).max_by(|&i, &j| a[i][col].abs().total_cmp(&a[j][col].abs()))?;
+            if a[pivot][col].abs() < 1e-12 {
+                return None;
+            }
+            a.swap(col, pivot);
+            inv.swap(col, pivot);
+            let p = a[col][col];
+            for j in 0..4 {
+                a[col][j] /= p;
+                inv[col][j] /= p;
+            }
+            for row in 0..4 {
+                if row != col {
+                    let f = a[row][col];
+                    for j in 0..4 {
+                        a[row][j] -= f * a[col][j];
+                        inv[row][j] -= f * inv[col][j];
+                    }
+                }
+            }
+        }
+        Some(Matrix4 { m: inv })
+    }
+}
+
+/// An affine transform along with its inverse.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct Transform {
+    matrix: Matrix4,
+    inverse: Matrix4,
+}
+
+impl Transform {
+    pub fn identity() -> Transform {
+        Transform {
+            matrix: Matrix4::identity(),
+            inverse: Matrix4::identity(),
+        }
+    }
+
+    // None if the matrix can't be inverted
+    pub fn new(matrix: Matrix4) -> Option<Transform> {
+        Some(Transform {
+            matrix,
+            inverse: matrix.inverse()?,
+        })
+    }
+
+    pub fn matrix(&self) -> &Matrix4 {
+        &self.matrix
+    }
+
+    pub fn translate(offset: Vector) -> Transform {
+        let mut matrix = Matrix4::identity();
+        let mut inverse = Matrix4::identity();
+        for (i, v) in [offset.x(), offset.y(), offset.z()].iter().enumerate() {
+            matrix.m[i][3] = *v;
+            inverse.m[i][3] = -v;
+        }
+        Transform { matrix, inverse }
+    }
+
+    // zero components are clamped to a tiny scale, so it stays invertible
+    pub fn scale(factors: Vector) -> Transform {
+        let mut matrix = Matrix4::identity();
+        let mut inverse = Matrix4::identity();
+        for (i, v) in [factors.x(), factors.y(), factors.z()].iter().enumerate() {
+            let v = if v.abs() < 1e-9 { 1e-9 } else { *v };
+            matrix.m[i][i] = v;
+            inverse.m[i][i] = 1.0 / v;
+        }
+        Transform { matrix, inverse }
+    }
+
+    /// Rotation by `degrees` counterclockwise around `axis`, looking down the axis.
+    pub fn rotate(axis: Vector, degrees: f64) -> Transform {
+        let a = axis.unit();
+        let (sin, cos) = degrees_to_radians(degrees).sin_cos();
+        let (x, y, z) = (a.x(), a.y(), a.z());
+        let mut matrix = Matrix4::identity();
+        matrix.m[0] = [
+            x * x + (1.0 - x * x) * cos,
+            x * y * (1.0 - cos) - z * sin,
+            x * z * (1.0 - cos) + y * sin,
+            0.0,
+        ];
+        matrix.m[1] = [
+            x * y * (1.0 - cos) + z * sin,
+            y * y + (1.0 - y * y) * cos,
+            y * z * (1.0 - cos) - x * sin,
+            0.0,
+        ];
+        matrix.m[2] = [
+            x * z * (1.0 - cos) - y * sin,
+            y * z * (1.0 - cos) + x * sin,
+            z * z + (1.0 - z * z) * cos,
+            0.0,
+        ];
+        // rotations are orthogonal
+        Transform {
+            matrix,
+            inverse: matrix.transpose(),
+        }
+    }
+
+    // the rotation of a quaternion, which doesn't need to be normalised
+    pub fn from_quaternion(q: Quaternion) -> Transform {
+        let Quaternion { w, x, y, z } = q.normalised();
+        let mut matrix = Matrix4::identity();
+        matrix.m[0] = [
+            1.0 - 2.0 * (y * y + z * z),
+            2.0 * (x * y - w * z),
+            2.0 * (x * z + w * y),
+            0.0,
+        ];
+        matrix.m[1] = [
+            2.0 * (x * y + w * z),
+            1.0 - 2.0 * (x * x + z * z),
+            2.0 * (y * z - w * x),
+            0.0,
+        ];
+        matrix.m[2] = [
+            2.0 * (x * z - w * y),
+            2.0 * (y * z + w * x),
+            1.0 - 2.0 * (x * x + y * y),
+            0.0,
+        ];
+        Transform {
+            matrix,
+            inverse: matrix.transpose(),
+        }
+    }
+
+    pub fn rotate_x(degrees: f64) -> Transform {
+        Transform::rotate(Vector::new(1.0, 0.0, 0.0), degrees)
+    }
+
+    pub fn rotate_y(degrees: f64) -> Transform {
+        Transform::rotate(Vector::new(0.0, 1.0, 0.0), degrees)
+    }
+
+    pub fn rotate_z(degrees: f64) -> Transform {
+        Transform::rotate(Vector::new(0.0, 0.0, 1.0), degrees)
+    }
+
+    // this transform followed by `next`
+    pub fn then(&self, next: &Transform) -> Transform {
+        Transform {
+            matrix: next.matrix.mul(&self.matrix),
+            inverse: self.inverse.mul(&next.inverse),
+        }
+    }
+
+    pub fn inverse(&self) -> Transform {
+        Transform {
+            matrix: self.inverse,
+            inverse: self.matrix,
+        }
+    }
+
+    pub fn point(&self, p: Vector) -> Vector {
+        let m = &self.matrix.m;
+        let w = m[3][0] * p.x() + m[3][1] * p.y() + m[3][2] * p.z() + m[3][3];
+        apply(m, p, 1.0) / w
+    }
+
+    pub fn vector(&self, v: Vector) -> Vector {
+        apply(&self.matrix.m, v, 0.0)
+    }
+
+    // normals transform with the inverse transpose, the result isn't normalised
+    pub fn normal(&self, n: Vector) -> Vector {
+        let m = &self.inverse.m;
+        Vector::new(
+            m[0][0] * n.x() + m[1][0] * n.y() + m[2][0] * n.z(),
+            m[0][1] * n.x() + m[1][1] * n.y() + m[2][1] * n.z(),
+            m[0][2] * n.x() + m[1][2] * n.y() + m[2][2] * n.z(),
+        )
+    }
+
+    // the ray in the space this transform maps from, with the same parametrisation
+    pub fn ray_to_local(&self, ray: &Ray) -> Ray {
+        let inverse = self.inverse();
+        Ray::new(
+            inverse.point(ray.origin()),
+            inverse.vector(ray.direction()),
+            ray.time(),
+        )
+    }
+
+    // `hit` found in local space, moved to world space
+    pub fn hit_to_world<'a>(&self, ray: &Ray, hit: HitRecord<'a>) -> HitRecord<'a> {
+        HitRecord {
+            p: ray.at(hit.t),
+            normal: self.normal(hit.normal).unit(),
+            tangent: self.vector(hit.tangent),
+            ..hit
+        }
+    }
+}
+
+/// A rotation, as a unit quaternion. Unlike angles, quaternions can be
+/// interpolated without gimbal lock, along the shortest arc (see `slerp`).
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct Quaternion {
+    pub w: f64,
+    pub x: f64,
+    pub y: f64,
+    pub z: f64,
+}
+
+impl Quaternion {
+    pub fn new(w: f64, x: f64, y: f64, z: f64) -> Quaternion {
+        Quaternion { w, x, y, z }
+    }
+
+    pub fn identity() -> Quaternion {
+        Quaternion::new(1.0, 0.0, 0.0, 0.0)
+    }
+
+    /// Rotation by `degrees` counterclockwise around `axis`, like `Transform::rotate`.
+    pub fn from_axis_angle(axis: Vector, degrees: f64) -> Quaternion {
+        let a = axis.unit();
+        let (sin, cos) = (degrees_to_radians(degrees) / 2.0).sin_cos();
+        Quaternion::new(cos, a.x() * sin, a.y() * sin, a.z() * sin)
+    }
+
+    /// Rotation by angles in degrees around the x, then y, then z axis.
+    pub fn from_euler(degrees: Vector) -> Quaternion {
+        let x = Quaternion::from_axis_angle(Vector::new(1.0, 0.0, 0.0), degrees.x());
+        let y = Quaternion::from_axis_angle(Vector::new(0.0, 1.0, 0.0), degrees.y());
+        let z = Quaternion::from_axis_angle(Vector::new(0.0, 0.0, 1.0), degrees.z());
+        z.mul(&y).mul(&x)
+    }
+
+    // the rotation `other` followed by this one
+    pub fn mul(&self, other: &Quaternion) -> Quaternion {
+        let (a, b) = (self, other);
+        Quaternion::new(
+            a.w * b.w - a.x * b.x - a.y * b.y - a.z * b.z,
+            a.w * b.x + a.x * b.w + a.y * b.z - a.z * b.y,
+            a.w * b.y - a.x * b.z + a.y * b.w + a.z * b.x,
+            a.w * b.z + a.x * b.y - a.y * b.x + a.z * b.w,
+        )
+    }
+
+    pub fn dot(&self, other: &Quaternion) -> f64 {
+        self.w * other.w + self.x * other.x + self.y * other.y + self.z * other.z
+    }
+
+    pub fn normalised(&self) -> Quaternion {
+        let length = self.dot(self).sqrt();
+        if length == 0.0 {
+            return Quaternion::identity();
+        }
+        Quaternion::new(
+            self.w / length,
+            self.x / length,
+            self.y / length,
+            self.z / length,
+        )
+    }
+
+    /// Spherical linear interpolation, turning at a constant speed along the
+    /// shortest arc from this rotation (u = 0) to `other` (u = 1).
+    pub fn slerp(&self, other: &Quaternion, u: f64) -> Quaternion {
+        let (a, mut b) = (self.normalised(), other.normalised());
+        // q and -q are the same rotation, pick the closest one
+        let mut cos = a.dot(&b);
+        if cos < 0.0 {
+            b = Quaternion::new(-b.w, -b.x, -b.y, -b.z);
+            cos = -cos;
+        }
+        let (ka, kb) = if cos > 0.9995 {
+            // nearly the same, where lerping is as good and stays stable
+            (1.0 - u, u)
+        } else {
+            let theta = cos.acos();
+            let sin = theta.sin();
+            (((1.0 - u) * theta).sin() / sin, (u * theta).sin() / sin)
+        };
+        Quaternion::new(
+            ka * a.w + kb * b.w,
+            ka * a.x + kb * b.x,
+            ka * a.y + kb * b.y,
+            ka * a.z + kb * b.z,
+        )
+        .normalised()
+    }
+}
+
+fn apply(m: &[[f64; 4]; 4], v: Vector, w: f64) -> Vector {
+    Vector::new(
+        m[0][0] * v.x() + m[0][1] * v.y() + m[0][2] * v.z() + m[0][3] * w,
+        m[1][0] * v.x() + m[1][1] * v.y() + m[1][2] * v.z() + m[1][3] * w,
+        m[2][0] * v.x() + m[2][1] * v.y() + m[2][2] * v.z() + m[2][3] * w,
+    )
+}
+
+/// An object placed in the scene by a fixed transform.
+pub struct Transformed<H: Hitable> {
+    object: H,
+    transform: Transform,
+}
+
+impl<H: Hitable> Transformed<H> {
+    pub fn new(object: H, transform: Transform) -> Self {
+        Transformed { object, transform }
+    }
+}
+
+impl<H: Hitable> Hitable for Transformed<H> {
+    fn hit(&self, ray: &Ray, t_min: f64, t_max: f64) -> Option<HitRecord<'_>> {
+        let local = self.transform.ray_to_local(ray);
+        let hit = self.object.hit(&local, t_min, t_max)?;
+        Some(self.transform.hit_to_world(ray, hit))
+    }
+
+    fn bounding_box(&self, time0: f64, time1: f64) -> Option<Aabb> {
+        let aabb = self.object.bounding_box(time0, time1)?;
+        Some(aabb.transformed(&self.transform))
+    }
+
+    fn object_count(&self) -> u32 {
+        self.object.object_count()
+    }
+}
+
+impl<H: Solid> Solid for Transformed<H> {
+    fn intervals(&self, ray: &Ray) -> Vec<Interval<'_>> {
+        let local = self.transform.ray_to_local(ray);
+        intervals_to_world(&self.transform, ray, self.object.intervals(&local))
+    }
+}
+
+// intervals found in local space, moved to world space
+fn intervals_to_world<'a>(
+    transform: &Transform,
+    ray: &Ray,
+    intervals: Vec<Interval<'a>>,
+) -> Vec<Interval<'a>> {
+    intervals
+        .into_iter()
+        .map(|interval| Interval {
+            enter: transform.hit_to_world(ray, interval.enter),
+            exit: transform.hit_to_world(ray, interval.exit),
+        })
+        .collect()
+}
+
+/// An object moved by a keyframed transform, evaluated at the time of each
+/// ray so that it gets motion blurred.
+pub struct Animated<H: Hitable> {
+    object: H,
+    animation: TransformAnimation,
+}
+
+impl<H: Hitable> Animated<H> {
+    pub fn new(object: H, animation: TransformAnimation) -> Self {
+        Animated { object, animation }
+    }
+}
+
+impl<H: Hitable> Hitable for Animated<H> {
+    fn hit(&self, ray: &Ray, t_min: f64, t_max: f64) -> Option<HitRecord<'_>> {
+        let transform = self.animation.at(ray.time());
+        let local = transform.ray_to_local(ray);
+        let hit = self.object.hit(&local, t_min, t_max)?;
+        Some(transform.hit_to_world(ray, hit))
+    }
+
+    // The object's box moved along the animation at evenly spaced times. The
+    // corners travel along curves between them, which stray less than half a
+    // step from the straight line between two times, so the boxes at both
+    // ends of every step are padded by half of the longest one a corner takes.
+    fn bounding_box(&self, time0: f64, time1: f64) -> Option<Aabb> {
+        let aabb = self.object.bounding_box(time0, time1)?;
+        let corners = aabb.corners();
+        let start = self.animation.at(time0);
+        let mut previous = (aabb.transformed(&start), corners.map(|c| start.point(c)));
+        let mut bounds = previous.0;
+        let steps = if time1 > time0 {
+            ANIMATED_BOUNDS_STEPS
+        } else {
+            0
+        };
+        for i in 1..=steps {
+            let time = time0 + (time1 - time0) * i as f64 / steps as f64;
+            let transform = self.animation.at(time);
+            let current = (
+                aabb.transformed(&transform),
+                corners.map(|c| transform.point(c)),
+            );
+            let step = current
+                .1
+                .iter()
+                .zip(previous.1.iter())
+                .map(|(a, b)| (*a - *b).length())
+                .fold(0.0, f64::max);
+            bounds = bounds
+                .surrounding(&previous.0.padded(step / 2.0))
+                .surrounding(&current.0.padded(step / 2.0));
+            previous = current;
+        }
+        Some(bounds)
+    }
+
+    fn object_count(&self) -> u32 {
+        self.object.object_count()
+    }
+}
+
+impl<H: Solid> Solid for Animated<H> {
+    fn intervals(&self, ray: &Ray) -> Vec<Interval<'_>> {
+        let transform = self.animation.at(ray.time());
+        let local = transform.ray_to_local(ray);
+        intervals_to_world(&transform, ray, self.object.intervals(&local))
+    }
+}
+
+// times the transform of an animated object is sampled at to bound it
+const ANIMATED_BOUNDS_STEPS: u32 = 64;
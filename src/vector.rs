@@ -1,7 +1,7 @@
 use crate::tracer::{random_float, random_float_between};
 use std::ops;
 
-#[derive(Copy, Clone, Default)]
+#[derive(Copy, Clone, Debug, Default, PartialEq)]
 pub struct Vector {
     x: f64,
     y: f64,
@@ -25,12 +25,13 @@ impl Vector {
         self.z
     }
 
+    #[allow(clippy::should_implement_trait)]
     pub fn add(self, other: Vector) -> Vector {
-        return Vector {
+        Vector {
             x: self.x + other.x,
             y: self.y + other.y,
             z: self.z + other.z,
-        };
+        }
     }
 
     pub fn dot(self, other: Vector) -> f64 {
@@ -87,7 +88,7 @@ impl Vector {
 
     pub fn is_near_zero(self) -> bool {
         let s = 1e-8;
-        return (self.x.abs() < s) && (self.y.abs() < s) && (self.z.abs() < s);
+        (self.x.abs() < s) && (self.y.abs() < s) && (self.z.abs() < s)
     }
 
     pub fn reflect(self, normal: Vector) -> Self {
@@ -142,21 +143,21 @@ pub fn random_in_unit_disk() -> Vector {
 impl ops::Add<Vector> for Vector {
     type Output = Vector;
     fn add(self, v: Vector) -> Vector {
-        return Vector::new(self.x + v.x, self.y + v.y, self.z + v.z);
+        Vector::new(self.x + v.x, self.y + v.y, self.z + v.z)
     }
 }
 
 impl ops::Sub<Vector> for Vector {
     type Output = Vector;
     fn sub(self, v: Vector) -> Vector {
-        return Vector::new(self.x - v.x, self.y - v.y, self.z - v.z);
+        Vector::new(self.x - v.x, self.y - v.y, self.z - v.z)
     }
 }
 
 impl ops::Mul<Vector> for f64 {
     type Output = Vector;
     fn mul(self, v: Vector) -> Vector {
-        return Vector::new(v.x * self, v.y * self, v.z * self);
+        Vector::new(v.x * self, v.y * self, v.z * self)
     }
 }
 
@@ -0,0 +1,255 @@
+//! Just enough XML for scene files like Mitsuba's: elements and their
+//! attributes, read into an `Element` tree. Comments, the declaration, the
+//! doctype and text between elements are skipped, and errors give the line
+//! they were found on.
+
+use std::cell::Cell;
+
+/// An element, with its attributes in the order of the file.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Element {
+    pub name: String,
+    pub attributes: Vec<(String, String)>,
+    pub children: Vec<Element>,
+    /// The line the element starts on.
+    pub line: usize,
+}
+
+impl Element {
+    /// The root element of a document.
+    pub fn parse(text: &str) -> Result<Element, String> {
+        let mut parser = Parser {
+            bytes: text.as_bytes(),
+            position: 0,
+            counted: Cell::new((0, 1)),
+        };
+        parser.skip_misc()?;
+        if parser.bytes.get(parser.position) != Some(&b'<') {
+            return Err(parser.error("expected an element"));
+        }
+        let root = parser.element()?;
+        parser.skip_misc()?;
+        if parser.position < parser.bytes.len() {
+            return Err(parser.error("unexpected text after the root element"));
+        }
+        Ok(root)
+    }
+
+    pub fn attribute(&self, name: &str) -> Option<&str> {
+        self.attributes
+            .iter()
+            .find(|(n, _)| n == name)
+            .map(|(_, v)| v.as_str())
+    }
+}
+
+struct Parser<'a> {
+    bytes: &'a [u8],
+    position: usize,
+    // a position the lines were last counted up to, and the line it is on,
+    // so that each element only counts the lines since the one before
+    counted: Cell<(usize, usize)>,
+}
+
+impl Parser<'_> {
+    fn error(&self, message: &str) -> String {
+        format!("line {}: {}", self.line(), message)
+    }
+
+    fn line(&self) -> usize {
+        let end = self.position.min(self.bytes.len());
+        // the parser only moves forward
+        let (start, line) = self.counted.get();
+        let line = line
+            + self.bytes[start..end]
+                .iter()
+                .filter(|&&b| b == b'\n')
+                .count();
+        self.counted.set((end, line));
+        line
+    }
+
+    fn rest(&self) -> &[u8] {
+        &self.bytes[self.position.min(self.bytes.len())..]
+    }
+
+    fn skip_whitespace(&mut self) {
+        while self
+            .bytes
+            .get(self.position)
+            .is_some_and(|b| b.is_ascii_whitespace())
+        {
+            self.position += 1;
+        }
+    }
+
+    // moves past `end`, which must come before the end of the document
+    fn skip_past(&mut self, end: &[u8], what: &str) -> Result<(), String> {
+        match self.rest().windows(end.len()).position(|w| w == end) {
+            Some(i) => {
+                self.position += i + end.len();
+                Ok(())
+            }
+            None => Err(self.error(&format!("unterminated {}", what))),
+        }
+    }
+
+    // whitespace, comments, the declaration and the doctype
+    fn skip_misc(&mut self) -> Result<(), String> {
+        loop {
+            self.skip_whitespace();
+            let rest = self.rest();
+            if rest.starts_with(b"<!--") {
+                self.skip_past(b"-->", "comment")?;
+            } else if rest.starts_with(b"<?") {
+                self.skip_past(b"?>", "declaration")?;
+            } else if rest.starts_with(b"<!DOCTYPE") {
+                self.skip_past(b">", "doctype")?;
+            } else {
+                return Ok(());
+            }
+        }
+    }
+
+    fn name(&mut self) -> Result<String, String> {
+        let start = self.position;
+        while self
+            .bytes
+            .get(self.position)
+            .is_some_and(|&b| b.is_ascii_alphanumeric() || b"_-.:".contains(&b) || b >= 0x80)
+        {
+            self.position += 1;
+        }
+        if start == self.position {
+            return Err(self.error("expected a name"));
+        }
+        Ok(String::from_utf8_lossy(&self.bytes[start..self.position]).into_owned())
+    }
+
+    // at the '<' of a start tag
+    fn element(&mut self) -> Result<Element, String> {
+        let line = self.line();
+        self.position += 1;
+        let name = self.name()?;
+        let mut element = Element {
+            name,
+            attributes: Vec::new(),
+            children: Vec::new(),
+            line,
+        };
+        loop {
+            self.skip_whitespace();
+            match self.bytes.get(self.position) {
+                Some(b'/') => {
+                    if self.bytes.get(self.position + 1) != Some(&b'>') {
+                        return Err(self.error("expected '>' after '/'"));
+                    }
+                    self.position += 2;
+                    return Ok(element);
+                }
+                Some(b'>') => {
+                    self.position += 1;
+                    break;
+                }
+                Some(_) => {
+                    let attribute = self.name()?;
+                    self.skip_whitespace();
+                    if self.bytes.get(self.position) != Some(&b'=') {
+                        return Err(self.error(&format!("expected '=' after {}", attribute)));
+                    }
+                    self.position += 1;
+                    self.skip_whitespace();
+                    let value = self.quoted()?;
+                    if element.attribute(&attribute).is_some() {
+                        return Err(self.error(&format!("{} is given twice", attribute)));
+                    }
+                    element.attributes.push((attribute, value));
+                }
+                None => return Err(self.error(&format!("unterminated <{}>", element.name))),
+            }
+        }
+        // the content, up to the end tag
+        loop {
+            let rest = self.rest();
+            if rest.is_empty() {
+                return Err(format!("line {}: <{}> isn't closed", line, element.name));
+            } else if rest.starts_with(b"</") {
+                self.position += 2;
+                let name = self.name()?;
+                if name != element.name {
+                    return Err(self.error(&format!(
+                        "</{}> doesn't match <{}> on line {}",
+                        name, element.name, line
+                    )));
+                }
+                self.skip_whitespace();
+                if self.bytes.get(self.position) != Some(&b'>') {
+                    return Err(self.error("expected '>'"));
+                }
+                self.position += 1;
+                return Ok(element);
+            } else if rest.starts_with(b"<!--") {
+                self.skip_past(b"-->", "comment")?;
+            } else if rest.starts_with(b"<![CDATA[") {
+                self.skip_past(b"]]>", "CDATA section")?;
+            } else if rest.starts_with(b"<?") {
+                self.skip_past(b"?>", "processing instruction")?;
+            } else if rest.starts_with(b"<") {
+                element.children.push(self.element()?);
+            } else {
+                // text, which scene files don't use
+                self.position += 1;
+            }
+        }
+    }
+
+    fn quoted(&mut self) -> Result<String, String> {
+        let quote = match self.bytes.get(self.position) {
+            Some(&q) if q == b'"' || q == b'\'' => q,
+            _ => return Err(self.error("expected a quoted value")),
+        };
+        self.position += 1;
+        let start = self.position;
+        while self.bytes.get(self.position).is_some_and(|&b| b != quote) {
+            self.position += 1;
+        }
+        if self.position >= self.bytes.len() {
+            return Err(self.error("unterminated attribute value"));
+        }
+        let raw = String::from_utf8(self.bytes[start..self.position].to_vec())
+            .map_err(|_| self.error("invalid UTF-8 in attribute value"))?;
+        self.position += 1;
+        unescape(&raw).map_err(|e| self.error(&e))
+    }
+}
+
+// replaces entity and character references
+fn unescape(raw: &str) -> Result<String, String> {
+    let mut text = String::new();
+    let mut rest = raw;
+    while let Some(i) = rest.find('&') {
+        text.push_str(&rest[..i]);
+        let end = rest[i..]
+            .find(';')
+            .ok_or("unterminated reference in attribute value")?;
+        let reference = &rest[i + 1..i + end];
+        let c = match reference {
+            "amp" => '&',
+            "lt" => '<',
+            "gt" => '>',
+            "quot" => '"',
+            "apos" => '\'',
+            _ => reference
+                .strip_prefix("#x")
+                .map(|hex| u32::from_str_radix(hex, 16))
+                .or_else(|| reference.strip_prefix('#').map(str::parse))
+                .and_then(Result::ok)
+                .and_then(char::from_u32)
+                .ok_or_else(|| format!("unknown reference &{};", reference))?,
+        };
+        text.push(c);
+        rest = &rest[i + end + 1..];
+    }
+    text.push_str(rest);
+    Ok(text)
+}